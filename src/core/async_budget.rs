@@ -0,0 +1,410 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::crypto::{CryptoEngine, CryptoBuffer};
+use crate::error::{Result, Error};
+use crate::sync::{SyncEngine, SyncProgress};
+use crate::datetime::Timestamp;
+use crate::storage::{DataStorage, Id, Transaction, Account, AccountType, Category, Plan, RecurringTransaction};
+use super::budget::Budget;
+use super::report::{PlanProgress, PlanAlert, MonthlyReport, SyncPreview, SyncSummary, SyncStatus, InstanceInfo};
+use super::BLOCKING_TASK_CANCELLED;
+
+
+/// Asynchronous wrapper around [`Budget`].
+///
+/// Budget performs blocking I/O and cryptographic operations, hence
+/// each call is executed on tokio's blocking thread pool. Budget is
+/// shared with running operations, so the wrapper is cheap to clone.
+///
+/// # Cancellation
+///
+/// Operations cannot be cancelled. Operation starts when its future
+/// is polled for the first time, and if the future is dropped after
+/// that, the operation still runs to completion in background, e.g.
+/// a dropped `add_transaction` may add the transaction anyway and a
+/// dropped `perform_sync` finishes synchronization. Only the result
+/// is lost, hence it is safe to drop futures, but dropping does not
+/// undo anything.
+///
+/// # Panics
+///
+/// Methods must be called within tokio runtime. Panic in budget
+/// is propagated to the caller.
+pub struct AsyncBudget<Ce, Se, St>
+where
+    Ce: CryptoEngine,
+    Se: SyncEngine,
+    St: DataStorage
+{
+    /// Shared budget, that is moved into blocking tasks.
+    budget: Arc<Budget<Ce, Se, St>>,
+}
+
+
+impl<Ce, Se, St> Clone for AsyncBudget<Ce, Se, St>
+where
+    Ce: CryptoEngine,
+    Se: SyncEngine,
+    St: DataStorage
+{
+    fn clone(&self) -> Self {
+        AsyncBudget { budget: Arc::clone(&self.budget) }
+    }
+}
+
+
+impl<Ce, Se, St> From<Budget<Ce, Se, St>> for AsyncBudget<Ce, Se, St>
+where
+    Ce: CryptoEngine,
+    Se: SyncEngine,
+    St: DataStorage
+{
+    fn from(budget: Budget<Ce, Se, St>) -> Self {
+        AsyncBudget::new(budget)
+    }
+}
+
+
+impl<Ce, Se, St> AsyncBudget<Ce, Se, St>
+where
+    Ce: CryptoEngine,
+    Se: SyncEngine,
+    St: DataStorage
+{
+    /// Wraps a budget.
+    ///
+    /// * `budget` - budget to wrap
+    pub fn new(budget: Budget<Ce, Se, St>) -> Self {
+        AsyncBudget { budget: Arc::new(budget) }
+    }
+
+    /// Obtain the wrapped budget. It can be used for cheap operations,
+    /// e.g. obtaining settings, but it blocks current thread otherwise.
+    pub fn blocking(&self) -> &Budget<Ce, Se, St> {
+        &self.budget
+    }
+}
+
+
+impl<Ce, Se, St> AsyncBudget<Ce, Se, St>
+where
+    Ce: CryptoEngine,
+    Se: SyncEngine,
+    St: DataStorage,
+    Budget<Ce, Se, St>: Send + Sync + 'static
+{
+    /// Asynchronous version of [`Budget::add_transaction`].
+    pub async fn add_transaction(&self, transaction: &Transaction) -> Result<Id> {
+        let transaction = transaction.clone();
+        self.run(move |budget| budget.add_transaction(&transaction)).await
+    }
+
+    /// Asynchronous version of [`Budget::add_transaction_checked`].
+    pub async fn add_transaction_checked(&self, transaction: &Transaction) -> Result<Vec<PlanAlert>> {
+        let transaction = transaction.clone();
+        self.run(move |budget| budget.add_transaction_checked(&transaction)).await
+    }
+
+    /// Asynchronous version of [`Budget::update_transaction`].
+    pub async fn update_transaction(&self, transaction: &Transaction) -> Result<()> {
+        let transaction = transaction.clone();
+        self.run(move |budget| budget.update_transaction(&transaction)).await
+    }
+
+    /// Asynchronous version of [`Budget::remove_transaction`].
+    pub async fn remove_transaction(&self, transaction: Id, emergency: bool, removal_timestamp: Timestamp) -> Result<()> {
+        self.run(move |budget| budget.remove_transaction(transaction, emergency, removal_timestamp)).await
+    }
+
+    /// Asynchronous version of [`Budget::restore_transaction`].
+    pub async fn restore_transaction(&self, transaction: Id) -> Result<()> {
+        self.run(move |budget| budget.restore_transaction(transaction)).await
+    }
+
+    /// Asynchronous version of [`Budget::add_transfer`].
+    pub async fn add_transfer(&self, amount: isize, from_account: Id, to_account: Id, timestamp: Timestamp) -> Result<(Id, Id)> {
+        self.run(move |budget| budget.add_transfer(amount, from_account, to_account, timestamp)).await
+    }
+
+    /// Asynchronous version of [`Budget::remove_transfer`].
+    pub async fn remove_transfer(&self, transfer: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.run(move |budget| budget.remove_transfer(transfer, removal_timestamp)).await
+    }
+
+    /// Asynchronous version of [`Budget::settle_due`].
+    pub async fn settle_due(&self, now: Timestamp) -> Result<Vec<Id>> {
+        self.run(move |budget| budget.settle_due(now)).await
+    }
+
+    /// Asynchronous version of [`Budget::transaction`].
+    pub async fn transaction(&self, transaction: Id) -> Result<Transaction> {
+        self.run(move |budget| budget.transaction(transaction)).await
+    }
+
+    /// Asynchronous version of [`Budget::transactions`].
+    pub async fn transactions(&self) -> Result<Vec<Transaction>> {
+        self.run(|budget| budget.transactions()).await
+    }
+
+    /// Asynchronous version of [`Budget::transactions_page`].
+    pub async fn transactions_page(&self, offset: usize, limit: usize) -> Result<Vec<Transaction>> {
+        self.run(move |budget| budget.transactions_page(offset, limit)).await
+    }
+
+    /// Asynchronous version of [`Budget::transactions_count`].
+    pub async fn transactions_count(&self) -> Result<usize> {
+        self.run(|budget| budget.transactions_count()).await
+    }
+
+    /// Asynchronous version of [`Budget::transactions_between`].
+    pub async fn transactions_between(&self, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<Transaction>> {
+        self.run(move |budget| budget.transactions_between(start_timestamp, end_timestamp)).await
+    }
+
+    /// Asynchronous version of [`Budget::transactions_of`].
+    pub async fn transactions_of(&self, account: Id) -> Result<Vec<Transaction>> {
+        self.run(move |budget| budget.transactions_of(account)).await
+    }
+
+    /// Asynchronous version of [`Budget::transactions_with`].
+    pub async fn transactions_with(&self, category: Id) -> Result<Vec<Transaction>> {
+        self.run(move |budget| budget.transactions_with(category)).await
+    }
+
+    /// Asynchronous version of [`Budget::add_account`].
+    pub async fn add_account(&self, account: &Account) -> Result<Id> {
+        let account = account.clone();
+        self.run(move |budget| budget.add_account(&account)).await
+    }
+
+    /// Asynchronous version of [`Budget::update_account`].
+    pub async fn update_account(&self, account: &Account) -> Result<()> {
+        let account = account.clone();
+        self.run(move |budget| budget.update_account(&account)).await
+    }
+
+    /// Asynchronous version of [`Budget::adjust_initial_balance`].
+    pub async fn adjust_initial_balance(&self, account: Id, new_initial: isize) -> Result<(isize, isize)> {
+        self.run(move |budget| budget.adjust_initial_balance(account, new_initial)).await
+    }
+
+    /// Asynchronous version of [`Budget::remove_account`].
+    pub async fn remove_account(&self, account: Id, force: bool, removal_timestamp: Timestamp) -> Result<()> {
+        self.run(move |budget| budget.remove_account(account, force, removal_timestamp)).await
+    }
+
+    /// Asynchronous version of [`Budget::account`].
+    pub async fn account(&self, account: Id) -> Result<Account> {
+        self.run(move |budget| budget.account(account)).await
+    }
+
+    /// Asynchronous version of [`Budget::accounts`].
+    pub async fn accounts(&self) -> Result<Vec<Account>> {
+        self.run(|budget| budget.accounts()).await
+    }
+
+    /// Asynchronous version of [`Budget::accounts_of_type`].
+    pub async fn accounts_of_type(&self, account_type: Option<AccountType>) -> Result<Vec<Account>> {
+        self.run(move |budget| budget.accounts_of_type(account_type)).await
+    }
+
+    /// Asynchronous version of [`Budget::balance_at`].
+    pub async fn balance_at(&self, account: Id, at: Timestamp) -> Result<isize> {
+        self.run(move |budget| budget.balance_at(account, at)).await
+    }
+
+    /// Asynchronous version of [`Budget::net_worth`].
+    pub async fn net_worth(&self) -> Result<BTreeMap<String, isize>> {
+        self.run(|budget| budget.net_worth()).await
+    }
+
+    /// Asynchronous version of [`Budget::net_worth_by_type`].
+    pub async fn net_worth_by_type(&self) -> Result<BTreeMap<AccountType, BTreeMap<String, isize>>> {
+        self.run(|budget| budget.net_worth_by_type()).await
+    }
+
+    /// Asynchronous version of [`Budget::spendable_balance`].
+    pub async fn spendable_balance(&self) -> Result<BTreeMap<String, isize>> {
+        self.run(|budget| budget.spendable_balance()).await
+    }
+
+    /// Asynchronous version of [`Budget::add_category`].
+    pub async fn add_category(&self, category: &Category) -> Result<Id> {
+        let category = category.clone();
+        self.run(move |budget| budget.add_category(&category)).await
+    }
+
+    /// Asynchronous version of [`Budget::update_category`].
+    pub async fn update_category(&self, category: &Category) -> Result<()> {
+        let category = category.clone();
+        self.run(move |budget| budget.update_category(&category)).await
+    }
+
+    /// Asynchronous version of [`Budget::remove_category`].
+    pub async fn remove_category(&self, category: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.run(move |budget| budget.remove_category(category, removal_timestamp)).await
+    }
+
+    /// Asynchronous version of [`Budget::category`].
+    pub async fn category(&self, category: Id) -> Result<Category> {
+        self.run(move |budget| budget.category(category)).await
+    }
+
+    /// Asynchronous version of [`Budget::categories`].
+    pub async fn categories(&self) -> Result<Vec<Category>> {
+        self.run(|budget| budget.categories()).await
+    }
+
+    /// Asynchronous version of [`Budget::add_plan`].
+    pub async fn add_plan(&self, plan: &Plan) -> Result<Id> {
+        let plan = plan.clone();
+        self.run(move |budget| budget.add_plan(&plan)).await
+    }
+
+    /// Asynchronous version of [`Budget::update_plan`].
+    pub async fn update_plan(&self, plan: &Plan) -> Result<()> {
+        let plan = plan.clone();
+        self.run(move |budget| budget.update_plan(&plan)).await
+    }
+
+    /// Asynchronous version of [`Budget::remove_plan`].
+    pub async fn remove_plan(&self, plan: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.run(move |budget| budget.remove_plan(plan, removal_timestamp)).await
+    }
+
+    /// Asynchronous version of [`Budget::plan`].
+    pub async fn plan(&self, plan: Id) -> Result<Plan> {
+        self.run(move |budget| budget.plan(plan)).await
+    }
+
+    /// Asynchronous version of [`Budget::plans`].
+    pub async fn plans(&self) -> Result<Vec<Plan>> {
+        self.run(|budget| budget.plans()).await
+    }
+
+    /// Asynchronous version of [`Budget::plans_progress`].
+    pub async fn plans_progress(&self, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<PlanProgress>> {
+        self.run(move |budget| budget.plans_progress(start_timestamp, end_timestamp)).await
+    }
+
+    /// Asynchronous version of [`Budget::plan_alerts`].
+    pub async fn plan_alerts(&self, at: Timestamp) -> Result<Vec<PlanAlert>> {
+        self.run(move |budget| budget.plan_alerts(at)).await
+    }
+
+    /// Asynchronous version of [`Budget::monthly_report`].
+    pub async fn monthly_report(&self, year: i32, month: u32) -> Result<MonthlyReport> {
+        self.run(move |budget| budget.monthly_report(year, month)).await
+    }
+
+    /// Asynchronous version of [`Budget::add_recurring`].
+    pub async fn add_recurring(&self, recurring: &RecurringTransaction) -> Result<Id> {
+        let recurring = recurring.clone();
+        self.run(move |budget| budget.add_recurring(&recurring)).await
+    }
+
+    /// Asynchronous version of [`Budget::update_recurring`].
+    pub async fn update_recurring(&self, recurring: &RecurringTransaction) -> Result<()> {
+        let recurring = recurring.clone();
+        self.run(move |budget| budget.update_recurring(&recurring)).await
+    }
+
+    /// Asynchronous version of [`Budget::remove_recurring`].
+    pub async fn remove_recurring(&self, recurring: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.run(move |budget| budget.remove_recurring(recurring, removal_timestamp)).await
+    }
+
+    /// Asynchronous version of [`Budget::recurrings`].
+    pub async fn recurrings(&self) -> Result<Vec<RecurringTransaction>> {
+        self.run(|budget| budget.recurrings()).await
+    }
+
+    /// Asynchronous version of [`Budget::materialize_due`].
+    pub async fn materialize_due(&self, up_to: Timestamp) -> Result<Vec<Id>> {
+        self.run(move |budget| budget.materialize_due(up_to)).await
+    }
+
+    /// Asynchronous version of [`Budget::perform_sync`].
+    pub async fn perform_sync(&self, auth: &[u8], compact_threshold: Option<f64>) -> Result<SyncSummary> {
+        let auth = CryptoBuffer::from(auth);
+        self.run(move |budget| budget.perform_sync(auth.as_bytes(), compact_threshold)).await
+    }
+
+    /// Asynchronous version of [`Budget::perform_sync_with_progress`].
+    ///
+    /// The latest progress is published to the channel, hence a slow
+    /// receiver observes only the most recent stage. Channel is not
+    /// closed when synchronization finishes, the returned future is
+    /// used to wait for it.
+    ///
+    /// * `auth` - authentication information for synchronization
+    /// * `compact_threshold` - storage is compacted after synchronization if fraction
+    ///   of reclaimable space exceeds this value, `None` disables compaction
+    /// * `progress` - sender of channel, that receives progress
+    pub async fn perform_sync_with_progress(&self, auth: &[u8], compact_threshold: Option<f64>,
+        progress: tokio::sync::watch::Sender<Option<SyncProgress>>) -> Result<SyncSummary>
+    {
+        let auth = CryptoBuffer::from(auth);
+        self.run(move |budget| {
+            budget.perform_sync_with_progress(auth.as_bytes(), compact_threshold, |current| {
+                //
+                // Progress is reported even if nobody listens,
+                // receivers can subscribe at any moment
+                //
+
+                progress.send_replace(Some(current));
+            })
+        }).await
+    }
+
+    /// Asynchronous version of [`Budget::perform_sync_with`].
+    pub async fn perform_sync_with(&self, remote: &str, auth: &[u8], compact_threshold: Option<f64>) -> Result<SyncSummary> {
+        let remote = remote.to_owned();
+        let auth = CryptoBuffer::from(auth);
+        self.run(move |budget| budget.perform_sync_with(&remote, auth.as_bytes(), compact_threshold)).await
+    }
+
+    /// Asynchronous version of [`Budget::preview_sync`].
+    pub async fn preview_sync(&self, auth: &[u8]) -> Result<SyncPreview> {
+        let auth = CryptoBuffer::from(auth);
+        self.run(move |budget| budget.preview_sync(auth.as_bytes())).await
+    }
+
+    /// Asynchronous version of [`Budget::sync_status`].
+    pub async fn sync_status(&self) -> Result<SyncStatus> {
+        self.run(|budget| budget.sync_status()).await
+    }
+
+    /// Asynchronous version of [`Budget::instances`].
+    pub async fn instances(&self) -> Result<Vec<InstanceInfo>> {
+        self.run(|budget| budget.instances()).await
+    }
+}
+
+
+impl<Ce, Se, St> AsyncBudget<Ce, Se, St>
+where
+    Ce: CryptoEngine,
+    Se: SyncEngine,
+    St: DataStorage,
+    Budget<Ce, Se, St>: Send + Sync + 'static
+{
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Budget<Ce, Se, St>) -> Result<T> + Send + 'static
+    {
+        //
+        // Task is detached if this future is dropped, hence budget
+        // is kept alive by the task itself until it finishes
+        //
+
+        let budget = Arc::clone(&self.budget);
+        match tokio::task::spawn_blocking(move || f(&budget)).await {
+            Ok(result) => result,
+            Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+            Err(_) => Err(Error::new(BLOCKING_TASK_CANCELLED))
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::io::{Read, Write};
+
+use serde::{Serialize, Deserialize};
+
+use crate::error::{Result, Error};
+use crate::crypto::KdfParams;
+use crate::storage::{Transaction, Account, Category, Plan, RecurringTransaction};
+use super::{MALFORMED_BACKUP, UNSUPPORTED_BACKUP_VERSION};
+
+
+/// Magic bytes at the beginning of backup archive.
+const BACKUP_MAGIC: &[u8; 4] = b"BDGT";
+
+/// Current version of backup archive format.
+const BACKUP_VERSION: u8 = 2;
+
+/// Version of backup archive format, that has no key derivation
+/// parameters in header, default ones are used for it.
+const BACKUP_VERSION_DEFAULT_KDF: u8 = 1;
+
+/// Length of key derivation salt stored in archive header.
+pub(crate) const BACKUP_SALT_LENGTH: usize = 32;
+
+
+/// Complete decrypted dataset of a budget.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Backup {
+    /// Identifier of instance, that created the backup.
+    pub instance_id: uuid::Bytes,
+
+    /// All accounts.
+    pub accounts: Vec<Account>,
+
+    /// All categories.
+    pub categories: Vec<Category>,
+
+    /// All plans.
+    pub plans: Vec<Plan>,
+
+    /// All transactions.
+    pub transactions: Vec<Transaction>,
+
+    /// All recurring transactions.
+    #[serde(default)]
+    pub recurrings: Vec<RecurringTransaction>,
+}
+
+
+impl Backup {
+    /// Creates a new backup object from binary representation.
+    /// 
+    /// * `binary_backup` - binary backup representation
+    pub(crate) fn from_slice(binary_backup: &[u8]) -> Result<Self> {
+        flexbuffers::from_slice(binary_backup)
+            .map_err(Error::from)
+    }
+
+    /// Converts backup into its binary representation.
+    pub(crate) fn to_vec(&self) -> Result<Vec<u8>> {
+        flexbuffers::to_vec(self)
+            .map_err(Error::from)
+    }
+}
+
+
+/// Writes archive header: magic bytes, format version, salt
+/// and key derivation parameters.
+/// 
+/// * `writer` - destination of archive
+/// * `salt` - key derivation salt
+/// * `kdf_params` - key derivation parameters
+pub(crate) fn write_header<W: Write>(writer: &mut W, salt: &[u8], kdf_params: &KdfParams) -> Result<()> {
+    writer.write_all(BACKUP_MAGIC)?;
+    writer.write_all(&[BACKUP_VERSION])?;
+    writer.write_all(salt)?;
+    writer.write_all(&kdf_params.to_bytes())?;
+
+    Ok(())
+}
+
+
+/// Reads and validates archive header, returns key derivation
+/// salt and parameters. Archives of the first version have no
+/// parameters, default ones are returned for them.
+/// 
+/// * `reader` - source of archive
+pub(crate) fn read_header<R: Read>(reader: &mut R) -> Result<([u8; BACKUP_SALT_LENGTH], KdfParams)> {
+    let mut magic = [0u8; BACKUP_MAGIC.len()];
+    reader.read_exact(&mut magic)
+        .map_err(|_| Error::new(MALFORMED_BACKUP))?;
+
+    if &magic != BACKUP_MAGIC {
+        return Err(Error::new(MALFORMED_BACKUP));
+    }
+
+    let mut version = [0u8; 1];
+    reader.read_exact(&mut version)
+        .map_err(|_| Error::new(MALFORMED_BACKUP))?;
+
+    if version[0] != BACKUP_VERSION && version[0] != BACKUP_VERSION_DEFAULT_KDF {
+        return Err(Error::new_with_extra(UNSUPPORTED_BACKUP_VERSION, version[0].to_string()));
+    }
+
+    let mut salt = [0u8; BACKUP_SALT_LENGTH];
+    reader.read_exact(&mut salt)
+        .map_err(|_| Error::new(MALFORMED_BACKUP))?;
+
+    if version[0] == BACKUP_VERSION_DEFAULT_KDF {
+        return Ok((salt, KdfParams::default()));
+    }
+
+    let mut kdf_params = [0u8; KdfParams::SIZE];
+    reader.read_exact(&mut kdf_params)
+        .map_err(|_| Error::new(MALFORMED_BACKUP))?;
+
+    Ok((salt, KdfParams::from_bytes(&kdf_params)?))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_keeps_kdf_params() {
+        let salt = [7u8; BACKUP_SALT_LENGTH];
+        let kdf_params = KdfParams::scrypt(4, 8, 1);
+
+        let mut header = Vec::new();
+        write_header(&mut header, &salt, &kdf_params).unwrap();
+
+        assert_eq!(read_header(&mut header.as_slice()).unwrap(), (salt, kdf_params));
+    }
+
+    #[test]
+    fn first_version_header_uses_default_kdf_params() {
+        let salt = [7u8; BACKUP_SALT_LENGTH];
+
+        let mut header = BACKUP_MAGIC.to_vec();
+        header.push(BACKUP_VERSION_DEFAULT_KDF);
+        header.extend_from_slice(&salt);
+
+        assert_eq!(read_header(&mut header.as_slice()).unwrap(), (salt, KdfParams::default()));
+    }
+}
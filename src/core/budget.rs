@@ -1,15 +1,26 @@
 use std::array::TryFromSliceError;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::Write;
 
-use crate::crypto::{CryptoEngine, CryptoBuffer, Kdf};
-use crate::error::{Result, Error};
-use crate::sync::{Syncable, SyncEngine};
-use crate::datetime::{Clock, Timestamp, JANUARY_1970};
+use crate::crypto::{CryptoEngine, CryptoBuffer, Kdf, KdfParams, KeyIdentifier, Mac, Prng};
+use crate::error::{Result, Error, ErrorKind};
+use crate::location::{Location, LocationGuard};
+use crate::sync::{Syncable, SyncEngine, SyncProgress};
+use crate::datetime::{self, Clock, Timestamp, JANUARY_1970, FIRST_AFTER_JANUARY_1970};
 use crate::storage::{EncryptedTransaction, EncryptedAccount, EncryptedCategory, EncryptedPlan, MetaInfo};
-use crate::storage::{DataStorage, Id, Transaction, Account, Category, Plan, CategoryType};
+use crate::storage::{DataStorage, Id, Transaction, Account, Category, Plan, CategoryType, AccountType};
+use crate::storage::{EncryptedRecurringTransaction, RecurringTransaction, RecurrencePeriod, PlanPeriod, EncryptedBalanceSnapshot};
+use crate::storage::{IntegrityReport, IntegrityIssue, IntegrityIssueKind, CompactStats, StorageStats};
 use super::config::{Config, InstanceId};
-use super::changelog::Changelog;
-use super::MALFORMED_TIMESTAMP;
+use super::settings::Settings;
+use super::changelog::{Changelog, SimpleChangelog};
+use super::report::{PlanProgress, PlanStatus, PlanAlert, MonthlyReport, CashflowPoint, RemovedItems, ItemChanges, SyncPreview, ChangeCounts, SyncSummary, SyncStatus, InstanceInfo};
+use super::import::{self, CsvMapping, ImportReport};
+use super::export::{ExportFormat, NameResolver, ExportedDocument, ExportedTransaction, ExportedPlan};
+use super::backup::{self, Backup, BACKUP_SALT_LENGTH};
+use super::bundle;
+use super::{MALFORMED_TIMESTAMP, MALFORMED_INSTANCES, MALFORMED_CHANGELOG, REMOTE_DATA_CORRUPTED, SYNC_AUTHENTICATION_FAILED, MISSING_IDENTIFIER, INVALID_STEP, INVALID_WINDOW, TRANSFER_NOT_FOUND, ROTATION_IN_READ_ONLY, STORAGE_IS_NOT_EMPTY, ACCOUNT_IS_ARCHIVED, ACCOUNT_IS_REMOVED, INVALID_PERIOD,
+    CURRENCY_MISMATCH, INVALID_MONTH, INVALID_DATE, MISSING_KEY, KEY_FINGERPRINT_MISMATCH, AMBIGUOUS_NAME, MALFORMED_SYNC_BUNDLE, FULL_RESYNC_REQUIRED};
 
 
 /// Name of income transfer category.
@@ -24,8 +35,103 @@ const TRANSFER_OUTCOME_CAT_NAME: &str = "Transfer (outcome)";
 /// Name of outcome transfer transaction.
 const TRANSFER_OUTCOME_DESCRIPTION: &str = "Transfer (outcome) -->";
 
+/// Separator of transaction tags in their stored representation.
+const TAGS_SEPARATOR: &str = "\n";
+
+/// Reason of skipping a remote change, that is exported by this instance
+/// or is already merged during previous synchronization.
+const SKIPPED_AS_KNOWN: &str = "made by this instance or before last synchronization";
+
+/// Reason of skipping a remote change of an item, that is not changed.
+const SKIPPED_AS_UNCHANGED: &str = "not changed since last synchronization";
+
+/// Reason of skipping a remote change of an item, that is removed locally.
+const SKIPPED_AS_REMOVED: &str = "removed locally";
+
+/// Reason of skipping a remote change of an item, that is removed locally later.
+const SKIPPED_AS_REMOVED_LATER: &str = "removed locally after remote change";
+
+/// Reason of skipping a remote change of an item, that references items removed locally.
+const SKIPPED_AS_ORPHANED: &str = "references items removed locally";
+
+/// Reason of skipping a remotely added item, that already exists locally.
+const SKIPPED_AS_EXISTING: &str = "already exists locally";
+
+/// Reason of skipping a remote removal of an item, that is referenced locally.
+const SKIPPED_AS_REFERENCED: &str = "referenced by local items";
+
+/// Magic bytes at the beginning of changelog with key derivation parameters.
+const CHANGELOG_MAGIC: &[u8; 4] = b"BKDF";
+
+/// Magic bytes at the beginning of streamed changelog with key derivation parameters.
+const CHANGELOG_STREAM_MAGIC: &[u8; 4] = b"BKDS";
+
+/// Label used to derive authentication key of synchronized files.
+const SYNC_MAC_LABEL: &[u8] = b"bdgt sync authentication";
+
+//
+// Names of encrypted columns, that are used as a part of encryption context
+//
+
+const TRANSACTION_DESCRIPTION: &str = "transactions.description";
+const TRANSACTION_AMOUNT: &str = "transactions.amount";
+const TRANSACTION_TAGS: &str = "transactions.tags";
+const ACCOUNT_NAME: &str = "accounts.name";
+const ACCOUNT_BALANCE: &str = "accounts.balance";
+const ACCOUNT_INITIAL_BALANCE: &str = "accounts.initial_balance";
+const ACCOUNT_CURRENCY: &str = "accounts.currency";
+const CATEGORY_NAME: &str = "categories.name";
+const PLAN_NAME: &str = "plans.name";
+const PLAN_AMOUNT_LIMIT: &str = "plans.amount_limit";
+const RECURRING_DESCRIPTION: &str = "recurrings.description";
+const RECURRING_AMOUNT: &str = "recurrings.amount";
+const SNAPSHOT_BALANCE: &str = "balance_snapshots.balance";
+
+
+/// Iterator over decrypted fields.
+type Plaintexts = std::vec::IntoIter<CryptoBuffer>;
+
+/// Encrypted field with its encryption context.
+type Field<'a> = (&'a [u8], Vec<u8>);
+
+/// Watermarks of all known instances.
+type Watermarks = BTreeMap<InstanceId, Watermark>;
+
+
+/// Time of the last synchronization of an instance and its name.
+struct Watermark {
+    timestamp: Timestamp,
+    name: Option<String>,
+}
+
+
+/// All decrypted items of a storage (including removed ones).
+struct AllItems {
+    accounts: Vec<Account>,
+    categories: Vec<Category>,
+    plans: Vec<Plan>,
+    transactions: Vec<Transaction>,
+    recurrings: Vec<RecurringTransaction>,
+}
+
 
 /// Budget manager.
+/// 
+/// Budget can be shared between threads if its engines and storage can
+/// (all provided ones can). Operations are serialized on internal locks:
+/// 
+/// * synchronization, remote management and trimming of history hold
+///   a reentrant lock of sync engine until finished, including merging
+///   of received changes
+/// * storage operations of [`crate::storage::DbStorage`] hold a reentrant
+///   lock of database connection, a transaction holds it until committed,
+///   hence writes never interleave
+/// * GnuPG operations (key lookup, decryption of data key) are serialized
+///   by [`crate::crypto::GpgCryptoEngine`] on a lock of GnuPG context, but
+///   encryption and decryption of items run concurrently
+/// 
+/// Hence queries from one thread wait while another thread synchronizes
+/// or imports data, but they never observe partially applied changes.
 pub struct Budget<Ce, Se, St>
 where
     Ce: CryptoEngine,
@@ -35,8 +141,9 @@ where
     /// Cryptographic engine used to encrypt sensitive data.
     crypto_engine: Ce,
 
-    /// Syncronization engine.
-    sync_engine: Se,
+    /// Syncronization engine. Engines are rarely safe to use from several
+    /// threads at once, hence synchronization operations are serialized.
+    sync_engine: parking_lot::ReentrantMutex<Se>,
 
     /// Storage used to store the data.
     storage: St,
@@ -46,6 +153,9 @@ where
 
     /// Key used to encrypt and decrypt sensitive data.
     key: Ce::Key,
+
+    /// Lock of data location, that is held while budget exists.
+    _location_guard: Option<LocationGuard>,
 }
 
 
@@ -57,22 +167,103 @@ where
 {
     /// Creates a budget manager instance.
     /// 
+    /// Storage may be opened in read-only mode, then all modifying
+    /// operations fail, but queries work as usual. Interrupted key 
+    /// rotation cannot be finished with such storage, hence it is
+    /// an error.
+    /// 
     /// * `crypto_engine` - cryptographic engine used to encrypt sensitive data
     /// * `storage` - storage used to store data
     /// * `config` - app's configuration
-    pub fn new(crypto_engine: Ce, sync_engine: Se, storage: St, config: Config<Ce>) -> Result<Self> {
-        let key = crypto_engine
-            .lookup_key(config.key_id())?;
+    pub fn new(mut crypto_engine: Ce, sync_engine: Se, storage: St, config: Config<Ce>) -> Result<Self> {
+        let key = Self::lookup_any_key(&crypto_engine, &config)?;
+
+        //
+        // Key is preloaded here to perform all user interaction
+        // (e.g. passphrase prompt) at startup
+        //
+
+        crypto_engine
+            .preload(&key)?;
+
+        if crypto_engine.rotation_in_progress() {
+            if storage.is_read_only() {
+                return Err(Error::new(ROTATION_IN_READ_ONLY));
+            }
+
+            Self::finish_interrupted_rotation(&mut crypto_engine, &key, &storage)?;
+        }
 
         Ok(Budget { 
-            crypto_engine: crypto_engine, 
-            sync_engine: sync_engine,
-            storage: storage,
-            config: config,
-            key: key,
+            crypto_engine, 
+            sync_engine: parking_lot::ReentrantMutex::new(sync_engine),
+            storage,
+            config,
+            key,
+            _location_guard: None,
         })
     }
 
+    /// Creates a budget manager instance, that holds lock of data location,
+    /// hence another process cannot use the same location simultaneously.
+    /// 
+    /// Lock is exclusive, but it is shared if storage is opened in read-only
+    /// mode, hence several readers can work together. Lock is taken before
+    /// anything is modified, i.e. before [`Budget::new`] is called.
+    /// 
+    /// * `loc` - storage location provider (used to lock data location)
+    /// * `crypto_engine` - cryptographic engine used to encrypt sensitive data
+    /// * `storage` - storage used to store data
+    /// * `config` - app's configuration
+    pub fn new_locked<L: Location>(loc: &L, crypto_engine: Ce, sync_engine: Se, storage: St, config: Config<Ce>) -> Result<Self> {
+        let location_guard = match storage.is_read_only() {
+            true => loc.lock_shared()?,
+            false => loc.lock()?
+        };
+
+        let mut budget = Self::new(crypto_engine, sync_engine, storage, config)?;
+        budget._location_guard = Some(location_guard);
+
+        Ok(budget)
+    }
+
+    /// Creates a budget manager instance, that joins an existing remote.
+    /// 
+    /// Synchronization engine is created by the factory, e.g. it clones
+    /// remote repository. Then everything from remote is merged without
+    /// local initialization (predefined items are created by the merge),
+    /// hence the instance ends up in the same state as instances, that
+    /// are synchronized for a long time. There is no need to initialize
+    /// the returned instance.
+    /// 
+    /// Remote changelog must contain full history, i.e. it must not be
+    /// compacted, otherwise old items cannot be received.
+    /// 
+    /// * `crypto_engine` - cryptographic engine used to encrypt sensitive data
+    /// * `sync_engine_factory` - creates synchronization engine connected to remote
+    /// * `storage` - new storage used to store data
+    /// * `config` - app's configuration
+    /// * `remote` - remote URL
+    /// * `auth` - authentication information for synchronization
+    pub fn join_remote<F>(crypto_engine: Ce, sync_engine_factory: F, storage: St, config: Config<Ce>, 
+        remote: &str, auth: &[u8]) -> Result<Self>
+    where
+        F: FnOnce(&str) -> Result<Se>
+    {
+        let sync_engine = sync_engine_factory(remote)?;
+        let budget = Self::new(crypto_engine, sync_engine, storage, config)?;
+
+        budget.perform_sync(auth, None)?;
+
+        Ok(budget)
+    }
+
+    /// Checks if underlying storage is opened in read-only mode.
+    pub fn is_read_only(&self) -> bool {
+        self.storage
+            .is_read_only()
+    }
+
     /// Underlying cryptographic engine name.
     pub fn engine(&self) -> &str {
         self.crypto_engine
@@ -85,10 +276,10 @@ where
             .version()
     }
 
-    /// Encryption key identifier.
-    pub fn key_id(&self) -> &Ce::KeyId {
+    /// Encryption key identifiers.
+    pub fn key_ids(&self) -> &[Ce::KeyId] {
         self.config
-            .key_id()
+            .key_ids()
     }
 
     /// Local instance identifier.
@@ -97,733 +288,4004 @@ where
             .instance_id()
     }
 
-    /// Initializes budget instance for the first time.
-    pub fn initialize(&self) -> Result<()> {
-        //
-        // Add predefined items and ensure, that they have proper identifiers
-        // Predefined items creation timestamp is always equal to January 1970
-        //
-
-        self.add_category(&Category { 
-            id: Some(St::TRANSFER_INCOME_ID), 
-            name: TRANSFER_INCOME_CAT_NAME.to_owned(), 
-            category_type: CategoryType::Income,
-            meta_info: MetaInfo::new(Some(*JANUARY_1970), None, None)
-        })?;
-
-        self.add_category(&Category { 
-            id: Some(St::TRANSFER_OUTCOME_ID), 
-            name: TRANSFER_OUTCOME_CAT_NAME.to_owned(),
-            category_type: CategoryType::Outcome,
-            meta_info: MetaInfo::new(Some(*JANUARY_1970), None, None)
-        })
+    /// User preferences.
+    pub fn settings(&self) -> &Settings {
+        self.config
+            .settings()
     }
 
-    /// Add a new transaction.
+    /// Change and save user preferences.
     /// 
-    /// * `transaction` - transaction data
-    pub fn add_transaction(&self, transaction: &Transaction) -> Result<()> {
-        //
-        // Amount is considered to have a proper sign,
-        // so I just add it to a corresponding account's
-        // balance.
-        // Change timestamp for account should not be 
-        // modified in this case, so I don't modify it 
-        // in account instance.
-        //
+    /// * `loc` - storage location provider (used to update configuration)
+    /// * `f` - function, that changes settings
+    pub fn update_settings<L, F>(&mut self, loc: &L, f: F) -> Result<()>
+    where
+        L: Location,
+        F: FnOnce(&mut Settings)
+    {
+        self.config
+            .update_settings(loc, f)
+    }
 
-        let mut decrypted_account = self.decrypt_account(
-            &self.storage.account(transaction.account_id)?)?;
+    /// Replaces local instance identifier with a new one.
+    /// 
+    /// It is required if instance is cloned, e.g. restored from
+    /// a backup on another machine, since both copies would ignore
+    /// changes of each other as their own ones. Existing items keep
+    /// their origin, and only new changes carry the new identifier.
+    /// 
+    /// * `loc` - storage location provider (used to update configuration)
+    pub fn regenerate_instance_id<L: Location>(&mut self, loc: &L) -> Result<InstanceId> {
+        self.config
+            .regenerate_instance_id(loc)
+    }
 
-        decrypted_account.balance += transaction.amount;
+    /// Checks if another instance synchronizes with the same identifier.
+    /// 
+    /// Returns time of the last synchronization, that is performed with local
+    /// identifier, but is unknown to this instance, `None` if there is no such
+    /// one. In this case [`Budget::regenerate_instance_id`] should be called.
+    /// No network operations are performed, hence a clone is detected only
+    /// after synchronization files are received.
+    /// 
+    /// False positive is reported after [`Budget::reset_sync`] until
+    /// the next synchronization.
+    pub fn detect_cloned_instance(&self) -> Result<Option<Timestamp>> {
+        if !self.sync_engine().is_configured() {
+            return Ok(None);
+        }
 
-        //
-        // Well... It would be better to use DB's transactions here,
-        // but it is more complicated though. 
-        // If transaction will not be added, account will not be modified.
-        // If account update fails, one can just remove bad transaction
-        // with `emergency` flag set to `true`.
-        // Hence there is a way to restore consistency.
-        //
+        let last_sync = self.sync_engine()
+            .last_sync()?
+            .unwrap_or(*FIRST_AFTER_JANUARY_1970);
 
-        let mut transaction = self.encrypt_transaction(transaction)?;
-        transaction.meta_info.set_origin_if_absent(self.instance_id());
+        let clone_sync = self.instances()?
+            .into_iter()
+            .find(|instance| instance.id == *self.instance_id())
+            .map(|instance| instance.last_seen)
+            .filter(|last_seen| last_sync < *last_seen);
 
-        self.storage.add_transaction(transaction)?;
-        self.storage.update_account(self.encrypt_account(&decrypted_account)?)?;
+        Ok(clone_sync)
+    }
 
-        Ok(())
+    /// Local instance name, `None` if it is not set.
+    pub fn instance_name(&self) -> Option<&str> {
+        self.config
+            .instance_name()
     }
 
-    /// Add transfer transactions.
+    /// Re-encrypt all data (including removed items) for a new key.
     /// 
-    /// * `amount` - amount of money to transfer between accounts
-    /// * `from_account` - account to transfer from
-    /// * `to_account` - account to transfer to
-    /// * `timestamp` - transfer date
-    pub fn add_transfer(&self, amount: isize, from_account: Id, to_account: Id, timestamp: Timestamp) -> Result<()> {
+    /// A fresh data key is generated and wrapped with the new key. All items
+    /// are re-encrypted within a single storage transaction, and the new data
+    /// key replaces the current one only after the transaction is committed.
+    /// If the process is interrupted, rotation is either rolled back or finished
+    /// on next [`Budget::new`], depending on whether the storage is re-encrypted.
+    /// In the latter case the key identifiers must be updated manually 
+    /// via [`Config::set_key_ids`] and [`Config::repin_key`].
+    /// 
+    /// New data key is encrypted only for the new key, i.e. other
+    /// recipients must be added again.
+    /// 
+    /// * `loc` - storage location provider (used to update configuration)
+    /// * `new_key_id` - identifier of a new key
+    pub fn rotate_key<L: Location>(&mut self, loc: &L, new_key_id: &Ce::KeyId) -> Result<()> {
+        let new_key = self.crypto_engine
+            .lookup_key(new_key_id)?;
+
         //
-        // Transfer can be added only locally, i.e. when syncronization is performed, no notion
-        // of transfer exists. Only corresponding transactions are synchronized.
-        // Hence, all meta information is filled using reasonable default values.
+        // All items are decrypted with the current key before rotation starts
         //
 
-        let amount = amount.abs();
-        let now = Clock::now();
-
-        self.add_transaction(&Transaction{
-            id: None,
-            timestamp: timestamp,
-            description: TRANSFER_INCOME_DESCRIPTION.to_owned(),
-            account_id: to_account,
-            category_id: St::TRANSFER_INCOME_ID,
-            amount: amount,
-            meta_info: MetaInfo::new(Some(now), None, None)
-        })?;
-
-        self.add_transaction(&Transaction{
-            id: None,
-            timestamp: timestamp,
-            description: TRANSFER_OUTCOME_DESCRIPTION.to_owned(),
-            account_id: from_account,
-            category_id: St::TRANSFER_OUTCOME_ID,
-            amount: -amount,
-            meta_info: MetaInfo::new(Some(now), None, None)
-        })?;
-
-        Ok(())
-    }
+        let items = self.all_items()?;
 
-    /// Remove transaction.
-    /// 
-    /// * `transaction` - identifier of a transaction to remove
-    /// * `emergency` - if `true`, then the linked account will not be updated
-    /// * `removal_timestame` - this value will be written as removal timestamp
-    pub fn remove_transaction(&self, transaction: Id, emergency: bool, removal_timestamp: Timestamp) -> Result<()> {
-        if !emergency {
-            //
-            // Here is the same story: it would be probably better to use
-            // DB's transactions, but it is not the way here.
-            // If account is not updated, transaction will not be added.
-            // If transaction is not removed, but account is updated yet,
-            // one can remove transaction with `emergency` flag set.
-            // Hence there is a way to restore consistency.
-            //
+        //
+        // Now I start rotation, hence items are encrypted for the new key
+        //
 
-            let decrypted_transaction = self.decrypt_transaction(
-                &self.storage.transaction(transaction)?)?;
+        self.crypto_engine
+            .begin_rotation(&new_key)?;
 
-            let mut decrypted_account = self.decrypt_account(
-                &self.storage.account(decrypted_transaction.account_id)?)?;
+        if let Err(error) = self.rewrite_items(&items) {
+            self.crypto_engine.abort_rotation()?;
+            return Err(error);
+        }
 
-            //
-            // Again, amount in transaction is considered to have a proper sign,
-            // hence I just subtract it from account's balance
-            //
+        self.crypto_engine
+            .commit_rotation()?;
 
-            decrypted_account.balance -= decrypted_transaction.amount;
+        self.config
+            .set_key_ids(loc, std::slice::from_ref(new_key_id))?;
 
-            self.storage.update_account(self.encrypt_account(&decrypted_account)?)?;
-        }
+        self.config
+            .repin_key(loc, &self.crypto_engine, new_key_id)?;
 
-        self.storage.remove_transaction(transaction, removal_timestamp)
-    }
+        self.key = new_key;
 
-    // Return all transactions.
-    pub fn transactions(&self) -> Result<Vec<Transaction>> {
-        self.decrypt_transactions(&self.storage.transactions()?)
+        Ok(())
     }
 
-    /// Return all transactions between a given time points (including start 
-    /// of the interval and excluding the end) sorted by timestamp in 
-    /// descending order.
+    /// Re-encrypt all items (including removed ones) binding each encrypted 
+    /// field to its row.
     /// 
-    /// Used for optimization.
-    /// 
-    /// * `start_timestamp` - point in time to start from
-    /// * `end_timestamp` - point in time to end before
-    pub fn transactions_between(&self, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<Transaction>> {
-        self.decrypt_transactions(&self.storage.transactions_between(start_timestamp, end_timestamp)?) 
+    /// Items written by older versions are encrypted without context, they 
+    /// are still readable, but their fields can be swapped between rows 
+    /// unnoticed. Items are upgraded on update anyway, this function upgrades 
+    /// all of them atomically at once.
+    pub fn upgrade_encryption(&self) -> Result<()> {
+        let items = self.all_items()?;
+        self.rewrite_items(&items)
     }
 
-    /// Return all transactions bound with a given account sorted by timestamp 
-    /// in descending order.
-    /// 
-    /// Used for optimization.
+    /// Initializes budget instance for the first time.
     /// 
-    /// * `account` - account identifier to return transactions for
-    pub fn transactions_of(&self, account: Id) -> Result<Vec<Transaction>> {
-        self.decrypt_transactions(&self.storage.transactions_of(account)?) 
+    /// Predefined items, that already exist (e.g. received
+    /// during synchronization), are left untouched.
+    pub fn initialize(&self) -> Result<()> {
+        self.add_predefined_items()
     }
 
-    /// Return all transactions between a given time points (including start 
-    /// of the interval and excluding the end) bound with a given account 
-    /// sorted by timestamp in descending order.
+    /// Add a new transaction. Returns identifier of the added transaction.
     /// 
-    /// Used for optimization.
+    /// Fails if account is archived.
     /// 
-    /// * `account` - account identifier to return transactions for
-    /// * `start_timestamp` - point in time to start from
-    /// * `end_timestamp` - point in time to end before
-    pub fn transactions_of_between(&self, account: Id, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<Transaction>> {
-        self.decrypt_transactions(&self.storage.transactions_of_between(account, start_timestamp, end_timestamp)?) 
+    /// * `transaction` - transaction data
+    pub fn add_transaction(&self, transaction: &Transaction) -> Result<Id> {
+        self.insert_transaction(transaction, false)
     }
 
-    /// Return all transactions with given category sorted by timestamp in
-    /// descending order.
+    /// Add a new pending (scheduled) transaction. Returns identifier
+    /// of the added transaction.
     /// 
-    /// Used for optimization.
+    /// Account's balance is not changed until the transaction is settled
+    /// with [`Budget::settle_due`].
     /// 
-    /// * `category` - category to return transactions with
-    pub fn transactions_with(&self, category: Id) -> Result<Vec<Transaction>> {
-        self.decrypt_transactions(&self.storage.transactions_with(category)?) 
+    /// * `transaction` - transaction data
+    pub fn add_pending_transaction(&self, transaction: &Transaction) -> Result<Id> {
+        Self::ensure_not_archived(&self.account(transaction.account_id)?)?;
+
+        let mut transaction = self.encrypt_transaction(transaction)?;
+        transaction.meta_info.set_origin_if_absent(self.instance_id());
+        transaction.pending = true;
+
+        self.storage.add_transaction(transaction)
     }
 
-    /// Return all transactions between a given time points (including start 
-    /// of the interval and excluding the end) and with given category 
-    /// sorted by timestamp in descending order.
+    /// Settle all pending transactions, that are due at a given point in time
+    /// (inclusive), and apply their amounts to accounts' balances.
     /// 
-    /// Used for optimization.
+    /// Returns identifiers of settled transactions.
     /// 
-    /// * `category` - category to return transactions with
-    /// * `start_timestamp` - point in time to start from
-    /// * `end_timestamp` - point in time to end before
-    pub fn transactions_with_between(&self, category: Id, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<Transaction>> {
-        self.decrypt_transactions(&self.storage.transactions_with_between(category, start_timestamp, end_timestamp)?) 
-    }
+    /// * `now` - point in time to settle transactions up to
+    pub fn settle_due(&self, now: Timestamp) -> Result<Vec<Id>> {
+        let change_timestamp = Clock::now();
 
-    /// Add a new account.
-    /// 
-    /// * `account` - account data
-    pub fn add_account(&self, account: &Account) -> Result<()> {
-        let mut account = self.encrypt_account(account)?;
-        account.meta_info.set_origin_if_absent(self.instance_id());
+        self.storage.with_transaction(|_| {
+            let mut settled = Vec::new();
 
-        self.storage.add_account(account)
+            for mut transaction in self.pending_transactions()? {
+                if transaction.timestamp > now {
+                    continue;
+                }
+
+                transaction.pending = false;
+                transaction.meta_info.changed_timestamp = Some(change_timestamp);
+
+                self.update_transaction(&transaction)?;
+                settled.push(transaction.id.unwrap());
+            }
+
+            Ok(settled)
+        })
     }
 
-    /// Remove an account if possible (or forced).
+    /// Return all pending transactions sorted by timestamp.
+    pub fn pending_transactions(&self) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.pending_transactions()?)
+    }
+
+    /// Update an existing transaction.
     /// 
-    /// If account has transaction and `force` is false, then this function fails.
+    /// Linked account's balance is adjusted by the difference between
+    /// old and new amounts. If the transaction is moved to another
+    /// account, its old amount is subtracted from the old account and
+    /// new amount is added to the new one.
     /// 
-    /// * `account` - identifier of an account to remove
-    /// * `force` - if true, then account is deleted anyway with all of its transactions
-    /// * `removal_timestame` - this value will be written as removal timestamp
-    pub fn remove_account(&self, account: Id, force: bool, removal_timestamp: Timestamp) -> Result<()> {
-        if force {
+    /// If no change timestamp is set in transaction's meta info, current
+    /// time is used.
+    /// 
+    /// * `transaction` - transaction data (with identifier of a transaction to update)
+    pub fn update_transaction(&self, transaction: &Transaction) -> Result<()> {
+        let id = transaction.id
+            .ok_or(Error::new(MISSING_IDENTIFIER))?;
+
+        //
+        // The same story as with adding: transaction and accounts
+        // are read and updated atomically
+        //
+
+        self.storage.with_transaction(|storage| {
             //
-            // Forced removal is requested, hence I need to remove
-            // all linked transactions first
+            // Removed transactions cannot be updated, this case
+            // is handled by storage here
             //
 
-            for transaction in self.storage.transactions_of(account)? {
-                self.storage.remove_transaction(transaction.id.unwrap(), removal_timestamp)?;
+            let old_transaction = self.decrypt_transaction(
+                &storage.transaction(id)?)?;
+
+            let mut new_account = self.decrypt_account(
+                &storage.account(transaction.account_id)?)?;
+
+            let old_account = if old_transaction.account_id != transaction.account_id {
+                //
+                // Transaction is moved to another account, hence old account
+                // should be updated separately
+                //
+
+                let mut old_account = self.decrypt_account(
+                    &storage.account(old_transaction.account_id)?)?;
+
+                old_account.balance -= Self::balance_amount(&old_transaction);
+                new_account.balance += Self::balance_amount(transaction);
+
+                Some(old_account)
             }
-        }
+            else {
+                new_account.balance += Self::balance_amount(transaction) - Self::balance_amount(&old_transaction);
+                None
+            };
 
-        self.storage.remove_account(account, removal_timestamp)
-    }
+            let mut transaction = self.encrypt_transaction(transaction)?;
+            transaction.meta_info.set_changed_timestamp_if_absent(Clock::now());
 
-    /// Return account with a given identifier.
-    /// 
-    /// * `account` - identifier to return record for
-    pub fn account(&self, account: Id) -> Result<Account> {
-        self.decrypt_account(&self.storage.account(account)?)
-    }
+            storage.update_transaction(transaction)?;
+            storage.update_account(self.encrypt_account(&new_account)?)?;
 
-    /// Return all accounts.
-    pub fn accounts(&self) -> Result<Vec<Account>> {
-        self.decrypt_accounts(&self.storage.accounts()?)
+            if let Some(old_account) = old_account {
+                storage.update_account(self.encrypt_account(&old_account)?)?;
+            }
+
+            Ok(())
+        })
     }
 
-    /// Add a new category.
+    /// Add transfer transactions. Returns identifiers of income and outcome
+    /// transactions respectively.
     /// 
-    /// * `category` - category data
-    pub fn add_category(&self, category: &Category) -> Result<()> {
-        let mut category = self.encrypt_category(category)?;
-        category.meta_info.set_origin_if_absent(self.instance_id());
+    /// Both accounts must have the same currency, use [`Budget::add_transfer_fx`]
+    /// for transfers between accounts with different currencies.
+    /// 
+    /// * `amount` - amount of money to transfer between accounts
+    /// * `from_account` - account to transfer from
+    /// * `to_account` - account to transfer to
+    /// * `timestamp` - transfer date
+    pub fn add_transfer(&self, amount: isize, from_account: Id, to_account: Id, timestamp: Timestamp) -> Result<(Id, Id)> {
+        if self.account(from_account)?.currency != self.account(to_account)?.currency {
+            return Err(Error::new(CURRENCY_MISMATCH));
+        }
 
-        self.storage.add_category(category)
+        self.add_transfer_fx(amount, amount, from_account, to_account, timestamp)
     }
 
-    /// Remove category if possible.
-    /// 
-    /// If there is at leas one transaction with the specified
-    /// category, then this function fails. There is no way to
-    /// remove category with existing transactions.
+    /// Add transfer transactions between accounts with (possibly) different currencies.
+    /// Returns identifiers of income and outcome transactions respectively.
     /// 
-    /// * `category` - identifier of category to remove
+    /// * `amount` - amount of money to withdraw from source account (in its currency)
+    /// * `converted_amount` - amount of money to deposit to destination account (in its currency)
+    /// * `from_account` - account to transfer from
+    /// * `to_account` - account to transfer to
+    /// * `timestamp` - transfer date
+    pub fn add_transfer_fx(&self, amount: isize, converted_amount: isize, from_account: Id, 
+        to_account: Id, timestamp: Timestamp) -> Result<(Id, Id)> 
+    {
+        //
+        // Transfer can be added only locally, i.e. when syncronization is performed, no notion
+        // of transfer exists. Only corresponding transactions are synchronized.
+        // Hence, all meta information is filled using reasonable default values.
+        // Both transactions are added atomically and are linked with common
+        // transfer identifier.
+        //
+
+        let amount = amount.abs();
+        let converted_amount = converted_amount.abs();
+        let now = Clock::now();
+        let transfer_id = Some(Id::random());
+
+        self.storage.with_transaction(|_| {
+            let income = self.add_transaction(&Transaction{
+                id: None,
+                timestamp,
+                description: TRANSFER_INCOME_DESCRIPTION.to_owned(),
+                account_id: to_account,
+                category_id: St::TRANSFER_INCOME_ID,
+                amount: converted_amount,
+                transfer_id,
+                tags: Vec::new(),
+                pending: false,
+                meta_info: MetaInfo::new(Some(now), None, None)
+            })?;
+
+            let outcome = self.add_transaction(&Transaction{
+                id: None,
+                timestamp,
+                description: TRANSFER_OUTCOME_DESCRIPTION.to_owned(),
+                account_id: from_account,
+                category_id: St::TRANSFER_OUTCOME_ID,
+                amount: -amount,
+                transfer_id,
+                tags: Vec::new(),
+                pending: false,
+                meta_info: MetaInfo::new(Some(now), None, None)
+            })?;
+
+            Ok((income, outcome))
+        })
+    }
+
+    /// Import transactions from CSV.
+    /// 
+    /// Rows, that cannot be parsed, are skipped. Rows, that duplicate existing
+    /// transactions (with the same account, timestamp, amount and description),
+    /// are not imported. All transactions and missing categories are added
+    /// atomically, accounts' balances are updated once per account.
+    /// 
+    /// * `reader` - source of CSV data
+    /// * `mapping` - description of CSV columns
+    pub fn import_csv<R: std::io::Read>(&self, reader: R, mapping: &CsvMapping) -> Result<ImportReport> {
+        let now = Clock::now();
+        let mut report = ImportReport::default();
+
+        let accounts: HashMap<String, Id> = self.accounts()?
+            .into_iter()
+            .map(|account| (account.name, account.id.unwrap()))
+            .collect();
+
+        let mut categories: HashMap<String, Id> = self.categories()?
+            .into_iter()
+            .map(|category| (category.name, category.id.unwrap()))
+            .collect();
+
+        let mut known_transactions: HashSet<_> = self.transactions()?
+            .into_iter()
+            .map(|transaction| (transaction.account_id, transaction.timestamp, transaction.amount, transaction.description))
+            .collect();
+
+        //
+        // Parse all records first and then add everything at once
+        //
+
+        let mut new_categories = Vec::new();
+        let mut transactions = Vec::new();
+
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .delimiter(mapping.delimiter)
+            .has_headers(mapping.has_headers)
+            .from_reader(reader);
+
+        for (index, record) in csv_reader.records().enumerate() {
+            let row = index + 1;
+            let parsed = record
+                .map_err(Error::from)
+                .and_then(|record| import::parse_record(&record, mapping, &accounts, 
+                    &mut categories, &mut new_categories, now));
+
+            let transaction = match parsed {
+                Ok(transaction) => transaction,
+                Err(error) => {
+                    report.skipped.push((row, error.to_string()));
+                    continue;
+                }
+            };
+
+            let key = (transaction.account_id, transaction.timestamp, 
+                transaction.amount, transaction.description.clone());
+
+            if known_transactions.insert(key) {
+                transactions.push(transaction);
+                report.imported.push(row);
+            }
+            else {
+                report.duplicates.push(row);
+            }
+        }
+
+        self.storage.with_transaction(|_| {
+            for category in &new_categories {
+                self.add_category(category)?;
+            }
+
+            self.add_transactions_bulk(&transactions)
+        })?;
+
+        Ok(report)
+    }
+
+    /// Return groups of probable duplicate transactions.
+    /// 
+    /// Transactions are considered duplicates if they belong to the same account,
+    /// have the same amount and are made within a time window from each other.
+    /// Transactions of transfers are never considered duplicates. Each group
+    /// contains at least two transactions sorted by timestamp, groups are sorted
+    /// by timestamp of their first transaction.
+    /// 
+    /// * `window` - maximum time between neighbouring transactions in a group
+    /// * `match_description` - if `true`, descriptions must be equal too
+    pub fn find_duplicates(&self, window: chrono::Duration, match_description: bool) -> Result<Vec<Vec<Transaction>>> {
+        if window < chrono::Duration::zero() {
+            return Err(Error::new(INVALID_WINDOW));
+        }
+
+        let mut candidates: HashMap<_, Vec<Transaction>> = HashMap::new();
+        for transaction in self.transactions()? {
+            if transaction.transfer_id.is_some() {
+                continue;
+            }
+
+            let description = match_description
+                .then(|| transaction.description.clone());
+
+            candidates
+                .entry((transaction.account_id, transaction.amount, description))
+                .or_default()
+                .push(transaction);
+        }
+
+        //
+        // Transactions are sorted in descending order, hence I reverse
+        // them and split each set of candidates at gaps wider than window
+        //
+
+        let mut groups = Vec::new();
+        for mut transactions in candidates.into_values() {
+            transactions.reverse();
+
+            let mut group: Vec<Transaction> = Vec::new();
+            for transaction in transactions {
+                if group.last().is_some_and(|last| window < transaction.timestamp - last.timestamp) {
+                    let finished = std::mem::take(&mut group);
+                    if 1 < finished.len() {
+                        groups.push(finished);
+                    }
+                }
+
+                group.push(transaction);
+            }
+
+            if 1 < group.len() {
+                groups.push(group);
+            }
+        }
+
+        groups.sort_by_key(|group| group[0].timestamp);
+        Ok(groups)
+    }
+
+    /// Remove probable duplicate transactions leaving one transaction per group.
+    /// 
+    /// Groups are found as in [`Budget::find_duplicates`]. Transactions are
+    /// removed atomically, accounts' balances are adjusted. Returns number
+    /// of removed transactions.
+    /// 
+    /// * `window` - maximum time between neighbouring transactions in a group
+    /// * `match_description` - if `true`, descriptions must be equal too
+    /// * `keep_oldest` - if `true`, the earliest added transaction is kept, otherwise the latest one
     /// * `removal_timestame` - this value will be written as removal timestamp
-    pub fn remove_category(&self, category: Id, removal_timestamp: Timestamp) -> Result<()> {
-        self.storage.remove_category(category, removal_timestamp)
+    pub fn remove_duplicates(&self, window: chrono::Duration, match_description: bool, keep_oldest: bool, 
+        removal_timestamp: Timestamp) -> Result<usize> 
+    {
+        let groups = self.find_duplicates(window, match_description)?;
+
+        self.storage.with_transaction(|_| {
+            let mut removed = 0;
+            for mut group in groups {
+                group.sort_by_key(|transaction| transaction.meta_info.added_timestamp);
+                if !keep_oldest {
+                    group.reverse();
+                }
+
+                for transaction in group.iter().skip(1) {
+                    self.remove_transaction(transaction.id.unwrap(), false, removal_timestamp)?;
+                    removed += 1;
+                }
+            }
+
+            Ok(removed)
+        })
     }
 
-    /// Return category with a given identifier.
+    /// Export decrypted budget data.
     /// 
-    /// * `category` - identifier to return record for
-    pub fn category(&self, category: Id) -> Result<Category> {
-        self.decrypt_category(&self.storage.category(category)?)
+    /// Identifiers of accounts and categories are resolved into their names.
+    /// CSV contains transactions only, while JSON contains accounts, categories,
+    /// plans and transactions in a single document.
+    /// 
+    /// * `writer` - destination of exported data
+    /// * `format` - format of exported data
+    /// * `range` - optional time range of transactions to export
+    pub fn export<W: std::io::Write>(&self, writer: W, format: ExportFormat, range: Option<(Timestamp, Timestamp)>) -> Result<()> {
+        let accounts = self.accounts_including_archived()?;
+        let categories = self.categories()?;
+        let resolver = NameResolver::new(&accounts, &categories);
+
+        let transactions: Vec<_> = match range {
+            Some((start_timestamp, end_timestamp)) => self.transactions_between(start_timestamp, end_timestamp)?,
+            None => self.transactions()?
+        }
+        .into_iter()
+        .map(|transaction| ExportedTransaction::new(transaction, &resolver))
+        .collect();
+
+        match format {
+            ExportFormat::Csv => {
+                let mut csv_writer = csv::Writer::from_writer(writer);
+                for transaction in transactions {
+                    csv_writer.serialize(transaction)?;
+                }
+
+                csv_writer.flush()?;
+            },
+            ExportFormat::Json => {
+                let document = ExportedDocument {
+                    plans: self.plans()?
+                        .into_iter()
+                        .map(|plan| ExportedPlan::new(plan, &resolver))
+                        .collect(),
+                    accounts: accounts.into_iter().map(Into::into).collect(),
+                    categories: categories.into_iter().map(Into::into).collect(),
+                    transactions
+                };
+
+                serde_json::to_writer_pretty(writer, &document)?;
+            }
+        }
+
+        Ok(())
     }
 
-    /// Return all categories.
-    pub fn categories(&self) -> Result<Vec<Category>> {
-        self.decrypt_categories(&self.storage.categories()?)
+    /// Write an encrypted backup of the complete budget.
+    /// 
+    /// Archive consists of a header (magic bytes, format version, random salt and
+    /// key derivation parameters) followed by decrypted dataset encrypted with
+    /// a key derived from passphrase using configured parameters.
+    /// 
+    /// * `writer` - destination of backup archive
+    /// * `passphrase` - passphrase to derive encryption key from
+    pub fn backup<W: std::io::Write>(&self, mut writer: W, passphrase: &[u8]) -> Result<()> {
+        let backup = Backup {
+            instance_id: self.instance_id().into_bytes(),
+            accounts: self.accounts_including_archived()?,
+            categories: self.categories()?,
+            plans: self.plans()?,
+            transactions: self.transactions()?,
+            recurrings: self.recurrings()?
+        };
+
+        let mut salt = [0u8; BACKUP_SALT_LENGTH];
+        Prng::new().generate(&mut salt)?;
+
+        let kdf_params = *self.config.kdf_params();
+        let key = Kdf::derive_key(passphrase, &salt, self.crypto_engine.symmetric_key_length(), 
+            &kdf_params)?;
+        let encrypted_backup = self.crypto_engine
+            .encrypt_symmetric(key.as_bytes(), &backup.to_vec()?)?;
+
+        backup::write_header(&mut writer, &salt, &kdf_params)?;
+        writer.write_all(encrypted_backup.as_bytes())?;
+
+        Ok(())
     }
 
-    /// Return all categories of specific type.
+    /// Restore budget from an encrypted backup.
     /// 
-    /// * `category_type` - type to return categories of
-    pub fn categories_of(&self, category_type: CategoryType) -> Result<Vec<Category>> {
-        self.decrypt_categories(&self.storage.categories_of(category_type)?)
+    /// Items are restored with their original identifiers and meta information,
+    /// hence synchronization state remains coherent. Current instance identifier
+    /// is not changed.
+    /// 
+    /// If storage is not empty, restoration is refused unless `force` is set.
+    /// In the latter case items, that already exist, are left intact, and
+    /// balances of all accounts are recalculated after restoration.
+    /// 
+    /// * `reader` - source of backup archive
+    /// * `passphrase` - passphrase to derive decryption key from
+    /// * `force` - restore even if storage is not empty
+    pub fn restore<R: std::io::Read>(&self, mut reader: R, passphrase: &[u8], force: bool) -> Result<()> {
+        let (salt, kdf_params) = backup::read_header(&mut reader)?;
+
+        let mut encrypted_backup = Vec::new();
+        reader.read_to_end(&mut encrypted_backup)?;
+
+        let key = Kdf::derive_key(passphrase, &salt, self.crypto_engine.symmetric_key_length(), 
+            &kdf_params)?;
+        let decrypted_backup = self.crypto_engine
+            .decrypt_symmetric(key.as_bytes(), &encrypted_backup)?;
+
+        let backup = Backup::from_slice(decrypted_backup.as_bytes())?;
+
+        //
+        // Predefined categories are always present, hence
+        // they do not make storage non-empty
+        //
+
+        let existing_categories: HashSet<Id> = self.storage.categories()?
+            .into_iter()
+            .filter_map(|category| category.id)
+            .collect();
+
+        let existing_accounts: HashSet<Id> = self.storage.accounts_including_archived()?
+            .into_iter()
+            .filter_map(|account| account.id)
+            .collect();
+
+        let existing_plans: HashSet<Id> = self.storage.plans()?
+            .into_iter()
+            .filter_map(|plan| plan.id)
+            .collect();
+
+        let existing_transactions: HashSet<Id> = self.storage.transactions()?
+            .into_iter()
+            .filter_map(|transaction| transaction.id)
+            .collect();
+
+        let existing_recurrings: HashSet<Id> = self.storage.recurrings()?
+            .into_iter()
+            .filter_map(|recurring| recurring.id)
+            .collect();
+
+        let has_user_categories = existing_categories
+            .iter()
+            .any(|category| !Self::is_transfer_category(*category));
+
+        let is_empty = !has_user_categories && existing_accounts.is_empty() && 
+            existing_plans.is_empty() && existing_transactions.is_empty() && existing_recurrings.is_empty();
+
+        if !is_empty && !force {
+            return Err(Error::new(STORAGE_IS_NOT_EMPTY));
+        }
+
+        self.storage.with_transaction(|storage| {
+            for category in backup.categories.iter().filter(|c| !existing_categories.contains(&c.id.unwrap())) {
+                storage.add_category(self.encrypt_category(category)?)?;
+                if category.meta_info.changed_timestamp.is_some() {
+                    storage.update_category(self.encrypt_category(category)?)?;
+                }
+            }
+
+            for account in backup.accounts.iter().filter(|a| !existing_accounts.contains(&a.id.unwrap())) {
+                storage.add_account(self.encrypt_account(account)?)?;
+                if let Some(changed_timestamp) = account.meta_info.changed_timestamp {
+                    storage.update_account_with_timestamp(self.encrypt_account(account)?, changed_timestamp)?;
+                }
+            }
+
+            for plan in backup.plans.iter().filter(|p| !existing_plans.contains(&p.id.unwrap())) {
+                storage.add_plan(self.encrypt_plan(plan)?)?;
+                if plan.meta_info.changed_timestamp.is_some() {
+                    storage.update_plan(self.encrypt_plan(plan)?)?;
+                }
+            }
+
+            for recurring in backup.recurrings.iter().filter(|r| !existing_recurrings.contains(&r.id.unwrap())) {
+                storage.add_recurring(self.encrypt_recurring(recurring)?)?;
+                if recurring.meta_info.changed_timestamp.is_some() {
+                    storage.update_recurring(self.encrypt_recurring(recurring)?)?;
+                }
+            }
+
+            for transaction in backup.transactions.iter().filter(|t| !existing_transactions.contains(&t.id.unwrap())) {
+                storage.add_transaction(self.encrypt_transaction(transaction)?)?;
+                if transaction.meta_info.changed_timestamp.is_some() {
+                    storage.update_transaction(self.encrypt_transaction(transaction)?)?;
+                }
+            }
+
+            //
+            // Restored transactions could belong to already existing accounts,
+            // so balances are recalculated when restoring into non-empty storage
+            //
+
+            if !is_empty {
+                for account in self.storage.accounts_including_archived()? {
+                    self.recalculate_balance(account.id.unwrap())?;
+                }
+            }
+
+            Ok(())
+        })
     }
 
-    /// Add a new plan.
+    /// Remove transaction.
     /// 
-    /// * `plan` - plan data
-    pub fn add_plan(&self, plan: &Plan) -> Result<()> {
-        let mut plan = self.encrypt_plan(plan)?;
-        plan.meta_info.set_origin_if_absent(self.instance_id());
-        
-        self.storage.add_plan(plan)
+    /// * `transaction` - identifier of a transaction to remove
+    /// * `emergency` - if `true`, then the linked account will not be updated
+    /// * `removal_timestame` - this value will be written as removal timestamp
+    pub fn remove_transaction(&self, transaction: Id, emergency: bool, removal_timestamp: Timestamp) -> Result<()> {
+        if emergency {
+            return self.storage.remove_transaction(transaction, removal_timestamp);
+        }
+
+        //
+        // Account update and transaction removal are performed atomically
+        //
+
+        self.storage.with_transaction(|storage| {
+            let decrypted_transaction = self.decrypt_transaction(
+                &storage.transaction(transaction)?)?;
+
+            let mut decrypted_account = self.decrypt_account(
+                &storage.account(decrypted_transaction.account_id)?)?;
+
+            //
+            // Again, amount in transaction is considered to have a proper sign,
+            // hence I just subtract it from account's balance
+            //
+
+            decrypted_account.balance -= Self::balance_amount(&decrypted_transaction);
+
+            storage.update_account(self.encrypt_account(&decrypted_account)?)?;
+            storage.remove_transaction(transaction, removal_timestamp)
+        })
     }
 
-    /// Remove plan.
+    /// Restore a removed transaction, that is not deleted permanently yet.
     /// 
-    /// * `plan` - identifier of plan to remove
+    /// Transaction's amount is applied to account's balance again. Fails
+    /// with consistency error if transaction's account or category has
+    /// been removed.
+    /// 
+    /// * `transaction` - identifier of a transaction to restore
+    pub fn restore_transaction(&self, transaction: Id) -> Result<()> {
+        self.storage.with_transaction(|storage| {
+            let decrypted_transaction = self.decrypt_transaction(
+                &storage.removed_transaction(transaction)?)?;
+
+            let encrypted_account = match storage.account(decrypted_transaction.account_id) {
+                Err(error) if error.is_not_found() => return Err(Error::new(ACCOUNT_IS_REMOVED)),
+                result => result?
+            };
+
+            storage.restore_transaction(transaction, Clock::now())?;
+
+            let mut decrypted_account = self.decrypt_account(&encrypted_account)?;
+            decrypted_account.balance += Self::balance_amount(&decrypted_transaction);
+
+            storage.update_account(self.encrypt_account(&decrypted_account)?)
+        })
+    }
+
+    /// Remove both transactions of a transfer.
+    /// 
+    /// Balances of both accounts are adjusted atomically.
+    /// 
+    /// * `transfer` - identifier of a transfer to remove
     /// * `removal_timestame` - this value will be written as removal timestamp
-    pub fn remove_plan(&self, plan: Id, removal_timestamp: Timestamp) -> Result<()> {
-        self.storage.remove_plan(plan, removal_timestamp)
+    pub fn remove_transfer(&self, transfer: Id, removal_timestamp: Timestamp) -> Result<()> {
+        let transactions = self.storage.transactions_of_transfer(transfer)?;
+        if transactions.is_empty() {
+            return Err(Error::new(TRANSFER_NOT_FOUND));
+        }
+
+        self.storage.with_transaction(|_| {
+            for transaction in transactions {
+                self.remove_transaction(transaction.id.unwrap(), false, removal_timestamp)?;
+            }
+
+            Ok(())
+        })
     }
 
-    /// Return plan with a given identifier.
+    /// Return transaction with a given identifier.
     /// 
-    /// * `plan` - identifier to return record for
-    pub fn plan(&self, plan: Id) -> Result<Plan> {
-        self.decrypt_plan(&self.storage.plan(plan)?)
+    /// * `transaction` - identifier to return record for
+    pub fn transaction(&self, transaction: Id) -> Result<Transaction> {
+        self.decrypt_transaction(&self.storage.transaction(transaction)?)
+    }
+
+    /// Return transaction with a given identifier even if it is removed
+    /// (but not deleted permanently yet).
+    /// 
+    /// Removal state can be checked using transaction's meta info.
+    /// 
+    /// * `transaction` - identifier to return record for
+    pub fn transaction_any(&self, transaction: Id) -> Result<Transaction> {
+        self.decrypt_transaction(&self.storage.transaction_any(transaction)?)
+    }
+
+    // Return all transactions.
+    pub fn transactions(&self) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions()?)
+    }
+
+    /// Return an iterator over all transactions sorted by timestamp in descending
+    /// order.
+    /// 
+    /// Transactions are decrypted lazily while iterating. Decryption errors
+    /// are returned as items, hence a corrupted item can be skipped.
+    pub fn transactions_iter(&self) -> Result<impl Iterator<Item = Result<Transaction>> + '_> {
+        let transactions = self.storage
+            .transactions()?
+            .into_iter()
+            .map(|transaction| self.decrypt_transaction(&transaction));
+
+        Ok(transactions)
+    }
+
+    /// Return a page of transactions sorted by timestamp in descending order.
+    /// 
+    /// * `offset` - number of transactions to skip
+    /// * `limit` - maximum number of transactions to return
+    pub fn transactions_page(&self, offset: usize, limit: usize) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions_page(offset, limit)?)
+    }
+
+    /// Return number of transactions. Nothing is decrypted here.
+    pub fn transactions_count(&self) -> Result<usize> {
+        self.storage.transactions_count()
+    }
+
+    /// Return all transactions between a given time points (including start 
+    /// of the interval and excluding the end) sorted by timestamp in 
+    /// descending order.
+    /// 
+    /// Used for optimization.
+    /// 
+    /// * `start_timestamp` - point in time to start from
+    /// * `end_timestamp` - point in time to end before
+    pub fn transactions_between(&self, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions_between(start_timestamp, end_timestamp)?) 
+    }
+
+    /// Return all transactions made on a given day sorted by timestamp in
+    /// descending order.
+    /// 
+    /// Day starts at local midnight in configured timezone (UTC if no
+    /// timezone is configured).
+    /// 
+    /// * `date` - calendar date
+    pub fn transactions_on_day(&self, date: chrono::NaiveDate) -> Result<Vec<Transaction>> {
+        let (start_timestamp, end_timestamp) = datetime::local_day_bounds(date, self.timezone())
+            .ok_or_else(|| Error::new_with_extra(INVALID_DATE, date.to_string()))?;
+
+        self.transactions_between(start_timestamp, end_timestamp)
+    }
+
+    /// Return all transactions bound with a given account sorted by timestamp 
+    /// in descending order.
+    /// 
+    /// Used for optimization.
+    /// 
+    /// * `account` - account identifier to return transactions for
+    pub fn transactions_of(&self, account: Id) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions_of(account)?) 
+    }
+
+    /// Return a page of transactions bound with a given account sorted by 
+    /// timestamp in descending order.
+    /// 
+    /// * `account` - account identifier to return transactions for
+    /// * `offset` - number of transactions to skip
+    /// * `limit` - maximum number of transactions to return
+    pub fn transactions_of_page(&self, account: Id, offset: usize, limit: usize) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions_of_page(account, offset, limit)?)
+    }
+
+    /// Return all transactions between a given time points (including start 
+    /// of the interval and excluding the end) bound with a given account 
+    /// sorted by timestamp in descending order.
+    /// 
+    /// Used for optimization.
+    /// 
+    /// * `account` - account identifier to return transactions for
+    /// * `start_timestamp` - point in time to start from
+    /// * `end_timestamp` - point in time to end before
+    pub fn transactions_of_between(&self, account: Id, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions_of_between(account, start_timestamp, end_timestamp)?) 
+    }
+
+    /// Return all transactions with given category sorted by timestamp in
+    /// descending order.
+    /// 
+    /// Used for optimization.
+    /// 
+    /// * `category` - category to return transactions with
+    pub fn transactions_with(&self, category: Id) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions_with(category)?) 
+    }
+
+    /// Return transactions marked with a tag.
+    /// 
+    /// Tags are encrypted, hence all transactions are decrypted and
+    /// filtered afterwards.
+    /// 
+    /// * `tag` - tag to return transactions with
+    pub fn transactions_tagged(&self, tag: &str) -> Result<Vec<Transaction>> {
+        let transactions = self.transactions()?
+            .into_iter()
+            .filter(|transaction| transaction.tags.iter().any(|t| t == tag))
+            .collect();
+
+        Ok(transactions)
+    }
+
+    /// Return a page of transactions with given category sorted by timestamp
+    /// in descending order.
+    /// 
+    /// * `category` - category to return transactions with
+    /// * `offset` - number of transactions to skip
+    /// * `limit` - maximum number of transactions to return
+    pub fn transactions_with_page(&self, category: Id, offset: usize, limit: usize) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions_with_page(category, offset, limit)?)
+    }
+
+    /// Return all transactions between a given time points (including start 
+    /// of the interval and excluding the end) and with given category 
+    /// sorted by timestamp in descending order.
+    /// 
+    /// Used for optimization.
+    /// 
+    /// * `category` - category to return transactions with
+    /// * `start_timestamp` - point in time to start from
+    /// * `end_timestamp` - point in time to end before
+    pub fn transactions_with_between(&self, category: Id, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions_with_between(category, start_timestamp, end_timestamp)?) 
+    }
+
+    /// Add a new account. Returns identifier of the added account.
+    /// 
+    /// * `account` - account data
+    pub fn add_account(&self, account: &Account) -> Result<Id> {
+        let mut account = self.encrypt_account(account)?;
+        account.meta_info.set_origin_if_absent(self.instance_id());
+
+        self.storage.add_account(account)
+    }
+
+    /// Update an existing account.
+    /// 
+    /// Current balance from `account` is ignored: it is recomputed as
+    /// initial balance plus sum of all account's transactions.
+    /// 
+    /// If no change timestamp is set in account's meta info, current
+    /// time is used.
+    /// 
+    /// * `account` - account data (with identifier of an account to update)
+    pub fn update_account(&self, account: &Account) -> Result<()> {
+        let id = account.id
+            .ok_or(Error::new(MISSING_IDENTIFIER))?;
+
+        //
+        // Balance is recomputed here to prevent its desync
+        // with the account's transactions
+        //
+
+        let mut account = account.clone();
+        account.balance = account.initial_balance + self.transactions_sum(id)?;
+
+        let change_timestamp = account.meta_info.changed_timestamp
+            .unwrap_or(Clock::now());
+
+        self.storage.update_account_with_timestamp(self.encrypt_account(&account)?, change_timestamp)
+    }
+
+    /// Change initial balance of an account. Current balance is shifted
+    /// by the same delta, hence balances in the past remain consistent
+    /// with the account's transactions.
+    ///
+    /// Fails if account is removed or archived. Returns old and new
+    /// initial balances.
+    ///
+    /// * `account` - identifier of an account to adjust
+    /// * `new_initial` - new initial balance
+    pub fn adjust_initial_balance(&self, account: Id, new_initial: isize) -> Result<(isize, isize)> {
+        self.storage.with_transaction(|storage| {
+            let mut decrypted_account = self.account(account)?;
+            Self::ensure_not_archived(&decrypted_account)?;
+
+            let old_initial = decrypted_account.initial_balance;
+            decrypted_account.initial_balance = new_initial;
+            decrypted_account.balance += new_initial - old_initial;
+
+            storage.update_account_with_timestamp(self.encrypt_account(&decrypted_account)?, Clock::now())?;
+
+            Ok((old_initial, new_initial))
+        })
+    }
+
+    /// Remove an account if possible (or forced).
+    /// 
+    /// If account has transaction and `force` is false, then this function fails.
+    /// 
+    /// * `account` - identifier of an account to remove
+    /// * `force` - if true, then account is deleted anyway with all of its transactions and recurring transactions
+    /// * `removal_timestame` - this value will be written as removal timestamp
+    pub fn remove_account(&self, account: Id, force: bool, removal_timestamp: Timestamp) -> Result<()> {
+        self.storage.with_transaction(|storage| {
+            if force {
+                //
+                // Forced removal is requested, hence I need to remove
+                // all linked transactions first
+                //
+
+                for transaction in storage.transactions_of(account)? {
+                    storage.remove_transaction(transaction.id.unwrap(), removal_timestamp)?;
+                }
+
+                for recurring in storage.recurrings()?.into_iter().filter(|r| r.account_id == account) {
+                    storage.remove_recurring(recurring.id.unwrap(), removal_timestamp)?;
+                }
+            }
+
+            storage.remove_account(account, removal_timestamp)
+        })
+    }
+
+    /// Restore a removed account, that is not deleted permanently yet.
+    /// 
+    /// Transactions removed together with the account are not restored,
+    /// hence account's balance is recalculated.
+    /// 
+    /// * `account` - identifier of an account to restore
+    pub fn restore_account(&self, account: Id) -> Result<()> {
+        self.storage.with_transaction(|storage| {
+            storage.restore_account(account, Clock::now())?;
+            self.recalculate_balance(account)?;
+
+            Ok(())
+        })
+    }
+
+    /// Return account with a given identifier.
+    /// 
+    /// * `account` - identifier to return record for
+    pub fn account(&self, account: Id) -> Result<Account> {
+        self.decrypt_account(&self.storage.account(account)?)
+    }
+
+    /// Return all accounts except archived ones.
+    pub fn accounts(&self) -> Result<Vec<Account>> {
+        self.decrypt_accounts(&self.storage.accounts()?)
+    }
+
+    /// Return all accounts of a given type except archived ones.
+    /// 
+    /// * `account_type` - type to return accounts of, `None` means any type
+    pub fn accounts_of_type(&self, account_type: Option<AccountType>) -> Result<Vec<Account>> {
+        match account_type {
+            Some(account_type) => self.decrypt_accounts(&self.storage.accounts_of_type(account_type)?),
+            None => self.accounts()
+        }
+    }
+
+    /// Return all accounts including archived ones.
+    pub fn accounts_including_archived(&self) -> Result<Vec<Account>> {
+        self.decrypt_accounts(&self.storage.accounts_including_archived()?)
+    }
+
+    /// Return account with a given name (case-insensitive) if it exists.
+    /// Archived accounts are looked up too.
+    /// 
+    /// Fails if there are several accounts with the same name.
+    /// 
+    /// * `name` - name of an account to return
+    pub fn account_by_name(&self, name: &str) -> Result<Option<Account>> {
+        Self::find_by_name(self.accounts_including_archived()?, name, |account| &account.name)
+    }
+
+    /// Return all accounts (including archived ones), which names start
+    /// with a given prefix (case-insensitive).
+    /// 
+    /// * `prefix` - prefix of accounts' names
+    pub fn accounts_matching(&self, prefix: &str) -> Result<Vec<Account>> {
+        Ok(Self::filter_by_prefix(self.accounts_including_archived()?, prefix, |account| &account.name))
+    }
+
+    /// Archive an account. Transactions cannot be added to archived account,
+    /// but its history remains available.
+    /// 
+    /// * `account` - identifier of an account to archive
+    /// * `timestamp` - this value will be written as change timestamp
+    pub fn archive_account(&self, account: Id, timestamp: Timestamp) -> Result<()> {
+        self.set_archived(account, true, timestamp)
+    }
+
+    /// Unarchive a previously archived account.
+    /// 
+    /// * `account` - identifier of an account to unarchive
+    /// * `timestamp` - this value will be written as change timestamp
+    pub fn unarchive_account(&self, account: Id, timestamp: Timestamp) -> Result<()> {
+        self.set_archived(account, false, timestamp)
+    }
+
+    /// Return an iterator over all accounts except archived ones.
+    /// 
+    /// Accounts are decrypted lazily while iterating. Decryption errors
+    /// are returned as items, hence a corrupted item can be skipped.
+    pub fn accounts_iter(&self) -> Result<impl Iterator<Item = Result<Account>> + '_> {
+        let accounts = self.storage
+            .accounts()?
+            .into_iter()
+            .map(|account| self.decrypt_account(&account));
+
+        Ok(accounts)
+    }
+
+    /// Return account's balance at a given point in time.
+    /// 
+    /// Balance is computed as initial balance plus sum of all account's
+    /// transactions made strictly before the time point. Only transactions
+    /// made after the nearest balance snapshot are summed up
+    /// (see [`Budget::update_snapshots`]).
+    /// 
+    /// * `account` - identifier of an account to return balance of
+    /// * `at` - point in time to return balance at
+    pub fn balance_at(&self, account: Id, at: Timestamp) -> Result<isize> {
+        let initial_balance = self.account(account)?.initial_balance;
+
+        let (start, snapshot_balance) = match self.storage.nearest_snapshot(account, at)? {
+            Some(snapshot) => (snapshot.period_start, self.decrypt_snapshot(&snapshot)?),
+            None => (*JANUARY_1970, 0)
+        };
+
+        let transactions_sum: isize = self.transactions_of_between(account, start, at)?
+            .iter()
+            .map(Self::balance_amount)
+            .sum();
+
+        Ok(initial_balance + snapshot_balance + transactions_sum)
+    }
+
+    /// Add missing monthly balance snapshots for all accounts.
+    /// 
+    /// Snapshot is made at the start of each month up to the current one
+    /// starting from the latest existing snapshot, hence the function is cheap
+    /// if snapshots are up-to-date. Storage deletes snapshots affected by
+    /// changed transactions, so it is safe to call this function at any time.
+    /// Snapshots are derived data and they are not synchronized.
+    pub fn update_snapshots(&self) -> Result<()> {
+        let current_period = datetime::start_of_month(&Clock::now());
+
+        self.storage.with_transaction(|storage| {
+            for account in storage.accounts_including_archived()? {
+                let account = account.id.unwrap();
+                let snapshot = storage.nearest_snapshot(account, current_period)?;
+
+                let (start, mut balance) = match &snapshot {
+                    Some(snapshot) => (snapshot.period_start, self.decrypt_snapshot(snapshot)?),
+                    None => (*JANUARY_1970, 0)
+                };
+
+                //
+                // Transactions are sorted in descending order, hence
+                // I reverse them to process in chronological order
+                //
+
+                let mut transactions = self.transactions_of_between(account, start, current_period)?;
+                transactions.reverse();
+
+                let mut period_start = match (snapshot, transactions.first()) {
+                    (Some(_), _) => start,
+                    (None, Some(oldest)) => datetime::start_of_month(&oldest.timestamp),
+                    (None, None) => continue
+                };
+
+                let mut transactions = transactions
+                    .into_iter()
+                    .peekable();
+
+                while let Some(period_end) = datetime::add_months(&period_start, 1).filter(|end| *end <= current_period) {
+                    while let Some(transaction) = transactions.next_if(|transaction| transaction.timestamp < period_end) {
+                        balance += Self::balance_amount(&transaction);
+                    }
+
+                    storage.add_snapshot(self.encrypt_snapshot(account, period_end, balance)?)?;
+                    period_start = period_end;
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Delete all balance snapshots and make them again.
+    /// 
+    /// Used for recovery, if snapshots are suspected to be wrong.
+    pub fn rebuild_snapshots(&self) -> Result<()> {
+        self.storage.with_transaction(|storage| {
+            storage.clear_snapshots()?;
+            self.update_snapshots()
+        })
+    }
+
+    /// Return account's balance history between given time points.
+    /// 
+    /// Interval is split into buckets of `step` length (the last one can be
+    /// shorter). For each bucket its start and the balance at its end are
+    /// returned. Transactions made exactly at a bucket boundary belong to
+    /// the later bucket.
+    /// 
+    /// * `account` - identifier of an account to return balance history of
+    /// * `start_timestamp` - point in time to start from
+    /// * `end_timestamp` - point in time to end before
+    /// * `step` - length of each bucket, must be positive
+    pub fn balance_series(&self, account: Id, start_timestamp: Timestamp, end_timestamp: Timestamp, 
+        step: chrono::Duration) -> Result<Vec<(Timestamp, isize)>> 
+    {
+        if step <= chrono::Duration::zero() {
+            return Err(Error::new(INVALID_STEP));
+        }
+
+        //
+        // All transactions are queried at once. They are sorted in descending
+        // order, hence I reverse them to fold in chronological order
+        //
+
+        let mut balance = self.account(account)?.initial_balance;
+        let mut transactions = self.transactions_of_between(account, *JANUARY_1970, end_timestamp)?;
+        transactions.reverse();
+
+        let mut transactions = transactions
+            .into_iter()
+            .peekable();
+
+        let mut series = Vec::new();
+        let mut bucket_start = start_timestamp;
+
+        while bucket_start < end_timestamp {
+            let bucket_end = std::cmp::min(bucket_start + step, end_timestamp);
+            while let Some(transaction) = transactions.next_if(|transaction| transaction.timestamp < bucket_end) {
+                balance += Self::balance_amount(&transaction);
+            }
+
+            series.push((bucket_start, balance));
+            bucket_start = bucket_end;
+        }
+
+        Ok(series)
+    }
+
+    /// Return sum of current balances of all non-archived accounts per currency.
+    pub fn net_worth(&self) -> Result<BTreeMap<String, isize>> {
+        let mut net_worth: BTreeMap<String, isize> = BTreeMap::new();
+        for account in self.accounts()? {
+            *net_worth.entry(account.currency).or_default() += account.balance;
+        }
+
+        Ok(net_worth)
+    }
+
+    /// Return sum of current balances of all non-archived accounts
+    /// per account type and currency.
+    /// 
+    /// Balances are summed as is, hence debts on credit accounts
+    /// are negative and decrease the net worth.
+    pub fn net_worth_by_type(&self) -> Result<BTreeMap<AccountType, BTreeMap<String, isize>>> {
+        let mut net_worth: BTreeMap<AccountType, BTreeMap<String, isize>> = BTreeMap::new();
+        for account in self.accounts()? {
+            *net_worth.entry(account.account_type)
+                .or_default()
+                .entry(account.currency)
+                .or_default() += account.balance;
+        }
+
+        Ok(net_worth)
+    }
+
+    /// Return sum of current balances of all non-archived spendable
+    /// accounts per currency (see [`AccountType::is_spendable`]).
+    pub fn spendable_balance(&self) -> Result<BTreeMap<String, isize>> {
+        let mut balance: BTreeMap<String, isize> = BTreeMap::new();
+        for account in self.accounts()? {
+            if account.account_type.is_spendable() {
+                *balance.entry(account.currency).or_default() += account.balance;
+            }
+        }
+
+        Ok(balance)
+    }
+
+    /// Return sum of balances of all non-archived accounts at a given 
+    /// point in time per currency.
+    /// 
+    /// Each balance is computed as initial balance plus sum of all account's
+    /// transactions made strictly before the time point (as in [`Budget::balance_at`]).
+    /// 
+    /// * `at` - point in time to return net worth at
+    pub fn net_worth_at(&self, at: Timestamp) -> Result<BTreeMap<String, isize>> {
+        //
+        // Transactions are queried and decrypted only once,
+        // hence I aggregate them per account in memory
+        //
+
+        let mut sums: HashMap<Id, isize> = HashMap::new();
+        for transaction in self.transactions_between(*JANUARY_1970, at)? {
+            *sums.entry(transaction.account_id).or_default() += Self::balance_amount(&transaction);
+        }
+
+        let mut net_worth: BTreeMap<String, isize> = BTreeMap::new();
+        for account in self.accounts()? {
+            let balance = account.initial_balance + sums.get(&account.id.unwrap()).copied().unwrap_or_default();
+            *net_worth.entry(account.currency).or_default() += balance;
+        }
+
+        Ok(net_worth)
+    }
+
+    /// Recalculate account's balance from its transactions and store it.
+    /// 
+    /// Balance is set to initial balance plus sum of all account's transactions.
+    /// Returns the corrected balance.
+    /// 
+    /// * `account` - identifier of an account to recalculate balance of
+    pub fn recalculate_balance(&self, account: Id) -> Result<isize> {
+        self.storage.with_transaction(|storage| {
+            let mut decrypted_account = self.account(account)?;
+            decrypted_account.balance = decrypted_account.initial_balance + self.transactions_sum(account)?;
+
+            storage.update_account(self.encrypt_account(&decrypted_account)?)?;
+
+            Ok(decrypted_account.balance)
+        })
+    }
+
+    /// Return all accounts, which stored balance disagrees with the one
+    /// computed from transactions.
+    /// 
+    /// For each such account a tuple of its identifier, stored balance and
+    /// computed balance is returned. Nothing is modified.
+    pub fn verify_balances(&self) -> Result<Vec<(Id, isize, isize)>> {
+        //
+        // All transactions are queried and decrypted only once
+        //
+
+        let mut sums: HashMap<Id, isize> = HashMap::new();
+        for transaction in self.transactions()? {
+            *sums.entry(transaction.account_id).or_default() += Self::balance_amount(&transaction);
+        }
+
+        let mismatches = self.accounts_including_archived()?
+            .into_iter()
+            .filter_map(|account| {
+                let id = account.id.unwrap();
+                let computed = account.initial_balance + sums.get(&id).copied().unwrap_or_default();
+
+                (computed != account.balance)
+                    .then_some((id, account.balance, computed))
+            })
+            .collect();
+
+        Ok(mismatches)
+    }
+
+    /// Return numbers of stored items and other statistics.
+    /// 
+    /// Nothing is decrypted, hence it is much cheaper than
+    /// querying all items and counting them.
+    pub fn stats(&self) -> Result<StorageStats> {
+        self.storage.stats()
+    }
+
+    /// Check storage and balances of accounts for problems.
+    /// 
+    /// Storage integrity report is extended with accounts, which balance
+    /// disagrees with transactions (see [`Budget::verify_balances`]).
+    /// Nothing is modified.
+    pub fn verify(&self) -> Result<IntegrityReport> {
+        let mut report = self.storage.verify_integrity()?;
+
+        let mismatches = self.verify_balances()?
+            .into_iter()
+            .map(|(id, stored, computed)| IntegrityIssue {
+                kind: IntegrityIssueKind::BalanceMismatch,
+                table: "accounts".to_owned(),
+                item: Some(id),
+                reference: None,
+                details: format!("stored: {}, computed: {}", stored, computed)
+            });
+
+        report.issues.extend(mismatches);
+        Ok(report)
+    }
+
+    /// Add a new category. Returns identifier of the added category.
+    /// 
+    /// * `category` - category data
+    pub fn add_category(&self, category: &Category) -> Result<Id> {
+        let mut category = self.encrypt_category(category)?;
+        category.meta_info.set_origin_if_absent(self.instance_id());
+
+        self.storage.add_category(category)
+    }
+
+    /// Update an existing category.
+    /// 
+    /// Only category name can be changed, its type is preserved.
+    /// Predefined categories cannot be updated.
+    /// 
+    /// If no change timestamp is set in category's meta info, current
+    /// time is used.
+    /// 
+    /// * `category` - category data (with identifier of a category to update)
+    pub fn update_category(&self, category: &Category) -> Result<()> {
+        if category.id.is_none() {
+            return Err(Error::new(MISSING_IDENTIFIER));
+        }
+
+        let mut category = self.encrypt_category(category)?;
+        category.meta_info.set_changed_timestamp_if_absent(Clock::now());
+
+        self.storage.update_category(category)
+    }
+
+    /// Remove category if possible.
+    /// 
+    /// If there is at leas one transaction with the specified
+    /// category, then this function fails. There is no way to
+    /// remove category with existing transactions.
+    /// 
+    /// * `category` - identifier of category to remove
+    /// * `removal_timestame` - this value will be written as removal timestamp
+    pub fn remove_category(&self, category: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.storage.remove_category(category, removal_timestamp)
+    }
+
+    /// Restore a removed category, that is not deleted permanently yet.
+    /// 
+    /// * `category` - identifier of a category to restore
+    pub fn restore_category(&self, category: Id) -> Result<()> {
+        self.storage.restore_category(category, Clock::now())
+    }
+
+    /// Return category with a given identifier.
+    /// 
+    /// * `category` - identifier to return record for
+    pub fn category(&self, category: Id) -> Result<Category> {
+        self.decrypt_category(&self.storage.category(category)?)
+    }
+
+    /// Return all categories.
+    pub fn categories(&self) -> Result<Vec<Category>> {
+        self.decrypt_categories(&self.storage.categories()?)
+    }
+
+    /// Return an iterator over all categories.
+    /// 
+    /// Categories are decrypted lazily while iterating. Decryption errors
+    /// are returned as items, hence a corrupted item can be skipped.
+    pub fn categories_iter(&self) -> Result<impl Iterator<Item = Result<Category>> + '_> {
+        let categories = self.storage
+            .categories()?
+            .into_iter()
+            .map(|category| self.decrypt_category(&category));
+
+        Ok(categories)
+    }
+
+    /// Return all categories of specific type.
+    /// 
+    /// * `category_type` - type to return categories of
+    pub fn categories_of(&self, category_type: CategoryType) -> Result<Vec<Category>> {
+        self.decrypt_categories(&self.storage.categories_of(category_type)?)
+    }
+
+    /// Return category with a given name (case-insensitive) if it exists.
+    /// 
+    /// Fails if there are several categories with the same name,
+    /// e.g. income and outcome ones, and type is not specified.
+    /// 
+    /// * `name` - name of a category to return
+    /// * `category_type` - type of a category to return, any type if `None`
+    pub fn category_by_name(&self, name: &str, category_type: Option<CategoryType>) -> Result<Option<Category>> {
+        Self::find_by_name(self.categories_by_type(category_type)?, name, |category| &category.name)
+    }
+
+    /// Return all categories, which names start with a given prefix (case-insensitive).
+    /// 
+    /// * `prefix` - prefix of categories' names
+    /// * `category_type` - type of categories to return, any type if `None`
+    pub fn categories_matching(&self, prefix: &str, category_type: Option<CategoryType>) -> Result<Vec<Category>> {
+        Ok(Self::filter_by_prefix(self.categories_by_type(category_type)?, prefix, |category| &category.name))
+    }
+
+    /// Add a new plan. Returns identifier of the added plan.
+    /// 
+    /// * `plan` - plan data
+    pub fn add_plan(&self, plan: &Plan) -> Result<Id> {
+        let mut plan = self.encrypt_plan(plan)?;
+        plan.meta_info.set_origin_if_absent(self.instance_id());
+        
+        self.storage.add_plan(plan)
+    }
+
+    /// Update an existing plan.
+    /// 
+    /// If no change timestamp is set in plan's meta info, current
+    /// time is used.
+    /// 
+    /// * `plan` - plan data (with identifier of a plan to update)
+    pub fn update_plan(&self, plan: &Plan) -> Result<()> {
+        if plan.id.is_none() {
+            return Err(Error::new(MISSING_IDENTIFIER));
+        }
+
+        let mut plan = self.encrypt_plan(plan)?;
+        plan.meta_info.set_changed_timestamp_if_absent(Clock::now());
+
+        self.storage.update_plan(plan)
+    }
+
+    /// Remove plan.
+    /// 
+    /// * `plan` - identifier of plan to remove
+    /// * `removal_timestame` - this value will be written as removal timestamp
+    pub fn remove_plan(&self, plan: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.storage.remove_plan(plan, removal_timestamp)
+    }
+
+    /// Restore a removed plan, that is not deleted permanently yet.
+    /// 
+    /// Fails if plan's category has been removed.
+    /// 
+    /// * `plan` - identifier of a plan to restore
+    pub fn restore_plan(&self, plan: Id) -> Result<()> {
+        self.storage.restore_plan(plan, Clock::now())
+    }
+
+    /// Return plan with a given identifier.
+    /// 
+    /// * `plan` - identifier to return record for
+    pub fn plan(&self, plan: Id) -> Result<Plan> {
+        self.decrypt_plan(&self.storage.plan(plan)?)
+    }
+
+    /// Return all plans sorted by category.
+    pub fn plans(&self) -> Result<Vec<Plan>> {
+        self.decrypt_plans(&self.storage.plans()?)
+    }
+
+    /// Return an iterator over all plans sorted by category.
+    /// 
+    /// Plans are decrypted lazily while iterating. Decryption errors
+    /// are returned as items, hence a corrupted item can be skipped.
+    pub fn plans_iter(&self) -> Result<impl Iterator<Item = Result<Plan>> + '_> {
+        let plans = self.storage
+            .plans()?
+            .into_iter()
+            .map(|plan| self.decrypt_plan(&plan));
+
+        Ok(plans)
+    }
+
+    /// Return all plans for specific category.
+    /// 
+    /// * `category` - category to return plans for
+    pub fn plans_for(&self, category: Id) -> Result<Vec<Plan>> {
+        self.decrypt_plans(&self.storage.plans_for(category)?)
+    }
+
+    /// Return progress of a plan during a given period (including start
+    /// of the interval and excluding the end).
+    /// 
+    /// Spent amount is a sum of absolute amounts of transactions with
+    /// plan's category.
+    /// 
+    /// * `plan` - identifier of a plan to return progress for
+    /// * `start_timestamp` - point in time to start from
+    /// * `end_timestamp` - point in time to end before
+    pub fn plan_progress(&self, plan: Id, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<PlanProgress> {
+        let plan = self.plan(plan)?;
+        let transactions = self.transactions_with_between(plan.category_id, start_timestamp, end_timestamp)?;
+
+        Ok(PlanProgress::new(plan.id.unwrap(), plan.amount_limit, 
+            Self::spent_amount(transactions.iter())))
+    }
+
+    /// Return status of a plan in its period, that contains a given time point.
+    /// 
+    /// If plan has rollover enabled, unused allowance of all previous periods
+    /// (since plan's creation) is carried over. Overspending is not carried over.
+    /// 
+    /// * `plan` - identifier of a plan to return status for
+    /// * `at` - point in time to determine the current period
+    pub fn plan_status(&self, plan: Id, at: Timestamp) -> Result<PlanStatus> {
+        let plan = self.plan(plan)?;
+        let (period_start, period_end) = self.plan_period_bounds(plan.period, &at)?;
+
+        let spent = Self::spent_amount(self
+            .transactions_with_between(plan.category_id, period_start, period_end)?
+            .iter());
+
+        let carried_over = match (plan.rollover, plan.meta_info.added_timestamp) {
+            (true, Some(created)) => {
+                //
+                // Previous periods are processed in chronological order,
+                // transactions are queried only once for all of them
+                //
+
+                let (mut previous_start, _) = self.plan_period_bounds(plan.period, &created)?;
+                let transactions = self.transactions_with_between(plan.category_id, previous_start, period_start)?;
+
+                let mut carried_over = 0;
+                while previous_start < period_start {
+                    let (_, previous_end) = self.plan_period_bounds(plan.period, &previous_start)?;
+                    let previous_spent = Self::spent_amount(transactions
+                        .iter()
+                        .filter(|t| previous_start <= t.timestamp && t.timestamp < previous_end));
+
+                    carried_over = std::cmp::max(0, plan.amount_limit + carried_over - previous_spent);
+                    previous_start = previous_end;
+                }
+
+                carried_over
+            },
+            _ => 0
+        };
+
+        Ok(PlanStatus::new(plan.id.unwrap(), (period_start, period_end), 
+            plan.amount_limit, carried_over, spent))
+    }
+
+    /// Add a new transaction and return alerts about plans for its category,
+    /// that are exceeded after the transaction is added.
+    /// 
+    /// Plans are checked in their periods, that contain the transaction.
+    /// Plans, that were exceeded before, are reported too, since the transaction
+    /// increases their overshoot. If category has no plans, nothing is evaluated.
+    /// If plans cannot be evaluated, the transaction is not added.
+    /// 
+    /// * `transaction` - transaction to add
+    pub fn add_transaction_checked(&self, transaction: &Transaction) -> Result<Vec<PlanAlert>> {
+        self.storage.with_transaction(|storage| {
+            self.add_transaction(transaction)?;
+
+            let plans = storage.plans_for(transaction.category_id)?;
+            self.plans_exceeded_at(plans.iter().filter_map(|plan| plan.id), transaction.timestamp)
+        })
+    }
+
+    /// Return alerts about all plans, that are exceeded in their periods,
+    /// which contain a given time point.
+    /// 
+    /// * `at` - point in time to determine periods of plans
+    pub fn plan_alerts(&self, at: Timestamp) -> Result<Vec<PlanAlert>> {
+        let plans = self.storage.plans()?;
+        self.plans_exceeded_at(plans.iter().filter_map(|plan| plan.id), at)
+    }
+
+    /// Return progress of all plans during a given period (including start
+    /// of the interval and excluding the end).
+    /// 
+    /// Transactions are queried and decrypted only once for all plans.
+    /// 
+    /// * `start_timestamp` - point in time to start from
+    /// * `end_timestamp` - point in time to end before
+    pub fn plans_progress(&self, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<PlanProgress>> {
+        let transactions = self.transactions_between(start_timestamp, end_timestamp)?;
+
+        let progress = self.plans()?
+            .iter()
+            .map(|plan| {
+                let spent = Self::spent_amount(transactions
+                    .iter()
+                    .filter(|transaction| transaction.category_id == plan.category_id));
+
+                PlanProgress::new(plan.id.unwrap(), plan.amount_limit, spent)
+            })
+            .collect();
+
+        Ok(progress)
+    }
+
+    /// Return signed totals of transactions for each category and currency 
+    /// during a given period (including start of the interval and excluding the end).
+    /// 
+    /// Amounts in different currencies are never summed up, hence a category
+    /// appears once per currency of its transactions' accounts.
+    /// Transactions are queried and decrypted only once.
+    /// 
+    /// * `start_timestamp` - point in time to start from
+    /// * `end_timestamp` - point in time to end before
+    /// * `include_empty` - if `true`, then categories without transactions are included with zero total in default currency
+    /// * `include_transfers` - if `true`, then predefined transfer categories are included
+    pub fn category_totals(&self, start_timestamp: Timestamp, end_timestamp: Timestamp, 
+        include_empty: bool, include_transfers: bool) -> Result<Vec<(Category, String, isize)>> 
+    {
+        let currencies = Self::account_currencies(&self.accounts_including_archived()?);
+        let transactions = self.transactions_between(start_timestamp, end_timestamp)?;
+
+        self.group_category_totals(&transactions, &currencies, include_empty, include_transfers)
+    }
+
+    /// Return summary of a calendar month.
+    /// 
+    /// Transactions are queried and decrypted only once. Accounts' deltas
+    /// ignore pending transactions as accounts' balances do. Month starts
+    /// at local midnight in configured timezone (UTC if no timezone is
+    /// configured).
+    /// 
+    /// * `year` - calendar year
+    /// * `month` - month number (starting from 1)
+    pub fn monthly_report(&self, year: i32, month: u32) -> Result<MonthlyReport> {
+        let (period_start, period_end) = datetime::local_month_bounds(year, month, self.timezone())
+            .ok_or_else(|| Error::new_with_extra(INVALID_MONTH, format!("{year}-{month}")))?;
+
+        let transactions = self.transactions_between(period_start, period_end)?;
+        let accounts = self.accounts_including_archived()?;
+        let currencies = Self::account_currencies(&accounts);
+
+        //
+        // Income and outcome are computed by sign of amount,
+        // transfers are skipped here, because they only move
+        // money between own accounts
+        //
+
+        let mut income: BTreeMap<String, isize> = BTreeMap::new();
+        let mut outcome: BTreeMap<String, isize> = BTreeMap::new();
+        let mut deltas: HashMap<Id, isize> = HashMap::new();
+
+        for transaction in &transactions {
+            *deltas.entry(transaction.account_id).or_default() += Self::balance_amount(transaction);
+
+            if Self::is_transfer_category(transaction.category_id) {
+                continue;
+            }
+
+            let currency = currencies.get(&transaction.account_id)
+                .map_or(self.config.currency(), String::as_str)
+                .to_owned();
+            match transaction.amount.is_negative() {
+                true => *outcome.entry(currency).or_default() += transaction.amount.abs(),
+                false => *income.entry(currency).or_default() += transaction.amount
+            }
+        }
+
+        let categories = self.group_category_totals(&transactions, &currencies, false, false)?;
+
+        let accounts = accounts
+            .into_iter()
+            .filter_map(|account| {
+                let delta = deltas.get(&account.id.unwrap()).copied();
+                delta.map(|delta| (account, delta))
+            })
+            .collect();
+
+        let plan_overruns = self.plans()?
+            .iter()
+            .map(|plan| {
+                let spent = Self::spent_amount(transactions
+                    .iter()
+                    .filter(|transaction| transaction.category_id == plan.category_id));
+
+                PlanProgress::new(plan.id.unwrap(), plan.amount_limit, spent)
+            })
+            .filter(|progress| progress.remaining < 0)
+            .collect();
+
+        Ok(MonthlyReport {
+            period_start,
+            period_end,
+            income,
+            outcome,
+            categories,
+            accounts,
+            plan_overruns,
+            transactions_count: transactions.len()
+        })
+    }
+
+    /// Return income and outcome history between given time points.
+    /// 
+    /// Interval is split into buckets of `bucket` length (the last one can be
+    /// shorter). Buckets without transactions are returned with zeros.
+    /// 
+    /// If timezone is configured and `bucket` is a whole number of days, then
+    /// each bucket ends at the same local time of day as it starts, hence
+    /// buckets stay aligned with local days across daylight saving time
+    /// transitions.
+    /// 
+    /// * `start_timestamp` - point in time to start from
+    /// * `end_timestamp` - point in time to end before
+    /// * `bucket` - length of each bucket, must be positive
+    /// * `include_transfers` - if `true`, then transactions in predefined transfer categories are included
+    pub fn cashflow_series(&self, start_timestamp: Timestamp, end_timestamp: Timestamp, 
+        bucket: chrono::Duration, include_transfers: bool) -> Result<Vec<CashflowPoint>> 
+    {
+        if bucket <= chrono::Duration::zero() {
+            return Err(Error::new(INVALID_STEP));
+        }
+
+        let currencies = Self::account_currencies(&self.accounts_including_archived()?);
+        let zeros: BTreeMap<String, isize> = currencies
+            .values()
+            .map(|currency| (currency.clone(), 0))
+            .collect();
+
+        //
+        // All transactions are queried at once. They are sorted in descending
+        // order, hence I reverse them to process in chronological order
+        //
+
+        let mut transactions = self.transactions_between(start_timestamp, end_timestamp)?;
+        transactions.reverse();
+
+        let mut transactions = transactions
+            .into_iter()
+            .filter(|transaction| include_transfers || !Self::is_transfer_category(transaction.category_id))
+            .peekable();
+
+        let mut series = Vec::new();
+        let mut bucket_start = start_timestamp;
+
+        while bucket_start < end_timestamp {
+            let bucket_end = datetime::add_local(&bucket_start, bucket, self.timezone())
+                .map_or(end_timestamp, |bucket_end| std::cmp::min(bucket_end, end_timestamp));
+            let mut point = CashflowPoint {
+                start: bucket_start,
+                income: zeros.clone(),
+                outcome: zeros.clone()
+            };
+
+            while let Some(transaction) = transactions.next_if(|transaction| transaction.timestamp < bucket_end) {
+                let currency = currencies.get(&transaction.account_id)
+                    .map_or(self.config.currency(), String::as_str)
+                    .to_owned();
+
+                match transaction.amount.is_negative() {
+                    true => *point.outcome.entry(currency).or_default() += transaction.amount.abs(),
+                    false => *point.income.entry(currency).or_default() += transaction.amount
+                }
+            }
+
+            series.push(point);
+            bucket_start = bucket_end;
+        }
+
+        Ok(series)
+    }
+
+    /// Add a new recurring transaction. Returns identifier of the added recurring transaction.
+    /// 
+    /// * `recurring` - recurring transaction data
+    pub fn add_recurring(&self, recurring: &RecurringTransaction) -> Result<Id> {
+        let mut recurring = self.encrypt_recurring(recurring)?;
+        recurring.meta_info.set_origin_if_absent(self.instance_id());
+
+        self.storage.add_recurring(recurring)
+    }
+
+    /// Update an existing recurring transaction.
+    /// 
+    /// If no change timestamp is set in recurring transaction's meta info, 
+    /// current time is used.
+    /// 
+    /// * `recurring` - recurring transaction data (with identifier of an item to update)
+    pub fn update_recurring(&self, recurring: &RecurringTransaction) -> Result<()> {
+        if recurring.id.is_none() {
+            return Err(Error::new(MISSING_IDENTIFIER));
+        }
+
+        let mut recurring = self.encrypt_recurring(recurring)?;
+        recurring.meta_info.set_changed_timestamp_if_absent(Clock::now());
+
+        self.storage.update_recurring(recurring)
+    }
+
+    /// Remove recurring transaction. Already created transactions are kept.
+    /// 
+    /// * `recurring` - identifier of recurring transaction to remove
+    /// * `removal_timestame` - this value will be written as removal timestamp
+    pub fn remove_recurring(&self, recurring: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.storage.remove_recurring(recurring, removal_timestamp)
+    }
+
+    /// Return recurring transaction with a given identifier.
+    /// 
+    /// * `recurring` - identifier to return record for
+    pub fn recurring(&self, recurring: Id) -> Result<RecurringTransaction> {
+        self.decrypt_recurring(&self.storage.recurring(recurring)?)
+    }
+
+    /// Return all recurring transactions sorted by start timestamp.
+    pub fn recurrings(&self) -> Result<Vec<RecurringTransaction>> {
+        self.decrypt_recurrings(&self.storage.recurrings()?)
+    }
+
+    /// Create transactions for all occurrences of recurring transactions, 
+    /// that are due up to a given time point (inclusive) and were not
+    /// created yet. 
+    /// 
+    /// Transactions are added as usual ones, i.e. balances are updated.
+    /// Returns identifiers of created transactions.
+    /// 
+    /// * `up_to` - point in time to create occurrences up to
+    pub fn materialize_due(&self, up_to: Timestamp) -> Result<Vec<Id>> {
+        let now = Clock::now();
+
+        self.storage.with_transaction(|_| {
+            let mut materialized = Vec::new();
+
+            for mut recurring in self.recurrings()? {
+                let mut last_occurrence = None;
+
+                //
+                // Each occurrence is computed from the start point to avoid
+                // drifting of dates, e.g. 31st Jan -> 28th Feb -> 31st Mar
+                //
+
+                for occurrence in (0..).map_while(|index| Self::nth_occurrence(&recurring, index)) {
+                    let is_after_end = recurring.end_timestamp
+                        .is_some_and(|end| occurrence > end);
+
+                    if occurrence > up_to || is_after_end {
+                        break;
+                    }
+
+                    let is_materialized = recurring.materialized_timestamp
+                        .is_some_and(|materialized| occurrence <= materialized);
+
+                    if is_materialized {
+                        continue;
+                    }
+
+                    let id = self.add_transaction(&Transaction {
+                        id: None,
+                        timestamp: occurrence,
+                        description: recurring.description.clone(),
+                        account_id: recurring.account_id,
+                        category_id: recurring.category_id,
+                        amount: recurring.amount,
+                        transfer_id: None,
+                        tags: Vec::new(),
+                        pending: false,
+                        meta_info: MetaInfo::new(Some(now), None, None)
+                    })?;
+
+                    materialized.push(id);
+                    last_occurrence = Some(occurrence);
+                }
+
+                //
+                // Materialization point is synced as usual change, hence
+                // other instances will not create the same transactions
+                //
+
+                if last_occurrence.is_some() {
+                    recurring.materialized_timestamp = last_occurrence;
+                    recurring.meta_info.changed_timestamp = Some(now);
+
+                    self.update_recurring(&recurring)?;
+                }
+            }
+
+            Ok(materialized)
+        })
+    }
+
+    /// Return all removed items, that are not deleted permanently yet.
+    /// 
+    /// Such items are deleted by [`Budget::clean_removed`] and
+    /// can be restored until then.
+    pub fn removed_items(&self) -> Result<RemovedItems> {
+        Ok(RemovedItems {
+            accounts: self.decrypt_accounts(&self.storage.accounts_removed()?)?,
+            categories: self.decrypt_categories(&self.storage.categories_removed()?)?,
+            plans: self.decrypt_plans(&self.storage.plans_removed()?)?,
+            transactions: self.decrypt_transactions(&self.storage.transactions_removed()?)?,
+            recurrings: self.decrypt_recurrings(&self.storage.recurrings_removed()?)?
+        })
+    }
+
+    /// Delete permanently all previously removed items.
+    /// 
+    /// Actually `remove_*` functions can perform no removal, e.g.
+    /// just mark items as removed. This function therefore permanently
+    /// deletes such marked items.
+    pub fn clean_removed(&self) -> Result<()> {
+        self.storage.clean_removed()
+    }
+
+    /// Delete permanently items removed strictly before a given time point.
+    /// 
+    /// Items removed later remain restorable.
+    /// 
+    /// * `cutoff` - point in time. Items removed later are kept.
+    pub fn clean_removed_before(&self, cutoff: Timestamp) -> Result<()> {
+        self.storage.clean_removed_before(cutoff)
+    }
+
+    /// Reclaims storage space left after permanently deleted items.
+    /// 
+    /// Returns storage size before and after compaction.
+    pub fn compact(&self) -> Result<CompactStats> {
+        self.storage.compact()
+    }
+
+    /// Performs synchronization with remote instances.
+    /// 
+    /// Returns numbers of merged and exported changes, and compaction
+    /// result if storage was compacted.
+    /// 
+    /// * `auth` - authentication information for synchronization
+    /// * `compact_threshold` - storage is compacted after synchronization if fraction
+    ///   of reclaimable space exceeds this value, `None` disables compaction
+    pub fn perform_sync(&self, auth: &[u8], compact_threshold: Option<f64>) -> Result<SyncSummary> {
+        let context = CryptoBuffer::from(auth);
+        self.sync_and_clean(compact_threshold, || {
+            self.sync_engine().perform_sync(self.config.instance_id(), self, &context)
+        })
+    }
+
+    /// Performs synchronization and reports its progress.
+    /// 
+    /// Works the same way as [`Budget::perform_sync`], but progress
+    /// is reported to the provided callback.
+    /// 
+    /// * `auth` - authentication information for synchronization
+    /// * `compact_threshold` - storage is compacted after synchronization if fraction
+    ///   of reclaimable space exceeds this value, `None` disables compaction
+    /// * `progress` - callback, that receives progress
+    pub fn perform_sync_with_progress<P>(&self, auth: &[u8], compact_threshold: Option<f64>, progress: P) -> Result<SyncSummary> 
+    where
+        P: Fn(SyncProgress)
+    {
+        let context = CryptoBuffer::from(auth);
+        self.sync_and_clean(compact_threshold, || {
+            self.sync_engine().perform_sync_with_progress(self.config.instance_id(), self, &context, &progress)
+        })
+    }
+
+    /// Performs synchronization with a named remote.
+    /// 
+    /// Works the same way as [`Budget::perform_sync`], but last
+    /// synchronization time is tracked for each remote separately,
+    /// hence the same data can be pushed to several remotes.
+    /// 
+    /// * `remote` - name of remote
+    /// * `auth` - authentication information for synchronization
+    /// * `compact_threshold` - storage is compacted after synchronization if fraction
+    ///   of reclaimable space exceeds this value, `None` disables compaction
+    pub fn perform_sync_with(&self, remote: &str, auth: &[u8], compact_threshold: Option<f64>) -> Result<SyncSummary> {
+        let context = CryptoBuffer::from(auth);
+        self.sync_and_clean(compact_threshold, || {
+            self.sync_engine().perform_sync_with(remote, self.config.instance_id(), self, &context)
+        })
+    }
+
+    /// Returns state of synchronization without any network operations.
+    pub fn sync_status(&self) -> Result<SyncStatus> {
+        if !self.sync_engine().is_configured() {
+            return Ok(SyncStatus::default());
+        }
+
+        //
+        // Items created before the first synchronization are exported
+        // since the initial timestamp, predefined items are older
+        //
+
+        let last_sync = self.sync_engine().last_sync()?;
+        let base = last_sync.unwrap_or(*FIRST_AFTER_JANUARY_1970);
+
+        Ok(SyncStatus {
+            last_sync,
+            pending: self.local_change_counts(base)?,
+            remote_url: self.sync_engine().remote_url()?,
+            configured: true,
+            repository_size: self.sync_engine().local_size()?
+        })
+    }
+
+    /// Returns instances, that participate in synchronization, with times
+    /// of their last synchronization. No network operations are performed,
+    /// hence the list is as fresh as the last synchronization.
+    /// 
+    /// Instances, that have never exported anything, are not listed.
+    pub fn known_instances(&self) -> Result<Vec<(InstanceId, Timestamp)>> {
+        let instances = self.instances()?
+            .into_iter()
+            .map(|instance| (instance.id, instance.last_seen))
+            .collect();
+
+        Ok(instances)
+    }
+
+    /// Returns every instance, that has ever synchronized, with its name
+    /// and time of its last synchronization. No network operations are
+    /// performed, hence the list is as fresh as the last synchronization.
+    /// 
+    /// Instances, that have never exported anything, are not listed.
+    pub fn instances(&self) -> Result<Vec<InstanceInfo>> {
+        self.sync_engine()
+            .known_instances(self)
+    }
+
+    /// Describes changes, that synchronization would apply to local storage.
+    /// 
+    /// Remote changes are received, but nothing is applied locally
+    /// and nothing is sent to remote.
+    /// 
+    /// * `auth` - authentication information for synchronization
+    pub fn preview_sync(&self, auth: &[u8]) -> Result<SyncPreview> {
+        let context = CryptoBuffer::from(auth);
+        self.sync_engine()
+            .preview_sync(self, &context)
+    }
+
+    /// Replaces an existsing remote URL with a new one.
+    /// 
+    /// * `remote` - new remote URL
+    pub fn set_remote_url(&self, remote: &str) -> Result<()> {
+        self.sync_engine()
+            .change_remote(remote)
+    }
+
+    /// Returns current remote URL, `None` if there is no remote.
+    pub fn remote_url(&self) -> Result<Option<String>> {
+        self.sync_engine()
+            .remote_url()
+    }
+
+    /// Adds a remote. Fails if there is a remote already.
+    /// 
+    /// * `remote` - remote URL
+    pub fn add_remote(&self, remote: &str) -> Result<()> {
+        self.sync_engine()
+            .add_remote(remote)
+    }
+
+    /// Removes an existing remote.
+    pub fn remove_remote(&self) -> Result<()> {
+        self.sync_engine()
+            .remove_remote()
+    }
+
+    /// Returns names and urls of all remotes.
+    pub fn remotes(&self) -> Result<Vec<(String, String)>> {
+        self.sync_engine()
+            .remotes()
+    }
+
+    /// Adds a named remote, that is synchronized independently
+    /// of the other ones. Fails if there is a remote with the
+    /// same name already.
+    /// 
+    /// * `name` - name of remote
+    /// * `remote` - remote URL
+    pub fn add_named_remote(&self, name: &str, remote: &str) -> Result<()> {
+        self.sync_engine()
+            .add_named_remote(name, remote)
+    }
+
+    /// Removes an existing named remote.
+    /// 
+    /// * `name` - name of remote
+    pub fn remove_named_remote(&self, name: &str) -> Result<()> {
+        self.sync_engine()
+            .remove_named_remote(name)
+    }
+
+    /// Discards local synchronization state and makes it match remote.
+    /// 
+    /// It is intended to recover from corrupted local synchronization
+    /// state, e.g. after interrupted checkout. Unsent local commits are
+    /// dropped, but local storage is never touched, hence all local items
+    /// are exported again during the next synchronization. Remote ones
+    /// are recognized by their origin and are not duplicated.
+    /// 
+    /// Next synchronization fails if remote changelog is compacted,
+    /// since it is performed as the very first one.
+    pub fn reset_sync(&self) -> Result<()> {
+        self.sync_engine()
+            .reset_to_remote()
+    }
+
+    /// Drops history of synchronized files and keeps their latest
+    /// revision only. Local storage is never touched.
+    /// 
+    /// Remote history is replaced as well, hence it requires an explicit
+    /// permission to force push. Remote must not have changes, that are not
+    /// synchronized yet. After that other instances should call
+    /// [`Budget::reset_sync`], otherwise their next synchronization
+    /// brings the old history back.
+    /// 
+    /// * `force_push` - whether remote history can be replaced
+    pub fn trim_sync_history(&self, force_push: bool) -> Result<()> {
+        self.sync_engine()
+            .gc(force_push)
+    }
+
+    /// Writes a synchronization bundle for offline synchronization.
+    /// 
+    /// Bundle contains all local changes since the last synchronization
+    /// and is encrypted the same way as synchronized files. Nothing
+    /// is marked as synchronized, hence the same changes are written
+    /// into the next bundle too.
+    /// 
+    /// * `writer` - destination of bundle
+    /// * `auth` - authentication information for synchronization
+    pub fn export_sync_bundle<W: std::io::Write>(&self, mut writer: W, auth: &[u8]) -> Result<()> {
+        let base = self.sync_engine()
+            .last_sync()?
+            .unwrap_or(*FIRST_AFTER_JANUARY_1970);
+
+        let changelog = self.export_local_changes(&base)?;
+
+        let mut timestamp = std::io::Cursor::new(Vec::new());
+        let mut instance = std::io::Cursor::new(Vec::new());
+        let mut encrypted_changelog = std::io::Cursor::new(Vec::new());
+        let mut mac = std::io::Cursor::new(Vec::new());
+
+        self.write_sync_files(&changelog, &mut timestamp, &mut instance, 
+            &mut encrypted_changelog, &mut mac, &CryptoBuffer::from(auth))?;
+
+        //
+        // Base timestamp is written before the rest of files,
+        // it is used by importer instead of its last sync timestamp
+        //
+
+        bundle::write_header(&mut writer)?;
+        Self::write_timestamp(&base, &mut writer)?;
+
+        writer.write_all(timestamp.get_ref())?;
+        writer.write_all(instance.get_ref())?;
+        writer.write_all(mac.get_ref())?;
+        writer.write_all(encrypted_changelog.get_ref())?;
+
+        Ok(())
+    }
+
+    /// Merges changes from a synchronization bundle into local storage.
+    /// 
+    /// Bundle is verified before anything is merged. Bundles, that are
+    /// not newer than the last imported bundle of the same instance,
+    /// are skipped, i.e. an empty summary is returned.
+    /// 
+    /// * `loc` - storage location provider (used to update configuration)
+    /// * `reader` - source of bundle
+    /// * `auth` - authentication information for synchronization
+    pub fn import_sync_bundle<L: Location, R: std::io::Read>(&mut self, loc: &L, mut reader: R, auth: &[u8]) -> Result<SyncSummary> {
+        bundle::read_header(&mut reader)?;
+
+        let base = bundle::read_part(&mut reader, std::mem::size_of::<i64>())?;
+        let timestamp = bundle::read_part(&mut reader, std::mem::size_of::<i64>())?;
+        let instance = bundle::read_part(&mut reader, std::mem::size_of::<uuid::Bytes>())?;
+        let mac = bundle::read_part(&mut reader, 2 * Mac::TAG_SIZE)?;
+
+        let mut encrypted_changelog = Vec::new();
+        reader.read_to_end(&mut encrypted_changelog)?;
+
+        //
+        // Bundle is authenticated and decrypted before anything is merged
+        //
+
+        let base = Self::read_timestamp(&mut base.as_slice())?;
+        let (changelog, remote) = self.read_remote_changelog(&mut std::io::Cursor::new(timestamp), 
+            &mut std::io::Cursor::new(instance), &mut std::io::Cursor::new(encrypted_changelog), 
+            &mut std::io::Cursor::new(mac), &CryptoBuffer::from(auth))?;
+
+        let (remote_timestamp, remote_instance) = remote
+            .ok_or(Error::new(MALFORMED_SYNC_BUNDLE))?;
+
+        let mut summary = SyncSummary {
+            remote_timestamp: Some(remote_timestamp),
+            remote_instance: Some(remote_instance),
+            ..SyncSummary::default()
+        };
+
+        let is_imported = self.config
+            .last_bundle_import(&remote_instance)
+            .is_some_and(|last_import| remote_timestamp <= last_import);
+
+        if is_imported {
+            return Ok(summary);
+        }
+
+        //
+        // Items kept despite of remote removal are marked as changed
+        // locally, hence they are exported during the next synchronization
+        //
+
+        summary.merged = self.merge_changes(&changelog, &base, &mut Changelog::new())?;
+
+        self.config
+            .set_last_bundle_import(loc, &remote_instance, remote_timestamp)?;
+
+        Ok(summary)
+    }
+}
+
+
+impl<Ce, Se, St> Syncable for Budget<Ce, Se, St> 
+where
+    Ce: CryptoEngine,
+    Se: SyncEngine,
+    St: DataStorage
+{
+    type Context = CryptoBuffer;
+
+    type InstanceId = InstanceId;
+
+    type Preview = SyncPreview;
+
+    type Summary = SyncSummary;
+
+    type Instances = Vec<InstanceInfo>;
+
+    fn instance_name(&self) -> Option<&str> {
+        self.config
+            .instance_name()
+    }
+
+    fn merge_and_export_changes<Ts, Li, Cl, Ma>(&self, timestamp_rw: &mut Ts, last_instance_rw: &mut Li, 
+        changelog_rw: &mut Cl, mac_rw: &mut Ma, last_sync: &Timestamp, auth: &Self::Context) -> Result<Self::Summary>
+    where
+        Ts: std::io::Read + std::io::Write + std::io::Seek,
+        Li: std::io::Read + std::io::Write + std::io::Seek,
+        Cl: std::io::Read + std::io::Write + std::io::Seek,
+        Ma: std::io::Read + std::io::Write + std::io::Seek
+    {
+        let (mut cumulative_changelog, remote) = self.read_remote_changelog(timestamp_rw, last_instance_rw, 
+            changelog_rw, mac_rw, auth)?;
+
+        Self::ensure_not_compacted(&cumulative_changelog, last_sync)?;
+
+        //
+        // Merge remote and export local changes
+        // Then join them together
+        //
+
+        let mut local_changelog = self.export_local_changes(last_sync)?;
+
+        //
+        // Items kept despite of remote removal are exported as changed,
+        // hence other instances resurrect them during this synchronization
+        //
+
+        let mut kept_changelog = Changelog::new();
+        let merged = self.merge_changes(&cumulative_changelog, last_sync, &mut kept_changelog)?;
+        local_changelog.append(kept_changelog)?;
+
+        let summary = SyncSummary {
+            merged,
+            exported: local_changelog.change_counts(),
+            remote_timestamp: remote.map(|(timestamp, _)| timestamp),
+            remote_instance: remote.map(|(_, instance)| instance),
+            compaction: None,
+            local_only: false
+        };
+
+        //
+        // Files are left untouched if there is nothing to sync,
+        // hence sync engine can skip sending of them
+        //
+
+        if summary.is_empty() {
+            return Ok(summary);
+        }
+        
+        //
+        // Current instance has received everything, hence its
+        // watermark is moved to the time of this synchronization
+        //
+
+        let local_timestamp = Clock::now();
+
+        let mut watermarks = match remote {
+            Some(_) => Self::read_watermarks(timestamp_rw, last_instance_rw)?,
+            None => Watermarks::new()
+        };
+
+        watermarks.insert(*self.instance_id(), Watermark {
+            timestamp: local_timestamp,
+            name: self.config.instance_name().map(str::to_owned)
+        });
+
+        //
+        // Old remote changes are dropped, hence changelog does not grow 
+        // forever. Changes, that some known instance has not received yet,
+        // are kept, and unknown instances, that were not synchronized since
+        // then, are rejected. Local changes are appended after compaction,
+        // since other instances have not received them yet
+        //
+
+        if let Some(retention) = self.config.changelog_retention() {
+            let horizon = watermarks
+                .values()
+                .map(|watermark| watermark.timestamp)
+                .fold(local_timestamp - retention, Timestamp::min);
+
+            cumulative_changelog.compact(horizon);
+        }
+
+        cumulative_changelog.append(local_changelog)?;
+
+        self.write_sync_files(&cumulative_changelog, timestamp_rw, last_instance_rw, 
+            changelog_rw, mac_rw, auth)?;
+
+        //
+        // Watermarks follow the last synchronized instance
+        //
+
+        Self::write_watermarks(&watermarks, last_instance_rw)?;
+
+        Ok(summary)
+    }
+
+    fn preview_changes<Ts, Li, Cl, Ma>(&self, timestamp_r: &mut Ts, last_instance_r: &mut Li, 
+        changelog_r: &mut Cl, mac_r: &mut Ma, last_sync: &Timestamp, auth: &Self::Context) -> Result<Self::Preview>
+    where
+        Ts: std::io::Read + std::io::Seek,
+        Li: std::io::Read + std::io::Seek,
+        Cl: std::io::Read + std::io::Seek,
+        Ma: std::io::Read + std::io::Seek
+    {
+        let (changelog, _) = self.read_remote_changelog(timestamp_r, last_instance_r, 
+            changelog_r, mac_r, auth)?;
+
+        Self::ensure_not_compacted(&changelog, last_sync)?;
+
+        Ok(SyncPreview {
+            accounts: self.preview_step(&changelog.accounts, last_sync,
+                |account| (account.id.unwrap(), &account.meta_info),
+                |account| self.storage.account_any(account).map(|account| account.meta_info))?,
+            categories: self.preview_step(&changelog.categories, last_sync,
+                |category| (category.id.unwrap(), &category.meta_info),
+                |category| self.storage.category_any(category).map(|category| category.meta_info))?,
+            plans: self.preview_step(&changelog.plans, last_sync,
+                |plan| (plan.id.unwrap(), &plan.meta_info),
+                |plan| self.storage.plan_any(plan).map(|plan| plan.meta_info))?,
+            transactions: self.preview_step(&changelog.transactions, last_sync,
+                |transaction| (transaction.id.unwrap(), &transaction.meta_info),
+                |transaction| self.storage.transaction_any(transaction).map(|transaction| transaction.meta_info))?,
+            recurrings: self.preview_step(&changelog.recurrings, last_sync,
+                |recurring| (recurring.id.unwrap(), &recurring.meta_info),
+                |recurring| self.storage.recurring_any(recurring).map(|recurring| recurring.meta_info))?
+        })
+    }
+
+    fn read_instances<Ts, Li>(&self, timestamp_r: &mut Ts, last_instance_r: &mut Li) -> Result<Self::Instances>
+    where
+        Ts: std::io::Read + std::io::Seek,
+        Li: std::io::Read + std::io::Seek
+    {
+        let instances = Self::read_watermarks(timestamp_r, last_instance_r)?
+            .into_iter()
+            .map(|(id, watermark)| InstanceInfo { id, name: watermark.name, last_seen: watermark.timestamp })
+            .collect();
+
+        Ok(instances)
+    }
+}
+
+impl<Ce, Se, St> Budget<Ce, Se, St>
+where
+    Ce: CryptoEngine,
+    Se: SyncEngine,
+    St: DataStorage
+{
+    fn sync_and_clean<F>(&self, compact_threshold: Option<f64>, sync: F) -> Result<SyncSummary> 
+    where
+        F: FnOnce() -> Result<SyncSummary>
+    {
+        let previous_sync = self.previous_sync()?;
+        let mut summary = sync()?;
+
+        //
+        // Items removed before the previous sync had been pushed to remote
+        // already, and now it is not necessary to keep them locally.
+        // Newer tombstones are kept to protect laggard instances from
+        // resurrection of removed items, and user can keep them longer
+        //
+
+        let cutoff = match self.config.settings().tombstone_retention_days() {
+            Some(days) => previous_sync.min(Clock::now() - chrono::Duration::days(days.into())),
+            None => previous_sync
+        };
+
+        self.clean_removed_before(cutoff)?;
+
+        summary.compaction = match compact_threshold {
+            Some(threshold) if threshold < self.storage.reclaimable_fraction()? => Some(self.compact()?),
+            _ => None
+        };
+
+        Ok(summary)
+    }
+
+    fn previous_sync(&self) -> Result<Timestamp> {
+        //
+        // Tombstones must be pushed to every remote before they
+        // are cleaned, hence the earliest synchronization is taken
+        //
+
+        let mut previous_sync = self.sync_engine()
+            .last_sync()?
+            .unwrap_or(*FIRST_AFTER_JANUARY_1970);
+
+        for (remote, _) in self.sync_engine().remotes()? {
+            let last_sync = self.sync_engine()
+                .last_sync_with(&remote)?
+                .unwrap_or(*FIRST_AFTER_JANUARY_1970);
+
+            previous_sync = previous_sync.min(last_sync);
+        }
+
+        Ok(previous_sync)
+    }
+
+    fn read_remote_changelog<Ts, Li, Cl, Ma>(&self, timestamp_r: &mut Ts, last_instance_r: &mut Li, 
+        changelog_r: &mut Cl, mac_r: &mut Ma, auth: &CryptoBuffer) -> Result<(Changelog, Option<(Timestamp, InstanceId)>)>
+    where
+        Ts: std::io::Read + std::io::Seek,
+        Li: std::io::Read + std::io::Seek,
+        Cl: std::io::Read + std::io::Seek,
+        Ma: std::io::Read + std::io::Seek
+    {
+        if self.empty_sync_files(timestamp_r, last_instance_r, changelog_r)? {
+            //
+            // Files are correct, but empty
+            // Just return empty changelog
+            //
+
+            Ok((Changelog::new(), None))
+        }
+        else {
+            //
+            // Read remote timestamp and instance identifiers, and key derivation
+            // parameters from changelog header to derive decryption key
+            //
+
+            let remote_timestamp = Self::read_timestamp(timestamp_r)?;
+            let remote_instance = Self::read_instance(last_instance_r)?;
+
+            let (remote_kdf_params, is_streamed, header_size) = Self::read_changelog_header(changelog_r)?;
+
+            let remote_salt = Self::make_key_derivation_salt(&remote_timestamp, &remote_instance)?;
+            let decryption_key = Kdf::derive_key(auth.as_bytes(), remote_salt.as_bytes(), 
+                self.crypto_engine.symmetric_key_length(), &remote_kdf_params)?;
+
+            //
+            // Verify changelog with timestamp and instance before 
+            // decryption, hence tampering is reported explicitly
+            //
+
+            let mut remote_mac = Vec::new();
+            mac_r.read_to_end(&mut remote_mac)?;
+
+            Self::verify_sync_files(decryption_key.as_bytes(), remote_salt.as_bytes(), 
+                changelog_r, &remote_mac)?;
+
+            //
+            // Changelogs pushed by older versions are encrypted as a single BLOB.
+            // They have no authentication tag, hence wrong passphrase is
+            // detected only when decryption fails
+            //
+
+            changelog_r.seek(std::io::SeekFrom::Start(header_size))?;
+
+            let remote_changelog = match is_streamed {
+                true => {
+                    //
+                    // Ciphertext is decrypted right from the file chunk by chunk,
+                    // but plaintext is kept in memory, since changelog can be
+                    // deserialized only from a contiguous buffer
+                    //
+
+                    let mut plaintext = CryptoBuffer::new();
+                    self.crypto_engine
+                        .decrypt_symmetric_stream(decryption_key.as_bytes(), &mut *changelog_r, &mut plaintext)
+                        .map_err(Self::sync_authentication_error)?;
+
+                    plaintext
+                },
+                false => {
+                    let mut ciphertext = Vec::new();
+                    changelog_r.read_to_end(&mut ciphertext)?;
+
+                    self.crypto_engine
+                        .decrypt_symmetric(decryption_key.as_bytes(), &ciphertext)
+                        .map_err(Self::sync_authentication_error)?
+                }
+            };
+
+            let remote_changelog = Changelog::from_slice(remote_changelog.as_bytes())?;
+            Ok((remote_changelog, Some((remote_timestamp, remote_instance))))
+        }
+    }
+
+    fn write_sync_files<Ts, Li, Cl, Ma>(&self, changelog: &Changelog, timestamp_rw: &mut Ts, last_instance_rw: &mut Li, 
+        changelog_rw: &mut Cl, mac_rw: &mut Ma, auth: &CryptoBuffer) -> Result<()>
+    where
+        Ts: std::io::Write + std::io::Seek,
+        Li: std::io::Write + std::io::Seek,
+        Cl: std::io::Read + std::io::Write + std::io::Seek,
+        Ma: std::io::Write + std::io::Seek
+    {
+        //
+        // Derive new encryption key, encrypt and write updated values
+        //
+
+        let local_timestamp = Clock::now();
+        let local_instance = self.instance_id();
+
+        Self::prepare_for_overwrite(timestamp_rw)?;
+        Self::write_timestamp(&local_timestamp, timestamp_rw)?;
+
+        Self::prepare_for_overwrite(last_instance_rw)?;
+        Self::write_instance(local_instance, last_instance_rw)?;
+
+        let local_kdf_params = self.config.kdf_params();
+        let local_salt = Self::make_key_derivation_salt(&local_timestamp, local_instance)?;
+        let encryption_key = Kdf::derive_key(auth.as_bytes(), local_salt.as_bytes(), 
+            self.crypto_engine.symmetric_key_length(), local_kdf_params)?;
+
+        let changelog = CryptoBuffer::from(changelog.to_vec()?);
+
+        Self::prepare_for_overwrite(changelog_rw)?;
+        Self::write_changelog_header(local_kdf_params, changelog_rw)?;
+
+        self.crypto_engine
+            .encrypt_symmetric_stream(encryption_key.as_bytes(), changelog.as_bytes(), &mut *changelog_rw)?;
+
+        //
+        // Compacted changelog can be shorter than the previous version,
+        // hence the rest of file is garbage. I authenticate only new
+        // content and finish exactly at its end, then sync engine
+        // truncates the file at this position
+        //
+
+        let changelog_size = changelog_rw.stream_position()?;
+        changelog_rw.rewind()?;
+
+        let local_mac = Self::authenticate_sync_files(encryption_key.as_bytes(), local_salt.as_bytes(), 
+            &mut std::io::Read::take(&mut *changelog_rw, changelog_size))?;
+
+        changelog_rw.seek(std::io::SeekFrom::Start(changelog_size))?;
+
+        Self::prepare_for_overwrite(mac_rw)?;
+        mac_rw.write_all(local_mac.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn sync_authentication_error(error: Error) -> Error {
+        match error.kind() {
+            ErrorKind::Crypto => Error::new(SYNC_AUTHENTICATION_FAILED),
+            _ => error
+        }
+    }
+
+    fn ensure_not_compacted(changelog: &Changelog, last_sync: &Timestamp) -> Result<()> {
+        match changelog.horizon {
+            Some(horizon) if *last_sync < horizon => Err(Error::new_with_extra(FULL_RESYNC_REQUIRED, 
+                format!("compacted before: {}, last sync: {}", horizon, last_sync))),
+            _ => Ok(())
+        }
+    }
+
+    fn empty_sync_files<Ts, Li, Cl>(&self, timestamp: &mut Ts, last_instance: &mut Li, changelog: &mut Cl) -> Result<bool>
+    where
+        Ts: std::io::Seek,
+        Li: std::io::Seek,
+        Cl: std::io::Seek 
+    {
+        let seek_position = std::io::SeekFrom::End(0);
+
+        let timestamp_size = timestamp.seek(seek_position)?;
+        timestamp.rewind()?;
+
+        let last_instance_size = last_instance.seek(seek_position)?;
+        last_instance.rewind()?;
+
+        let changelog_size = changelog.seek(seek_position)?;
+        changelog.rewind()?;
+
+        //
+        // Either all files are empty, or none of them. Otherwise, the file,
+        // that differs from the other ones, is considered malformed
+        //
+
+        let (malformed, size) = match (timestamp_size, last_instance_size, changelog_size) {
+            (0, 0, 0) => return Ok(true),
+            (1.., 1.., 1..) => return Ok(false),
+            (0, 1.., 1..) | (1.., 0, 0) => (MALFORMED_TIMESTAMP, timestamp_size),
+            (1.., 0, 1..) | (0, 1.., 0) => (MALFORMED_INSTANCES, last_instance_size),
+            (1.., 1.., 0) | (0, 0, 1..) => (MALFORMED_CHANGELOG, changelog_size)
+        };
+
+        //
+        // If recovery is enabled, inconsistent files are considered
+        // empty, hence they are regenerated during synchronization
+        //
+
+        match self.config.sync_files_recovery() {
+            true => Ok(true),
+            false => Err(Error::new_with_extra(malformed, format!("size: {}", size)))
+        }
+    }
+
+    fn read_timestamp<R: std::io::Read>(timestamp_reader: &mut R) -> Result<Timestamp> {
+        let mut buffer = [0; std::mem::size_of::<i64>()];
+        let seconds = match timestamp_reader.read_exact(&mut buffer) {
+            Ok(_) => i64::from_le_bytes(buffer),
+            _ => 0i64
+        };
+
+        Timestamp::from_timestamp(seconds, 0)
+            .ok_or(Error::new(MALFORMED_TIMESTAMP))
+    }
+
+    fn write_timestamp<W: std::io::Write>(timestamp: &Timestamp, timestamp_writer: &mut W) -> Result<()> {
+        let timestamp = timestamp
+            .timestamp()
+            .to_le_bytes();
+
+        timestamp_writer
+            .write_all(&timestamp)
+            .map_err(Error::from)
+    }
+
+    fn read_instance<R: std::io::Read>(last_instance_reader: &mut R) -> Result<InstanceId> {
+        let mut buffer = [0; 16];
+        last_instance_reader.read_exact(&mut buffer)?;
+
+        Ok(uuid::Uuid::from_bytes(buffer))
+    }
+
+    fn write_instance<W: std::io::Write>(instance: &InstanceId, last_instance_writer: &mut W) -> Result<()> {
+        last_instance_writer
+            .write_all(&instance.into_bytes())
+            .map_err(Error::from)
+    }
+
+    fn read_watermarks<Ts, Li>(timestamp_r: &mut Ts, last_instance_r: &mut Li) -> Result<Watermarks>
+    where
+        Ts: std::io::Read + std::io::Seek,
+        Li: std::io::Read + std::io::Seek
+    {
+        timestamp_r.rewind()?;
+        last_instance_r.rewind()?;
+
+        let mut instances = Vec::new();
+        last_instance_r.read_to_end(&mut instances)?;
+
+        let mut watermarks = Watermarks::new();
+        if instances.is_empty() {
+            return Ok(watermarks);
+        }
+
+        //
+        // Instance file starts with the last synchronized instance followed
+        // by watermarks of all known instances. Files written by older
+        // versions have no watermarks, hence only the last synchronized
+        // instance is known, and it is synchronized at remote timestamp.
+        // Watermarks are not authenticated, but forged ones can only
+        // prevent compaction or make an instance to resynchronize
+        //
+
+        let record_size = std::mem::size_of::<uuid::Bytes>() + std::mem::size_of::<i64>();
+        let (last_instance, records) = instances
+            .split_at_checked(std::mem::size_of::<uuid::Bytes>())
+            .ok_or(Error::new(MALFORMED_INSTANCES))?;
+
+        //
+        // Names of instances follow watermarks after nil identifier,
+        // that is never generated for a real instance
+        //
+
+        let names_start = (0..records.len())
+            .step_by(record_size)
+            .find(|start| records[*start..].starts_with(uuid::Uuid::nil().as_bytes()));
+
+        let (records, mut names) = match names_start {
+            Some(start) => (&records[..start], &records[start + std::mem::size_of::<uuid::Bytes>()..]),
+            None => (records, &[][..])
+        };
+
+        if records.is_empty() {
+            watermarks.insert(Self::read_instance(&mut &*last_instance)?, Watermark {
+                timestamp: Self::read_timestamp(timestamp_r)?,
+                name: None
+            });
+        }
+
+        if records.len() % record_size != 0 {
+            return Err(Error::new(MALFORMED_INSTANCES));
+        }
+
+        for mut record in records.chunks_exact(record_size) {
+            let instance = Self::read_instance(&mut record)?;
+            let timestamp = Self::read_timestamp(&mut record)?;
+
+            watermarks.insert(instance, Watermark { timestamp, name: None });
+        }
+
+        while !names.is_empty() {
+            let instance = Self::read_instance(&mut names)
+                .map_err(|_| Error::new(MALFORMED_INSTANCES))?;
+
+            let (length, rest) = names
+                .split_first()
+                .ok_or(Error::new(MALFORMED_INSTANCES))?;
+
+            let (name, rest) = rest
+                .split_at_checked(*length as usize)
+                .ok_or(Error::new(MALFORMED_INSTANCES))?;
+
+            let name = String::from_utf8(name.to_vec())
+                .map_err(|_| Error::new(MALFORMED_INSTANCES))?;
+
+            if let Some(watermark) = watermarks.get_mut(&instance) {
+                watermark.name = Some(name);
+            }
+
+            names = rest;
+        }
+
+        Ok(watermarks)
+    }
+
+    fn write_watermarks<W: std::io::Write>(watermarks: &Watermarks, last_instance_writer: &mut W) -> Result<()> {
+        for (instance, watermark) in watermarks {
+            Self::write_instance(instance, last_instance_writer)?;
+            Self::write_timestamp(&watermark.timestamp, last_instance_writer)?;
+        }
+
+        //
+        // Names section is written only if some instance is named,
+        // hence instance file remains readable by older versions
+        //
+
+        let named: Vec<_> = watermarks
+            .iter()
+            .filter_map(|(instance, watermark)| Some((instance, watermark.name.as_ref()?)))
+            .collect();
+
+        if named.is_empty() {
+            return Ok(());
+        }
+
+        Self::write_instance(&uuid::Uuid::nil(), last_instance_writer)?;
+
+        for (instance, name) in named {
+            let length = u8::try_from(name.len())
+                .map_err(|_| Error::new(MALFORMED_INSTANCES))?;
+
+            Self::write_instance(instance, last_instance_writer)?;
+            last_instance_writer.write_all(&[length])?;
+            last_instance_writer.write_all(name.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn prepare_for_overwrite<S: std::io::Seek>(s: &mut S) -> Result<()> {
+        s.rewind()
+            .map_err(Error::from)
+    }
+
+    fn make_key_derivation_salt(timestamp: &Timestamp, instance: &InstanceId) -> Result<CryptoBuffer> {
+        let mut salt = Vec::new();
+        salt.write_all(&timestamp.timestamp().to_le_bytes())?;
+        salt.write_all(&instance.into_bytes())?;
+
+        Ok(CryptoBuffer::from(salt))
+    }
+
+    fn write_changelog_header<W: std::io::Write>(kdf_params: &KdfParams, changelog_writer: &mut W) -> Result<()> {
+        changelog_writer.write_all(CHANGELOG_STREAM_MAGIC)?;
+        changelog_writer.write_all(&kdf_params.to_bytes())?;
+
+        Ok(())
+    }
+
+    fn read_changelog_header<R>(changelog_reader: &mut R) -> Result<(KdfParams, bool, u64)> 
+    where
+        R: std::io::Read + std::io::Seek
+    {
+        //
+        // Changelogs pushed by older versions may have no header, such
+        // changelogs are encrypted with a key derived using default parameters.
+        // Returns parameters, whether changelog is streamed and header size.
+        //
+
+        let mut header = [0u8; CHANGELOG_STREAM_MAGIC.len() + KdfParams::SIZE];
+        if changelog_reader.read_exact(&mut header).is_err() {
+            changelog_reader.rewind()?;
+            return Ok((KdfParams::default(), false, 0));
+        }
+
+        let (magic, kdf_params) = header.split_at(CHANGELOG_STREAM_MAGIC.len());
+        let is_streamed = match magic {
+            m if m == CHANGELOG_STREAM_MAGIC => true,
+            m if m == CHANGELOG_MAGIC => false,
+            _ => {
+                changelog_reader.rewind()?;
+                return Ok((KdfParams::default(), false, 0));
+            }
+        };
+
+        Ok((KdfParams::from_bytes(kdf_params)?, is_streamed, header.len() as u64))
+    }
+
+    fn authenticate_sync_files<R>(key: &[u8], salt: &[u8], changelog_reader: &mut R) -> Result<CryptoBuffer> 
+    where
+        R: std::io::Read
+    {
+        //
+        // Salt consists of timestamp and instance identifier, hence it is
+        // enough to authenticate it with changelog. Unkeyed digest is stored
+        // before the tag to distinguish corrupted data from a wrong passphrase.
+        //
+
+        let mac_key = Mac::derive_subkey(key, SYNC_MAC_LABEL)?;
+        let (digest, tag) = Mac::authenticate_stream(mac_key.as_bytes(), salt, &mut *changelog_reader)?;
+
+        Ok(digest.append(tag))
+    }
+
+    fn verify_sync_files<R>(key: &[u8], salt: &[u8], changelog_reader: &mut R, mac: &[u8]) -> Result<()> 
+    where
+        R: std::io::Read + std::io::Seek
+    {
+        //
+        // Files pushed by older versions have no authentication tag
+        //
+
+        if mac.is_empty() {
+            return Ok(());
+        }
+
+        if mac.len() != 2 * Mac::TAG_SIZE {
+            return Err(Error::new(REMOTE_DATA_CORRUPTED));
+        }
+
+        changelog_reader.rewind()?;
+        let actual_mac = Self::authenticate_sync_files(key, salt, changelog_reader)?;
+
+        let (expected_digest, expected_tag) = mac.split_at(Mac::TAG_SIZE);
+        let (actual_digest, actual_tag) = actual_mac.as_bytes().split_at(Mac::TAG_SIZE);
+
+        if actual_digest != expected_digest {
+            return Err(Error::new(REMOTE_DATA_CORRUPTED));
+        }
+
+        if !CryptoBuffer::from(actual_tag).ct_eq(&CryptoBuffer::from(expected_tag)) {
+            return Err(Error::new(SYNC_AUTHENTICATION_FAILED));
+        }
+
+        Ok(())
+    }
+
+    fn local_change_counts(&self, base: Timestamp) -> Result<ChangeCounts> {
+        //
+        // Items are just counted, hence they are not decrypted
+        //
+
+        let added = self.storage.accounts_added_since(base)?.len() + 
+            self.storage.categories_added_since(base)?.len() + 
+            self.storage.plans_added_since(base)?.len() + 
+            self.storage.recurrings_added_since(base)?.len() + 
+            self.storage.transactions_added_since(base)?.len();
+
+        let changed = self.storage.accounts_changed_since(base)?.len() + 
+            self.storage.categories_changed_since(base)?.len() + 
+            self.storage.plans_changed_since(base)?.len() + 
+            self.storage.recurrings_changed_since(base)?.len() + 
+            self.storage.transactions_changed_since(base)?.len();
+
+        let removed = self.storage.accounts_removed_since(base)?.len() + 
+            self.storage.categories_removed_since(base)?.len() + 
+            self.storage.plans_removed_since(base)?.len() + 
+            self.storage.recurrings_removed_since(base)?.len() + 
+            self.storage.transactions_removed_since(base)?.len();
+
+        Ok(ChangeCounts { added, changed, removed })
+    }
+
+    fn export_local_changes(&self, last_sync: &Timestamp) -> Result<Changelog> {
+        let mut local_changelog = Changelog::new();
+
+        //
+        // I don't filter out "foreign" items, because it is assumed, that
+        // there are none of them since this instance has not been synced
+        // during the interval (last_sync, now]
+        //
+
+        local_changelog.accounts.added = self.accounts_added_since(*last_sync)?;
+        local_changelog.accounts.changed = self.accounts_changed_since(*last_sync)?;
+        local_changelog.accounts.removed = self.accounts_removed_since(*last_sync)?;
+
+        local_changelog.categories.added = self.categories_added_since(*last_sync)?;
+        local_changelog.categories.changed = self.categories_changed_since(*last_sync)?;
+        local_changelog.categories.removed = self.categories_removed_since(*last_sync)?;
+
+        local_changelog.plans.added = self.plans_added_since(*last_sync)?;
+        local_changelog.plans.changed = self.plans_changed_since(*last_sync)?;
+        local_changelog.plans.removed = self.plans_removed_since(*last_sync)?;
+
+        local_changelog.transactions.added = self.transactions_added_since(*last_sync)?;
+        local_changelog.transactions.changed = self.transactions_changed_since(*last_sync)?;
+        local_changelog.transactions.removed = self.transactions_removed_since(*last_sync)?;
+
+        local_changelog.recurrings.added = self.recurrings_added_since(*last_sync)?;
+        local_changelog.recurrings.changed = self.recurrings_changed_since(*last_sync)?;
+        local_changelog.recurrings.removed = self.recurrings_removed_since(*last_sync)?;
+
+        Ok(local_changelog)
+    }
+
+    fn merge_changes(&self, changelog: &Changelog, last_sync: &Timestamp, kept: &mut Changelog) -> Result<ChangeCounts> {
+        //
+        // Remote changes are applied all at once. If some of them fails,
+        // nothing is applied and synchronized files are not written,
+        // hence the next attempt starts from the same state
+        //
+
+        self.storage.with_transaction(|_| {
+            self.apply_changes(changelog, last_sync, kept)
+        })
+    }
+
+    fn apply_changes(&self, changelog: &Changelog, last_sync: &Timestamp, kept: &mut Changelog) -> Result<ChangeCounts> {
+        let mut merged = ChangeCounts::default();
+
+        //
+        // Predefined items are older than any synchronization base, hence
+        // they are never exported. Instance, that joined a remote, gets them
+        // here before remote transfers and changes, that refer to them.
+        // Remote copies have the same identifiers and are skipped as existing
+        //
+
+        self.add_predefined_items()?;
+
+        //
+        // First, added items are processed in the following order:
+        //  1. Accounts
+        //  2. Categories
+        //  3. Plans
+        //  4. Recurring transactions
+        //  5. Transactions
+        // Items, that already exist locally, are skipped
+        //
+
+        merged.added += self.merge_step(&changelog.accounts.added,
+            |account| Self::accept_change(account.id, self.is_added_remotely(&account.meta_info, last_sync), SKIPPED_AS_KNOWN),
+            |account| {
+                //
+                // Explicitly set account's balance to its initial value, because
+                // they may differ in synced account. It could lead to inconsistency.
+                //
+
+                let mut account = account.clone();
+                account.balance = account.initial_balance;
+
+                Self::skip_existing(account.id, self.add_account(&account))
+            }
+        )?;
+
+        merged.added += self.merge_step(&changelog.categories.added,
+            |category| Self::accept_change(category.id, self.is_added_remotely(&category.meta_info, last_sync), SKIPPED_AS_KNOWN),
+            |category| { Self::skip_existing(category.id, self.add_category(category)) }
+        )?;
+
+        //
+        // Restored items are synced as changed ones, but here they are
+        // removed or even deleted permanently. Hence they are resurrected
+        // before processing of changes in the same order as added ones
+        // Accounts and categories are resurrected before adding of plans,
+        // recurring transactions and transactions, that may reference them
+        // Resurrected items are counted later as changed ones
+        //
+
+        let alive_accounts = Self::identifiers(self.storage.accounts_including_archived()?, |account| account.id);
+
+        self.merge_step(&changelog.accounts.changed,
+            |account| {
+                Self::is_changed_since(&account.meta_info, last_sync) &&
+                !alive_accounts.contains(&account.id.unwrap())
+            },
+            |account| {
+                match self.storage.removed_account(account.id.unwrap()) {
+                    Ok(removed) => self.resurrect(account.id, &removed.meta_info, &account.meta_info,
+                        || self.restore_account(account.id.unwrap())),
+                    Err(_) => {
+                        let mut account = account.clone();
+                        account.balance = account.initial_balance;
+
+                        self.add_account(&account).map(|_| ())
+                    }
+                }
+            }
+        )?;
+
+        let alive_categories = Self::identifiers(self.storage.categories()?, |category| category.id);
+
+        self.merge_step(&changelog.categories.changed,
+            |category| {
+                Self::is_changed_since(&category.meta_info, last_sync) &&
+                !alive_categories.contains(&category.id.unwrap())
+            },
+            |category| {
+                match self.storage.removed_category(category.id.unwrap()) {
+                    Ok(removed) => self.resurrect(category.id, &removed.meta_info, &category.meta_info,
+                        || self.restore_category(category.id.unwrap())),
+                    Err(_) => self.add_category(category).map(|_| ())
+                }
+            }
+        )?;
+
+        merged.added += self.merge_step(&changelog.plans.added,
+            |plan| Self::accept_change(plan.id, self.is_added_remotely(&plan.meta_info, last_sync), SKIPPED_AS_KNOWN),
+            |plan| { Self::skip_existing(plan.id, self.add_plan(plan)) }
+        )?;
+
+        merged.added += self.merge_step(&changelog.recurrings.added,
+            |recurring| Self::accept_change(recurring.id, self.is_added_remotely(&recurring.meta_info, last_sync), SKIPPED_AS_KNOWN),
+            |recurring| { Self::skip_existing(recurring.id, self.add_recurring(recurring)) }
+        )?;
+
+        merged.added += self.merge_step(&changelog.transactions.added,
+            |transaction| Self::accept_change(transaction.id, self.is_added_remotely(&transaction.meta_info, last_sync), SKIPPED_AS_KNOWN),
+            |transaction| { Self::skip_existing(transaction.id, self.insert_transaction(transaction, true)) }
+        )?;
+
+        //
+        // Plans and transactions are resurrected after added items, because
+        // they may reference parents resurrected above
+        //
+
+        let alive_plans = Self::identifiers(self.storage.plans()?, |plan| plan.id);
+
+        self.merge_step(&changelog.plans.changed,
+            |plan| {
+                Self::is_changed_since(&plan.meta_info, last_sync) &&
+                !alive_plans.contains(&plan.id.unwrap())
+            },
+            |plan| {
+                match self.storage.removed_plan(plan.id.unwrap()) {
+                    Ok(removed) => self.resurrect(plan.id, &removed.meta_info, &plan.meta_info,
+                        || self.restore_plan(plan.id.unwrap())),
+                    Err(_) => self.add_plan(plan).map(|_| ())
+                }
+            }
+        )?;
+
+        let alive_transactions = Self::identifiers(self.storage.transactions()?, |transaction| transaction.id);
+
+        self.merge_step(&changelog.transactions.changed,
+            |transaction| {
+                Self::is_changed_since(&transaction.meta_info, last_sync) &&
+                !alive_transactions.contains(&transaction.id.unwrap())
+            },
+            |transaction| {
+                match self.storage.removed_transaction(transaction.id.unwrap()) {
+                    Ok(removed) => self.resurrect(transaction.id, &removed.meta_info, &transaction.meta_info,
+                        || self.restore_transaction(transaction.id.unwrap())),
+                    Err(_) => self.insert_transaction(transaction, true).map(|_| ())
+                }
+            }
+        )?;
+
+        //
+        // Then, changed items are processed in the reverse order
+        // Origin is not checked here, because it identifies an instance,
+        // where an item was created, and not the one, where it was changed
+        // Items, that are removed here and were not resurrected, are skipped
+        //
+
+        let alive_accounts = Self::identifiers(self.storage.accounts_including_archived()?, |account| account.id);
+        let alive_categories = Self::identifiers(self.storage.categories()?, |category| category.id);
+        let alive_plans = Self::identifiers(self.storage.plans()?, |plan| plan.id);
+        let alive_transactions = Self::identifiers(self.storage.transactions()?, |transaction| transaction.id);
+        let alive_recurrings = Self::identifiers(self.storage.recurrings()?, |recurring| recurring.id);
+
+        merged.changed += self.merge_step(&changelog.transactions.changed,
+            |transaction| {
+                Self::accept_change(transaction.id, Self::is_changed_since(&transaction.meta_info, last_sync), SKIPPED_AS_UNCHANGED) &&
+                Self::accept_change(transaction.id, alive_transactions.contains(&transaction.id.unwrap()), SKIPPED_AS_REMOVED)
+            },
+            |transaction| { self.update_transaction(transaction) }
+        )?;
+
+        //
+        // Recurring transactions cannot be restored, hence they are skipped
+        // if their account or category is removed locally as well
+        //
+
+        merged.changed += self.merge_step(&changelog.recurrings.changed,
+            |recurring| {
+                Self::accept_change(recurring.id, Self::is_changed_since(&recurring.meta_info, last_sync), SKIPPED_AS_UNCHANGED) &&
+                Self::accept_change(recurring.id, alive_recurrings.contains(&recurring.id.unwrap()), SKIPPED_AS_REMOVED) &&
+                Self::accept_change(recurring.id, alive_accounts.contains(&recurring.account_id) && 
+                    alive_categories.contains(&recurring.category_id), SKIPPED_AS_ORPHANED)
+            },
+            |recurring| { self.update_recurring(recurring) }
+        )?;
+
+        merged.changed += self.merge_step(&changelog.plans.changed,
+            |plan| {
+                Self::accept_change(plan.id, Self::is_changed_since(&plan.meta_info, last_sync), SKIPPED_AS_UNCHANGED) &&
+                Self::accept_change(plan.id, alive_plans.contains(&plan.id.unwrap()), SKIPPED_AS_REMOVED)
+            },
+            |plan| { self.update_plan(plan) }
+        )?;
+
+        merged.changed += self.merge_step(&changelog.categories.changed,
+            |category| {
+                Self::accept_change(category.id, Self::is_changed_since(&category.meta_info, last_sync), SKIPPED_AS_UNCHANGED) &&
+                Self::accept_change(category.id, alive_categories.contains(&category.id.unwrap()), SKIPPED_AS_REMOVED)
+            },
+            |category| { self.update_category(category) }
+        )?;
+
+        merged.changed += self.merge_step(&changelog.accounts.changed,
+            |account| {
+                Self::accept_change(account.id, Self::is_changed_since(&account.meta_info, last_sync), SKIPPED_AS_UNCHANGED) &&
+                Self::accept_change(account.id, alive_accounts.contains(&account.id.unwrap()), SKIPPED_AS_REMOVED)
+            },
+            |account| { self.update_account(account) }
+        )?;
+
+        //
+        // Finally, removed items are processed in the reverse order too.
+        // Items may be removed locally as well, hence such items are
+        // skipped to keep local removal timestamp
+        //
+
+        merged.removed += self.merge_step(&changelog.transactions.removed,
+            |transaction| Self::accept_change(transaction.id, self.is_removed_remotely(&transaction.meta_info, last_sync), SKIPPED_AS_KNOWN),
+            |transaction| {
+                let local = self.storage.transaction_any(transaction.id.unwrap());
+                if Self::is_removed_locally(transaction.id, local.map(|transaction| transaction.meta_info))? {
+                    return Ok(());
+                }
+
+                self.remove_transaction(transaction.id.unwrap(), false,
+                    transaction.meta_info.removed_timestamp.unwrap())
+            }
+        )?;
+
+        merged.removed += self.merge_step(&changelog.recurrings.removed,
+            |recurring| Self::accept_change(recurring.id, self.is_removed_remotely(&recurring.meta_info, last_sync), SKIPPED_AS_KNOWN),
+            |recurring| {
+                let local = self.storage.recurring_any(recurring.id.unwrap());
+                if Self::is_removed_locally(recurring.id, local.map(|recurring| recurring.meta_info))? {
+                    return Ok(());
+                }
+
+                self.remove_recurring(recurring.id.unwrap(), recurring.meta_info.removed_timestamp.unwrap())
+            }
+        )?;
+
+        merged.removed += self.merge_step(&changelog.plans.removed,
+            |plan| Self::accept_change(plan.id, self.is_removed_remotely(&plan.meta_info, last_sync), SKIPPED_AS_KNOWN),
+            |plan| {
+                let local = self.storage.plan_any(plan.id.unwrap());
+                if Self::is_removed_locally(plan.id, local.map(|plan| plan.meta_info))? {
+                    return Ok(());
+                }
+
+                self.remove_plan(plan.id.unwrap(), plan.meta_info.removed_timestamp.unwrap())
+            }
+        )?;
+
+        merged.removed += self.merge_step(&changelog.categories.removed,
+            |category| Self::accept_change(category.id, self.is_removed_remotely(&category.meta_info, last_sync), SKIPPED_AS_KNOWN),
+            |category| {
+                let local = self.storage.category_any(category.id.unwrap());
+                if Self::is_removed_locally(category.id, local.map(|category| category.meta_info))? {
+                    return Ok(());
+                }
+
+                let removed = self.remove_category(category.id.unwrap(), category.meta_info.removed_timestamp.unwrap());
+                Self::keep_referenced(category.id, removed, || {
+                    let mut encrypted_category = self.storage.category(category.id.unwrap())?;
+                    encrypted_category.meta_info.changed_timestamp = Some(Clock::now());
+                    self.storage.update_category(encrypted_category)?;
+
+                    kept.categories.changed.push(self.category(category.id.unwrap())?);
+                    Ok(())
+                })
+            }
+        )?;
+
+        merged.removed += self.merge_step(&changelog.accounts.removed,
+            |account| Self::accept_change(account.id, self.is_removed_remotely(&account.meta_info, last_sync), SKIPPED_AS_KNOWN),
+            |account| {
+                let local = self.storage.account_any(account.id.unwrap());
+                if Self::is_removed_locally(account.id, local.map(|account| account.meta_info))? {
+                    return Ok(());
+                }
+
+                let removed = self.remove_account(account.id.unwrap(), false,
+                    account.meta_info.removed_timestamp.unwrap());
+
+                Self::keep_referenced(account.id, removed, || {
+                    let encrypted_account = self.storage.account_any(account.id.unwrap())?;
+                    self.storage.update_account_with_timestamp(encrypted_account, Clock::now())?;
+
+                    kept.accounts.changed.push(self.decrypt_account(&self.storage.account_any(account.id.unwrap())?)?);
+                    Ok(())
+                })
+            }
+        )?;
+
+        Ok(merged)
+    }
+
+    fn preview_step<T, Ii, Lo>(&self, changelog: &SimpleChangelog<T>, last_sync: &Timestamp, 
+        item_info: Ii, local_meta_info: Lo) -> Result<ItemChanges>
+    where
+        Ii: Fn(&T) -> (Id, &MetaInfo),
+        Lo: Fn(Id) -> Result<MetaInfo>
+    {
+        //
+        // The same filters are applied as in merge, but instead of
+        // merging I look at local state of items:
+        //  - added items are skipped if they exist locally
+        //  - changed items, that are removed or deleted locally, are
+        //    restored if they were changed after local removal
+        //  - removed items are skipped if they are removed locally
+        //
+
+        let mut changes = ItemChanges::default();
+        let mut added = HashSet::new();
+
+        let local_state = |id| match local_meta_info(id) {
+            Ok(meta_info) => Ok(Some(meta_info)),
+            Err(error) if error.is_not_found() => Ok(None),
+            Err(error) => Err(error)
+        };
+
+        for (id, meta_info) in changelog.added.iter().map(&item_info) {
+            if self.is_added_remotely(meta_info, last_sync) && local_state(id)?.is_none() {
+                added.insert(id);
+                changes.added.push(id);
+            }
+        }
+
+        for (id, meta_info) in changelog.changed.iter().map(&item_info) {
+            if !Self::is_changed_since(meta_info, last_sync) {
+                continue;
+            }
+
+            match local_state(id)? {
+                Some(local) if local.removed_timestamp.is_some() => {
+                    if meta_info.changed_timestamp > local.removed_timestamp {
+                        added.insert(id);
+                        changes.added.push(id);
+                    }
+                },
+                None if !added.contains(&id) => {
+                    added.insert(id);
+                    changes.added.push(id);
+                },
+                _ => changes.changed.push(id)
+            }
+        }
+
+        for (id, meta_info) in changelog.removed.iter().map(&item_info) {
+            if !self.is_removed_remotely(meta_info, last_sync) {
+                continue;
+            }
+
+            let is_alive = local_state(id)?
+                .is_some_and(|local| local.removed_timestamp.is_none());
+
+            if is_alive || added.contains(&id) {
+                changes.removed.push(id);
+            }
+        }
+
+        Ok(changes)
+    }
+
+    fn is_added_remotely(&self, meta_info: &MetaInfo, last_sync: &Timestamp) -> bool {
+        //
+        // Changes are taken from half-open interval (last_sync, now],
+        // the same as storage queries used by export do. Last sync
+        // time is rounded down to seconds, hence changes made in
+        // the same second after synchronization are not lost
+        //
+
+        meta_info.added_timestamp.unwrap().gt(last_sync) &&
+        meta_info.origin.unwrap() != self.instance_id().into_bytes()
+    }
+
+    fn is_removed_remotely(&self, meta_info: &MetaInfo, last_sync: &Timestamp) -> bool {
+        meta_info.removed_timestamp.unwrap().gt(last_sync) &&
+        meta_info.origin.unwrap() != self.instance_id().into_bytes()
+    }
+
+    fn is_changed_since(meta_info: &MetaInfo, last_sync: &Timestamp) -> bool {
+        meta_info.changed_timestamp.unwrap().gt(last_sync)
+    }
+
+    fn identifiers<T, F>(items: Vec<T>, identifier: F) -> HashSet<Id>
+    where
+        F: Fn(&T) -> Option<Id>
+    {
+        items
+            .iter()
+            .filter_map(identifier)
+            .collect()
     }
 
-    /// Return all plans sorted by category.
-    pub fn plans(&self) -> Result<Vec<Plan>> {
-        self.decrypt_plans(&self.storage.plans()?)
+    fn resurrect<R>(&self, id: Option<Id>, local: &MetaInfo, remote: &MetaInfo, restore_operation: R) -> Result<()>
+    where
+        R: FnOnce() -> Result<()>
+    {
+        //
+        // Item is restored only if it was changed remotely after
+        // its local removal, otherwise local removal wins
+        //
+
+        if Self::accept_change(id, remote.changed_timestamp > local.removed_timestamp, SKIPPED_AS_REMOVED_LATER) {
+            restore_operation()?;
+        }
+
+        Ok(())
     }
 
-    /// Return all plans for specific category.
-    /// 
-    /// * `category` - category to return plans for
-    pub fn plans_for(&self, category: Id) -> Result<Vec<Plan>> {
-        self.decrypt_plans(&self.storage.plans_for(category)?)
+    fn skip_existing(id: Option<Id>, added: Result<Id>) -> Result<()> {
+        //
+        // Item may already exist locally, e.g. predefined one
+        // or received during an interrupted synchronization
+        //
+
+        match added {
+            Err(error) if error.is_already_exists() => {
+                trace_skipped!(id.unwrap(), SKIPPED_AS_EXISTING);
+                Ok(())
+            },
+            result => result.map(|_| ())
+        }
     }
 
-    /// Delete permanently all previously removed items.
-    /// 
-    /// Actually `remove_*` functions can perform no removal, e.g.
-    /// just mark items as removed. This function therefore permanently
-    /// deletes such marked items.
-    pub fn clean_removed(&self) -> Result<()> {
-        self.storage.clean_removed()
+    fn keep_referenced<K>(id: Option<Id>, removed: Result<()>, keep: K) -> Result<()>
+    where
+        K: FnOnce() -> Result<()>
+    {
+        //
+        // Local items may still reference an item removed remotely, then
+        // removal conflicts with them. Local data wins: item is kept and
+        // marked as changed, hence other instances resurrect it instead
+        // of rejecting local items referencing it
+        //
+
+        match removed {
+            Err(error) if error.is_consistency_violation() => {
+                trace_skipped!(id.unwrap(), SKIPPED_AS_REFERENCED);
+                keep()
+            },
+            result => result
+        }
     }
 
-    /// Performs synchronization with remote instances.
-    /// 
-    /// * `auth` - authentication information for synchronization
-    pub fn perform_sync(&self, auth: &[u8]) -> Result<()> {
+    fn is_removed_locally(id: Option<Id>, local: Result<MetaInfo>) -> Result<bool> {
         //
-        // Just use the synchronization engine
+        // Item, that is already deleted permanently, is removed too
         //
 
-        let context = CryptoBuffer::from(auth);
-        self.sync_engine
-            .perform_sync(self.config.instance_id(), self, &context)?;
+        let removed = match local {
+            Ok(meta_info) => meta_info.removed_timestamp.is_some(),
+            Err(error) if error.is_not_found() => true,
+            Err(error) => return Err(error)
+        };
+
+        if removed {
+            trace_skipped!(id.unwrap(), SKIPPED_AS_REMOVED);
+        }
+
+        Ok(removed)
+    }
 
+    fn accept_change(id: Option<Id>, accepted: bool, reason: &'static str) -> bool {
         //
-        // Some items had been removed since the previous sync,
-        // but they were pushed to remote, and now it is not
-        // necessary to keep them locally
+        // Rejected changes are reported with the reason,
+        // it is useful to find out why something is not merged
         //
 
-        self.clean_removed()
+        if !accepted {
+            trace_skipped!(id.unwrap(), reason);
+        }
+
+        accepted
     }
 
-    /// Replaces an existsing remote URL with a new one.
-    /// 
-    /// * `remote` - new remote URL
-    pub fn set_remote_url(&self, remote: &str) -> Result<()> {
-        self.sync_engine
-            .change_remote(remote)
+    fn merge_step<T, I, F, Mo, R>(&self, items: I, filter: F, mut merge_operation: Mo) -> Result<usize>
+    where
+        I: IntoIterator<Item = T>,
+        F: Fn(&T) -> bool,
+        Mo: FnMut(T) -> Result<R>
+    {
+        let mut merged = 0;
+        for item in items.into_iter().filter(filter) {
+            merge_operation(item)?;
+            merged += 1;
+        }
+
+        Ok(merged)
     }
 }
 
 
-impl<Ce, Se, St> Syncable for Budget<Ce, Se, St> 
+impl<Ce, Se, St> Budget<Ce, Se, St>
 where
     Ce: CryptoEngine,
     Se: SyncEngine,
     St: DataStorage
 {
-    type Context = CryptoBuffer;
-
-    type InstanceId = InstanceId;
-
-    fn merge_and_export_changes<Ts, Li, Cl>(&self, timestamp_rw: &mut Ts, last_instance_rw: &mut Li, 
-        changelog_rw: &mut Cl, last_sync: &Timestamp, auth: &Self::Context) -> Result<()>
-    where
-        Ts: std::io::Read + std::io::Write + std::io::Seek,
-        Li: std::io::Read + std::io::Write + std::io::Seek,
-        Cl: std::io::Read + std::io::Write + std::io::Seek
-    {
-        let mut cumulative_changelog = if Self::empty_sync_files(timestamp_rw, last_instance_rw, changelog_rw)? {
-            //
-            // Files are correct, but empty
-            // Just return empty changelog
-            //
-
-            Changelog::new()
-        }
-        else {
-            //
-            // Read remote timestamp and instance identifiers to derive decryption key
-            //
-
-            let remote_timestamp = Self::read_timestamp(timestamp_rw)?;
-            let remote_instance = Self::read_instance(last_instance_rw)?;
-
-            let remote_salt = Self::make_key_derivation_salt(&remote_timestamp, &remote_instance)?;
-            let decryption_key = Kdf::derive_key(auth.as_bytes(), remote_salt.as_bytes(), 
-                self.crypto_engine.symmetric_key_length())?;
+    fn transactions_added_since(&self, base: Timestamp) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions_added_since(base)?)
+    }
 
-            //
-            // Read and decrypt changelog
-            //
+    fn transactions_changed_since(&self, base: Timestamp) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions_changed_since(base)?)
+    }
 
-            let mut remote_changelog = Vec::new();
-            changelog_rw.read_to_end(&mut remote_changelog)?;
+    fn transactions_removed_since(&self, base: Timestamp) -> Result<Vec<Transaction>> {
+        self.decrypt_transactions(&self.storage.transactions_removed_since(base)?)
+    }
 
-            let remote_changelog = self.crypto_engine
-                .decrypt_symmetric(decryption_key.as_bytes(), &remote_changelog)?;
+    fn accounts_added_since(&self, base: Timestamp) -> Result<Vec<Account>> {
+        self.decrypt_accounts(&self.storage.accounts_added_since(base)?)
+    }
 
-            Changelog::from_slice(remote_changelog.as_bytes())?
-        };
+    fn accounts_changed_since(&self, base: Timestamp) -> Result<Vec<Account>> {
+        self.decrypt_accounts(&self.storage.accounts_changed_since(base)?)
+    }
 
-        //
-        // Merge remote and export local changes
-        // Then join them together
-        //
+    fn accounts_removed_since(&self, base: Timestamp) -> Result<Vec<Account>> {
+        self.decrypt_accounts(&self.storage.accounts_removed_since(base)?)
+    }
 
-        let local_changelog = self.export_local_changes(last_sync)?;
-        self.merge_changes(&cumulative_changelog, last_sync)?;
-        
-        cumulative_changelog.append(local_changelog)?;
+    fn categories_added_since(&self, base: Timestamp) -> Result<Vec<Category>> {
+        self.decrypt_categories(&self.storage.categories_added_since(base)?)
+    }
 
-        //
-        // Derive new encryption key, encrypt and write updated values
-        //
+    fn categories_changed_since(&self, base: Timestamp) -> Result<Vec<Category>> {
+        self.decrypt_categories(&self.storage.categories_changed_since(base)?)
+    }
 
-        let local_timestamp = Clock::now();
-        let local_instance = self.instance_id();
+    fn categories_removed_since(&self, base: Timestamp) -> Result<Vec<Category>> {
+        self.decrypt_categories(&self.storage.categories_removed_since(base)?)
+    }
 
-        Self::prepare_for_overwrite(timestamp_rw)?;
-        Self::write_timestamp(&local_timestamp, timestamp_rw)?;
+    fn plans_added_since(&self, base: Timestamp) -> Result<Vec<Plan>> {
+        self.decrypt_plans(&self.storage.plans_added_since(base)?)
+    }
 
-        Self::prepare_for_overwrite(last_instance_rw)?;
-        Self::write_instance(&local_instance, last_instance_rw)?;
+    fn plans_changed_since(&self, base: Timestamp) -> Result<Vec<Plan>> {
+        self.decrypt_plans(&self.storage.plans_changed_since(base)?)
+    }
 
-        let local_salt = Self::make_key_derivation_salt(&local_timestamp, &local_instance)?;
-        let encryption_key = Kdf::derive_key(auth.as_bytes(), local_salt.as_bytes(), 
-            self.crypto_engine.symmetric_key_length())?;
+    fn plans_removed_since(&self, base: Timestamp) -> Result<Vec<Plan>> {
+        self.decrypt_plans(&self.storage.plans_removed_since(base)?)
+    }
 
-        let cumulative_changelog = self.crypto_engine
-            .encrypt_symmetric(encryption_key.as_bytes(), &cumulative_changelog.to_vec()?)?;
+    fn recurrings_added_since(&self, base: Timestamp) -> Result<Vec<RecurringTransaction>> {
+        self.decrypt_recurrings(&self.storage.recurrings_added_since(base)?)
+    }
 
-        Self::prepare_for_overwrite(changelog_rw)?;
-        changelog_rw.write_all(cumulative_changelog.as_bytes())?;
+    fn recurrings_changed_since(&self, base: Timestamp) -> Result<Vec<RecurringTransaction>> {
+        self.decrypt_recurrings(&self.storage.recurrings_changed_since(base)?)
+    }
 
-        Ok(())
+    fn recurrings_removed_since(&self, base: Timestamp) -> Result<Vec<RecurringTransaction>> {
+        self.decrypt_recurrings(&self.storage.recurrings_removed_since(base)?)
     }
 }
 
+
 impl<Ce, Se, St> Budget<Ce, Se, St>
 where
     Ce: CryptoEngine,
     Se: SyncEngine,
     St: DataStorage
 {
-    fn empty_sync_files<Ts, Li, Cl>(timestamp: &mut Ts, last_instance: &mut Li, changelog: &mut Cl) -> Result<bool>
-    where
-        Ts: std::io::Seek,
-        Li: std::io::Seek,
-        Cl: std::io::Seek 
-    {
-        let seek_position = std::io::SeekFrom::End(0);
+    fn insert_transaction(&self, transaction: &Transaction, archived_allowed: bool) -> Result<Id> {
+        //
+        // Amount is considered to have a proper sign,
+        // so I just add it to a corresponding account's
+        // balance.
+        // Change timestamp for account should not be 
+        // modified in this case, so I don't modify it 
+        // in account instance.
+        //
+        // Transaction is added and account is updated atomically:
+        // if account update fails, transaction is not added too.
+        // Account is read in the same storage transaction, hence
+        // concurrent changes of its balance are not lost
+        // Archived accounts reject new local transactions only,
+        // but transactions from other instances are merged
+        //
 
-        let timestamp_size = timestamp.seek(seek_position)?;
-        timestamp.rewind()?;
+        self.storage.with_transaction(|storage| {
+            let mut decrypted_account = self.decrypt_account(
+                &storage.account(transaction.account_id)?)?;
 
-        let last_instance_size = last_instance.seek(seek_position)?;
-        last_instance.rewind()?;
+            if !archived_allowed {
+                Self::ensure_not_archived(&decrypted_account)?;
+            }
 
-        let changelog_size = changelog.seek(seek_position)?;
-        changelog.rewind()?;
+            decrypted_account.balance += Self::balance_amount(transaction);
+
+            let mut transaction = self.encrypt_transaction(transaction)?;
+            transaction.meta_info.set_origin_if_absent(self.instance_id());
+
+            let id = storage.add_transaction(transaction)?;
+            storage.update_account(self.encrypt_account(&decrypted_account)?)?;
+
+            Ok(id)
+        })
+    }
 
+    fn add_transactions_bulk(&self, transactions: &[Transaction]) -> Result<()> {
         //
-        // Either all files are, or timestamp and last instanse are not.
-        // Otherwise, files are considered malformed
+        // Each account is updated only once with the sum of
+        // all its transactions amounts
         //
 
-        match (timestamp_size, last_instance_size, changelog_size) {
-            (0, 0, 0) => return Ok(true),
-            (1.., 1.., _) => return Ok(false),
-            _ => return Err(Error::from_message("msg"))
-        };
-    }
+        let mut deltas: HashMap<Id, isize> = HashMap::new();
+        for transaction in transactions {
+            *deltas.entry(transaction.account_id).or_default() += Self::balance_amount(transaction);
+        }
 
-    fn read_timestamp<R: std::io::Read>(timestamp_reader: &mut R) -> Result<Timestamp> {
-        let mut buffer = [0; std::mem::size_of::<i64>()];
-        let seconds = match timestamp_reader.read_exact(&mut buffer) {
-            Ok(_) => i64::from_le_bytes(buffer),
-            _ => 0i64
-        };
+        let transactions = transactions
+            .iter()
+            .map(|transaction| {
+                let mut transaction = self.encrypt_transaction(transaction)?;
+                transaction.meta_info.set_origin_if_absent(self.instance_id());
+
+                Ok(transaction)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.storage.with_transaction(|storage| {
+            let accounts = deltas
+                .into_iter()
+                .map(|(account, delta)| {
+                    let mut decrypted_account = self.decrypt_account(&storage.account(account)?)?;
+                    Self::ensure_not_archived(&decrypted_account)?;
+                    decrypted_account.balance += delta;
+
+                    self.encrypt_account(&decrypted_account)
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            for transaction in transactions {
+                storage.add_transaction(transaction)?;
+            }
 
-        Timestamp::from_timestamp(seconds, 0)
-            .ok_or(Error::from_message(MALFORMED_TIMESTAMP))
+            for account in accounts {
+                storage.update_account(account)?;
+            }
+
+            Ok(())
+        })
     }
 
-    fn write_timestamp<W: std::io::Write>(timestamp: &Timestamp, timestamp_writer: &mut W) -> Result<()> {
-        let timestamp = timestamp
-            .timestamp()
-            .to_le_bytes();
+    fn set_archived(&self, account: Id, archived: bool, timestamp: Timestamp) -> Result<()> {
+        let mut decrypted_account = self.account(account)?;
+        decrypted_account.archived = archived;
+        decrypted_account.meta_info.changed_timestamp = Some(timestamp);
 
-        timestamp_writer
-            .write_all(&timestamp)
-            .map_err(Error::from)
+        self.update_account(&decrypted_account)
     }
 
-    fn read_instance<R: std::io::Read>(last_instance_reader: &mut R) -> Result<InstanceId> {
-        let mut buffer = [0; 16];
-        last_instance_reader.read_exact(&mut buffer)?;
+    fn all_items(&self) -> Result<AllItems> {
+        let mut accounts = self.storage.accounts_including_archived()?;
+        accounts.extend(self.storage.accounts_removed()?);
 
-        Ok(uuid::Uuid::from_bytes(buffer))
-    }
+        let mut categories = self.storage.categories()?;
+        categories.extend(self.storage.categories_removed()?);
 
-    fn write_instance<W: std::io::Write>(instance: &InstanceId, last_instance_writer: &mut W) -> Result<()> {
-        last_instance_writer
-            .write_all(&instance.into_bytes())
-            .map_err(Error::from)
-    }
+        let mut plans = self.storage.plans()?;
+        plans.extend(self.storage.plans_removed()?);
 
-    fn prepare_for_overwrite<S: std::io::Seek>(s: &mut S) -> Result<()> {
-        s.rewind()
-            .map_err(Error::from)
-    }
+        let mut transactions = self.storage.transactions()?;
+        transactions.extend(self.storage.transactions_removed()?);
 
-    fn make_key_derivation_salt(timestamp: &Timestamp, instance: &InstanceId) -> Result<CryptoBuffer> {
-        let mut salt = Vec::new();
-        salt.write_all(&timestamp.timestamp().to_le_bytes())?;
-        salt.write_all(&instance.into_bytes())?;
+        let mut recurrings = self.storage.recurrings()?;
+        recurrings.extend(self.storage.recurrings_removed()?);
 
-        Ok(CryptoBuffer::from(salt))
+        Ok(AllItems {
+            accounts: self.decrypt_accounts(&accounts)?,
+            categories: self.decrypt_categories(&categories)?,
+            plans: self.decrypt_plans(&plans)?,
+            transactions: self.decrypt_transactions(&transactions)?,
+            recurrings: self.decrypt_recurrings(&recurrings)?
+        })
     }
 
-    fn export_local_changes(&self, last_sync: &Timestamp) -> Result<Changelog> {
-        let mut local_changelog = Changelog::new();
-
+    fn rewrite_items(&self, items: &AllItems) -> Result<()> {
         //
-        // I don't filter out "foreign" items, because it is assumed, that
-        // there are none of them since this instance has not been synced
-        // during the interval (last_sync, now]
+        // Only encrypted fields are replaced, hence items are 
+        // not considered as changed ones by synchronization
         //
 
-        local_changelog.accounts.added = self.accounts_added_since(*last_sync)?;
-        local_changelog.accounts.changed = self.accounts_changed_since(*last_sync)?;
-        local_changelog.accounts.removed = self.accounts_removed_since(*last_sync)?;
+        self.storage.with_transaction(|storage| {
+            for account in &items.accounts {
+                storage.rewrite_account(self.encrypt_account(account)?)?;
+            }
 
-        local_changelog.categories.added = self.categories_added_since(*last_sync)?;
-        local_changelog.categories.changed = self.categories_changed_since(*last_sync)?;
-        local_changelog.categories.removed = self.categories_removed_since(*last_sync)?;
+            for category in &items.categories {
+                storage.rewrite_category(self.encrypt_category(category)?)?;
+            }
 
-        local_changelog.plans.added = self.plans_added_since(*last_sync)?;
-        local_changelog.plans.changed = self.plans_changed_since(*last_sync)?;
-        local_changelog.plans.removed = self.plans_removed_since(*last_sync)?;
+            for plan in &items.plans {
+                storage.rewrite_plan(self.encrypt_plan(plan)?)?;
+            }
 
-        local_changelog.transactions.added = self.transactions_added_since(*last_sync)?;
-        local_changelog.transactions.changed = self.transactions_changed_since(*last_sync)?;
-        local_changelog.transactions.removed = self.transactions_removed_since(*last_sync)?;
+            for transaction in &items.transactions {
+                storage.rewrite_transaction(self.encrypt_transaction(transaction)?)?;
+            }
 
-        Ok(local_changelog)
-    }
+            for recurring in &items.recurrings {
+                storage.rewrite_recurring(self.encrypt_recurring(recurring)?)?;
+            }
 
-    fn merge_changes(&self, changelog: &Changelog, last_sync: &Timestamp) -> Result<()> {
-        //
-        // First, added items are processed in the following order:
-        //  1. Accounts
-        //  2. Categories
-        //  3. Plans
-        //  4. Transactions
-        //
+            //
+            // Snapshots can be made again at any time, hence
+            // they are just deleted instead of re-encryption
+            //
 
-        self.merge_step(&changelog.accounts.added,
-            |account| {
-                account.meta_info.added_timestamp.unwrap().ge(last_sync) &&
-                account.meta_info.origin.unwrap() != self.instance_id().into_bytes()
-            }, 
-            |account| {
-                //
-                // Explicitly set account's balance to its initial value, because
-                // they may differ in synced account. It could lead to inconsistency.
-                //
+            storage.clear_snapshots()
+        })
+    }
 
-                let mut account = account.clone();
-                account.balance = account.initial_balance;
+    fn lookup_any_key(crypto_engine: &Ce, config: &Config<Ce>) -> Result<Ce::Key> {
+        //
+        // Budget can be shared between several keys, hence
+        // it is enough to have any of them locally
+        //
 
-                self.add_account(&account)
+        let mut last_error = Error::new(MISSING_KEY);
+        for key_id in config.key_ids() {
+            match crypto_engine.lookup_key(key_id) {
+                Ok(key) => return Self::verify_pinned_fingerprint(crypto_engine, config, key_id, key),
+                Err(error) => last_error = error
             }
-        )?;
+        }
 
-        self.merge_step(&changelog.categories.added,
-            |category| {
-                category.meta_info.added_timestamp.unwrap().ge(last_sync) &&
-                category.meta_info.origin.unwrap() != self.instance_id().into_bytes()
-            },
-            |category| { self.add_category(category) }
-        )?;
+        Err(last_error)
+    }
 
-        self.merge_step(&changelog.plans.added,
-            |plan| {
-                plan.meta_info.added_timestamp.unwrap().ge(last_sync) &&
-                plan.meta_info.origin.unwrap() != self.instance_id().into_bytes()
-            }, 
-            |plan| { self.add_plan(plan) }
-        )?;
+    fn verify_pinned_fingerprint(crypto_engine: &Ce, config: &Config<Ce>, key_id: &Ce::KeyId, key: Ce::Key) -> Result<Ce::Key> {
+        //
+        // Substituted key is not skipped in favor of other keys,
+        // because it is a sign of tampering with keyring
+        //
 
-        self.merge_step(&changelog.transactions.added,
-            |transaction| {
-                transaction.meta_info.added_timestamp.unwrap().ge(last_sync) &&
-                transaction.meta_info.origin.unwrap() != self.instance_id().into_bytes()
-            },
-            |transaction| { self.add_transaction(transaction) }
-        )?;
+        let pinned = match config.pinned_fingerprint(key_id) {
+            Some(pinned) => pinned,
+            None => return Ok(key)
+        };
 
+        match crypto_engine.key_fingerprint(&key) {
+            Some(fingerprint) if fingerprint == pinned => Ok(key),
+            _ => Err(Error::new_with_extra(KEY_FINGERPRINT_MISMATCH, key_id.as_string()))
+        }
+    }
+
+    fn finish_interrupted_rotation(crypto_engine: &mut Ce, key: &Ce::Key, storage: &St) -> Result<()> {
         //
-        // Then, changed items are processed in the reverse order
+        // Storage is re-encrypted atomically, hence it is enough to check
+        // any item. Predefined categories always exist in initialized storage.
+        // If it can be decrypted with the current key, then storage was not
+        // re-encrypted and rotation is rolled back. Rotation is finished only
+        // if the item can be decrypted with the new key, otherwise something
+        // else is broken and the error is reported leaving rotation pending.
         //
 
-        // For now, no changes can be made, therefore, nothing is processed
+        let category = match storage.categories()?.into_iter().next() {
+            Some(category) => category,
+            None => return crypto_engine.abort_rotation()
+        };
+
+        let context = Self::field_context(CATEGORY_NAME, &category.id);
+        let error = match crypto_engine.decrypt_with_context(key, &category.name, &context) {
+            Ok(_) => return crypto_engine.abort_rotation(),
+            Err(error) => error
+        };
+
+        match crypto_engine.decrypt_pending_with_context(key, &category.name, &context) {
+            Ok(_) => crypto_engine.commit_rotation(),
+            Err(_) => Err(error)
+        }
+    }
 
+    fn add_predefined_items(&self) -> Result<()> {
         //
-        // Finally, removed items are processed in the reverse order too
+        // Add predefined items and ensure, that they have proper identifiers
+        // Predefined items creation timestamp is always equal to January 1970
         //
 
-        self.merge_step(&changelog.transactions.removed,
-            |transaction| {
-                transaction.meta_info.removed_timestamp.unwrap().ge(last_sync) &&
-                transaction.meta_info.origin.unwrap() != self.instance_id().into_bytes()
-            },
-            |transaction| {
-                self.remove_transaction(transaction.id.unwrap(), false,
-                    transaction.meta_info.removed_timestamp.unwrap())
-            }
-        )?;
+        self.add_predefined_category(&Category { 
+            id: Some(St::TRANSFER_INCOME_ID), 
+            name: TRANSFER_INCOME_CAT_NAME.to_owned(), 
+            category_type: CategoryType::Income,
+            meta_info: MetaInfo::new(Some(*JANUARY_1970), None, None)
+        })?;
 
-        self.merge_step(&changelog.plans.removed,
-            |plan| {
-                plan.meta_info.removed_timestamp.unwrap().ge(last_sync) &&
-                plan.meta_info.origin.unwrap() != self.instance_id().into_bytes()
-            },
-            |plan| {
-                self.remove_plan(plan.id.unwrap(), plan.meta_info.removed_timestamp.unwrap())
-            }
-        )?;
+        self.add_predefined_category(&Category { 
+            id: Some(St::TRANSFER_OUTCOME_ID), 
+            name: TRANSFER_OUTCOME_CAT_NAME.to_owned(),
+            category_type: CategoryType::Outcome,
+            meta_info: MetaInfo::new(Some(*JANUARY_1970), None, None)
+        })
+    }
 
-        self.merge_step(&changelog.categories.removed,
-            |category| {
-                category.meta_info.removed_timestamp.unwrap().ge(last_sync) &&
-                category.meta_info.origin.unwrap() != self.instance_id().into_bytes()
-            },
-            |category| {
-                self.remove_category(category.id.unwrap(), category.meta_info.removed_timestamp.unwrap())
-            }
-        )?;
+    fn add_predefined_category(&self, category: &Category) -> Result<()> {
+        match self.storage.category_any(category.id.unwrap()) {
+            Ok(_) => Ok(()),
+            Err(error) if error.is_not_found() => self.add_category(category).map(|_| ()),
+            Err(error) => Err(error)
+        }
+    }
 
-        self.merge_step(&changelog.accounts.removed,
-            |account| {
-                account.meta_info.removed_timestamp.unwrap().ge(last_sync) &&
-                account.meta_info.origin.unwrap() != self.instance_id().into_bytes()
-            },
-            |account| {
-                self.remove_account(account.id.unwrap(), false,
-                    account.meta_info.removed_timestamp.unwrap())
-            }
-        )?;
+    fn categories_by_type(&self, category_type: Option<CategoryType>) -> Result<Vec<Category>> {
+        match category_type {
+            Some(category_type) => self.categories_of(category_type),
+            None => self.categories()
+        }
+    }
 
-        Ok(())
+    fn find_by_name<T, F>(items: Vec<T>, name: &str, item_name: F) -> Result<Option<T>>
+    where
+        F: Fn(&T) -> &String
+    {
+        //
+        // Names are encrypted, hence I have to decrypt all items
+        // and compare names here instead of storage
+        //
+
+        let lowercase_name = name.to_lowercase();
+        let mut found = items
+            .into_iter()
+            .filter(|item| item_name(item).to_lowercase() == lowercase_name);
+
+        let item = found.next();
+        if found.next().is_some() {
+            return Err(Error::new_with_extra(AMBIGUOUS_NAME, name));
+        }
+
+        Ok(item)
     }
 
-    fn merge_step<T, I, F, Mo>(&self, items: I, filter: F, merge_operation: Mo) -> Result<()>
+    fn filter_by_prefix<T, F>(items: Vec<T>, prefix: &str, item_name: F) -> Vec<T>
     where
-        I: IntoIterator<Item = T>,
-        F: Fn(&T) -> bool,
-        Mo: Fn(T) -> Result<()>
+        F: Fn(&T) -> &String
     {
-        for item in items.into_iter().filter(filter) {
-            merge_operation(item)?;
+        let prefix = prefix.to_lowercase();
+        items
+            .into_iter()
+            .filter(|item| item_name(item).to_lowercase().starts_with(&prefix))
+            .collect()
+    }
+
+    fn ensure_not_archived(account: &Account) -> Result<()> {
+        if account.archived {
+            return Err(Error::new_with_extra(ACCOUNT_IS_ARCHIVED, account.name.clone()));
         }
 
         Ok(())
     }
-}
 
+    fn transactions_sum(&self, account: Id) -> Result<isize> {
+        let sum = self.transactions_of(account)?
+            .iter()
+            .map(Self::balance_amount)
+            .sum();
 
-impl<Ce, Se, St> Budget<Ce, Se, St>
-where
-    Ce: CryptoEngine,
-    Se: SyncEngine,
-    St: DataStorage
-{
-    fn transactions_added_since(&self, base: Timestamp) -> Result<Vec<Transaction>> {
-        self.decrypt_transactions(&self.storage.transactions_added_since(base)?)
+        Ok(sum)
     }
 
-    fn transactions_changed_since(&self, base: Timestamp) -> Result<Vec<Transaction>> {
-        self.decrypt_transactions(&self.storage.transactions_changed_since(base)?)
-    }
+    fn balance_amount(transaction: &Transaction) -> isize {
+        //
+        // Pending transactions do not affect balance until settled
+        //
 
-    fn transactions_removed_since(&self, base: Timestamp) -> Result<Vec<Transaction>> {
-        self.decrypt_transactions(&self.storage.transactions_removed_since(base)?)
+        match transaction.pending {
+            true => 0,
+            false => transaction.amount
+        }
     }
 
-    fn accounts_added_since(&self, base: Timestamp) -> Result<Vec<Account>> {
-        self.decrypt_accounts(&self.storage.accounts_added_since(base)?)
+    fn is_transfer_category(category: Id) -> bool {
+        category == St::TRANSFER_INCOME_ID || 
+        category == St::TRANSFER_OUTCOME_ID
     }
 
-    fn accounts_changed_since(&self, base: Timestamp) -> Result<Vec<Account>> {
-        self.decrypt_accounts(&self.storage.accounts_changed_since(base)?)
+    fn nth_occurrence(recurring: &RecurringTransaction, index: u32) -> Option<Timestamp> {
+        let start = recurring.start_timestamp;
+
+        match recurring.period {
+            RecurrencePeriod::Weekly => start.checked_add_signed(chrono::Duration::weeks(index.into())),
+            RecurrencePeriod::Monthly => start.checked_add_months(chrono::Months::new(index)),
+            RecurrencePeriod::Yearly => start.checked_add_months(chrono::Months::new(index.checked_mul(12)?))
+        }
     }
 
-    fn accounts_removed_since(&self, base: Timestamp) -> Result<Vec<Account>> {
-        self.decrypt_accounts(&self.storage.accounts_removed_since(base)?)
+    fn sync_engine(&self) -> parking_lot::ReentrantMutexGuard<'_, Se> {
+        self.sync_engine.lock()
     }
 
-    fn categories_added_since(&self, base: Timestamp) -> Result<Vec<Category>> {
-        self.decrypt_categories(&self.storage.categories_added_since(base)?)
+    /// IANA name of timezone used for day and month boundaries,
+    /// `None` if UTC is used.
+    fn timezone(&self) -> Option<&str> {
+        self.config
+            .settings()
+            .timezone()
     }
 
-    fn categories_changed_since(&self, base: Timestamp) -> Result<Vec<Category>> {
-        self.decrypt_categories(&self.storage.categories_changed_since(base)?)
+    fn plan_period_bounds(&self, period: PlanPeriod, at: &Timestamp) -> Result<(Timestamp, Timestamp)> {
+        datetime::period_containing(at, period, self.config.settings().week_starts_on(), self.timezone())
+            .ok_or(Error::new(INVALID_PERIOD))
     }
 
-    fn categories_removed_since(&self, base: Timestamp) -> Result<Vec<Category>> {
-        self.decrypt_categories(&self.storage.categories_removed_since(base)?)
+    fn plans_exceeded_at<I>(&self, plans: I, at: Timestamp) -> Result<Vec<PlanAlert>>
+    where
+        I: Iterator<Item = Id>
+    {
+        let mut alerts = Vec::new();
+        for plan in plans {
+            if let Some(alert) = PlanAlert::exceeded(&self.plan_status(plan, at)?) {
+                alerts.push(alert);
+            }
+        }
+
+        Ok(alerts)
     }
 
-    fn plans_added_since(&self, base: Timestamp) -> Result<Vec<Plan>> {
-        self.decrypt_plans(&self.storage.plans_added_since(base)?)
+    fn spent_amount<'a, I>(transactions: I) -> isize
+    where
+        I: Iterator<Item = &'a Transaction>
+    {
+        transactions
+            .filter(|transaction| transaction.amount < 0)
+            .map(|transaction| transaction.amount.abs())
+            .sum()
     }
 
-    fn plans_changed_since(&self, base: Timestamp) -> Result<Vec<Plan>> {
-        self.decrypt_plans(&self.storage.plans_changed_since(base)?)
+    fn account_currencies(accounts: &[Account]) -> HashMap<Id, String> {
+        accounts
+            .iter()
+            .map(|account| (account.id.unwrap(), account.currency.clone()))
+            .collect()
     }
 
-    fn plans_removed_since(&self, base: Timestamp) -> Result<Vec<Plan>> {
-        self.decrypt_plans(&self.storage.plans_removed_since(base)?)
+    fn group_category_totals(&self, transactions: &[Transaction], currencies: &HashMap<Id, String>, 
+        include_empty: bool, include_transfers: bool) -> Result<Vec<(Category, String, isize)>> 
+    {
+        let default_currency = self.config.currency();
+
+        let mut totals: HashMap<Id, BTreeMap<&str, isize>> = HashMap::new();
+        for transaction in transactions {
+            let currency = currencies.get(&transaction.account_id)
+                .map_or(default_currency, String::as_str);
+
+            *totals.entry(transaction.category_id)
+                .or_default()
+                .entry(currency)
+                .or_default() += transaction.amount;
+        }
+
+        let totals = self.categories()?
+            .into_iter()
+            .filter(|category| include_transfers || !Self::is_transfer_category(category.id.unwrap()))
+            .flat_map(|category| {
+                match totals.get(&category.id.unwrap()) {
+                    Some(by_currency) => by_currency
+                        .iter()
+                        .map(|(currency, total)| (category.clone(), currency.to_string(), *total))
+                        .collect(),
+                    None if include_empty => vec![(category, default_currency.to_owned(), 0)],
+                    None => Vec::new()
+                }
+            })
+            .collect();
+
+        Ok(totals)
     }
 }
 
@@ -834,56 +4296,178 @@ where
     Se: SyncEngine,
     St: DataStorage
 {
-    fn encrypt_string(&self, data: &String) -> Result<CryptoBuffer> {
+    fn field_context(column: &str, id: &Option<Id>) -> Vec<u8> {
+        //
+        // Ciphertext is bound to a column and a row, hence it
+        // cannot be moved to another column or another row
+        //
+
+        let mut context = column.as_bytes().to_vec();
+        if let Some(id) = id {
+            context.extend_from_slice(id.as_bytes());
+        }
+
+        context
+    }
+
+    fn encrypt_string(&self, data: &String, context: &[u8]) -> Result<CryptoBuffer> {
         self.crypto_engine
-            .encrypt(&self.key, data.as_bytes())
+            .encrypt_with_context(&self.key, data.as_bytes(), context)
+    }
+
+    fn decrypt_fields(&self, fields: &[Field]) -> Result<Plaintexts> {
+        let ciphertexts: Vec<(&[u8], &[u8])> = fields
+            .iter()
+            .map(|(ciphertext, context)| (*ciphertext, context.as_slice()))
+            .collect();
+
+        let plaintexts = self.crypto_engine
+            .decrypt_many_with_context(&self.key, &ciphertexts)?;
+
+        Ok(plaintexts.into_iter())
+    }
+
+    fn decrypt_batch<E, T>(&self, items: &[E], ciphertexts: fn(&E) -> Vec<Field>, 
+        build: fn(&Self, &E, &mut Plaintexts) -> Result<T>) -> Result<Vec<T>> 
+    {
+        //
+        // Fields of all items are decrypted at once, then plaintexts
+        // are consumed in the same order to build decrypted items
+        //
+
+        let fields: Vec<Field> = items
+            .iter()
+            .flat_map(ciphertexts)
+            .collect();
+
+        let mut plaintexts = self.decrypt_fields(&fields)?;
+
+        items
+            .iter()
+            .map(|item| build(self, item, &mut plaintexts))
+            .collect()
     }
 
-    fn decrypt_string(&self, data: &[u8]) -> Result<String> {
-        let decrypted = self.crypto_engine
-            .decrypt(&self.key, data)?;
+    fn next_string(plaintexts: &mut Plaintexts) -> String {
+        let decrypted = plaintexts
+            .next()
+            .expect("Each ciphertext has a plaintext");
 
-        Ok(
-            String::from_utf8_lossy(decrypted.as_bytes())
-                .to_string()
-        )
+        String::from_utf8_lossy(decrypted.as_bytes())
+            .to_string()
     }
 
-    fn encrypt_isize(&self, data: &isize) -> Result<CryptoBuffer> {
+    fn encrypt_isize(&self, data: &isize, context: &[u8]) -> Result<CryptoBuffer> {
         self.crypto_engine
-            .encrypt(&self.key, &data.to_le_bytes())
+            .encrypt_with_context(&self.key, &data.to_le_bytes(), context)
     }
 
-    fn decrypt_isize(&self, data: &[u8]) -> Result<isize> {
-        let decrypted = self.crypto_engine
-            .decrypt(&self.key, data)?;
+    fn next_isize(plaintexts: &mut Plaintexts) -> Result<isize> {
+        let decrypted = plaintexts
+            .next()
+            .expect("Each ciphertext has a plaintext");
 
         let bytes = decrypted
             .as_bytes()
             .try_into()
-            .map_err(|e: TryFromSliceError| Error::from_message(e.to_string()))?;
+            .map_err(|e: TryFromSliceError| Error::from_kind(ErrorKind::Malformed, e.to_string()))?;
 
         Ok(isize::from_le_bytes(bytes))
     }
 
+    fn encrypt_snapshot(&self, account: Id, period_start: Timestamp, balance: isize) -> Result<EncryptedBalanceSnapshot> {
+        let encrypted_balance = self.encrypt_isize(&balance, 
+            &Self::snapshot_context(account, period_start))?;
+
+        Ok(EncryptedBalanceSnapshot {
+            account_id: account,
+            period_start,
+            balance: encrypted_balance.as_bytes().into()
+        })
+    }
+
+    fn decrypt_snapshot(&self, encrypted_snapshot: &EncryptedBalanceSnapshot) -> Result<isize> {
+        let context = Self::snapshot_context(encrypted_snapshot.account_id, encrypted_snapshot.period_start);
+        let mut plaintexts = self.decrypt_fields(&[(&encrypted_snapshot.balance, context)])?;
+
+        Self::next_isize(&mut plaintexts)
+    }
+
+    fn snapshot_context(account: Id, period_start: Timestamp) -> Vec<u8> {
+        //
+        // Snapshot has no identifier, it is identified by
+        // an account and a period start instead
+        //
+
+        let mut context = Self::field_context(SNAPSHOT_BALANCE, &Some(account));
+        context.extend_from_slice(&period_start.timestamp().to_le_bytes());
+
+        context
+    }
+
     fn encrypt_transaction(&self, transaction: &Transaction) -> Result<EncryptedTransaction> {
-        let encrypted_description = self.encrypt_string(&transaction.description)?;
-        let encrypted_amount = self.encrypt_isize(&transaction.amount)?;
+        //
+        // Identifier is a part of encryption context, hence it is
+        // generated here instead of storage for new transactions
+        //
+
+        let id = transaction.id.or_else(|| Some(Id::random()));
+
+        let encrypted_description = self.encrypt_string(&transaction.description, 
+            &Self::field_context(TRANSACTION_DESCRIPTION, &id))?;
+        let encrypted_amount = self.encrypt_isize(&transaction.amount, 
+            &Self::field_context(TRANSACTION_AMOUNT, &id))?;
+
+        //
+        // Tags are joined and encrypted as a single blob, 
+        // NULL is stored if there are no tags at all
+        //
+
+        let encrypted_tags = match transaction.tags.is_empty() {
+            true => None,
+            false => Some(self.encrypt_string(&transaction.tags.join(TAGS_SEPARATOR), 
+                &Self::field_context(TRANSACTION_TAGS, &id))?.as_bytes().into())
+        };
 
         Ok(EncryptedTransaction {
-            id: transaction.id,
+            id,
             timestamp: transaction.timestamp,
             description: encrypted_description.as_bytes().into(),
             account_id: transaction.account_id,
             category_id: transaction.category_id,
             amount: encrypted_amount.as_bytes().into(),
+            transfer_id: transaction.transfer_id,
+            tags: encrypted_tags,
+            pending: transaction.pending,
             meta_info: transaction.meta_info
         })
     }
 
-    fn decrypt_transaction(&self, encrypted_transaction: &EncryptedTransaction) -> Result<Transaction> {
-        let decrypted_description = self.decrypt_string(&encrypted_transaction.description)?;
-        let decrypted_amount = self.decrypt_isize(&encrypted_transaction.amount)?;
+    fn transaction_ciphertexts(encrypted_transaction: &EncryptedTransaction) -> Vec<Field<'_>> {
+        let id = &encrypted_transaction.id;
+        let mut ciphertexts: Vec<Field> = vec![
+            (&encrypted_transaction.description, Self::field_context(TRANSACTION_DESCRIPTION, id)), 
+            (&encrypted_transaction.amount, Self::field_context(TRANSACTION_AMOUNT, id))
+        ];
+
+        if let Some(tags) = &encrypted_transaction.tags {
+            ciphertexts.push((tags, Self::field_context(TRANSACTION_TAGS, id)));
+        }
+
+        ciphertexts
+    }
+
+    fn transaction_from_plaintexts(&self, encrypted_transaction: &EncryptedTransaction, plaintexts: &mut Plaintexts) -> Result<Transaction> {
+        let decrypted_description = Self::next_string(plaintexts);
+        let decrypted_amount = Self::next_isize(plaintexts)?;
+
+        let decrypted_tags = match &encrypted_transaction.tags {
+            Some(_) => Self::next_string(plaintexts)
+                .split(TAGS_SEPARATOR)
+                .map(str::to_owned)
+                .collect(),
+            None => Vec::new()
+        };
 
         Ok(Transaction {
             id: encrypted_transaction.id,
@@ -892,65 +4476,126 @@ where
             account_id: encrypted_transaction.account_id,
             category_id: encrypted_transaction.category_id,
             amount: decrypted_amount,
+            transfer_id: encrypted_transaction.transfer_id,
+            tags: decrypted_tags,
+            pending: encrypted_transaction.pending,
             meta_info: encrypted_transaction.meta_info
         })
     }
 
-    fn decrypt_transactions(&self, encrypted_transactions: &Vec<EncryptedTransaction>) -> Result<Vec<Transaction>> {
-        encrypted_transactions
-            .iter()
-            .map(|transaction| self.decrypt_transaction(transaction))
-            .collect()
+    fn decrypt_transaction(&self, encrypted_transaction: &EncryptedTransaction) -> Result<Transaction> {
+        let mut plaintexts = self.decrypt_fields(&Self::transaction_ciphertexts(encrypted_transaction))?;
+        self.transaction_from_plaintexts(encrypted_transaction, &mut plaintexts)
+    }
+
+    fn decrypt_transactions(&self, encrypted_transactions: &[EncryptedTransaction]) -> Result<Vec<Transaction>> {
+        self.decrypt_batch(encrypted_transactions, Self::transaction_ciphertexts, Self::transaction_from_plaintexts)
     }
 
     fn encrypt_account(&self, account: &Account) -> Result<EncryptedAccount> {
-        let encrypted_name = self.encrypt_string(&account.name)?;
-        let encrypted_balance = self.encrypt_isize(&account.balance)?;
-        let encrypted_initial_balance = self.encrypt_isize(&account.initial_balance)?;
+        let id = account.id.or_else(|| Some(Id::random()));
+
+        let encrypted_name = self.encrypt_string(&account.name, 
+            &Self::field_context(ACCOUNT_NAME, &id))?;
+        let encrypted_balance = self.encrypt_isize(&account.balance, 
+            &Self::field_context(ACCOUNT_BALANCE, &id))?;
+        let encrypted_initial_balance = self.encrypt_isize(&account.initial_balance, 
+            &Self::field_context(ACCOUNT_INITIAL_BALANCE, &id))?;
+
+        //
+        // Account without explicit currency gets the default one
+        //
+
+        let currency = match account.currency.is_empty() {
+            true => self.config.currency().to_owned(),
+            false => account.currency.clone()
+        };
+
+        let encrypted_currency = self.encrypt_string(&currency, 
+            &Self::field_context(ACCOUNT_CURRENCY, &id))?;
 
         Ok(EncryptedAccount { 
-            id: account.id,
+            id,
             name: encrypted_name.as_bytes().into(), 
             balance: encrypted_balance.as_bytes().into(),
             initial_balance: encrypted_initial_balance.as_bytes().into(),
+            currency: Some(encrypted_currency.as_bytes().into()),
+            archived: account.archived,
+            account_type: account.account_type,
             meta_info: account.meta_info
         })
     }
 
-    fn decrypt_account(&self, encrypted_account: &EncryptedAccount) -> Result<Account> {
-        let decrypted_name = self.decrypt_string(&encrypted_account.name)?;
-        let decrypted_balance = self.decrypt_isize(&encrypted_account.balance)?;
-        let decrypted_initial_balance = self.decrypt_isize(&encrypted_account.initial_balance)?;
+    fn account_ciphertexts(encrypted_account: &EncryptedAccount) -> Vec<Field<'_>> {
+        let id = &encrypted_account.id;
+        let mut ciphertexts: Vec<Field> = vec![
+            (&encrypted_account.name, Self::field_context(ACCOUNT_NAME, id)), 
+            (&encrypted_account.balance, Self::field_context(ACCOUNT_BALANCE, id)), 
+            (&encrypted_account.initial_balance, Self::field_context(ACCOUNT_INITIAL_BALANCE, id))
+        ];
+
+        if let Some(currency) = &encrypted_account.currency {
+            ciphertexts.push((currency, Self::field_context(ACCOUNT_CURRENCY, id)));
+        }
+
+        ciphertexts
+    }
+
+    fn account_from_plaintexts(&self, encrypted_account: &EncryptedAccount, plaintexts: &mut Plaintexts) -> Result<Account> {
+        let decrypted_name = Self::next_string(plaintexts);
+        let decrypted_balance = Self::next_isize(plaintexts)?;
+        let decrypted_initial_balance = Self::next_isize(plaintexts)?;
+
+        //
+        // NULL is stored for accounts created before currencies were introduced
+        //
+
+        let decrypted_currency = match &encrypted_account.currency {
+            Some(_) => Self::next_string(plaintexts),
+            None => self.config.currency().to_owned()
+        };
 
         Ok(Account { 
             id: encrypted_account.id,
             name: decrypted_name, 
             balance: decrypted_balance,
             initial_balance: decrypted_initial_balance,
+            currency: decrypted_currency,
+            archived: encrypted_account.archived,
+            account_type: encrypted_account.account_type,
             meta_info: encrypted_account.meta_info
         })
     }
 
-    fn decrypt_accounts(&self, encrypted_accounts: &Vec<EncryptedAccount>) -> Result<Vec<Account>> {
-        encrypted_accounts
-            .iter()
-            .map(|account| self.decrypt_account(account))
-            .collect()
+    fn decrypt_account(&self, encrypted_account: &EncryptedAccount) -> Result<Account> {
+        let mut plaintexts = self.decrypt_fields(&Self::account_ciphertexts(encrypted_account))?;
+        self.account_from_plaintexts(encrypted_account, &mut plaintexts)
+    }
+
+    fn decrypt_accounts(&self, encrypted_accounts: &[EncryptedAccount]) -> Result<Vec<Account>> {
+        self.decrypt_batch(encrypted_accounts, Self::account_ciphertexts, Self::account_from_plaintexts)
     }
 
     fn encrypt_category(&self, category: &Category) -> Result<EncryptedCategory> {
-        let encrypted_name = self.encrypt_string(&category.name)?;
+        let id = category.id.or_else(|| Some(Id::random()));
+
+        let encrypted_name = self.encrypt_string(&category.name, 
+            &Self::field_context(CATEGORY_NAME, &id))?;
 
         Ok(EncryptedCategory {
-            id: category.id,
+            id,
             name: encrypted_name.as_bytes().into(),
             category_type: category.category_type,
             meta_info: category.meta_info
         })
     }
 
-    fn decrypt_category(&self, encrypted_category: &EncryptedCategory) -> Result<Category> {
-        let decrypted_category = self.decrypt_string(&encrypted_category.name)?;
+    fn category_ciphertexts(encrypted_category: &EncryptedCategory) -> Vec<Field<'_>> {
+        vec![(&encrypted_category.name, Self::field_context(CATEGORY_NAME, &encrypted_category.id))]
+    }
+
+    fn category_from_plaintexts(&self, encrypted_category: &EncryptedCategory, plaintexts: &mut Plaintexts) -> Result<Category> {
+        let decrypted_category = Self::next_string(plaintexts);
 
         Ok(Category { 
             id: encrypted_category.id,
@@ -960,43 +4605,126 @@ where
         })
     }
 
-    fn decrypt_categories(&self, encrypted_categories: &Vec<EncryptedCategory>) -> Result<Vec<Category>> {
-        encrypted_categories
-            .iter()
-            .map(|category| self.decrypt_category(category))
-            .collect()
+    fn decrypt_category(&self, encrypted_category: &EncryptedCategory) -> Result<Category> {
+        let mut plaintexts = self.decrypt_fields(&Self::category_ciphertexts(encrypted_category))?;
+        self.category_from_plaintexts(encrypted_category, &mut plaintexts)
+    }
+
+    fn decrypt_categories(&self, encrypted_categories: &[EncryptedCategory]) -> Result<Vec<Category>> {
+        self.decrypt_batch(encrypted_categories, Self::category_ciphertexts, Self::category_from_plaintexts)
     }
 
     fn encrypt_plan(&self, plan: &Plan) -> Result<EncryptedPlan> {
-        let encrypted_name = self.encrypt_string(&plan.name)?;
-        let encrypted_amount_limit = self.encrypt_isize(&plan.amount_limit)?;
+        let id = plan.id.or_else(|| Some(Id::random()));
+
+        let encrypted_name = self.encrypt_string(&plan.name, 
+            &Self::field_context(PLAN_NAME, &id))?;
+        let encrypted_amount_limit = self.encrypt_isize(&plan.amount_limit, 
+            &Self::field_context(PLAN_AMOUNT_LIMIT, &id))?;
 
         Ok(EncryptedPlan { 
-            id: plan.id, 
+            id, 
             category_id: plan.category_id, 
             name: encrypted_name.as_bytes().into(), 
             amount_limit: encrypted_amount_limit.as_bytes().into(),
+            period: plan.period,
+            rollover: plan.rollover,
             meta_info: plan.meta_info
         })
     }
 
-    fn decrypt_plan(&self, encrypted_plan: &EncryptedPlan) -> Result<Plan> {
-        let decrypted_name = self.decrypt_string(&encrypted_plan.name)?;
-        let decrypted_amount_limit = self.decrypt_isize(&encrypted_plan.amount_limit)?;
+    fn plan_ciphertexts(encrypted_plan: &EncryptedPlan) -> Vec<Field<'_>> {
+        let id = &encrypted_plan.id;
+        vec![
+            (&encrypted_plan.name, Self::field_context(PLAN_NAME, id)), 
+            (&encrypted_plan.amount_limit, Self::field_context(PLAN_AMOUNT_LIMIT, id))
+        ]
+    }
+
+    fn plan_from_plaintexts(&self, encrypted_plan: &EncryptedPlan, plaintexts: &mut Plaintexts) -> Result<Plan> {
+        let decrypted_name = Self::next_string(plaintexts);
+        let decrypted_amount_limit = Self::next_isize(plaintexts)?;
 
         Ok(Plan { 
             id: encrypted_plan.id, 
             category_id: encrypted_plan.category_id, 
             name: decrypted_name, 
             amount_limit: decrypted_amount_limit,
+            period: encrypted_plan.period,
+            rollover: encrypted_plan.rollover,
             meta_info: encrypted_plan.meta_info
         })
     }
 
-    fn decrypt_plans(&self, encrypted_plans: &Vec<EncryptedPlan>) -> Result<Vec<Plan>> {
-        encrypted_plans
-            .iter()
-            .map(|plan| self.decrypt_plan(plan))
-            .collect()
+    fn decrypt_plan(&self, encrypted_plan: &EncryptedPlan) -> Result<Plan> {
+        let mut plaintexts = self.decrypt_fields(&Self::plan_ciphertexts(encrypted_plan))?;
+        self.plan_from_plaintexts(encrypted_plan, &mut plaintexts)
+    }
+
+    fn decrypt_plans(&self, encrypted_plans: &[EncryptedPlan]) -> Result<Vec<Plan>> {
+        self.decrypt_batch(encrypted_plans, Self::plan_ciphertexts, Self::plan_from_plaintexts)
+    }
+
+    fn encrypt_recurring(&self, recurring: &RecurringTransaction) -> Result<EncryptedRecurringTransaction> {
+        let id = recurring.id.or_else(|| Some(Id::random()));
+
+        let encrypted_description = self.encrypt_string(&recurring.description, 
+            &Self::field_context(RECURRING_DESCRIPTION, &id))?;
+        let encrypted_amount = self.encrypt_isize(&recurring.amount, 
+            &Self::field_context(RECURRING_AMOUNT, &id))?;
+
+        Ok(EncryptedRecurringTransaction {
+            id,
+            description: encrypted_description.as_bytes().into(),
+            account_id: recurring.account_id,
+            category_id: recurring.category_id,
+            amount: encrypted_amount.as_bytes().into(),
+            period: recurring.period,
+            start_timestamp: recurring.start_timestamp,
+            end_timestamp: recurring.end_timestamp,
+            materialized_timestamp: recurring.materialized_timestamp,
+            meta_info: recurring.meta_info
+        })
+    }
+
+    fn recurring_ciphertexts(encrypted_recurring: &EncryptedRecurringTransaction) -> Vec<Field<'_>> {
+        let id = &encrypted_recurring.id;
+        vec![
+            (&encrypted_recurring.description, Self::field_context(RECURRING_DESCRIPTION, id)), 
+            (&encrypted_recurring.amount, Self::field_context(RECURRING_AMOUNT, id))
+        ]
+    }
+
+    fn recurring_from_plaintexts(&self, encrypted_recurring: &EncryptedRecurringTransaction, 
+        plaintexts: &mut Plaintexts) -> Result<RecurringTransaction> 
+    {
+        let decrypted_description = Self::next_string(plaintexts);
+        let decrypted_amount = Self::next_isize(plaintexts)?;
+
+        Ok(RecurringTransaction {
+            id: encrypted_recurring.id,
+            description: decrypted_description,
+            account_id: encrypted_recurring.account_id,
+            category_id: encrypted_recurring.category_id,
+            amount: decrypted_amount,
+            period: encrypted_recurring.period,
+            start_timestamp: encrypted_recurring.start_timestamp,
+            end_timestamp: encrypted_recurring.end_timestamp,
+            materialized_timestamp: encrypted_recurring.materialized_timestamp,
+            meta_info: encrypted_recurring.meta_info
+        })
+    }
+
+    fn decrypt_recurring(&self, encrypted_recurring: &EncryptedRecurringTransaction) -> Result<RecurringTransaction> {
+        let mut plaintexts = self.decrypt_fields(&Self::recurring_ciphertexts(encrypted_recurring))?;
+        self.recurring_from_plaintexts(encrypted_recurring, &mut plaintexts)
+    }
+
+    fn decrypt_recurrings(&self, encrypted_recurrings: &[EncryptedRecurringTransaction]) -> Result<Vec<RecurringTransaction>> {
+        self.decrypt_batch(encrypted_recurrings, Self::recurring_ciphertexts, Self::recurring_from_plaintexts)
     }
 }
+
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,940 @@
+use std::io::{Cursor, Seek};
+
+use crate::crypto::{CryptoBuffer, PasswordCryptoEngine};
+use crate::core::{Budget, ACCOUNT_IS_REMOVED, REMOTE_DATA_CORRUPTED, MALFORMED_TIMESTAMP, MALFORMED_INSTANCES, MALFORMED_CHANGELOG};
+use crate::core::changelog::Changelog;
+use crate::datetime::{Clock, FIRST_AFTER_JANUARY_1970};
+use crate::location::Location;
+use crate::sync::{SyncEngine, GitSyncEngine, DirSyncEngine};
+use crate::error::ErrorKind;
+use crate::storage::{DataStorage, DbStorage, MemoryStorage, CategoryType, Id};
+use crate::testing::{self, at};
+
+
+#[test]
+fn transactions_change_balance() {
+    let (_loc, budget) = testing::budget();
+    let account = budget.add_account(&testing::account("Wallet", 100)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+
+    let spent = budget.add_transaction(&testing::transaction(account, category, -30, at(2024, 5, 1))).unwrap();
+    budget.add_transaction(&testing::transaction(account, category, -20, at(2024, 5, 2))).unwrap();
+    assert_eq!(budget.account(account).unwrap().balance, 50);
+
+    let mut transaction = budget.transaction(spent).unwrap();
+    transaction.amount = -10;
+    budget.update_transaction(&transaction).unwrap();
+    assert_eq!(budget.account(account).unwrap().balance, 70);
+
+    budget.remove_transaction(spent, false, at(2024, 5, 3)).unwrap();
+    assert_eq!(budget.account(account).unwrap().balance, 80);
+    assert_eq!(budget.transactions_of(account).unwrap().len(), 1);
+}
+
+
+#[test]
+fn transfer_moves_money() {
+    let (_loc, budget) = testing::budget();
+    let from = budget.add_account(&testing::account("Checking", 100)).unwrap();
+    let to = budget.add_account(&testing::account("Savings", 0)).unwrap();
+
+    let (income, outcome) = budget.add_transfer(40, from, to, at(2024, 5, 1)).unwrap();
+    assert_eq!(budget.account(from).unwrap().balance, 60);
+    assert_eq!(budget.account(to).unwrap().balance, 40);
+
+    let transfer_id = budget.transaction(outcome).unwrap().transfer_id;
+    assert!(transfer_id.is_some());
+    assert_eq!(budget.transaction(income).unwrap().transfer_id, transfer_id);
+
+    budget.remove_transfer(transfer_id.unwrap(), at(2024, 5, 2)).unwrap();
+    assert_eq!(budget.account(from).unwrap().balance, 100);
+    assert_eq!(budget.account(to).unwrap().balance, 0);
+}
+
+
+#[test]
+fn account_with_transactions_is_not_removed() {
+    let (_loc, budget) = testing::budget();
+    let account = budget.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    budget.add_transaction(&testing::transaction(account, category, -5, at(2024, 5, 1))).unwrap();
+
+    assert!(budget.remove_account(account, false, at(2024, 5, 2)).is_err());
+    assert!(budget.account(account).is_ok());
+
+    budget.remove_account(account, true, at(2024, 5, 2)).unwrap();
+    assert!(budget.account(account).is_err());
+    assert!(budget.transactions().unwrap().is_empty());
+}
+
+
+#[test]
+fn sensitive_data_is_encrypted() {
+    let (_loc, budget) = testing::budget();
+    let account = budget.add_account(&testing::account("Wallet", 100)).unwrap();
+
+    let stored = budget.storage.account(account).unwrap();
+    assert_ne!(stored.name, b"Wallet");
+
+    let decrypted = budget.account(account).unwrap();
+    assert_eq!(decrypted.name, "Wallet");
+    assert_eq!(decrypted.initial_balance, 100);
+}
+
+
+#[test]
+fn batch_decryption_matches_per_item() {
+    let (_loc, budget) = testing::budget();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    budget.add_plan(&testing::plan(category, 500)).unwrap();
+
+    for i in 0..10 {
+        let account = budget.add_account(&testing::account(&format!("Account {}", i), i)).unwrap();
+        for day in 1..=20 {
+            let mut transaction = testing::transaction(account, category, -day, at(2024, 5, day as u32));
+            transaction.tags = vec![format!("tag {}", day % 3)];
+            budget.add_transaction(&transaction).unwrap();
+        }
+    }
+
+    let encrypted = budget.storage.transactions().unwrap();
+    let batch = budget.decrypt_transactions(&encrypted).unwrap();
+    let per_item: Vec<_> = encrypted.iter().map(|t| budget.decrypt_transaction(t).unwrap()).collect();
+    assert_eq!(batch.len(), 200);
+    assert_eq!(batch, per_item);
+
+    let encrypted = budget.storage.accounts().unwrap();
+    let per_item: Vec<_> = encrypted.iter().map(|a| budget.decrypt_account(a).unwrap()).collect();
+    assert_eq!(budget.decrypt_accounts(&encrypted).unwrap(), per_item);
+
+    let encrypted = budget.storage.categories().unwrap();
+    let per_item: Vec<_> = encrypted.iter().map(|c| budget.decrypt_category(c).unwrap()).collect();
+    assert_eq!(budget.decrypt_categories(&encrypted).unwrap(), per_item);
+
+    let encrypted = budget.storage.plans().unwrap();
+    let per_item: Vec<_> = encrypted.iter().map(|p| budget.decrypt_plan(p).unwrap()).collect();
+    assert_eq!(budget.decrypt_plans(&encrypted).unwrap(), per_item);
+}
+
+
+#[test]
+fn sync_files_are_streamed() {
+    let (_loc, budget) = testing::budget();
+    let account = budget.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+
+    for i in 0..1000 {
+        budget.add_transaction(&testing::transaction(account, category, -i, at(2024, 5, 1))).unwrap();
+    }
+
+    //
+    // Changelog takes several chunks of encrypted stream
+    //
+
+    let changelog = budget.export_local_changes(&FIRST_AFTER_JANUARY_1970).unwrap();
+    let auth = CryptoBuffer::from(testing::PASSPHRASE);
+
+    let mut timestamp = Cursor::new(Vec::new());
+    let mut instance = Cursor::new(Vec::new());
+    let mut encrypted_changelog = Cursor::new(Vec::new());
+    let mut mac = Cursor::new(Vec::new());
+
+    budget.write_sync_files(&changelog, &mut timestamp, &mut instance, &mut encrypted_changelog, &mut mac, &auth).unwrap();
+    assert!(2 * 64 * 1024 < encrypted_changelog.get_ref().len());
+    mac.rewind().unwrap();
+
+    let (remote_changelog, remote) = budget.read_remote_changelog(&mut timestamp, &mut instance, 
+        &mut encrypted_changelog, &mut mac, &auth).unwrap();
+
+    assert_eq!(remote.map(|(_, instance)| instance), Some(*budget.instance_id()));
+    assert_eq!(remote_changelog.change_counts(), changelog.change_counts());
+    assert_eq!(remote_changelog.transactions.added, changelog.transactions.added);
+}
+
+
+#[test]
+fn unknown_references_are_errors() {
+    let (_loc, budget) = testing::budget();
+    let account = budget.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+
+    let unknown = Id::random();
+    let result = budget.add_transaction(&testing::transaction(unknown, category, -5, at(2024, 5, 1)));
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+
+    assert_eq!(budget.account(unknown).unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(budget.transaction(unknown).unwrap_err().kind(), ErrorKind::NotFound);
+    assert_eq!(budget.remove_transaction(unknown, false, at(2024, 5, 2)).unwrap_err().kind(), ErrorKind::NotFound);
+
+    assert!(budget.transactions().unwrap().is_empty());
+    assert_eq!(budget.account(account).unwrap().balance, 0);
+}
+
+
+#[test]
+fn added_items_return_their_ids() {
+    let (_loc, budget) = testing::budget();
+    let account = budget.add_account(&testing::account("Wallet", 100)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    let plan = budget.add_plan(&testing::plan(category, 500)).unwrap();
+    let transaction = budget.add_transaction(&testing::transaction(account, category, -5, at(2024, 5, 1))).unwrap();
+
+    assert_eq!(budget.account(account).unwrap().name, "Wallet");
+    assert_eq!(budget.category(category).unwrap().name, "Food");
+    assert_eq!(budget.plan(plan).unwrap().amount_limit, 500);
+    assert_eq!(budget.transaction(transaction).unwrap().amount, -5);
+
+    let savings = budget.add_account(&testing::account("Savings", 0)).unwrap();
+    let (income, outcome) = budget.add_transfer(10, account, savings, at(2024, 5, 2)).unwrap();
+    assert_eq!(budget.transaction(outcome).unwrap().account_id, account);
+    assert_eq!(budget.transaction(income).unwrap().account_id, savings);
+}
+
+
+#[test]
+fn diverged_histories_are_merged() {
+    let remote = testing::bare_remote();
+    let other = testing::bare_remote();
+    let (_first_loc, first) = testing::git_budget(Some(&remote.root()));
+    let (_second_loc, second) = testing::git_budget(Some(&remote.root()));
+
+    let add = |budget: &testing::TestBudget<GitSyncEngine>, day: isize| {
+        let account = budget.add_account(&testing::account(&format!("Account {}", day), 0)).unwrap();
+        let category = budget.add_category(&testing::category(&format!("Category {}", day), CategoryType::Outcome)).unwrap();
+        budget.add_transaction(&testing::transaction(account, category, -day, at(2024, 5, day as u32))).unwrap();
+    };
+
+    add(&first, 1);
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    //
+    // First instance pushes on top of the shared history, while the
+    // second one commits on top of the same commit, but pushes to
+    // another remote, hence histories diverge the same way as in a race
+    //
+
+    add(&first, 3);
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    second.add_named_remote("other", other.root().to_str().unwrap()).unwrap();
+    add(&second, 2);
+    second.perform_sync_with("other", testing::PASSPHRASE, None).unwrap();
+
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    //
+    // Remote has a single branch, which name depends on git configuration
+    //
+
+    let repo = git2::Repository::open_bare(remote.root()).unwrap();
+    let head = repo
+        .branches(Some(git2::BranchType::Local)).unwrap()
+        .map(|branch| branch.unwrap().0.get().peel_to_commit().unwrap())
+        .next().unwrap();
+
+    let merges = std::iter::successors(Some(head), |commit| commit.parents().next())
+        .filter(|commit| 1 < commit.parent_count())
+        .count();
+
+    assert_eq!(merges, 1);
+
+    for budget in [&first, &second] {
+        let mut amounts: Vec<_> = budget.transactions().unwrap().iter().map(|t| t.amount).collect();
+        amounts.sort();
+        assert_eq!(amounts, [-3, -2, -1]);
+    }
+}
+
+
+#[test]
+fn local_only_syncs_accumulate_changelog() {
+    let (_loc, budget) = testing::git_budget(None);
+    let account = budget.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+
+    budget.add_transaction(&testing::transaction(account, category, -1, at(2024, 5, 1))).unwrap();
+    let summary = budget.perform_sync(testing::PASSPHRASE, None).unwrap();
+    assert!(summary.local_only);
+    assert!(0 < summary.exported.added);
+
+    budget.add_transaction(&testing::transaction(account, category, -2, at(2024, 5, 2))).unwrap();
+    let summary = budget.perform_sync(testing::PASSPHRASE, None).unwrap();
+    assert!(summary.local_only);
+    assert!(0 < summary.exported.added);
+
+    //
+    // Repository is moved to another machine manually (clone without
+    // remote stands for it), the latest commit contains everything
+    // exported by both synchronizations
+    //
+
+    let repo_path = budget.sync_engine.lock().repository().workdir().unwrap().to_owned();
+    let repo = git2::Repository::open(&repo_path).unwrap();
+    let mut commits = repo.revwalk().unwrap();
+    commits.push_head().unwrap();
+    assert_eq!(commits.count(), 2);
+
+    let (_other_loc, other) = testing::git_budget(Some(&repo_path));
+    other.remove_remote().unwrap();
+
+    let summary = other.perform_sync(testing::PASSPHRASE, None).unwrap();
+    assert!(summary.local_only);
+
+    let mut amounts: Vec<_> = other.transactions().unwrap().iter().map(|t| t.amount).collect();
+    amounts.sort();
+    assert_eq!(amounts, [-2, -1]);
+}
+
+
+#[test]
+fn shrunk_changelog_is_written_up_to_position() {
+    let (_loc, budget) = testing::budget();
+    let account = budget.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+
+    for i in 0..100 {
+        budget.add_transaction(&testing::transaction(account, category, -i, at(2024, 5, 1))).unwrap();
+    }
+
+    let auth = CryptoBuffer::from(testing::PASSPHRASE);
+    let mut timestamp = Cursor::new(Vec::new());
+    let mut instance = Cursor::new(Vec::new());
+    let mut encrypted_changelog = Cursor::new(Vec::new());
+    let mut mac = Cursor::new(Vec::new());
+
+    let changelog = budget.export_local_changes(&FIRST_AFTER_JANUARY_1970).unwrap();
+    budget.write_sync_files(&changelog, &mut timestamp, &mut instance, &mut encrypted_changelog, &mut mac, &auth).unwrap();
+
+    //
+    // Compacted changelog is written over the previous one, 
+    // which remains after the current position
+    //
+
+    let compacted = Changelog::new();
+    budget.write_sync_files(&compacted, &mut timestamp, &mut instance, &mut encrypted_changelog, &mut mac, &auth).unwrap();
+
+    let position = encrypted_changelog.position() as usize;
+    assert!(position < encrypted_changelog.get_ref().len());
+
+    mac.rewind().unwrap();
+    let error = budget.read_remote_changelog(&mut timestamp, &mut instance, 
+        &mut encrypted_changelog.clone(), &mut mac, &auth).err().unwrap();
+
+    assert!(error.to_string().contains(REMOTE_DATA_CORRUPTED.text()));
+
+    encrypted_changelog.get_mut().truncate(position);
+    mac.rewind().unwrap();
+
+    let (remote_changelog, _) = budget.read_remote_changelog(&mut timestamp, &mut instance, 
+        &mut encrypted_changelog, &mut mac, &auth).unwrap();
+
+    assert_eq!(remote_changelog.change_counts(), compacted.change_counts());
+}
+
+
+#[test]
+fn removed_account_referenced_remotely_is_kept() {
+    let remote = testing::bare_remote();
+    let (_first_loc, first) = testing::git_budget(Some(&remote.root()));
+    let (_second_loc, second) = testing::git_budget(Some(&remote.root()));
+
+    let account = first.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = first.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    //
+    // Second instance spends money from the account, that is removed by
+    // the first one concurrently. Removal conflicts with local transaction
+    // and is skipped, then the first instance resurrects the account
+    //
+
+    first.remove_account(account, false, Clock::now()).unwrap();
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    second.add_transaction(&testing::transaction(account, category, -5, at(2024, 5, 1))).unwrap();
+    let summary = second.perform_sync(testing::PASSPHRASE, None).unwrap();
+    assert!(0 < summary.exported.changed);
+
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    for budget in [&first, &second] {
+        assert_eq!(budget.account(account).unwrap().balance, -5);
+        assert_eq!(budget.transactions().unwrap().len(), 1);
+    }
+}
+
+
+#[test]
+fn failed_merge_changes_nothing() {
+    let (_first_loc, first) = testing::budget();
+    let (_second_loc, second) = testing::budget();
+
+    let account = second.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = second.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    let transaction = second.add_transaction(&testing::transaction(account, category, -5, at(2024, 5, 1))).unwrap();
+
+    //
+    // Transaction references an account unknown to both instances,
+    // hence it fails after account and category are already merged
+    //
+
+    let categories = first.categories().unwrap().len();
+
+    let mut changelog = Changelog::new();
+    changelog.accounts.added.push(second.account(account).unwrap());
+    changelog.categories.added.push(second.category(category).unwrap());
+
+    let mut orphan = second.transaction(transaction).unwrap();
+    orphan.account_id = Id::random();
+    changelog.transactions.added.push(orphan);
+
+    let result = first.merge_changes(&changelog, &FIRST_AFTER_JANUARY_1970, &mut Changelog::new());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+
+    assert!(first.accounts().unwrap().is_empty());
+    assert_eq!(first.categories().unwrap().len(), categories);
+    assert!(first.transactions().unwrap().is_empty());
+}
+
+
+/// Reads all files under a given folder recursively.
+fn snapshot(root: &std::path::Path) -> Vec<(std::path::PathBuf, Vec<u8>)> {
+    let mut files = Vec::new();
+    let mut folders = vec![root.to_path_buf()];
+
+    while let Some(folder) = folders.pop() {
+        for entry in std::fs::read_dir(folder).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                folders.push(path);
+            } else {
+                files.push((path.clone(), std::fs::read(path).unwrap()));
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+
+#[test]
+fn wrong_passphrase_changes_nothing() {
+    let (loc, budget) = testing::git_budget(None);
+    let account = budget.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    budget.add_transaction(&testing::transaction(account, category, -1, at(2024, 5, 1))).unwrap();
+    budget.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    budget.add_transaction(&testing::transaction(account, category, -2, at(2024, 5, 2))).unwrap();
+
+    let files = snapshot(&loc.root());
+    let last_sync = budget.sync_engine().last_sync().unwrap();
+    let transactions = budget.transactions().unwrap();
+
+    let error = budget.perform_sync(b"another passphrase", None).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::SyncAuthentication);
+
+    assert_eq!(snapshot(&loc.root()), files);
+    assert_eq!(budget.sync_engine().last_sync().unwrap(), last_sync);
+    assert_eq!(budget.transactions().unwrap(), transactions);
+}
+
+
+#[test]
+fn reset_sync_keeps_local_storage() {
+    let remote = testing::bare_remote();
+    let other = testing::bare_remote();
+    let (_first_loc, first) = testing::git_budget(Some(&remote.root()));
+    let (_second_loc, second) = testing::git_budget(Some(&remote.root()));
+
+    let account = first.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = first.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    first.add_transaction(&testing::transaction(account, category, -1, at(2024, 5, 1))).unwrap();
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    //
+    // Local commit, that is never sent to the remote, and a stale
+    // index lock left by an interrupted checkout are dropped
+    //
+
+    second.add_transaction(&testing::transaction(account, category, -2, at(2024, 5, 2))).unwrap();
+    second.add_named_remote("other", other.root().to_str().unwrap()).unwrap();
+    second.perform_sync_with("other", testing::PASSPHRASE, None).unwrap();
+
+    let repository_path = second.sync_engine().repository().path().to_path_buf();
+    std::fs::write(repository_path.join("index.lock"), b"").unwrap();
+
+    let accounts = second.accounts().unwrap();
+    let transactions = second.transactions().unwrap();
+
+    second.reset_sync().unwrap();
+
+    assert_eq!(second.accounts().unwrap(), accounts);
+    assert_eq!(second.transactions().unwrap(), transactions);
+    assert_eq!(second.sync_engine().last_sync().unwrap(), None);
+
+    let remote_head = git2::Repository::open_bare(remote.root()).unwrap()
+        .refname_to_id("refs/heads/main").unwrap();
+    assert_eq!(second.sync_engine().repository().head().unwrap().target(), Some(remote_head));
+
+    //
+    // Everything local is exported again, but nothing is duplicated
+    //
+
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    for budget in [&first, &second] {
+        let mut amounts: Vec<_> = budget.transactions().unwrap().iter().map(|t| t.amount).collect();
+        amounts.sort();
+        assert_eq!(amounts, [-2, -1]);
+        assert_eq!(budget.accounts().unwrap().len(), 1);
+    }
+}
+
+
+#[test]
+fn item_at_last_sync_is_synced_once() {
+    let (_first_loc, first) = testing::budget();
+    let (_second_loc, second) = testing::budget();
+
+    //
+    // Synchronizations cover half-open intervals (previous, last_sync]
+    // The item added exactly at the boundary belongs to the first one
+    //
+
+    let previous = at(2024, 5, 1);
+    let last_sync = at(2024, 5, 2);
+
+    let mut boundary = testing::account("Boundary", 0);
+    boundary.meta_info.added_timestamp = Some(last_sync);
+    first.add_account(&boundary).unwrap();
+
+    let mut after = testing::account("After", 0);
+    after.meta_info.added_timestamp = Some(last_sync + chrono::Duration::seconds(1));
+    first.add_account(&after).unwrap();
+
+    let names = |changelog: &Changelog| {
+        let mut names: Vec<_> = changelog.accounts.added.iter().map(|account| account.name.clone()).collect();
+        names.sort();
+        names
+    };
+
+    let exported = first.export_local_changes(&previous).unwrap();
+    assert_eq!(names(&exported), ["After", "Boundary"]);
+
+    let merged = second.merge_changes(&exported, &previous, &mut Changelog::new()).unwrap();
+    assert_eq!(merged.added, 2);
+
+    let exported = first.export_local_changes(&last_sync).unwrap();
+    assert_eq!(names(&exported), ["After"]);
+
+    //
+    // Remote changelog is cumulative, hence it still contains the
+    // boundary item, but it is known after the previous synchronization
+    //
+
+    let mut cumulative = first.export_local_changes(&previous).unwrap();
+    cumulative.accounts.added.retain(|account| account.name == "Boundary");
+
+    let merged = second.merge_changes(&cumulative, &last_sync, &mut Changelog::new()).unwrap();
+    assert_eq!(merged.added, 0);
+
+    let mut accounts: Vec<_> = second.accounts().unwrap().into_iter().map(|account| account.name).collect();
+    accounts.sort();
+    assert_eq!(accounts, ["After", "Boundary"]);
+}
+
+
+#[test]
+fn inconsistent_sync_files_are_reported_or_recovered() {
+    let (loc, mut budget) = testing::budget();
+
+    for recovery in [false, true] {
+        budget.config.set_sync_files_recovery(&loc, recovery).unwrap();
+
+        for combination in 0..8 {
+            let sizes = [combination & 1, combination & 2, combination & 4].map(|bit| if bit != 0 { 5 } else { 0 });
+            let mut timestamp = Cursor::new(vec![1u8; sizes[0]]);
+            let mut instance = Cursor::new(vec![1u8; sizes[1]]);
+            let mut changelog = Cursor::new(vec![1u8; sizes[2]]);
+
+            let result = budget.empty_sync_files(&mut timestamp, &mut instance, &mut changelog);
+
+            for cursor in [&timestamp, &instance, &changelog] {
+                assert_eq!(cursor.position(), 0);
+            }
+
+            //
+            // The file, that differs from the other ones, is malformed
+            //
+
+            let (malformed, size) = match sizes {
+                [0, 0, 0] => { assert!(result.unwrap()); continue; },
+                [5, 5, 5] => { assert!(!result.unwrap()); continue; },
+                [size, 5, 5] | [size, 0, 0] => (MALFORMED_TIMESTAMP, size),
+                [5, size, 5] | [0, size, 0] => (MALFORMED_INSTANCES, size),
+                [_, _, size] => (MALFORMED_CHANGELOG, size)
+            };
+
+            if recovery {
+                assert!(result.unwrap());
+            } else {
+                let error = result.unwrap_err();
+                assert_eq!(error.kind(), ErrorKind::Malformed);
+                assert!(error.to_string().contains(malformed.text()));
+                assert!(error.to_string().contains(&format!("size: {}", size)));
+            }
+        }
+    }
+}
+
+
+/// Counts commits reachable from a given reference.
+fn history_length(repository: &git2::Repository, reference: &str) -> usize {
+    let mut walk = repository.revwalk().unwrap();
+    walk.push_ref(reference).unwrap();
+    walk.count()
+}
+
+
+#[test]
+fn sync_history_is_trimmed() {
+    let remote = testing::bare_remote();
+    let (_first_loc, first) = testing::git_budget(Some(&remote.root()));
+    let account = first.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = first.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+
+    for day in 1..=5 {
+        first.add_transaction(&testing::transaction(account, category, -day, at(2024, 5, day as u32))).unwrap();
+        first.perform_sync(testing::PASSPHRASE, None).unwrap();
+    }
+
+    let remote_repository = git2::Repository::open_bare(remote.root()).unwrap();
+    assert_eq!(history_length(&remote_repository, "refs/heads/main"), 5);
+
+    //
+    // libgit2 ignores clone depth for local remotes, hence the clone is
+    // shallow with network remotes only, but it works the same way
+    //
+
+    let (_second_loc, second) = testing::git_budget(Some(&remote.root()));
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+    assert_eq!(second.transactions().unwrap().len(), 5);
+
+    //
+    // Shared history is replaced only if it is allowed explicitly
+    //
+
+    assert!(0 < first.sync_status().unwrap().repository_size.unwrap());
+
+    let error = first.trim_sync_history(false).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Sync);
+
+    //
+    // Changes of the second instance are not received yet
+    //
+
+    let remote_length = history_length(&remote_repository, "refs/heads/main");
+    assert_eq!(first.trim_sync_history(true).unwrap_err().kind(), ErrorKind::Sync);
+    assert_eq!(history_length(&remote_repository, "refs/heads/main"), remote_length);
+
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+    let size = first.sync_status().unwrap().repository_size.unwrap();
+
+    first.trim_sync_history(true).unwrap();
+    assert_eq!(history_length(first.sync_engine().repository(), "HEAD"), 1);
+    assert_eq!(history_length(&remote_repository, "refs/heads/main"), 1);
+    assert!(first.sync_status().unwrap().repository_size.unwrap() < size);
+
+    //
+    // Other instances reset to the new history and keep their data
+    //
+
+    second.reset_sync().unwrap();
+    second.add_transaction(&testing::transaction(account, category, -6, at(2024, 5, 6))).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    for budget in [&first, &second] {
+        assert_eq!(budget.transactions().unwrap().len(), 6);
+        assert_eq!(budget.account(account).unwrap().balance, -21);
+    }
+}
+
+
+#[test]
+fn backup_is_restored_with_configured_kdf_params() {
+    let (_loc, budget) = testing::budget();
+    let account = budget.add_account(&testing::account("Wallet", 100)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    budget.add_transaction(&testing::transaction(account, category, -30, at(2024, 5, 1))).unwrap();
+
+    let mut archive = Vec::new();
+    budget.backup(&mut archive, testing::PASSPHRASE).unwrap();
+
+    //
+    // Cheap parameters of test budgets are written into archive
+    // header, hence restoration does not depend on defaults
+    //
+
+    let (_other_loc, other) = testing::budget();
+    other.restore(archive.as_slice(), testing::PASSPHRASE, false).unwrap();
+
+    assert_eq!(other.account(account).unwrap().balance, 70);
+    assert_eq!(other.category(category).unwrap().name, "Food");
+    assert_eq!(other.transactions().unwrap().len(), 1);
+
+    assert!(other.restore(archive.as_slice(), b"wrong", true).is_err());
+}
+
+
+#[test]
+fn transaction_of_removed_account_is_not_restored() {
+    let (_loc, budget) = testing::budget();
+    let account = budget.add_account(&testing::account("Wallet", 100)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+
+    let transaction = budget.add_transaction(&testing::transaction(account, category, -30, at(2024, 5, 1))).unwrap();
+    budget.remove_transaction(transaction, false, at(2024, 5, 2)).unwrap();
+    budget.remove_account(account, false, at(2024, 5, 3)).unwrap();
+
+    let error = budget.restore_transaction(transaction).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Consistency);
+    assert!(error.to_string().contains(ACCOUNT_IS_REMOVED.text()));
+
+    //
+    // Nothing is changed, hence the transaction
+    // is restored with its account later
+    //
+
+    budget.restore_account(account).unwrap();
+    budget.restore_transaction(transaction).unwrap();
+    assert_eq!(budget.account(account).unwrap().balance, 70);
+}
+
+
+#[test]
+fn joined_instance_matches_synced_one() {
+    let remote = testing::bare_remote();
+    let (_first_loc, first) = testing::git_budget(Some(&remote.root()));
+    let (_second_loc, second) = testing::git_budget(Some(&remote.root()));
+
+    let from = first.add_account(&testing::account("Checking", 100)).unwrap();
+    let to = first.add_account(&testing::account("Savings", 0)).unwrap();
+    let category = first.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    first.add_transaction(&testing::transaction(from, category, -10, at(2024, 5, 1))).unwrap();
+    first.add_transfer(30, from, to, at(2024, 5, 2)).unwrap();
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    //
+    // Joined instance is not initialized, predefined
+    // categories must appear during the first merge
+    //
+
+    let (_joined_loc, joined) = testing::joined_budget(&remote.root());
+
+    let ids = |budget: &testing::TestBudget<GitSyncEngine>| {
+        let mut ids: Vec<_> = budget.categories().unwrap()
+            .into_iter()
+            .map(|category| (category.id.unwrap(), category.name, category.category_type))
+            .collect();
+
+        ids.sort_by_key(|(id, _, _)| *id);
+        ids
+    };
+
+    assert_eq!(ids(&joined).len(), 3);
+    assert_eq!(ids(&joined), ids(&second));
+    assert!(joined.category(MemoryStorage::TRANSFER_INCOME_ID).is_ok());
+    assert!(joined.category(MemoryStorage::TRANSFER_OUTCOME_ID).is_ok());
+
+    assert_eq!(joined.transactions().unwrap().len(), second.transactions().unwrap().len());
+    assert_eq!(joined.account(from).unwrap().balance, 60);
+    assert_eq!(joined.account(to).unwrap().balance, 30);
+}
+
+
+#[test]
+fn plan_progress_ignores_incomes() {
+    let (_loc, budget) = testing::budget();
+
+    let account = budget.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    let plan = budget.add_plan(&testing::plan(category, 100)).unwrap();
+
+    //
+    // Refund in plan's category must not be counted as spent
+    //
+
+    budget.add_transaction(&testing::transaction(account, category, -30, at(2024, 5, 10))).unwrap();
+    budget.add_transaction(&testing::transaction(account, category, 20, at(2024, 5, 12))).unwrap();
+
+    let progress = budget.plan_progress(plan, at(2024, 5, 1), at(2024, 6, 1)).unwrap();
+    assert_eq!(progress.spent, 30);
+    assert_eq!(progress.remaining, 70);
+
+    let status = budget.plan_status(plan, at(2024, 5, 15)).unwrap();
+    assert_eq!(status.spent, 30);
+}
+
+
+#[cfg(feature = "tz")]
+#[test]
+fn plan_periods_are_bounded_in_timezone() {
+    let (loc, mut budget) = testing::budget();
+    budget.update_settings(&loc, |settings| settings.set_timezone(Some("America/New_York"))).unwrap();
+
+    let account = budget.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    let plan = budget.add_plan(&testing::plan(category, 100)).unwrap();
+
+    //
+    // Late evening of May 31 in New York is June 1 in UTC, hence
+    // the transaction belongs to May plan period
+    //
+
+    let evening = at(2024, 6, 1) - chrono::Duration::hours(10);
+    budget.add_transaction(&testing::transaction(account, category, -30, evening)).unwrap();
+
+    let may = budget.plan_status(plan, at(2024, 5, 15)).unwrap();
+    assert_eq!(may.period_end, at(2024, 6, 1) - chrono::Duration::hours(8));
+    assert_eq!(may.spent, 30);
+
+    let june = budget.plan_status(plan, at(2024, 6, 15)).unwrap();
+    assert_eq!(june.period_start, may.period_end);
+    assert_eq!(june.spent, 0);
+}
+
+
+#[test]
+fn budget_is_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Budget<PasswordCryptoEngine, GitSyncEngine, DbStorage>>();
+    assert_send_sync::<Budget<PasswordCryptoEngine, DirSyncEngine, MemoryStorage>>();
+    assert_send_sync::<testing::TestBudget<GitSyncEngine>>();
+
+    #[cfg(feature = "gpg")]
+    assert_send_sync::<Budget<crate::crypto::GpgCryptoEngine, GitSyncEngine, DbStorage>>();
+
+    #[cfg(feature = "async")]
+    assert_send_sync::<std::sync::Arc<Budget<PasswordCryptoEngine, GitSyncEngine, DbStorage>>>();
+
+    #[cfg(all(feature = "async", feature = "gpg"))]
+    assert_send_sync::<crate::core::AsyncBudget<crate::crypto::GpgCryptoEngine, GitSyncEngine, DbStorage>>();
+
+    //
+    // Queries observe either none or all of transactions
+    // added by another thread within a storage transaction
+    //
+
+    let (_loc, budget) = testing::budget();
+    let account = budget.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = budget.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            for _ in 0..20 {
+                budget.storage.with_transaction(|_| {
+                    budget.add_transaction(&testing::transaction(account, category, -1, at(2024, 5, 1)))?;
+                    budget.add_transaction(&testing::transaction(account, category, -1, at(2024, 5, 2)))
+                }).unwrap();
+            }
+        });
+
+        scope.spawn(|| {
+            for _ in 0..20 {
+                assert_eq!(budget.transactions().unwrap().len() % 2, 0);
+            }
+        });
+    });
+
+    assert_eq!(budget.account(account).unwrap().balance, -40);
+}
+
+
+#[test]
+fn remote_change_of_removed_recurring_is_skipped() {
+    let remote = testing::bare_remote();
+    let (_first_loc, first) = testing::git_budget(Some(&remote.root()));
+    let (_second_loc, second) = testing::git_budget(Some(&remote.root()));
+
+    let account = first.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = first.add_category(&testing::category("Rent", CategoryType::Outcome)).unwrap();
+    let recurring = first.add_recurring(&testing::recurring(account, category, -10, at(2024, 5, 1))).unwrap();
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    //
+    // Second instance changes recurring transaction, while the first
+    // one removes it together with its account and category
+    //
+
+    let mut changed = second.recurring(recurring).unwrap();
+    changed.amount = -20;
+    changed.meta_info.changed_timestamp = None;
+    second.update_recurring(&changed).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    first.remove_recurring(recurring, Clock::now()).unwrap();
+    first.remove_account(account, false, Clock::now()).unwrap();
+    first.remove_category(category, Clock::now()).unwrap();
+
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    for budget in [&first, &second] {
+        assert!(budget.recurrings().unwrap().is_empty());
+        assert_eq!(budget.account(account).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    //
+    // Further synchronizations are not blocked by the skipped change
+    //
+
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+}
+
+
+#[test]
+fn remote_transactions_are_merged_into_archived_account() {
+    let remote = testing::bare_remote();
+    let (_first_loc, first) = testing::git_budget(Some(&remote.root()));
+    let (_second_loc, second) = testing::git_budget(Some(&remote.root()));
+
+    let account = first.add_account(&testing::account("Wallet", 0)).unwrap();
+    let category = first.add_category(&testing::category("Food", CategoryType::Outcome)).unwrap();
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    //
+    // First instance archives the account, while the second one
+    // still spends money from it
+    //
+
+    first.archive_account(account, Clock::now()).unwrap();
+    let error = first.add_transaction(&testing::transaction(account, category, -1, at(2024, 5, 1))).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Consistency);
+
+    second.add_transaction(&testing::transaction(account, category, -5, at(2024, 5, 1))).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    first.perform_sync(testing::PASSPHRASE, None).unwrap();
+    second.perform_sync(testing::PASSPHRASE, None).unwrap();
+
+    for budget in [&first, &second] {
+        let archived = budget.accounts_including_archived().unwrap();
+        assert_eq!(archived.len(), 1);
+        assert!(archived[0].archived);
+        assert_eq!(archived[0].balance, -5);
+        assert_eq!(budget.transactions().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,53 @@
+use std::io::{Read, Write};
+
+use crate::error::{Result, Error};
+use super::{MALFORMED_SYNC_BUNDLE, UNSUPPORTED_SYNC_BUNDLE_VERSION};
+
+
+/// Magic bytes at the beginning of synchronization bundle.
+const BUNDLE_MAGIC: &[u8; 4] = b"BDSB";
+
+/// Current version of synchronization bundle format.
+const BUNDLE_VERSION: u8 = 1;
+
+
+/// Writes bundle header: magic bytes and format version.
+/// 
+/// * `writer` - destination of bundle
+pub(crate) fn write_header<W: Write>(writer: &mut W) -> Result<()> {
+    writer.write_all(BUNDLE_MAGIC)?;
+    writer.write_all(&[BUNDLE_VERSION])?;
+
+    Ok(())
+}
+
+
+/// Reads and validates bundle header.
+/// 
+/// * `reader` - source of bundle
+pub(crate) fn read_header<R: Read>(reader: &mut R) -> Result<()> {
+    let magic = read_part(reader, BUNDLE_MAGIC.len())?;
+    if magic != BUNDLE_MAGIC {
+        return Err(Error::new(MALFORMED_SYNC_BUNDLE));
+    }
+
+    let version = read_part(reader, 1)?;
+    if version[0] != BUNDLE_VERSION {
+        return Err(Error::new_with_extra(UNSUPPORTED_SYNC_BUNDLE_VERSION, version[0].to_string()));
+    }
+
+    Ok(())
+}
+
+
+/// Reads a fixed-size part of bundle. Truncated bundle is malformed.
+/// 
+/// * `reader` - source of bundle
+/// * `size` - size of part
+pub(crate) fn read_part<R: Read>(reader: &mut R, size: usize) -> Result<Vec<u8>> {
+    let mut part = vec![0u8; size];
+    reader.read_exact(&mut part)
+        .map_err(|_| Error::new(MALFORMED_SYNC_BUNDLE))?;
+
+    Ok(part)
+}
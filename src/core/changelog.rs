@@ -1,7 +1,19 @@
 use serde::{Serialize, Deserialize};
 
 use crate::error::{Result, Error};
-use crate::storage::{Transaction, Account, Category, Plan};
+use crate::datetime::Timestamp;
+use crate::storage::{Transaction, Account, Category, Plan, RecurringTransaction, MetaInfo};
+use super::report::ChangeCounts;
+use super::UNSUPPORTED_CHANGELOG_VERSION;
+
+
+/// Major version of changelog format. Changelogs with another
+/// major version cannot be read at all.
+const CHANGELOG_MAJOR_VERSION: u16 = 1;
+
+/// Minor version of changelog format. Newer minor versions can only
+/// add fields, that are skipped by older versions.
+const CHANGELOG_MINOR_VERSION: u16 = 0;
 
 
 /// Simple changelog representation for some items.
@@ -26,6 +38,45 @@ impl<T> SimpleChangelog<T> {
             removed: Vec::new()
         }
     }
+
+    fn compact<F>(&mut self, horizon: &Timestamp, meta_info: F) -> usize
+    where
+        F: Fn(&T) -> &MetaInfo
+    {
+        let initial_size = self.added.len() + self.changed.len() + self.removed.len();
+
+        //
+        // Entries without a timestamp are kept, since
+        // I cannot tell if they are older than horizon
+        //
+
+        self.added.retain(|item| meta_info(item).added_timestamp.is_none_or(|added| added >= *horizon));
+        self.changed.retain(|item| meta_info(item).changed_timestamp.is_none_or(|changed| changed >= *horizon));
+        self.removed.retain(|item| meta_info(item).removed_timestamp.is_none_or(|removed| removed >= *horizon));
+
+        initial_size - (self.added.len() + self.changed.len() + self.removed.len())
+    }
+}
+
+
+impl<T> Default for SimpleChangelog<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/// Versioned envelope of serialized changelog.
+///
+/// Changelogs written before versioning have no envelope, they are
+/// considered to have version 0.
+#[derive(Serialize, Deserialize)]
+struct ChangelogEnvelope {
+    /// Major version in high 16 bits and minor one in low 16 bits.
+    version: u32,
+
+    /// Serialized changelog.
+    payload: Vec<u8>,
 }
 
 
@@ -43,6 +94,15 @@ pub(crate) struct Changelog {
 
     /// Plans changelog.
     pub plans: SimpleChangelog<Plan>,
+
+    /// Recurring transactions changelog (absent in older changelogs).
+    #[serde(default)]
+    pub recurrings: SimpleChangelog<RecurringTransaction>,
+
+    /// Changes made before this moment are dropped from changelog
+    /// (absent in older and never compacted changelogs).
+    #[serde(default)]
+    pub horizon: Option<Timestamp>,
 }
 
 
@@ -53,7 +113,9 @@ impl Changelog {
             accounts: SimpleChangelog::new(),
             categories: SimpleChangelog::new(),
             transactions: SimpleChangelog::new(),
-            plans: SimpleChangelog::new()
+            plans: SimpleChangelog::new(),
+            recurrings: SimpleChangelog::new(),
+            horizon: None
         }
     }
 
@@ -61,7 +123,30 @@ impl Changelog {
     /// 
     /// * `binary_changelog` - binary changelog representation
     pub(crate) fn from_slice(binary_changelog: &[u8]) -> Result<Self> {
-        flexbuffers::from_slice(binary_changelog)
+        //
+        // Legacy changelog is a serialized structure itself,
+        // hence it cannot be parsed as an envelope
+        //
+
+        let envelope: ChangelogEnvelope = match flexbuffers::from_slice(binary_changelog) {
+            Ok(envelope) => envelope,
+            Err(_) => return flexbuffers::from_slice(binary_changelog).map_err(Error::from)
+        };
+
+        //
+        // Unknown fields are skipped during deserialization, hence
+        // changelogs of newer minor versions are read as usual
+        //
+
+        let major = (envelope.version >> u16::BITS) as u16;
+        let minor = envelope.version as u16;
+
+        if major != CHANGELOG_MAJOR_VERSION {
+            return Err(Error::new_with_extra(UNSUPPORTED_CHANGELOG_VERSION, format!("changelog version: {}.{}, supported: {}.{}", 
+                major, minor, CHANGELOG_MAJOR_VERSION, CHANGELOG_MINOR_VERSION)));
+        }
+
+        flexbuffers::from_slice(&envelope.payload)
             .map_err(Error::from)
     }
 
@@ -85,12 +170,222 @@ impl Changelog {
         self.plans.changed.append(&mut changelog.plans.changed);
         self.plans.removed.append(&mut changelog.plans.removed);
 
+        self.recurrings.added.append(&mut changelog.recurrings.added);
+        self.recurrings.changed.append(&mut changelog.recurrings.changed);
+        self.recurrings.removed.append(&mut changelog.recurrings.removed);
+
         Ok(())
     }
 
+    /// Drops changes made before a horizon. Horizon is recorded only
+    /// if something is dropped, and it never moves back.
+    /// 
+    /// * `horizon` - moment, changes before which are dropped
+    pub(crate) fn compact(&mut self, horizon: Timestamp) {
+        if self.horizon.is_some_and(|current| horizon <= current) {
+            return;
+        }
+
+        let dropped = self.accounts.compact(&horizon, |account| &account.meta_info) + 
+            self.categories.compact(&horizon, |category| &category.meta_info) + 
+            self.transactions.compact(&horizon, |transaction| &transaction.meta_info) + 
+            self.plans.compact(&horizon, |plan| &plan.meta_info) + 
+            self.recurrings.compact(&horizon, |recurring| &recurring.meta_info);
+
+        if 0 < dropped {
+            self.horizon = Some(horizon);
+        }
+    }
+
+    /// Returns total numbers of added, changed and removed items.
+    pub(crate) fn change_counts(&self) -> ChangeCounts {
+        ChangeCounts {
+            added: self.accounts.added.len() + self.categories.added.len() + self.transactions.added.len() + 
+                self.plans.added.len() + self.recurrings.added.len(),
+            changed: self.accounts.changed.len() + self.categories.changed.len() + self.transactions.changed.len() + 
+                self.plans.changed.len() + self.recurrings.changed.len(),
+            removed: self.accounts.removed.len() + self.categories.removed.len() + self.transactions.removed.len() + 
+                self.plans.removed.len() + self.recurrings.removed.len()
+        }
+    }
+
     /// Converts current changelog into a binary representation.
     pub(crate) fn to_vec(&self) -> Result<Vec<u8>> {
-        flexbuffers::to_vec(self)
+        let envelope = ChangelogEnvelope {
+            version: (CHANGELOG_MAJOR_VERSION as u32) << u16::BITS | CHANGELOG_MINOR_VERSION as u32,
+            payload: flexbuffers::to_vec(self)?
+        };
+
+        flexbuffers::to_vec(&envelope)
             .map_err(Error::from)
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{Id, AccountType, CategoryType, PlanPeriod, RecurrencePeriod};
+    use crate::testing::at;
+    use super::*;
+
+    //
+    // Fixtures are captured once and must never be regenerated, unless
+    // format is changed intentionally. Otherwise they don't catch
+    // accidental changes of the format
+    //
+
+    const CURRENT_FIXTURE: &[u8] = include_bytes!("../../tests/fixtures/changelog-1.0.bin");
+    const LEGACY_FIXTURE: &[u8] = include_bytes!("../../tests/fixtures/changelog-0.bin");
+    const NEWER_MINOR_FIXTURE: &[u8] = include_bytes!("../../tests/fixtures/changelog-1.1.bin");
+    const NEWER_MAJOR_FIXTURE: &[u8] = include_bytes!("../../tests/fixtures/changelog-2.0.bin");
+
+    /// Changelog without envelope, that was written before versioning.
+    #[derive(Serialize)]
+    struct LegacyChangelog {
+        accounts: SimpleChangelog<Account>,
+        categories: SimpleChangelog<Category>,
+        transactions: SimpleChangelog<Transaction>,
+        plans: SimpleChangelog<Plan>,
+    }
+
+    /// Changelog of a newer minor version with an unknown field.
+    #[derive(Serialize)]
+    struct NewerChangelog {
+        #[serde(flatten)]
+        changelog: Changelog,
+        currencies: Vec<String>,
+    }
+
+    fn meta_info(day: u32) -> MetaInfo {
+        MetaInfo {
+            origin: Some([0x42; 16]),
+            ..MetaInfo::new(Some(at(2024, 5, day)), None, None)
+        }
+    }
+
+    fn id(byte: u8) -> Id {
+        Id::from_bytes([byte; 16])
+    }
+
+    fn fixture() -> Changelog {
+        let mut changelog = Changelog::new();
+
+        changelog.accounts.added.push(Account {
+            id: Some(id(1)),
+            name: "Wallet".to_owned(),
+            balance: 100,
+            initial_balance: 150,
+            currency: "EUR".to_owned(),
+            archived: false,
+            account_type: AccountType::Cash,
+            meta_info: meta_info(1)
+        });
+
+        changelog.categories.added.push(Category {
+            id: Some(id(2)),
+            name: "Food".to_owned(),
+            category_type: CategoryType::Outcome,
+            meta_info: meta_info(1)
+        });
+
+        changelog.transactions.added.push(Transaction {
+            id: Some(id(3)),
+            timestamp: at(2024, 5, 2),
+            description: "Groceries".to_owned(),
+            account_id: id(1),
+            category_id: id(2),
+            amount: -50,
+            transfer_id: None,
+            tags: vec!["weekly".to_owned()],
+            pending: true,
+            meta_info: meta_info(2)
+        });
+
+        changelog.plans.changed.push(Plan {
+            id: Some(id(4)),
+            category_id: id(2),
+            name: "Food limit".to_owned(),
+            amount_limit: 500,
+            period: PlanPeriod::Quarterly,
+            rollover: true,
+            meta_info: MetaInfo { changed_timestamp: Some(at(2024, 5, 3)), ..meta_info(1) }
+        });
+
+        changelog.recurrings.removed.push(RecurringTransaction {
+            id: Some(id(5)),
+            description: "Rent".to_owned(),
+            account_id: id(1),
+            category_id: id(2),
+            amount: -700,
+            period: RecurrencePeriod::Monthly,
+            start_timestamp: at(2024, 1, 1),
+            end_timestamp: None,
+            materialized_timestamp: Some(at(2024, 5, 1)),
+            meta_info: MetaInfo { removed_timestamp: Some(at(2024, 5, 4)), ..meta_info(1) }
+        });
+
+        changelog.horizon = Some(at(2024, 4, 1));
+        changelog
+    }
+
+    fn envelope(major: u16, minor: u16, payload: Vec<u8>) -> Vec<u8> {
+        let envelope = ChangelogEnvelope {
+            version: (major as u32) << u16::BITS | minor as u32,
+            payload
+        };
+
+        flexbuffers::to_vec(&envelope).unwrap()
+    }
+
+    /// Captures fixtures, run it explicitly with `--ignored`.
+    #[test]
+    #[ignore]
+    fn capture_fixtures() {
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        std::fs::create_dir_all(&fixtures).unwrap();
+
+        let Changelog { accounts, categories, transactions, plans, .. } = fixture();
+        let legacy = LegacyChangelog { accounts, categories, transactions, plans };
+        let newer = NewerChangelog { changelog: fixture(), currencies: vec!["EUR".to_owned()] };
+
+        std::fs::write(fixtures.join("changelog-1.0.bin"), fixture().to_vec().unwrap()).unwrap();
+        std::fs::write(fixtures.join("changelog-0.bin"), flexbuffers::to_vec(&legacy).unwrap()).unwrap();
+        std::fs::write(fixtures.join("changelog-1.1.bin"), envelope(1, 1, flexbuffers::to_vec(&newer).unwrap())).unwrap();
+        std::fs::write(fixtures.join("changelog-2.0.bin"), envelope(2, 0, flexbuffers::to_vec(fixture()).unwrap())).unwrap();
+    }
+
+    #[test]
+    fn current_format_round_trip() {
+        let changelog = Changelog::from_slice(CURRENT_FIXTURE).unwrap();
+        assert_eq!(changelog.to_vec().unwrap(), CURRENT_FIXTURE);
+        assert_eq!(changelog.to_vec().unwrap(), fixture().to_vec().unwrap());
+
+        assert_eq!(changelog.accounts.added[0].name, "Wallet");
+        assert_eq!(changelog.transactions.added[0].tags, ["weekly"]);
+        assert_eq!(changelog.recurrings.removed[0].amount, -700);
+        assert_eq!(changelog.horizon, Some(at(2024, 4, 1)));
+    }
+
+    #[test]
+    fn legacy_format_is_read() {
+        let changelog = Changelog::from_slice(LEGACY_FIXTURE).unwrap();
+
+        let mut expected = fixture();
+        expected.recurrings = SimpleChangelog::new();
+        expected.horizon = None;
+
+        assert_eq!(changelog.to_vec().unwrap(), expected.to_vec().unwrap());
+    }
+
+    #[test]
+    fn newer_minor_version_is_read() {
+        let changelog = Changelog::from_slice(NEWER_MINOR_FIXTURE).unwrap();
+        assert_eq!(changelog.to_vec().unwrap(), CURRENT_FIXTURE);
+    }
+
+    #[test]
+    fn newer_major_version_is_rejected() {
+        let error = Changelog::from_slice(NEWER_MAJOR_FIXTURE).err().unwrap();
+        assert!(error.to_string().contains(UNSUPPORTED_CHANGELOG_VERSION.text()));
+    }
+}
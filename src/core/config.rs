@@ -1,30 +1,117 @@
-use crate::error::Result;
+use std::collections::HashMap;
+use std::io::Write;
+
+use serde::{Serialize, Deserialize};
+
+use crate::error::{Result, Error};
 use crate::location::Location;
-use crate::crypto::{KeyIdentifier, CryptoEngine};
+use crate::crypto::{KeyIdentifier, CryptoEngine, KdfParams};
+use crate::datetime::Timestamp;
+use super::settings::Settings;
+use super::{MALFORMED_CHANGELOG_RETENTION, MALFORMED_INSTANCE_NAME, CONFIG_CORRUPTED, UNSUPPORTED_CONFIG_VERSION};
+
+
+/// File with configuration document name.
+const CONFIG_FILE: &str = "config.json";
 
+/// Current version of configuration document.
+const CONFIG_VERSION: u32 = 1;
 
-/// File with key identifier name.
+/// Legacy file with key identifier name.
 const KEY_IDENTIFIER_FILE: &str = "key";
 
-/// File with instance identifier name.
+/// Separator of key identifiers in key identifier file.
+const KEY_IDENTIFIERS_SEPARATOR: &str = "\n";
+
+/// File with pinned key fingerprints name.
+const KEY_FINGERPRINTS_FILE: &str = "fingerprints";
+
+/// Separator of key identifier and its fingerprint in fingerprints file.
+const KEY_FINGERPRINT_SEPARATOR: &str = "\t";
+
+/// Legacy file with instance identifier name.
 const INSTANCE_IDENTIFIER_FILE: &str = "instance";
 
+/// Extension of temporary files, that are renamed after writing.
+const TEMPORARY_EXTENSION: &str = "tmp";
+
+/// File with human-readable instance name.
+const INSTANCE_NAME_FILE: &str = "instance-name";
+
+/// Maximal length of instance name in bytes.
+const MAX_INSTANCE_NAME_LENGTH: usize = 255;
+
+/// Legacy file with default currency name.
+const CURRENCY_FILE: &str = "currency";
+
+/// File with key derivation parameters name.
+const KDF_PARAMS_FILE: &str = "kdf";
+
+/// File with retention period of synchronized changelog name.
+const CHANGELOG_RETENTION_FILE: &str = "retention";
+
+/// Retention period of synchronized changelog in days used if no period is configured.
+const DEFAULT_CHANGELOG_RETENTION_DAYS: i64 = 90;
+
+/// File with recovery mode of synchronized files name.
+const SYNC_FILES_RECOVERY_FILE: &str = "recovery";
+
+/// File with timestamps of imported synchronization bundles name.
+const BUNDLE_IMPORTS_FILE: &str = "bundles";
+
+/// Separator of instance identifier and timestamp in bundle imports file.
+const BUNDLE_IMPORT_SEPARATOR: &str = "\t";
+
 
 /// Type of local bdgt instance identifier.
 pub type InstanceId = uuid::Uuid;
 
 
+/// Serialized configuration document.
+#[derive(Serialize, Deserialize)]
+struct ConfigDocument {
+    version: u32,
+    key_ids: Vec<String>,
+    instance_id: String,
+    #[serde(default)]
+    settings: Settings,
+}
+
+
 /// App's instance configuration, contains long-term info.
 pub struct Config<Ce>
 where
     Ce: CryptoEngine
 {
-    /// Identifier of a key used to encrypt and decrypt sensitive data.
-    /// Id is represented in a native format for concrete cryptographic engine.
-    key_id: Ce::KeyId,
+    /// Identifiers of keys used to encrypt and decrypt sensitive data.
+    /// Ids are represented in a native format for concrete cryptographic engine.
+    key_ids: Vec<Ce::KeyId>,
+
+    /// Pinned fingerprints of keys by their identifiers.
+    fingerprints: HashMap<String, String>,
 
     /// Identifier of a local bdgt instance.
     instance_id: InstanceId,
+
+    /// Human-readable name of a local bdgt instance.
+    instance_name: Option<String>,
+
+    /// User preferences.
+    settings: Settings,
+
+    /// Key derivation parameters used for synchronization.
+    kdf_params: KdfParams,
+
+    /// Timestamps of the last imported synchronization bundles by their instances.
+    bundle_imports: HashMap<InstanceId, Timestamp>,
+
+    /// Changes older than this period are dropped from synchronized
+    /// changelog, `None` means that changelog is never compacted.
+    changelog_retention: Option<chrono::Duration>,
+
+    /// Whether inconsistent synchronized files are regenerated
+    /// instead of failing synchronization.
+    sync_files_recovery: bool,
 }
 
 
@@ -37,22 +124,120 @@ where
     /// 
     /// * `loc` - storage location provider
     pub fn open<L: Location>(loc: &L) -> Result<Self> {
-        let raw_id = std::fs::read_to_string(Self::key_file(loc))?;
-        
-        let instance_id = std::fs::read(Self::instance_file(loc))?;
-        let instance_id = uuid::Uuid::from_slice(&instance_id)?;
+        //
+        // Instances created before configuration document was
+        // introduced are migrated on the first opening
+        //
+
+        let document = match Self::read_document(loc)? {
+            Some(document) => document,
+            None => Self::migrate_legacy_files(loc)?
+        };
+
+        let key_ids: Vec<Ce::KeyId> = document.key_ids
+            .iter()
+            .map(String::as_str)
+            .map(Ce::KeyId::from_str)
+            .collect();
+
+        if key_ids.is_empty() {
+            return Err(Error::new_with_extra(CONFIG_CORRUPTED, "no key identifiers"));
+        }
+
+        let instance_id = uuid::Uuid::parse_str(&document.instance_id)
+            .map_err(|error| Error::new_with_extra(CONFIG_CORRUPTED, format!("instance identifier: {}", error)))?;
+
+        let settings = document.settings;
+        settings.validate()
+            .map_err(|error| Error::new_with_extra(CONFIG_CORRUPTED, error.to_string()))?;
+
+        //
+        // Instance has no name unless it is set explicitly
+        //
+
+        let instance_name = match std::fs::read_to_string(Self::instance_name_file(loc)) {
+            Ok(instance_name) => Some(instance_name.trim().to_owned()).filter(|name| !name.is_empty()),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error.into())
+        };
+
+        //
+        // Instances created before key pinning was introduced 
+        // have no fingerprints file, hence no keys are pinned
+        //
+
+        let fingerprints = match std::fs::read_to_string(Self::fingerprints_file(loc)) {
+            Ok(raw_fingerprints) => Self::parse_fingerprints(&raw_fingerprints),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(error.into())
+        };
+
+        let kdf_params = match std::fs::read(Self::kdf_params_file(loc)) {
+            Ok(kdf_params) => KdfParams::from_bytes(&kdf_params)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => KdfParams::default(),
+            Err(error) => return Err(error.into())
+        };
+
+        //
+        // Retention period is stored in seconds, non-positive value
+        // disables compaction. Default one is used if there is no file
+        //
+
+        let changelog_retention = match std::fs::read_to_string(Self::changelog_retention_file(loc)) {
+            Ok(raw_retention) => Self::parse_changelog_retention(&raw_retention)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Some(chrono::Duration::days(DEFAULT_CHANGELOG_RETENTION_DAYS)),
+            Err(error) => return Err(error.into())
+        };
+
+        //
+        // Recovery is disabled unless it is enabled explicitly
+        //
+
+        let sync_files_recovery = match std::fs::read_to_string(Self::sync_files_recovery_file(loc)) {
+            Ok(raw_recovery) => raw_recovery.trim() == "1",
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => false,
+            Err(error) => return Err(error.into())
+        };
+
+        //
+        // No bundles are imported if there is no file
+        //
+
+        let bundle_imports = match std::fs::read_to_string(Self::bundle_imports_file(loc)) {
+            Ok(raw_bundle_imports) => Self::parse_bundle_imports(&raw_bundle_imports),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(error.into())
+        };
 
         Ok(Config { 
-            key_id: Ce::KeyId::from_str(raw_id.as_str()),
-            instance_id: instance_id
+            key_ids,
+            fingerprints,
+            instance_id,
+            instance_name,
+            settings,
+            kdf_params,
+            bundle_imports,
+            changelog_retention,
+            sync_files_recovery
         })
     }
 
+    /// Checks if configuration is created at the location. Configuration
+    /// can still be corrupted, that is reported by [`Config::open`].
+    /// 
+    /// * `loc` - storage location provider
+    pub fn is_initialized<L: Location>(loc: &L) -> bool {
+        Self::config_file(loc).exists() || Self::key_file(loc).exists() || Self::instance_file(loc).exists()
+    }
+
     /// Creates a new storage and then loads configuration.
     /// 
+    /// Fingerprints of keys, that can be looked up by the engine, are pinned.
+    /// 
     /// * `loc` - storage location provider
-    /// * `key_id` - key identifier
-    pub fn create<L: Location>(loc: &L, key_id: &Ce::KeyId) -> Result<Self> {
+    /// * `crypto_engine` - cryptographic engine used to resolve keys
+    /// * `key_ids` - key identifiers
+    pub fn create<L: Location>(loc: &L, crypto_engine: &Ce, key_ids: &[Ce::KeyId]) -> Result<Self> {
         //
         // Check is root location exists and create it if necessary
         //
@@ -60,28 +245,316 @@ where
         loc.create_if_absent()?;
 
         //
-        // Save key into a file, generate new instance identifier,
-        // and then just open config :)
+        // Save keys and new instance identifier into a single
+        // document, and then just open config :)
+        //
+
+        let raw_ids: Vec<String> = key_ids
+            .iter()
+            .map(KeyIdentifier::as_string)
+            .collect();
+
+        Self::write_document(loc, &raw_ids, &Self::new_instance(), &Settings::default())?;
+
+        //
+        // Keys without local secret part cannot be looked up, 
+        // they are not pinned
         //
 
-        std::fs::write(Self::key_file(loc), 
-            key_id.as_string())?;
+        let fingerprints: HashMap<String, String> = key_ids
+            .iter()
+            .filter_map(|key_id| {
+                let key = crypto_engine.lookup_key(key_id).ok()?;
+                let fingerprint = crypto_engine.key_fingerprint(&key)?;
+
+                Some((key_id.as_string(), fingerprint))
+            })
+            .collect();
 
-        std::fs::write(Self::instance_file(loc), 
-            Self::new_instance())?;
+        Self::write_file(Self::fingerprints_file(loc), 
+            Self::format_fingerprints(&fingerprints))?;
 
         Self::open(loc)
     }
 
-    /// Obtain the stored key identifier.
-    pub fn key_id(&self) -> &Ce::KeyId {
-        &self.key_id
+    /// Obtain the stored key identifiers.
+    pub fn key_ids(&self) -> &[Ce::KeyId] {
+        &self.key_ids
+    }
+
+    /// Set and save the key identifiers.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `key_ids` - key identifiers
+    pub fn set_key_ids<L: Location>(&mut self, loc: &L, key_ids: &[Ce::KeyId]) -> Result<()> {
+        let raw_ids: Vec<String> = key_ids
+            .iter()
+            .map(KeyIdentifier::as_string)
+            .collect();
+
+        Self::write_document(loc, &raw_ids, &self.instance_id, &self.settings)?;
+
+        self.key_ids = raw_ids
+            .iter()
+            .map(String::as_str)
+            .map(Ce::KeyId::from_str)
+            .collect();
+
+        //
+        // Fingerprints of removed keys are not needed anymore
+        //
+
+        self.fingerprints
+            .retain(|key_id, _| raw_ids.contains(key_id));
+
+        Self::write_file(Self::fingerprints_file(loc), 
+            Self::format_fingerprints(&self.fingerprints))?;
+
+        Ok(())
+    }
+
+    /// Obtain the pinned fingerprint of a key, if it is pinned.
+    /// 
+    /// * `key_id` - key identifier
+    pub fn pinned_fingerprint(&self, key_id: &Ce::KeyId) -> Option<&str> {
+        self.fingerprints
+            .get(&key_id.as_string())
+            .map(String::as_str)
+    }
+
+    /// Pin and save the fingerprint of a key, that is currently
+    /// available under an identifier.
+    /// 
+    /// Used when a key is replaced legitimately, e.g. after expiration.
+    /// If the key has no fingerprint, it gets unpinned.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `crypto_engine` - cryptographic engine used to resolve key
+    /// * `key_id` - key identifier
+    pub fn repin_key<L: Location>(&mut self, loc: &L, crypto_engine: &Ce, key_id: &Ce::KeyId) -> Result<()> {
+        let key = crypto_engine.lookup_key(key_id)?;
+
+        match crypto_engine.key_fingerprint(&key) {
+            Some(fingerprint) => self.fingerprints.insert(key_id.as_string(), fingerprint),
+            None => self.fingerprints.remove(&key_id.as_string())
+        };
+
+        Self::write_file(Self::fingerprints_file(loc), 
+            Self::format_fingerprints(&self.fingerprints))?;
+
+        Ok(())
+    }
+
+    /// Add and save a key identifier. Nothing happens if it is already stored.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `key_id` - key identifier
+    pub fn add_key_id<L: Location>(&mut self, loc: &L, key_id: &Ce::KeyId) -> Result<()> {
+        let raw_id = key_id.as_string();
+        if self.key_ids.iter().any(|id| id.as_string() == raw_id) {
+            return Ok(());
+        }
+
+        let mut raw_ids: Vec<String> = self.key_ids
+            .iter()
+            .map(KeyIdentifier::as_string)
+            .collect();
+
+        raw_ids.push(raw_id);
+
+        Self::write_document(loc, &raw_ids, &self.instance_id, &self.settings)?;
+        self.key_ids.push(Ce::KeyId::from_str(&key_id.as_string()));
+
+        Ok(())
     }
 
     /// Obtain the stored instance identifier.
     pub fn instance_id(&self) -> &InstanceId {
         &self.instance_id
     }
+
+    /// Generate and save a new instance identifier.
+    /// 
+    /// * `loc` - storage location provider
+    pub fn regenerate_instance_id<L: Location>(&mut self, loc: &L) -> Result<InstanceId> {
+        let instance_id = Self::new_instance();
+
+        Self::write_document(loc, &self.raw_key_ids(), &instance_id, &self.settings)?;
+        self.instance_id = instance_id;
+
+        Ok(instance_id)
+    }
+
+    /// Obtain human-readable name of the instance, `None` if it is not set.
+    pub fn instance_name(&self) -> Option<&str> {
+        self.instance_name
+            .as_deref()
+    }
+
+    /// Set and save human-readable name of the instance.
+    /// 
+    /// Name is shared with other instances during synchronization,
+    /// hence it should not contain anything secret.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `name` - instance name, `None` removes it
+    pub fn set_instance_name<L: Location>(&mut self, loc: &L, name: Option<&str>) -> Result<()> {
+        let name = match name.map(str::trim) {
+            Some(name) if name.is_empty() || MAX_INSTANCE_NAME_LENGTH < name.len() || name.chars().any(char::is_control) => {
+                return Err(Error::new_with_extra(MALFORMED_INSTANCE_NAME, name));
+            },
+            name => name
+        };
+
+        match name {
+            Some(name) => Self::write_file(Self::instance_name_file(loc), name)?,
+            None => match std::fs::remove_file(Self::instance_name_file(loc)) {
+                Ok(_) => (),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+                Err(error) => return Err(error.into())
+            }
+        }
+
+        self.instance_name = name.map(str::to_owned);
+
+        Ok(())
+    }
+
+    /// Obtain user preferences.
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Change and save user preferences.
+    /// 
+    /// Settings are validated before saving, and nothing
+    /// is changed if they are invalid.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `f` - function, that changes settings
+    pub fn update_settings<L, F>(&mut self, loc: &L, f: F) -> Result<()>
+    where
+        L: Location,
+        F: FnOnce(&mut Settings)
+    {
+        let mut settings = self.settings.clone();
+        f(&mut settings);
+
+        settings.validate()?;
+        settings.ensure_supported()?;
+
+        Self::write_document(loc, &self.raw_key_ids(), &self.instance_id, &settings)?;
+        self.settings = settings;
+
+        Ok(())
+    }
+
+    /// Obtain the default currency code.
+    pub fn currency(&self) -> &str {
+        self.settings
+            .default_currency()
+    }
+
+    /// Set and save the default currency code.
+    /// 
+    /// Accounts without explicit currency use this one.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `currency` - ISO 4217 currency code (three uppercase letters)
+    pub fn set_currency<L: Location>(&mut self, loc: &L, currency: &str) -> Result<()> {
+        self.update_settings(loc, |settings| settings.set_default_currency(currency))
+    }
+
+    /// Obtain key derivation parameters used for synchronization.
+    pub fn kdf_params(&self) -> &KdfParams {
+        &self.kdf_params
+    }
+
+    /// Set and save key derivation parameters used for synchronization.
+    /// 
+    /// Parameters are sent along with changelog, hence other
+    /// instances can use different ones.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `kdf_params` - key derivation parameters
+    pub fn set_kdf_params<L: Location>(&mut self, loc: &L, kdf_params: KdfParams) -> Result<()> {
+        Self::write_file(Self::kdf_params_file(loc), kdf_params.to_bytes())?;
+        self.kdf_params = kdf_params;
+
+        Ok(())
+    }
+
+    /// Obtain retention period of synchronized changelog,
+    /// `None` if changelog is never compacted.
+    pub fn changelog_retention(&self) -> Option<chrono::Duration> {
+        self.changelog_retention
+    }
+
+    /// Set and save retention period of synchronized changelog.
+    /// 
+    /// Instances, that were not synchronized during this period,
+    /// cannot be synchronized anymore and require full resynchronization.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `retention` - retention period, `None` disables compaction
+    pub fn set_changelog_retention<L: Location>(&mut self, loc: &L, retention: Option<chrono::Duration>) -> Result<()> {
+        let seconds = retention
+            .map(|retention| retention.num_seconds())
+            .unwrap_or(0);
+
+        Self::write_file(Self::changelog_retention_file(loc), seconds.to_string())?;
+        self.changelog_retention = retention.filter(|_| 0 < seconds);
+
+        Ok(())
+    }
+
+    /// Checks if inconsistent synchronized files are regenerated.
+    pub fn sync_files_recovery(&self) -> bool {
+        self.sync_files_recovery
+    }
+
+    /// Set and save recovery mode of synchronized files.
+    /// 
+    /// If some of synchronized files are missing, synchronization fails
+    /// by default. With recovery enabled such files are considered
+    /// empty and are regenerated, hence remote changes, that are
+    /// stored in them, are lost.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `enabled` - whether recovery is enabled
+    pub fn set_sync_files_recovery<L: Location>(&mut self, loc: &L, enabled: bool) -> Result<()> {
+        Self::write_file(Self::sync_files_recovery_file(loc), if enabled { "1" } else { "0" })?;
+        self.sync_files_recovery = enabled;
+
+        Ok(())
+    }
+
+    /// Obtain timestamp of the last imported synchronization bundle
+    /// created by an instance, `None` if there was no such bundle.
+    /// 
+    /// * `instance` - identifier of instance, that created bundle
+    pub fn last_bundle_import(&self, instance: &InstanceId) -> Option<Timestamp> {
+        self.bundle_imports
+            .get(instance)
+            .copied()
+    }
+
+    /// Set and save timestamp of the last imported synchronization bundle.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `instance` - identifier of instance, that created bundle
+    /// * `timestamp` - timestamp of bundle
+    pub fn set_last_bundle_import<L: Location>(&mut self, loc: &L, instance: &InstanceId, timestamp: Timestamp) -> Result<()> {
+        let mut bundle_imports = self.bundle_imports.clone();
+        bundle_imports.insert(*instance, timestamp);
+
+        Self::write_file(Self::bundle_imports_file(loc), 
+            Self::format_bundle_imports(&bundle_imports))?;
+
+        self.bundle_imports = bundle_imports;
+
+        Ok(())
+    }
 }
 
 
@@ -90,15 +563,125 @@ where
     Ce: CryptoEngine,
     Ce::KeyId: KeyIdentifier
 {
+    fn config_file<L: Location>(loc: &L) -> std::path::PathBuf {
+        loc.root()
+            .join(CONFIG_FILE)
+    }
+
     fn key_file<L: Location>(loc: &L) -> std::path::PathBuf {
         loc.root()
             .join(KEY_IDENTIFIER_FILE)
     }
 
+    fn fingerprints_file<L: Location>(loc: &L) -> std::path::PathBuf {
+        loc.root()
+            .join(KEY_FINGERPRINTS_FILE)
+    }
+
     fn instance_file<L: Location>(loc: &L) -> std::path::PathBuf {
         loc.root()
             .join(INSTANCE_IDENTIFIER_FILE)
     }
+
+    fn instance_name_file<L: Location>(loc: &L) -> std::path::PathBuf {
+        loc.root()
+            .join(INSTANCE_NAME_FILE)
+    }
+
+    fn currency_file<L: Location>(loc: &L) -> std::path::PathBuf {
+        loc.root()
+            .join(CURRENCY_FILE)
+    }
+
+    fn kdf_params_file<L: Location>(loc: &L) -> std::path::PathBuf {
+        loc.root()
+            .join(KDF_PARAMS_FILE)
+    }
+
+    fn changelog_retention_file<L: Location>(loc: &L) -> std::path::PathBuf {
+        loc.root()
+            .join(CHANGELOG_RETENTION_FILE)
+    }
+
+    fn sync_files_recovery_file<L: Location>(loc: &L) -> std::path::PathBuf {
+        loc.root()
+            .join(SYNC_FILES_RECOVERY_FILE)
+    }
+
+    fn bundle_imports_file<L: Location>(loc: &L) -> std::path::PathBuf {
+        loc.root()
+            .join(BUNDLE_IMPORTS_FILE)
+    }
+
+    fn parse_key_ids(raw_ids: &str) -> Vec<Ce::KeyId> {
+        //
+        // Single identifier is stored by older versions,
+        // it is just a special case of the list
+        //
+
+        raw_ids
+            .split(KEY_IDENTIFIERS_SEPARATOR)
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(Ce::KeyId::from_str)
+            .collect()
+    }
+
+    fn parse_fingerprints(raw_fingerprints: &str) -> HashMap<String, String> {
+        //
+        // Each line contains key identifier and its fingerprint
+        //
+
+        raw_fingerprints
+            .split(KEY_IDENTIFIERS_SEPARATOR)
+            .filter_map(|line| line.split_once(KEY_FINGERPRINT_SEPARATOR))
+            .map(|(key_id, fingerprint)| (key_id.trim().to_owned(), fingerprint.trim().to_owned()))
+            .collect()
+    }
+
+    fn format_fingerprints(fingerprints: &HashMap<String, String>) -> String {
+        fingerprints
+            .iter()
+            .map(|(key_id, fingerprint)| format!("{}{}{}", key_id, KEY_FINGERPRINT_SEPARATOR, fingerprint))
+            .collect::<Vec<_>>()
+            .join(KEY_IDENTIFIERS_SEPARATOR)
+    }
+
+    fn parse_changelog_retention(raw_retention: &str) -> Result<Option<chrono::Duration>> {
+        let seconds: i64 = raw_retention
+            .trim()
+            .parse()
+            .map_err(|_| Error::new_with_extra(MALFORMED_CHANGELOG_RETENTION, raw_retention))?;
+
+        Ok(Some(chrono::Duration::seconds(seconds))
+            .filter(|_| 0 < seconds))
+    }
+
+    fn parse_bundle_imports(raw_bundle_imports: &str) -> HashMap<InstanceId, Timestamp> {
+        //
+        // Each line contains instance identifier and timestamp in seconds,
+        // malformed lines are skipped, hence bundle can be imported again
+        //
+
+        raw_bundle_imports
+            .lines()
+            .filter_map(|line| line.split_once(BUNDLE_IMPORT_SEPARATOR))
+            .filter_map(|(instance, timestamp)| {
+                let instance = uuid::Uuid::parse_str(instance.trim()).ok()?;
+                let timestamp = timestamp.trim().parse().ok()?;
+
+                Some((instance, Timestamp::from_timestamp(timestamp, 0)?))
+            })
+            .collect()
+    }
+
+    fn format_bundle_imports(bundle_imports: &HashMap<InstanceId, Timestamp>) -> String {
+        bundle_imports
+            .iter()
+            .map(|(instance, timestamp)| format!("{}{}{}", instance, BUNDLE_IMPORT_SEPARATOR, timestamp.timestamp()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
 
@@ -110,4 +693,115 @@ where
     fn new_instance() -> InstanceId {
         uuid::Uuid::new_v4()
     }
+
+    fn raw_key_ids(&self) -> Vec<String> {
+        self.key_ids
+            .iter()
+            .map(KeyIdentifier::as_string)
+            .collect()
+    }
+
+    fn read_document<L: Location>(loc: &L) -> Result<Option<ConfigDocument>> {
+        let raw_document = match std::fs::read(Self::config_file(loc)) {
+            Ok(raw_document) => raw_document,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into())
+        };
+
+        let document: ConfigDocument = serde_json::from_slice(&raw_document)
+            .map_err(|error| Error::new_with_extra(CONFIG_CORRUPTED, error.to_string()))?;
+
+        if CONFIG_VERSION < document.version {
+            return Err(Error::new_with_extra(UNSUPPORTED_CONFIG_VERSION, format!("version: {}", document.version)));
+        }
+
+        Ok(Some(document))
+    }
+
+    fn write_document<L: Location>(loc: &L, key_ids: &[String], instance_id: &InstanceId, settings: &Settings) -> Result<()> {
+        let document = ConfigDocument {
+            version: CONFIG_VERSION,
+            key_ids: key_ids.to_vec(),
+            instance_id: instance_id.to_string(),
+            settings: settings.clone()
+        };
+
+        Self::write_file(Self::config_file(loc), serde_json::to_vec_pretty(&document)?)
+    }
+
+    fn migrate_legacy_files<L: Location>(loc: &L) -> Result<ConfigDocument> {
+        //
+        // Config without some of mandatory files is written partially,
+        // while config without all of them is not created at all
+        //
+
+        let key_file = Self::key_file(loc);
+        let instance_file = Self::instance_file(loc);
+        let currency_file = Self::currency_file(loc);
+
+        if Self::is_initialized(loc) && !(key_file.exists() && instance_file.exists()) {
+            return Err(Error::new_with_extra(CONFIG_CORRUPTED, "key or instance file is missing"));
+        }
+
+        let key_ids: Vec<String> = Self::parse_key_ids(&std::fs::read_to_string(&key_file)?)
+            .iter()
+            .map(KeyIdentifier::as_string)
+            .collect();
+
+        let instance_id = std::fs::read(&instance_file)?;
+        let instance_id = uuid::Uuid::from_slice(&instance_id)
+            .map_err(|error| Error::new_with_extra(CONFIG_CORRUPTED, format!("instance identifier: {}", error)))?;
+
+        //
+        // Instances created before currencies were introduced
+        // have no currency file, hence default one is used
+        //
+
+        let mut settings = Settings::default();
+        match std::fs::read_to_string(&currency_file) {
+            Ok(currency) => settings.set_default_currency(currency.trim()),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+            Err(error) => return Err(error.into())
+        }
+
+        //
+        // Legacy files are removed only after the document is written,
+        // hence interrupted migration is just performed again
+        //
+
+        Self::write_document(loc, &key_ids, &instance_id, &settings)?;
+
+        for file in [key_file, instance_file, currency_file] {
+            match std::fs::remove_file(file) {
+                Ok(_) => (),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+                Err(error) => return Err(error.into())
+            }
+        }
+
+        Self::read_document(loc)?
+            .ok_or(Error::new_with_extra(CONFIG_CORRUPTED, "configuration document is missing"))
+    }
+
+    fn write_file<P, C>(path: P, content: C) -> Result<()> 
+    where
+        P: AsRef<std::path::Path>,
+        C: AsRef<[u8]>
+    {
+        //
+        // Content is written into a temporary file first and then
+        // it replaces the original one, hence interrupted write
+        // leaves either old or new version of the file
+        //
+
+        let path = path.as_ref();
+        let temporary_path = path.with_extension(TEMPORARY_EXTENSION);
+
+        let mut file = std::fs::File::create(&temporary_path)?;
+        file.write_all(content.as_ref())?;
+        file.sync_all()?;
+
+        std::fs::rename(&temporary_path, path)
+            .map_err(Error::from)
+    }
 }
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::storage::{Id, Transaction, Account, Category, Plan, CategoryType, AccountType};
+
+
+/// Format of exported data.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Transactions as CSV table with a header line
+    Csv,
+
+    /// Single JSON document with accounts, categories, plans and transactions
+    Json,
+}
+
+
+/// Exported account.
+#[derive(Serialize)]
+pub(crate) struct ExportedAccount {
+    id: String,
+    name: String,
+    balance: isize,
+    initial_balance: isize,
+    currency: String,
+    archived: bool,
+    account_type: AccountType,
+}
+
+
+/// Exported category.
+#[derive(Serialize)]
+pub(crate) struct ExportedCategory {
+    id: String,
+    name: String,
+    category_type: CategoryType,
+}
+
+
+/// Exported plan.
+#[derive(Serialize)]
+pub(crate) struct ExportedPlan {
+    id: String,
+    category_id: String,
+    category: String,
+    name: String,
+    amount_limit: isize,
+}
+
+
+/// Exported transaction.
+#[derive(Serialize)]
+pub(crate) struct ExportedTransaction {
+    id: String,
+    timestamp: String,
+    description: String,
+    account_id: String,
+    account: String,
+    category_id: String,
+    category: String,
+    amount: isize,
+    currency: String,
+    transfer_id: Option<String>,
+}
+
+
+/// Exported JSON document.
+#[derive(Serialize)]
+pub(crate) struct ExportedDocument {
+    pub accounts: Vec<ExportedAccount>,
+    pub categories: Vec<ExportedCategory>,
+    pub plans: Vec<ExportedPlan>,
+    pub transactions: Vec<ExportedTransaction>,
+}
+
+
+/// Resolver of identifiers into names of accounts and categories
+/// and into currencies of accounts.
+pub(crate) struct NameResolver {
+    accounts: HashMap<Id, String>,
+    currencies: HashMap<Id, String>,
+    categories: HashMap<Id, String>,
+}
+
+
+impl NameResolver {
+    /// Constructs a resolver from decrypted accounts and categories.
+    /// 
+    /// * `accounts` - decrypted accounts
+    /// * `categories` - decrypted categories
+    pub fn new(accounts: &[Account], categories: &[Category]) -> Self {
+        NameResolver {
+            accounts: accounts
+                .iter()
+                .map(|account| (account.id.unwrap(), account.name.clone()))
+                .collect(),
+
+            currencies: accounts
+                .iter()
+                .map(|account| (account.id.unwrap(), account.currency.clone()))
+                .collect(),
+
+            categories: categories
+                .iter()
+                .map(|category| (category.id.unwrap(), category.name.clone()))
+                .collect()
+        }
+    }
+
+    fn account(&self, id: &Id) -> String {
+        self.accounts.get(id).cloned().unwrap_or_default()
+    }
+
+    fn currency(&self, id: &Id) -> String {
+        self.currencies.get(id).cloned().unwrap_or_default()
+    }
+
+    fn category(&self, id: &Id) -> String {
+        self.categories.get(id).cloned().unwrap_or_default()
+    }
+}
+
+
+/// Converts an identifier into its textual representation.
+fn id_to_string(id: &Id) -> String {
+    uuid::Uuid::from_bytes(id.into_bytes()).to_string()
+}
+
+
+impl From<Account> for ExportedAccount {
+    fn from(account: Account) -> Self {
+        ExportedAccount {
+            id: id_to_string(&account.id.unwrap()),
+            name: account.name,
+            balance: account.balance,
+            initial_balance: account.initial_balance,
+            currency: account.currency,
+            archived: account.archived,
+            account_type: account.account_type
+        }
+    }
+}
+
+
+impl From<Category> for ExportedCategory {
+    fn from(category: Category) -> Self {
+        ExportedCategory {
+            id: id_to_string(&category.id.unwrap()),
+            name: category.name,
+            category_type: category.category_type
+        }
+    }
+}
+
+
+impl ExportedPlan {
+    /// Converts a decrypted plan.
+    /// 
+    /// * `plan` - decrypted plan
+    /// * `resolver` - names resolver
+    pub fn new(plan: Plan, resolver: &NameResolver) -> Self {
+        ExportedPlan {
+            id: id_to_string(&plan.id.unwrap()),
+            category_id: id_to_string(&plan.category_id),
+            category: resolver.category(&plan.category_id),
+            name: plan.name,
+            amount_limit: plan.amount_limit
+        }
+    }
+}
+
+
+impl ExportedTransaction {
+    /// Converts a decrypted transaction.
+    /// 
+    /// * `transaction` - decrypted transaction
+    /// * `resolver` - names resolver
+    pub fn new(transaction: Transaction, resolver: &NameResolver) -> Self {
+        ExportedTransaction {
+            id: id_to_string(&transaction.id.unwrap()),
+            timestamp: transaction.timestamp.to_rfc3339(),
+            description: transaction.description,
+            account_id: id_to_string(&transaction.account_id),
+            account: resolver.account(&transaction.account_id),
+            category_id: id_to_string(&transaction.category_id),
+            category: resolver.category(&transaction.category_id),
+            amount: transaction.amount,
+            currency: resolver.currency(&transaction.account_id),
+            transfer_id: transaction.transfer_id.as_ref().map(id_to_string)
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use crate::error::{Result, Error};
+use crate::datetime::Timestamp;
+use crate::storage::{Id, Transaction, Category, CategoryType, MetaInfo};
+use super::{MALFORMED_AMOUNT, MALFORMED_DATE, MISSING_COLUMN, UNKNOWN_ACCOUNT, UNKNOWN_CATEGORY};
+
+
+/// Description of CSV columns used for transactions import.
+///
+/// All column indices are zero-based.
+#[derive(Clone)]
+pub struct CsvMapping {
+    /// Column with transaction date
+    pub date_column: usize,
+
+    /// Format of date (in terms of [`chrono::format::strftime`]),
+    /// both dates and dates with time are supported
+    pub date_format: String,
+
+    /// Column with transaction description
+    pub description_column: usize,
+
+    /// Column with transaction amount
+    pub amount_column: usize,
+
+    /// Separator of integer and fractional parts of amount
+    pub decimal_separator: char,
+
+    /// Number of digits in fractional part of amount, i.e.
+    /// amounts are converted into minor units using this value
+    pub minor_digits: u32,
+
+    /// Column with account name (if absent, `default_account` is used)
+    pub account_column: Option<usize>,
+
+    /// Account to use if account column is absent or empty
+    pub default_account: Option<Id>,
+
+    /// Column with category name (if absent, `default_category` is used)
+    pub category_column: Option<usize>,
+
+    /// Category to use if category column is absent or empty
+    pub default_category: Option<Id>,
+
+    /// If `true`, unknown categories are created as outcome ones,
+    /// otherwise import fails on an unknown category
+    pub create_missing_categories: bool,
+
+    /// Field delimiter
+    pub delimiter: u8,
+
+    /// If `true`, the first line is considered to be a header
+    pub has_headers: bool,
+}
+
+
+impl CsvMapping {
+    /// Constructs a mapping with mandatory columns. Other parameters
+    /// are set to reasonable defaults: ISO 8601 dates, dot as a decimal
+    /// separator, two digits in fractional part, comma as a delimiter
+    /// and a header line.
+    ///
+    /// * `date_column` - column with transaction date
+    /// * `description_column` - column with transaction description
+    /// * `amount_column` - column with transaction amount
+    pub fn new(date_column: usize, description_column: usize, amount_column: usize) -> Self {
+        CsvMapping {
+            date_column,
+            date_format: "%Y-%m-%d".to_owned(),
+            description_column,
+            amount_column,
+            decimal_separator: '.',
+            minor_digits: 2,
+            account_column: None,
+            default_account: None,
+            category_column: None,
+            default_category: None,
+            create_missing_categories: false,
+            delimiter: b',',
+            has_headers: true,
+        }
+    }
+}
+
+
+/// Result of transactions import.
+///
+/// Rows are numbered starting from 1 excluding header.
+#[derive(Clone, Default)]
+pub struct ImportReport {
+    /// Successfully imported rows
+    pub imported: Vec<usize>,
+
+    /// Skipped rows with the reason
+    pub skipped: Vec<(usize, String)>,
+
+    /// Rows, that duplicate already existing transactions
+    pub duplicates: Vec<usize>,
+}
+
+
+/// Parses a date according to mapping.
+///
+/// * `value` - raw date
+/// * `mapping` - CSV mapping
+pub(crate) fn parse_date(value: &str, mapping: &CsvMapping) -> Result<Timestamp> {
+    let value = value.trim();
+
+    let date_time = chrono::NaiveDateTime::parse_from_str(value, &mapping.date_format)
+        .or_else(|_| {
+            chrono::NaiveDate::parse_from_str(value, &mapping.date_format)
+                .map(|date| date.and_time(chrono::NaiveTime::MIN))
+        })
+        .map_err(|_| Error::new_with_extra(MALFORMED_DATE, value))?;
+
+    Ok(date_time.and_utc())
+}
+
+
+/// Parses an amount into minor units according to mapping.
+///
+/// * `value` - raw amount
+/// * `mapping` - CSV mapping
+pub(crate) fn parse_amount(value: &str, mapping: &CsvMapping) -> Result<isize> {
+    let malformed = || Error::new_with_extra(MALFORMED_AMOUNT, value);
+
+    let cleaned: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+
+    let (negative, unsigned) = match cleaned.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, cleaned.strip_prefix('+').unwrap_or(&cleaned))
+    };
+
+    let (integer, fraction) = unsigned
+        .split_once(mapping.decimal_separator)
+        .unwrap_or((unsigned, ""));
+
+    let digits_only = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if integer.is_empty() || !digits_only(integer) || !digits_only(fraction) ||
+        fraction.len() > mapping.minor_digits as usize
+    {
+        return Err(malformed());
+    }
+
+    //
+    // Fractional part is padded with zeros up to required number of digits
+    //
+
+    let scale = 10isize.checked_pow(mapping.minor_digits)
+        .ok_or_else(malformed)?;
+
+    let integer: isize = integer.parse()
+        .map_err(|_| malformed())?;
+
+    let fraction: isize = match fraction.is_empty() {
+        true => 0,
+        false => fraction.parse::<isize>().map_err(|_| malformed())? *
+            10isize.pow(mapping.minor_digits - fraction.len() as u32)
+    };
+
+    let amount = integer.checked_mul(scale)
+        .and_then(|amount| amount.checked_add(fraction))
+        .ok_or_else(malformed)?;
+
+    Ok(if negative { -amount } else { amount })
+}
+
+
+/// Converts a CSV record into a transaction.
+///
+/// If category is unknown and mapping allows to create missing categories,
+/// a new category is created and appended to `new_categories`.
+///
+/// * `record` - CSV record
+/// * `mapping` - CSV mapping
+/// * `accounts` - known accounts by name
+/// * `categories` - known categories by name (updated with created ones)
+/// * `new_categories` - categories to create
+/// * `now` - creation timestamp for new items
+pub(crate) fn parse_record(record: &csv::StringRecord, mapping: &CsvMapping, accounts: &HashMap<String, Id>,
+    categories: &mut HashMap<String, Id>, new_categories: &mut Vec<Category>, now: Timestamp) -> Result<Transaction>
+{
+    let timestamp = parse_date(field(record, mapping.date_column)?, mapping)?;
+    let description = field(record, mapping.description_column)?.to_owned();
+    let amount = parse_amount(field(record, mapping.amount_column)?, mapping)?;
+
+    //
+    // Account must exist, while category can be created if necessary
+    //
+
+    let account_name = optional_field(record, mapping.account_column)?;
+    let account_id = match account_name {
+        Some(name) => accounts.get(name).copied(),
+        None => mapping.default_account
+    }
+    .ok_or_else(|| Error::new_with_extra(UNKNOWN_ACCOUNT, account_name.unwrap_or_default()))?;
+
+    let category_name = optional_field(record, mapping.category_column)?;
+    let category_id = match (category_name, mapping.default_category) {
+        (Some(name), _) => match categories.get(name) {
+            Some(id) => *id,
+            None if mapping.create_missing_categories => {
+                let id = Id::random();
+
+                categories.insert(name.to_owned(), id);
+                new_categories.push(Category {
+                    id: Some(id),
+                    name: name.to_owned(),
+                    category_type: CategoryType::Outcome,
+                    meta_info: MetaInfo::new(Some(now), None, None)
+                });
+
+                id
+            },
+            None => return Err(Error::new_with_extra(UNKNOWN_CATEGORY, name))
+        },
+        (None, Some(id)) => id,
+        (None, None) => return Err(Error::new(UNKNOWN_CATEGORY))
+    };
+
+    Ok(Transaction {
+        id: None,
+        timestamp,
+        description,
+        account_id,
+        category_id,
+        amount,
+        transfer_id: None,
+        tags: Vec::new(),
+        pending: false,
+        meta_info: MetaInfo::new(Some(now), None, None)
+    })
+}
+
+
+fn field(record: &csv::StringRecord, column: usize) -> Result<&str> {
+    record
+        .get(column)
+        .map(str::trim)
+        .ok_or_else(|| Error::new_with_extra(MISSING_COLUMN, column.to_string()))
+}
+
+
+fn optional_field(record: &csv::StringRecord, column: Option<usize>) -> Result<Option<&str>> {
+    let value = match column {
+        Some(column) => field(record, column)?,
+        None => return Ok(None)
+    };
+
+    Ok((!value.is_empty()).then_some(value))
+}
@@ -1,9 +1,142 @@
 mod budget;
 mod config;
 mod changelog;
+mod report;
+mod import;
+mod export;
+mod backup;
+mod bundle;
+mod settings;
+#[cfg(feature = "async")]
+mod async_budget;
 
 pub use self::budget::Budget;
+#[cfg(feature = "async")]
+pub use self::async_budget::AsyncBudget;
 pub use self::config::{Config, InstanceId};
+pub use self::settings::Settings;
+pub use self::report::{PlanProgress, PlanStatus, PlanAlert, MonthlyReport, CashflowPoint, RemovedItems, ItemChanges, SyncPreview, ChangeCounts, SyncSummary, SyncStatus, InstanceInfo};
+pub use self::import::{CsvMapping, ImportReport};
+pub use self::export::ExportFormat;
+
+use crate::error::{ErrorKind, ErrorMessage};
 
 /// Error shown in case of malformed timestamp file.
-const MALFORMED_TIMESTAMP: &str = "Timestamp file in repository is malformed";
+const MALFORMED_TIMESTAMP: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Timestamp file in repository is malformed");
+
+/// Error shown in case of malformed instance file.
+const MALFORMED_INSTANCES: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Instance file in repository is malformed");
+
+/// Error shown in case of malformed changelog file.
+const MALFORMED_CHANGELOG: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Changelog file in repository is malformed");
+
+/// Error shown in case of synchronized files, that do not match their digest.
+const REMOTE_DATA_CORRUPTED: ErrorMessage = ErrorMessage::new(ErrorKind::Sync, "Remote data is tampered or corrupted");
+
+/// Error shown in case of synchronized files, that fail authentication or decryption.
+const SYNC_AUTHENTICATION_FAILED: ErrorMessage = ErrorMessage::new(ErrorKind::SyncAuthentication, "Synchronization authentication failed, wrong passphrase or tampered remote");
+
+/// Error shown in case of updating an item without identifier.
+const MISSING_IDENTIFIER: ErrorMessage = ErrorMessage::new(ErrorKind::Other, "Item has no identifier");
+
+/// Error shown in case of non-positive time step.
+const INVALID_STEP: ErrorMessage = ErrorMessage::new(ErrorKind::Other, "Time step must be positive");
+
+/// Error shown in case of removing of missing transfer.
+const TRANSFER_NOT_FOUND: ErrorMessage = ErrorMessage::new(ErrorKind::NotFound, "Transfer is not found");
+
+/// Error shown in case of malformed amount in imported data.
+const MALFORMED_AMOUNT: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Malformed amount");
+
+/// Error shown in case of malformed date in imported data.
+const MALFORMED_DATE: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Malformed date");
+
+/// Error shown in case of missing column in imported data.
+const MISSING_COLUMN: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Column is missing");
+
+/// Error shown in case of unknown account in imported data.
+const UNKNOWN_ACCOUNT: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Unknown account");
+
+/// Error shown in case of unknown category in imported data.
+const UNKNOWN_CATEGORY: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Unknown category");
+
+/// Error shown in case of malformed backup archive.
+const MALFORMED_BACKUP: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Backup archive is malformed");
+
+/// Error shown in case of unsupported backup archive version.
+const UNSUPPORTED_BACKUP_VERSION: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Backup archive version is not supported");
+
+/// Error shown in case of malformed changelog retention period in configuration.
+const MALFORMED_CHANGELOG_RETENTION: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Malformed changelog retention period");
+
+/// Error shown if changes since the last synchronization are compacted in remote changelog.
+const FULL_RESYNC_REQUIRED: ErrorMessage = ErrorMessage::new(ErrorKind::Sync, "Remote changelog is compacted since the last synchronization, full resynchronization is required");
+
+/// Error shown in case of malformed or truncated synchronization bundle.
+const MALFORMED_SYNC_BUNDLE: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Synchronization bundle is malformed");
+
+/// Error shown in case of unsupported synchronization bundle version.
+const UNSUPPORTED_SYNC_BUNDLE_VERSION: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Synchronization bundle version is not supported");
+
+/// Error shown in case of changelog written by an incompatible version of library.
+const UNSUPPORTED_CHANGELOG_VERSION: ErrorMessage = ErrorMessage::new(ErrorKind::Sync, "Changelog is written by a newer version of bdgt, upgrade is required");
+
+/// Error shown in case of restoring a backup into non-empty storage.
+const STORAGE_IS_NOT_EMPTY: ErrorMessage = ErrorMessage::new(ErrorKind::Storage, "Storage is not empty");
+
+/// Error shown in case of adding a transaction to archived account.
+const ACCOUNT_IS_ARCHIVED: ErrorMessage = ErrorMessage::new(ErrorKind::Consistency, "Account is archived");
+
+/// Error shown in case of restoring a transaction of removed account.
+const ACCOUNT_IS_REMOVED: ErrorMessage = ErrorMessage::new(ErrorKind::Consistency, "Account is removed");
+
+/// Error shown in case of negative time window.
+const INVALID_WINDOW: ErrorMessage = ErrorMessage::new(ErrorKind::Other, "Time window must not be negative");
+
+/// Error shown in case of plan period of zero length.
+const INVALID_PERIOD: ErrorMessage = ErrorMessage::new(ErrorKind::Other, "Plan period must be positive");
+
+/// Error shown in case of partially written or malformed configuration.
+const CONFIG_CORRUPTED: ErrorMessage = ErrorMessage::new(ErrorKind::ConfigCorrupted, "Configuration is corrupted");
+
+/// Error shown in case of configuration written by an incompatible version of library.
+const UNSUPPORTED_CONFIG_VERSION: ErrorMessage = ErrorMessage::new(ErrorKind::ConfigCorrupted, "Configuration is written by a newer version of bdgt, upgrade is required");
+
+/// Error shown in case of malformed instance name.
+const MALFORMED_INSTANCE_NAME: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Instance name must be non-empty, printable and at most 255 bytes long");
+
+/// Error shown in case of malformed currency code.
+const MALFORMED_CURRENCY: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Malformed currency code");
+
+/// Error shown in case of unknown timezone name.
+#[cfg(feature = "tz")]
+const MALFORMED_TIMEZONE: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Unknown timezone");
+
+/// Error shown in case of timezone set without timezone support.
+#[cfg(not(feature = "tz"))]
+const TIMEZONES_UNSUPPORTED: ErrorMessage = ErrorMessage::new(ErrorKind::Other, "Timezones are not supported, `tz` feature is required");
+
+/// Error shown in case of transfer between accounts with different currencies.
+const CURRENCY_MISMATCH: ErrorMessage = ErrorMessage::new(ErrorKind::Consistency, "Accounts have different currencies");
+
+/// Error shown in case of invalid month.
+const INVALID_MONTH: ErrorMessage = ErrorMessage::new(ErrorKind::Other, "Invalid month");
+
+/// Error shown in case of date, which has no start in configured timezone.
+const INVALID_DATE: ErrorMessage = ErrorMessage::new(ErrorKind::Other, "Invalid date");
+
+/// Error shown in case of no available key.
+const MISSING_KEY: ErrorMessage = ErrorMessage::new(ErrorKind::KeyNotFound, "No suitable key is available");
+
+/// Error shown in case of interrupted key rotation, that cannot be finished in read-only mode.
+const ROTATION_IN_READ_ONLY: ErrorMessage = ErrorMessage::new(ErrorKind::Storage, "Interrupted key rotation cannot be finished in read-only mode");
+
+/// Error shown in case of key, which fingerprint differs from the pinned one.
+const KEY_FINGERPRINT_MISMATCH: ErrorMessage = ErrorMessage::new(ErrorKind::Crypto, "Key fingerprint mismatch");
+
+/// Error shown in case of several items with the same name.
+const AMBIGUOUS_NAME: ErrorMessage = ErrorMessage::new(ErrorKind::Consistency, "Several items have the same name");
+
+/// Error shown in case of blocking operation, that is cancelled by shutting down runtime.
+#[cfg(feature = "async")]
+const BLOCKING_TASK_CANCELLED: ErrorMessage = ErrorMessage::new(ErrorKind::Other, "Operation is cancelled, since runtime is shutting down");
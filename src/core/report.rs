@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+
+use crate::datetime::Timestamp;
+use crate::storage::{Id, Transaction, Account, Category, Plan, RecurringTransaction, CompactStats};
+use crate::sync::MergeSummary;
+use super::config::InstanceId;
+
+
+/// Plan execution progress during some period of time.
+#[derive(Clone)]
+pub struct PlanProgress {
+    /// Identifier of a plan
+    pub plan_id: Id,
+
+    /// Plan's amount limit
+    pub amount_limit: isize,
+
+    /// Amount spent during the period
+    pub spent: isize,
+
+    /// Remaining amount (negative if the limit is exceeded)
+    pub remaining: isize,
+}
+
+
+impl PlanProgress {
+    /// Constructs a progress from a limit and a spent amount.
+    /// 
+    /// * `plan_id` - identifier of a plan
+    /// * `amount_limit` - plan's amount limit
+    /// * `spent` - amount spent during the period
+    pub(crate) fn new(plan_id: Id, amount_limit: isize, spent: isize) -> Self {
+        PlanProgress {
+            plan_id,
+            amount_limit,
+            spent,
+            remaining: amount_limit - spent
+        }
+    }
+}
+
+
+/// Plan status during its current period.
+#[derive(Clone)]
+pub struct PlanStatus {
+    /// Identifier of a plan
+    pub plan_id: Id,
+
+    /// Start of the current period (inclusive)
+    pub period_start: Timestamp,
+
+    /// End of the current period (exclusive)
+    pub period_end: Timestamp,
+
+    /// Plan's amount limit
+    pub amount_limit: isize,
+
+    /// Unused allowance carried over from previous periods
+    pub carried_over: isize,
+
+    /// Amount spent during the current period
+    pub spent: isize,
+
+    /// Remaining amount (negative if the limit is exceeded)
+    pub remaining: isize,
+}
+
+
+impl PlanStatus {
+    /// Constructs a status from a limit, a carried over allowance and a spent amount.
+    /// 
+    /// * `plan_id` - identifier of a plan
+    /// * `period` - start and end of the current period
+    /// * `amount_limit` - plan's amount limit
+    /// * `carried_over` - allowance carried over from previous periods
+    /// * `spent` - amount spent during the current period
+    pub(crate) fn new(plan_id: Id, period: (Timestamp, Timestamp), amount_limit: isize, 
+        carried_over: isize, spent: isize) -> Self 
+    {
+        PlanStatus {
+            plan_id,
+            period_start: period.0,
+            period_end: period.1,
+            amount_limit,
+            carried_over,
+            spent,
+            remaining: amount_limit + carried_over - spent
+        }
+    }
+}
+
+
+/// Alert about a plan, which limit is exceeded.
+#[derive(Clone)]
+pub struct PlanAlert {
+    /// Identifier of a plan
+    pub plan_id: Id,
+
+    /// Plan's amount limit with allowance carried over from previous periods
+    pub amount_limit: isize,
+
+    /// Amount spent during the period
+    pub spent: isize,
+
+    /// Amount, by which the limit is exceeded (always positive)
+    pub overshoot: isize,
+}
+
+
+impl PlanAlert {
+    /// Constructs an alert from a plan status, if plan's limit is exceeded.
+    /// 
+    /// * `status` - status of a plan in its period
+    pub(crate) fn exceeded(status: &PlanStatus) -> Option<Self> {
+        if 0 <= status.remaining {
+            return None;
+        }
+
+        Some(PlanAlert {
+            plan_id: status.plan_id,
+            amount_limit: status.amount_limit + status.carried_over,
+            spent: status.spent,
+            overshoot: -status.remaining
+        })
+    }
+}
+
+
+/// Income and outcome during a time bucket.
+/// 
+/// Amounts in different currencies are never summed up. Every currency
+/// of existing accounts is present (with zero if there are no transactions).
+#[derive(Clone)]
+pub struct CashflowPoint {
+    /// Start of the bucket
+    pub start: Timestamp,
+
+    /// Total income per currency
+    pub income: BTreeMap<String, isize>,
+
+    /// Total outcome per currency (as an absolute value)
+    pub outcome: BTreeMap<String, isize>,
+}
+
+
+/// Summary of a calendar month.
+/// 
+/// Amounts in different currencies are never summed up. Transfers between
+/// own accounts are not counted as income or outcome, but affect accounts' deltas.
+pub struct MonthlyReport {
+    /// First moment of the month (inclusive)
+    pub period_start: Timestamp,
+
+    /// First moment of the next month (exclusive)
+    pub period_end: Timestamp,
+
+    /// Total income per currency
+    pub income: BTreeMap<String, isize>,
+
+    /// Total outcome per currency (as an absolute value)
+    pub outcome: BTreeMap<String, isize>,
+
+    /// Signed totals per category and currency (without transfers)
+    pub categories: Vec<(Category, String, isize)>,
+
+    /// Change of balance of each account with transactions during the month
+    pub accounts: Vec<(Account, isize)>,
+
+    /// Progress of plans, which limits are exceeded during the month
+    pub plan_overruns: Vec<PlanProgress>,
+
+    /// Number of transactions made during the month
+    pub transactions_count: usize,
+}
+
+
+/// Removed items, that are not deleted permanently yet.
+/// 
+/// Removal timestamps are available in items' meta information.
+pub struct RemovedItems {
+    /// Removed accounts
+    pub accounts: Vec<Account>,
+
+    /// Removed categories
+    pub categories: Vec<Category>,
+
+    /// Removed plans
+    pub plans: Vec<Plan>,
+
+    /// Removed transactions
+    pub transactions: Vec<Transaction>,
+
+    /// Removed recurring transactions
+    pub recurrings: Vec<RecurringTransaction>,
+}
+
+
+/// Identifiers of items of one type, that are changed by synchronization.
+#[derive(Clone, Default)]
+pub struct ItemChanges {
+    /// Items to add (or to restore, if they are removed or deleted locally)
+    pub added: Vec<Id>,
+
+    /// Items to update
+    pub changed: Vec<Id>,
+
+    /// Items to remove
+    pub removed: Vec<Id>,
+}
+
+
+impl ItemChanges {
+    /// Checks if there are no changes at all.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+
+/// Changes, that synchronization would apply to local storage.
+#[derive(Clone, Default)]
+pub struct SyncPreview {
+    /// Changes of accounts
+    pub accounts: ItemChanges,
+
+    /// Changes of categories
+    pub categories: ItemChanges,
+
+    /// Changes of plans
+    pub plans: ItemChanges,
+
+    /// Changes of transactions
+    pub transactions: ItemChanges,
+
+    /// Changes of recurring transactions
+    pub recurrings: ItemChanges,
+}
+
+
+impl SyncPreview {
+    /// Checks if synchronization would not change anything locally.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.categories.is_empty() && self.plans.is_empty() &&
+        self.transactions.is_empty() && self.recurrings.is_empty()
+    }
+}
+
+
+/// Numbers of items changed during synchronization.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChangeCounts {
+    /// Number of added items
+    pub added: usize,
+
+    /// Number of changed items
+    pub changed: usize,
+
+    /// Number of removed items
+    pub removed: usize,
+}
+
+
+impl ChangeCounts {
+    /// Returns total number of changed items.
+    pub fn total(&self) -> usize {
+        self.added + self.changed + self.removed
+    }
+
+    /// Checks if nothing is changed.
+    pub fn is_empty(&self) -> bool {
+        0 == self.total()
+    }
+}
+
+
+/// Result of synchronization.
+#[derive(Clone, Default)]
+pub struct SyncSummary {
+    /// Numbers of remote changes merged into local storage
+    pub merged: ChangeCounts,
+
+    /// Numbers of local changes exported to remote
+    pub exported: ChangeCounts,
+
+    /// Instance, that synchronized last before this one, `None` if remote is empty
+    pub remote_instance: Option<InstanceId>,
+
+    /// Time of the last synchronization of remote, `None` if remote is empty
+    pub remote_timestamp: Option<Timestamp>,
+
+    /// Result of compaction of storage if it was compacted after synchronization
+    pub compaction: Option<CompactStats>,
+
+    /// Exported changes are committed locally only, because there is no remote
+    pub local_only: bool,
+}
+
+
+impl SyncSummary {
+    /// Checks if synchronization had nothing to do, i.e. nothing
+    /// is merged and nothing is exported.
+    pub fn is_empty(&self) -> bool {
+        self.merged.is_empty() && self.exported.is_empty()
+    }
+}
+
+
+impl MergeSummary for SyncSummary {
+    fn is_empty(&self) -> bool {
+        SyncSummary::is_empty(self)
+    }
+
+    fn mark_local_only(&mut self) {
+        self.local_only = true;
+    }
+}
+
+
+/// Instance, that participates in synchronization.
+#[derive(Clone, Debug)]
+pub struct InstanceInfo {
+    /// Instance identifier
+    pub id: InstanceId,
+
+    /// Human-readable name of instance, `None` if it is not set
+    pub name: Option<String>,
+
+    /// Time of the last synchronization of instance
+    pub last_seen: Timestamp,
+}
+
+
+/// State of synchronization, that is known without network operations.
+#[derive(Clone, Default)]
+pub struct SyncStatus {
+    /// Time of the last successful synchronization, `None` if it was never performed
+    pub last_sync: Option<Timestamp>,
+
+    /// Numbers of local changes made since the last synchronization
+    pub pending: ChangeCounts,
+
+    /// Remote url, `None` if there is no remote associated
+    pub remote_url: Option<String>,
+
+    /// Whether synchronization is configured, nothing is
+    /// pending if it is not
+    pub configured: bool,
+
+    /// Size of local synchronization data in bytes, `None`
+    /// if the engine keeps no local copy of synchronized files
+    pub repository_size: Option<u64>,
+}
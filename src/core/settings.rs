@@ -0,0 +1,138 @@
+use serde::{Serialize, Deserialize};
+
+use crate::error::{Result, Error};
+use super::MALFORMED_CURRENCY;
+#[cfg(feature = "tz")]
+use super::MALFORMED_TIMEZONE;
+#[cfg(not(feature = "tz"))]
+use super::TIMEZONES_UNSUPPORTED;
+
+
+/// Currency used if no default currency is configured.
+const DEFAULT_CURRENCY: &str = "USD";
+
+
+/// User preferences stored in configuration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Default ISO 4217 currency code for accounts.
+    default_currency: String,
+
+    /// First day of week used for weekly periods.
+    week_starts_on: chrono::Weekday,
+
+    /// Removed items are kept at least this number of days,
+    /// `None` means that they are kept until synchronized.
+    tombstone_retention_days: Option<u32>,
+
+    /// IANA name of timezone used for day and month boundaries,
+    /// `None` means UTC.
+    timezone: Option<String>,
+}
+
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            default_currency: DEFAULT_CURRENCY.to_owned(),
+            week_starts_on: chrono::Weekday::Mon,
+            tombstone_retention_days: None,
+            timezone: None
+        }
+    }
+}
+
+
+impl Settings {
+    /// Obtain the default currency code.
+    ///
+    /// Accounts without explicit currency use this one.
+    pub fn default_currency(&self) -> &str {
+        &self.default_currency
+    }
+
+    /// Set the default currency code. It is validated
+    /// when settings are saved.
+    ///
+    /// * `currency` - ISO 4217 currency code (three uppercase letters)
+    pub fn set_default_currency(&mut self, currency: &str) {
+        self.default_currency = currency.to_owned();
+    }
+
+    /// Obtain the first day of week used for weekly periods.
+    pub fn week_starts_on(&self) -> chrono::Weekday {
+        self.week_starts_on
+    }
+
+    /// Set the first day of week used for weekly periods.
+    ///
+    /// * `weekday` - first day of week
+    pub fn set_week_starts_on(&mut self, weekday: chrono::Weekday) {
+        self.week_starts_on = weekday;
+    }
+
+    /// Obtain the number of days, that removed items are kept
+    /// after synchronization, `None` if they are not kept.
+    pub fn tombstone_retention_days(&self) -> Option<u32> {
+        self.tombstone_retention_days
+    }
+
+    /// Set the number of days, that removed items are kept
+    /// after synchronization.
+    ///
+    /// * `days` - number of days, `None` disables retention
+    pub fn set_tombstone_retention_days(&mut self, days: Option<u32>) {
+        self.tombstone_retention_days = days;
+    }
+
+    /// Obtain IANA name of timezone used for day and month boundaries,
+    /// `None` if UTC is used.
+    pub fn timezone(&self) -> Option<&str> {
+        self.timezone.as_deref()
+    }
+
+    /// Set timezone used for day and month boundaries. Timestamps are
+    /// stored in UTC anyway. It is validated when settings are saved.
+    /// 
+    /// Timezones are supported only if `tz` feature is enabled.
+    ///
+    /// * `timezone` - IANA timezone name, e.g. `Europe/Berlin`, `None` means UTC
+    pub fn set_timezone(&mut self, timezone: Option<&str>) {
+        self.timezone = timezone.map(str::to_owned);
+    }
+
+    /// Checks if settings can be saved.
+    pub(super) fn validate(&self) -> Result<()> {
+        let currency = &self.default_currency;
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(Error::new_with_extra(MALFORMED_CURRENCY, currency));
+        }
+
+        #[cfg(feature = "tz")]
+        if let Some(timezone) = &self.timezone {
+            timezone.parse::<chrono_tz::Tz>()
+                .map_err(|_| Error::new_with_extra(MALFORMED_TIMEZONE, timezone))?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks if settings can be used by this build of library.
+    /// 
+    /// Configuration written with timezone support can still be
+    /// opened without it, but timezone cannot be set then.
+    #[cfg(not(feature = "tz"))]
+    pub(super) fn ensure_supported(&self) -> Result<()> {
+        match &self.timezone {
+            Some(timezone) => Err(Error::new_with_extra(TIMEZONES_UNSUPPORTED, timezone)),
+            None => Ok(())
+        }
+    }
+
+    /// Checks if settings can be used by this build of library.
+    #[cfg(feature = "tz")]
+    pub(super) fn ensure_supported(&self) -> Result<()> {
+        Ok(())
+    }
+}
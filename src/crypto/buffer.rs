@@ -26,10 +26,40 @@ impl CryptoBuffer {
     /// Takes ownership on both of buffers (current and appended).
     /// 
     /// * `buffer` - something convertible to [`CryptoBuffer`]
-    pub fn append<B: Into<CryptoBuffer>>(mut self, buffer: B) -> CryptoBuffer {
+    pub fn append<B: Into<CryptoBuffer>>(self, buffer: B) -> CryptoBuffer {
         let buffer: CryptoBuffer = buffer.into();
-        self.data.extend_from_slice(buffer.as_bytes());
-        self
+
+        //
+        // Vector can reallocate during extension and leave old memory
+        // unwiped, hence I allocate exact amount of memory at once.
+        // Both of source buffers are wiped when dropped.
+        //
+
+        let mut data = Vec::with_capacity(self.data.len() + buffer.data.len());
+        data.extend_from_slice(&self.data);
+        data.extend_from_slice(&buffer.data);
+
+        CryptoBuffer { data }
+    }
+
+    /// Compares buffers in constant time (with respect to their content).
+    /// 
+    /// * `other` - buffer to compare with
+    pub fn ct_eq(&self, other: &CryptoBuffer) -> bool {
+        if self.data.len() != other.data.len() {
+            return false;
+        }
+
+        let difference = self.data
+            .iter()
+            .zip(&other.data)
+            .fold(0u8, |difference, (l, r)| difference | (l ^ r));
+
+        //
+        // Prevent the compiler from short-circuiting the comparison
+        //
+
+        std::hint::black_box(difference) == 0
     }
 
     /// Returns read-only raw bytes of the stored data.
@@ -50,14 +80,52 @@ impl CryptoBuffer {
 
 
 impl CryptoBuffer {
-    fn destroy_data(data: &mut [u8]) {
+    fn destroy_data(data: &mut Vec<u8>) {
         //
-        // Just zero passed memory block
+        // Whole allocation is zeroed (including spare capacity) with
+        // volatile writes, that cannot be removed by the optimizer
         //
-    
-        for e in data.iter_mut() {
-            *e = 0u8;
+
+        let ptr = data.as_mut_ptr();
+        for offset in 0..data.capacity() {
+            //
+            // SAFETY: offset is within the allocation owned by vector,
+            // and u8 has no invalid bit patterns
+            //
+
+            unsafe { std::ptr::write_volatile(ptr.add(offset), 0u8) };
+        }
+
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+
+impl std::io::Write for CryptoBuffer {
+    /// Appends data to the buffer, hence the buffer can be used as
+    /// a destination of decrypted stream.
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        //
+        // Vector must not reallocate by itself, since the old allocation
+        // would be left unwiped. Hence I grow it manually and wipe the old
+        // allocation. Capacity is doubled to keep appending cheap
+        //
+
+        let required = self.data.len() + buf.len();
+        if self.data.capacity() < required {
+            let mut data = Vec::with_capacity(std::cmp::max(required, 2 * self.data.capacity()));
+            data.extend_from_slice(&self.data);
+
+            Self::destroy_data(&mut self.data);
+            self.data = data;
         }
+
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
 }
 
@@ -88,3 +156,74 @@ impl From<&[u8]> for CryptoBuffer {
         Self { data: Vec::from(value) }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn allocation(data: &Vec<u8>) -> &[u8] {
+        //
+        // SAFETY: whole allocation is initialized by volatile
+        // writes, and it is not freed while vector is alive
+        //
+
+        unsafe { std::slice::from_raw_parts(data.as_ptr(), data.capacity()) }
+    }
+
+    #[test]
+    fn destruction_wipes_whole_allocation() {
+        let mut data = Vec::with_capacity(64);
+        data.extend_from_slice(&[0xaa; 40]);
+
+        //
+        // Drop wipes data the same way, but here memory
+        // is not freed, hence it can be inspected
+        //
+
+        let mut buffer = CryptoBuffer::from(data);
+        CryptoBuffer::destroy_data(&mut buffer.data);
+
+        assert_eq!(buffer.data.capacity(), 64);
+        assert!(allocation(&buffer.data).iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn append_allocates_exactly() {
+        let mut data = Vec::with_capacity(100);
+        data.extend_from_slice(b"secret");
+
+        let buffer = CryptoBuffer::from(data).append(CryptoBuffer::from(&b" data"[..]));
+
+        assert_eq!(buffer.as_bytes(), b"secret data");
+        assert_eq!(buffer.data.capacity(), buffer.data.len());
+    }
+
+    #[test]
+    fn writes_grow_buffer() {
+        let mut buffer = CryptoBuffer::new();
+        let mut expected = Vec::new();
+
+        for i in 0..1000u32 {
+            let chunk = i.to_le_bytes();
+            buffer.write_all(&chunk).unwrap();
+            expected.extend_from_slice(&chunk);
+
+            assert!(buffer.data.capacity() <= 2 * std::cmp::max(buffer.data.len(), chunk.len()));
+        }
+
+        assert_eq!(buffer.as_bytes(), expected);
+    }
+
+    #[test]
+    fn constant_time_comparison() {
+        let buffer = CryptoBuffer::from(&b"secret"[..]);
+
+        assert!(buffer.ct_eq(&CryptoBuffer::from(&b"secret"[..])));
+        assert!(!buffer.ct_eq(&CryptoBuffer::from(&b"secreT"[..])));
+        assert!(!buffer.ct_eq(&CryptoBuffer::from(&b"secret!"[..])));
+        assert!(CryptoBuffer::new().ct_eq(&CryptoBuffer::new()));
+    }
+}
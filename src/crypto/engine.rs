@@ -26,6 +26,14 @@ pub trait CryptoEngine {
     /// that is used by the engine.
     fn symmetric_key_length(&self) -> usize;
 
+    /// Returns full fingerprint of a key, if key has one.
+    /// 
+    /// Fingerprint is used to detect substitution of a key with
+    /// the same identifier.
+    /// 
+    /// * `key` - handle to a key
+    fn key_fingerprint(&self, key: &Self::Key) -> Option<String>;
+
     /// Looks for a key with specific identifier in engine's key storage.
     /// 
     /// Key is returned if and only if it exists and is suitable for bdgt.
@@ -33,6 +41,35 @@ pub trait CryptoEngine {
     /// * `id` - identifier of a key to look for
     fn lookup_key(&self, id: &Self::KeyId) -> Result<Self::Key>;
 
+    /// Prepares everything, that is necessary to encrypt and decrypt
+    /// data using a provided key.
+    /// 
+    /// Engine may require user interaction (e.g. a passphrase prompt)
+    /// to use a key, hence this method allows to do it eagerly instead
+    /// of the first cryptographic operation.
+    /// 
+    /// * `key` - handle to a key.
+    fn preload(&self, key: &Self::Key) -> Result<()>;
+
+    /// Starts rotation of a key.
+    /// 
+    /// Until rotation is committed or aborted, data is encrypted
+    /// for a new key, while decryption still uses the current one.
+    /// Rotation state must survive engine reopening, i.e. if
+    /// application crashes, rotation remains in progress.
+    /// 
+    /// * `new_key` - handle to a new key.
+    fn begin_rotation(&mut self, new_key: &Self::Key) -> Result<()>;
+
+    /// Checks if key rotation is started, but neither committed nor aborted.
+    fn rotation_in_progress(&self) -> bool;
+
+    /// Finishes key rotation, i.e. the new key replaces the current one.
+    fn commit_rotation(&mut self) -> Result<()>;
+
+    /// Cancels key rotation, i.e. the new key is discarded.
+    fn abort_rotation(&mut self) -> Result<()>;
+
     /// Encrypts a BLOB using a provided key.
     /// 
     /// This method is generic. It is not specified, which encryption 
@@ -53,9 +90,84 @@ pub trait CryptoEngine {
     /// * `ciphertext` - data to decrypt
     fn decrypt(&self, key: &Self::Key, ciphertext: &[u8]) -> Result<CryptoBuffer>;
 
+    /// Encrypts a BLOB using a provided key and binds it to a context.
+    /// 
+    /// Ciphertext can be decrypted only with the same context, i.e. it
+    /// cannot be moved to another place, that has a different context.
+    /// 
+    /// * `key` - handle to a key.
+    /// * `plaintext` - data to encrypt
+    /// * `context` - context to bind ciphertext to
+    fn encrypt_with_context(&self, key: &Self::Key, plaintext: &[u8], context: &[u8]) -> Result<CryptoBuffer>;
+
+    /// Decrypts a BLOB bound to a context using a provided key.
+    /// 
+    /// For backward compatibility BLOBs encrypted by [`CryptoEngine::encrypt`]
+    /// (i.e. without context) are decrypted as well.
+    /// 
+    /// * `key` - handle to a key.
+    /// * `ciphertext` - data to decrypt
+    /// * `context` - context, that ciphertext is bound to
+    fn decrypt_with_context(&self, key: &Self::Key, ciphertext: &[u8], context: &[u8]) -> Result<CryptoBuffer>;
+
+    /// Decrypts a BLOB bound to a context using a new key of the rotation
+    /// in progress, i.e. checks if data is already encrypted for it.
+    /// 
+    /// Fails if no rotation is in progress.
+    /// 
+    /// * `key` - handle to a key.
+    /// * `ciphertext` - data to decrypt
+    /// * `context` - context, that ciphertext is bound to
+    fn decrypt_pending_with_context(&self, key: &Self::Key, ciphertext: &[u8], context: &[u8]) -> Result<CryptoBuffer>;
+
+    /// Decrypts several BLOBs bound to contexts using a provided key.
+    /// 
+    /// Default implementation just calls [`CryptoEngine::decrypt_with_context`] 
+    /// for each BLOB. Engines may override it to amortize per-call overhead.
+    /// 
+    /// * `key` - handle to a key.
+    /// * `ciphertexts` - data to decrypt with corresponding contexts
+    fn decrypt_many_with_context(&self, key: &Self::Key, ciphertexts: &[(&[u8], &[u8])]) -> Result<Vec<CryptoBuffer>> {
+        ciphertexts
+            .iter()
+            .map(|(ciphertext, context)| self.decrypt_with_context(key, ciphertext, context))
+            .collect()
+    }
+
+    /// Encrypts several BLOBs using a provided key.
+    /// 
+    /// Default implementation just calls [`CryptoEngine::encrypt`] 
+    /// for each BLOB. Engines may override it to amortize per-call overhead.
+    /// 
+    /// * `key` - handle to a key.
+    /// * `plaintexts` - data to encrypt
+    fn encrypt_many(&self, key: &Self::Key, plaintexts: &[&[u8]]) -> Result<Vec<CryptoBuffer>> {
+        plaintexts
+            .iter()
+            .map(|plaintext| self.encrypt(key, plaintext))
+            .collect()
+    }
+
+    /// Decrypts several BLOBs using a provided key.
+    /// 
+    /// Default implementation just calls [`CryptoEngine::decrypt`] 
+    /// for each BLOB. Engines may override it to amortize per-call overhead.
+    /// 
+    /// * `key` - handle to a key.
+    /// * `ciphertexts` - data to decrypt
+    fn decrypt_many(&self, key: &Self::Key, ciphertexts: &[&[u8]]) -> Result<Vec<CryptoBuffer>> {
+        ciphertexts
+            .iter()
+            .map(|ciphertext| self.decrypt(key, ciphertext))
+            .collect()
+    }
+
     /// Encrypts a BLOB symmetrically using a provided key.
     /// 
-    /// This method mey be unsupported by some engines.
+    /// This method may be unsupported by some engines, in this case
+    /// an error is returned. Key length must be equal to 
+    /// [`CryptoEngine::symmetric_key_length`], otherwise an error 
+    /// is returned as well.
     /// 
     /// * `key` - binary key.
     /// * `plaintext` - data to encrypt
@@ -63,9 +175,35 @@ pub trait CryptoEngine {
 
     /// Decrypts a BLOB symmetrically using a provided key.
     /// 
-    /// This method mey be unsupported by some engines.
+    /// This method may be unsupported by some engines, in this case
+    /// an error is returned. Key length must be equal to 
+    /// [`CryptoEngine::symmetric_key_length`], otherwise an error 
+    /// is returned as well.
     /// 
     /// * `key` - binary key.
     /// * `ciphertext` - data to decrypt
     fn decrypt_symmetric(&self, key: &[u8], ciphertext: &[u8]) -> Result<CryptoBuffer>;
+
+    /// Encrypts a stream symmetrically using a provided key.
+    /// 
+    /// Data is processed chunk by chunk, hence it is not required to 
+    /// keep all of it in memory. The same restrictions as for 
+    /// [`CryptoEngine::encrypt_symmetric`] are applied.
+    /// 
+    /// * `key` - binary key.
+    /// * `reader` - source of data to encrypt
+    /// * `writer` - destination of encrypted data
+    fn encrypt_symmetric_stream<R: std::io::Read, W: std::io::Write>(&self, key: &[u8], reader: R, writer: W) -> Result<()>;
+
+    /// Decrypts a stream symmetrically using a provided key.
+    /// 
+    /// Data is processed chunk by chunk, hence it is not required to 
+    /// keep all of it in memory. The same restrictions as for 
+    /// [`CryptoEngine::decrypt_symmetric`] are applied. If an error is
+    /// returned, part of plaintext may be written already.
+    /// 
+    /// * `key` - binary key.
+    /// * `reader` - source of data to decrypt
+    /// * `writer` - destination of decrypted data
+    fn decrypt_symmetric_stream<R: std::io::Read, W: std::io::Write>(&self, key: &[u8], reader: R, writer: W) -> Result<()>;
 }
@@ -1,14 +1,21 @@
 use std::ffi::CString;
-use std::cell::{RefCell, RefMut};
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+use std::collections::HashSet;
+
+use base64ct::{Base64, Encoding};
+use sha2::{Digest, Sha256};
 
 use crate::error::{Error, Result};
 use crate::location::Location;
+use crate::datetime::Timestamp;
 use super::prng::Prng;
 use super::engine::CryptoEngine;
 use super::buffer::CryptoBuffer;
 use super::symmetric::SymmetricCipher;
-use super::key::{Key, KeyId, KeyHandle, KeyIdentifier};
+use super::key::{Key, KeyId, KeyInfo, KeyHandle, KeyIdentifier};
 use super::{MISSING_SECRET_KEY, KEY_IS_NOT_SUITABLE, ENCRYPTION_ERROR, DECRYPTION_ERROR, INVALID_ENGINE_STATE};
+use super::{INVALID_SYMMETRIC_KEY, MALFORMED_DATA_KEY_BACKUP};
+use super::{KEY_IS_REVOKED, KEY_IS_EXPIRED, KEY_IS_DISABLED, KEY_IS_INVALID, KEY_CANNOT_ENCRYPT};
 
 
 /// Homan-friendly name of GPG engine.
@@ -17,6 +24,21 @@ const ENGINE_NAME: &str = "GnuPG";
 /// Name of file with symmetric encryption key.
 const SYMMETRIC_KEY_FILE: &str = "symm";
 
+/// Name of file with new symmetric encryption key during key rotation.
+const PENDING_SYMMETRIC_KEY_FILE: &str = "symm.new";
+
+/// First line of exported data key.
+const DATA_KEY_BACKUP_BEGIN: &str = "-----BEGIN BDGT DATA KEY-----";
+
+/// Last line of exported data key.
+const DATA_KEY_BACKUP_END: &str = "-----END BDGT DATA KEY-----";
+
+/// Length of a line with base64 data in exported data key.
+const DATA_KEY_BACKUP_LINE_LENGTH: usize = 64;
+
+/// Size of a checksum appended to exported data key.
+const DATA_KEY_BACKUP_CHECKSUM_SIZE: usize = 4;
+
 
 /// Engine-specific key identifier type.
 type NativeId = CString;
@@ -45,11 +67,54 @@ impl KeyHandle for NativeHandle {
     fn can_encrypt(&self) -> bool {
         self.can_encrypt()
     }
+
+    fn fingerprint(&self) -> Option<String> {
+        self.fingerprint()
+            .ok()
+            .map(str::to_owned)
+    }
+}
+
+
+/// GPGME context, that can be moved between threads.
+/// 
+/// GPGME allows to use a context from any thread, but not from
+/// several threads at once. The latter is ensured by a mutex,
+/// which the context is always kept in.
+struct SendableContext(gpgme::Context);
+
+unsafe impl Send for SendableContext {}
+
+impl std::ops::Deref for SendableContext {
+    type Target = gpgme::Context;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for SendableContext {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+
+/// Locks a mutex. Poisoned mutex is still usable, because
+/// its content is never left in inconsistent state.
+/// 
+/// * `mutex` - mutex to lock
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock()
+        .unwrap_or_else(PoisonError::into_inner)
 }
 
 
 /// Encrypted passphrase holder.
 struct EncryptedKey {
+    /// Path to encrypted passphrase file.
+    path: std::path::PathBuf,
+
     /// Encrypted passphrase data. Initialized in constructor.
     encrypted_buffer: CryptoBuffer,
 
@@ -68,11 +133,31 @@ impl EncryptedKey {
         //
 
         Ok(EncryptedKey { 
+            path: path.to_owned(),
             encrypted_buffer: CryptoBuffer::from(std::fs::read(path)?), 
             decrypted_buffer: CryptoBuffer::default(), 
         })
     }
 
+    /// Wrap a new passphrase and save it.
+    /// 
+    /// * `path` - path to encrypted passphrase file
+    /// * `passphrase` - passphrase to wrap
+    /// * `keys` - keys used to encrypt passphrase
+    /// * `engine` - engine used to encrypt passphrase
+    pub fn create(path: &std::path::Path, passphrase: CryptoBuffer, keys: &[<GpgCryptoEngine as CryptoEngine>::Key], 
+        engine: &GpgCryptoEngine) -> Result<Self> 
+    {
+        let encrypted_buffer = engine.encrypt_asymmetric(keys, passphrase.as_bytes())?;
+        std::fs::write(path, encrypted_buffer.as_bytes())?;
+
+        Ok(EncryptedKey { 
+            path: path.to_owned(),
+            encrypted_buffer, 
+            decrypted_buffer: passphrase, 
+        })
+    }
+
     /// Decrypt passphrase if not decrypted yet.
     /// 
     /// * `key` - key used to decrypt passphrase
@@ -103,15 +188,30 @@ impl EncryptedKey {
 /// encrypted form to file.
 /// 
 /// In another word, [`GpgCryptoEngine`] performs hybrid encryption.
+/// 
+/// Engine can be shared between threads. GnuPG operations (key lookup
+/// and unwrapping of symmetric key) are serialized on an internal lock,
+/// while symmetric encryption runs concurrently once the symmetric key
+/// is decrypted.
 pub struct GpgCryptoEngine {
     /// Internal engine handle.
     engine: gpgme::Gpgme,
 
     /// Internal context.
-    ctx: RefCell<gpgme::Context>,
+    ctx: Mutex<SendableContext>,
 
     /// Encrypted symmetric key provider.
-    symmetric_key: Option<RefCell<EncryptedKey>>,
+    symmetric_key: Option<Mutex<EncryptedKey>>,
+
+    /// Symmetric cipher. Constructed once after the first
+    /// successful decryption of the symmetric key.
+    cipher: OnceLock<SymmetricCipher>,
+
+    /// New encrypted symmetric key provider, exists only during key rotation.
+    pending_symmetric_key: Option<Mutex<EncryptedKey>>,
+
+    /// Symmetric cipher for new symmetric key.
+    pending_cipher: OnceLock<SymmetricCipher>,
 }
 
 
@@ -123,7 +223,14 @@ impl GpgCryptoEngine {
     }
 
     /// Creates a cryptographic engine for bdgt and initializes it.
-    pub fn create<L: Location>(loc: &L, key_id: &<Self as CryptoEngine>::KeyId) -> Result<Self> {
+    /// 
+    /// Symmetric key is encrypted for all provided keys, hence any of
+    /// them can be used to open the engine. At least one secret key
+    /// must be available locally.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `key_ids` - identifiers of recipient keys
+    pub fn create<L: Location>(loc: &L, key_ids: &[<Self as CryptoEngine>::KeyId]) -> Result<Self> {
         //
         // Location for config may be absent
         //
@@ -131,7 +238,7 @@ impl GpgCryptoEngine {
         loc.create_if_absent()?;
         
         Self::new()
-            .and_then(|engine| engine.create_symmetric_key(loc, key_id))
+            .and_then(|engine| engine.create_symmetric_key(loc, key_ids))
     }
 
     /// Opens a cryptographic engine for bdgt.
@@ -139,6 +246,153 @@ impl GpgCryptoEngine {
         Self::new()
             .and_then(|engine| engine.open_symmetric_key(loc))
     }
+
+    /// Lists all keys available in keyring with their suitability for bdgt.
+    /// 
+    /// Does not require an initialized engine, hence it can be used
+    /// to choose keys before [`GpgCryptoEngine::create`].
+    pub fn available_keys() -> Result<Vec<KeyInfo>> {
+        Self::new()?
+            .list_keys()
+    }
+
+    /// Lists all keys available in keyring with their suitability for bdgt.
+    /// 
+    /// Suitability verdict is the same as one used during key lookup.
+    pub fn list_keys(&self) -> Result<Vec<KeyInfo>> {
+        let mut locked_ctx = self.context();
+
+        //
+        // Secret keys are listed separately, public key 
+        // listing does not tell if secret key is present
+        //
+
+        let secret_fingerprints: HashSet<String> = locked_ctx
+            .secret_keys()?
+            .filter_map(|key| key.ok())
+            .filter_map(|key| key.fingerprint().ok().map(str::to_owned))
+            .collect();
+
+        let mut keys = Vec::new();
+        for key in locked_ctx.keys()? {
+            let key = key?;
+
+            let id = match key.fingerprint() {
+                Ok(fingerprint) => fingerprint.to_owned(),
+                Err(_) => continue
+            };
+
+            let user_ids = key
+                .user_ids()
+                .filter_map(|user_id| user_id.id().ok().map(str::to_owned))
+                .collect();
+
+            let expires = key
+                .primary_key()
+                .and_then(|primary_key| primary_key.expiration_time())
+                .map(Timestamp::from);
+
+            let reason = Self::unsuitability_reason(&key, secret_fingerprints.contains(&id));
+
+            keys.push(KeyInfo {
+                id,
+                user_ids,
+                expires,
+                is_suitable: reason.is_none(),
+                unsuitable_reason: reason.map(str::to_owned)
+            });
+        }
+
+        Ok(keys)
+    }
+
+    /// Encrypts symmetric key for an additional key.
+    /// 
+    /// Existing recipients are preserved, only secret key of any 
+    /// of them is required.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `key_id` - identifier of a key to add
+    pub fn add_recipient<L: Location>(&self, loc: &L, key_id: &<Self as CryptoEngine>::KeyId) -> Result<()> {
+        let symmetric_key = self.symmetric_key
+            .as_ref()
+            .ok_or(Error::new(INVALID_ENGINE_STATE))?;
+
+        let new_recipient = self.lookup_recipient(key_id)?;
+
+        //
+        // Existing recipients are obtained from the encrypted key itself
+        //
+
+        let (passphrase, mut recipients) = self.decrypt_with_recipients(
+            lock(symmetric_key).encrypted_buffer.as_bytes())?;
+        recipients.push(new_recipient);
+
+        let encrypted_buffer = self.encrypt_asymmetric(&recipients, passphrase.as_bytes())?;
+        std::fs::write(Self::symmetric_key_file(loc), encrypted_buffer.as_bytes())?;
+
+        lock(symmetric_key)
+            .encrypted_buffer = encrypted_buffer;
+
+        Ok(())
+    }
+
+    /// Exports wrapped symmetric key in printable form for disaster recovery.
+    /// 
+    /// Symmetric key remains encrypted for all its recipients, hence
+    /// backup is useless without a secret key. Backup contains a checksum,
+    /// that is verified by [`GpgCryptoEngine::import_data_key`].
+    /// 
+    /// * `key` - key used to verify, that backup can be decrypted
+    pub fn export_data_key(&self, key: &<Self as CryptoEngine>::Key) -> Result<CryptoBuffer> {
+        let symmetric_key = self.decrypt_symmetric_key(&self.symmetric_key, key)?;
+
+        let checksum = Self::data_key_checksum(symmetric_key.encrypted_buffer.as_bytes());
+        let payload = CryptoBuffer::from(symmetric_key.encrypted_buffer.as_bytes())
+            .append(checksum.as_slice());
+
+        let encoded = Base64::encode_string(payload.as_bytes());
+
+        let mut lines = vec![DATA_KEY_BACKUP_BEGIN];
+        lines.extend(encoded
+            .as_bytes()
+            .chunks(DATA_KEY_BACKUP_LINE_LENGTH)
+            .map(|line| std::str::from_utf8(line).unwrap_or_default()));
+        lines.push(DATA_KEY_BACKUP_END);
+
+        Ok(CryptoBuffer::from(lines.join("\n").into_bytes()))
+    }
+
+    /// Restores symmetric key file from a backup made 
+    /// by [`GpgCryptoEngine::export_data_key`].
+    /// 
+    /// Backup is verified before anything is overwritten: checksum must
+    /// match and backup must be decryptable with a secret key.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `key_id` - identifier of a key to decrypt backup with
+    /// * `wrapped` - exported backup
+    pub fn import_data_key<L: Location>(loc: &L, key_id: &<Self as CryptoEngine>::KeyId, wrapped: &[u8]) -> Result<()> {
+        let encrypted_key = Self::decode_data_key_backup(wrapped)?;
+
+        //
+        // Ensure, that backup contains a valid symmetric key
+        // and only then write it
+        //
+
+        let engine = Self::new()?;
+        let key = engine.lookup_key(key_id)?;
+
+        let symmetric_key = engine.decrypt_asymmetric(&key, encrypted_key.as_bytes())?;
+        if symmetric_key.as_bytes().len() != SymmetricCipher::key_size() {
+            return Err(Error::new(INVALID_SYMMETRIC_KEY));
+        }
+
+        loc.create_if_absent()?;
+        std::fs::write(Self::symmetric_key_file(loc), encrypted_key.as_bytes())?;
+
+        Ok(())
+    }
 }
 
 
@@ -159,32 +413,161 @@ impl CryptoEngine for GpgCryptoEngine {
         SymmetricCipher::key_size()
     }
 
+    fn key_fingerprint(&self, key: &Self::Key) -> Option<String> {
+        key.fingerprint()
+    }
+
     fn lookup_key(&self, id: &Self::KeyId) -> Result<Self::Key> {
-        let internal_key = self.ctx
-            .borrow_mut()
+        let internal_key = self.context()
             .get_key(id.native_id())?;
 
         self.verify_key(Key::new(internal_key, id))
     }
+
+    fn preload(&self, key: &Self::Key) -> Result<()> {
+        self.with_cipher(key, |_| Ok(()))
+    }
+    
+    fn begin_rotation(&mut self, new_key: &Self::Key) -> Result<()> {
+        if self.symmetric_key.is_none() || self.rotation_in_progress() {
+            return Err(Error::new(INVALID_ENGINE_STATE));
+        }
+
+        //
+        // New symmetric key is saved into a separate file, hence
+        // current key remains valid until rotation is committed
+        //
+
+        let pending_path = Self::pending_symmetric_key_file(&self.symmetric_key_path());
+
+        let mut symmetric_key = CryptoBuffer::new_with_size(SymmetricCipher::key_size());
+        Prng::new()
+            .generate(symmetric_key.as_mut_bytes())?;
+
+        let pending_symmetric_key = EncryptedKey::create(&pending_path, symmetric_key, std::slice::from_ref(new_key), self)?;
+
+        self.pending_cipher = OnceLock::from(SymmetricCipher::new(pending_symmetric_key.decrypted_buffer.as_bytes())?);
+        self.pending_symmetric_key = Some(Mutex::new(pending_symmetric_key));
+
+        Ok(())
+    }
+
+    fn rotation_in_progress(&self) -> bool {
+        self.pending_symmetric_key.is_some()
+    }
+
+    fn commit_rotation(&mut self) -> Result<()> {
+        let mut pending_symmetric_key = self.pending_symmetric_key
+            .take()
+            .ok_or(Error::new(INVALID_ENGINE_STATE))?
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        //
+        // Renaming is atomic, hence symmetric key file
+        // always contains either old or new key
+        //
+
+        let path = self.symmetric_key_path();
+        std::fs::rename(&pending_symmetric_key.path, &path)?;
+
+        pending_symmetric_key.path = path;
+        self.symmetric_key = Some(Mutex::new(pending_symmetric_key));
+        self.cipher = std::mem::take(&mut self.pending_cipher);
+
+        Ok(())
+    }
+
+    fn abort_rotation(&mut self) -> Result<()> {
+        let pending_symmetric_key = self.pending_symmetric_key
+            .take()
+            .ok_or(Error::new(INVALID_ENGINE_STATE))?
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        self.pending_cipher = OnceLock::new();
+        std::fs::remove_file(pending_symmetric_key.path)?;
+
+        Ok(())
+    }
     
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = plaintext.len())))]
     fn encrypt(&self, key: &Self::Key, plaintext: &[u8]) -> Result<CryptoBuffer> {
-        let symmetric_key = self.decrypt_symmetric_key(key)?;
-        self.encrypt_symmetric(symmetric_key.decrypted_buffer.as_bytes(), plaintext)
+        self.with_encryption_cipher(key, |cipher| cipher.encrypt(plaintext, &[]))
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = ciphertext.len())))]
     fn decrypt(&self, key: &Self::Key, ciphertext: &[u8]) -> Result<CryptoBuffer> {
-        let symmetric_key = self.decrypt_symmetric_key(key)?;
-        self.decrypt_symmetric(symmetric_key.decrypted_buffer.as_bytes(), ciphertext)
+        self.with_cipher(key, |cipher| cipher.decrypt(ciphertext, &[]))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = plaintext.len())))]
+    fn encrypt_with_context(&self, key: &Self::Key, plaintext: &[u8], context: &[u8]) -> Result<CryptoBuffer> {
+        self.with_encryption_cipher(key, |cipher| cipher.encrypt(plaintext, context))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = ciphertext.len())))]
+    fn decrypt_with_context(&self, key: &Self::Key, ciphertext: &[u8], context: &[u8]) -> Result<CryptoBuffer> {
+        self.with_cipher(key, |cipher| cipher.decrypt_compatible(ciphertext, context))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = ciphertext.len())))]
+    fn decrypt_pending_with_context(&self, key: &Self::Key, ciphertext: &[u8], context: &[u8]) -> Result<CryptoBuffer> {
+        if !self.rotation_in_progress() {
+            return Err(Error::new(INVALID_ENGINE_STATE));
+        }
+
+        self.with_pending_cipher(key, |cipher| cipher.decrypt_compatible(ciphertext, context))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(count = ciphertexts.len())))]
+    fn decrypt_many_with_context(&self, key: &Self::Key, ciphertexts: &[(&[u8], &[u8])]) -> Result<Vec<CryptoBuffer>> {
+        self.with_cipher(key, |cipher| {
+            ciphertexts
+                .iter()
+                .map(|(ciphertext, context)| cipher.decrypt_compatible(ciphertext, context))
+                .collect()
+        })
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(count = plaintexts.len())))]
+    fn encrypt_many(&self, key: &Self::Key, plaintexts: &[&[u8]]) -> Result<Vec<CryptoBuffer>> {
+        self.with_encryption_cipher(key, |cipher| {
+            plaintexts
+                .iter()
+                .map(|plaintext| cipher.encrypt(plaintext, &[]))
+                .collect()
+        })
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(count = ciphertexts.len())))]
+    fn decrypt_many(&self, key: &Self::Key, ciphertexts: &[&[u8]]) -> Result<Vec<CryptoBuffer>> {
+        self.with_cipher(key, |cipher| {
+            ciphertexts
+                .iter()
+                .map(|ciphertext| cipher.decrypt(ciphertext, &[]))
+                .collect()
+        })
     }
 
     fn encrypt_symmetric(&self, key: &[u8], plaintext: &[u8]) -> Result<CryptoBuffer> {
         let cipher = SymmetricCipher::new(key)?;
-        cipher.encrypt(plaintext)
+        cipher.encrypt(plaintext, &[])
     }
 
     fn decrypt_symmetric(&self, key: &[u8], ciphertext: &[u8]) -> Result<CryptoBuffer> {
         let cipher = SymmetricCipher::new(key)?;
-        cipher.decrypt(ciphertext)
+        cipher.decrypt(ciphertext, &[])
+    }
+
+    fn encrypt_symmetric_stream<R: std::io::Read, W: std::io::Write>(&self, key: &[u8], reader: R, writer: W) -> Result<()> {
+        let cipher = SymmetricCipher::new(key)?;
+        cipher.encrypt_stream(reader, writer)
+    }
+
+    fn decrypt_symmetric_stream<R: std::io::Read, W: std::io::Write>(&self, key: &[u8], reader: R, writer: W) -> Result<()> {
+        let cipher = SymmetricCipher::new(key)?;
+        cipher.decrypt_stream(reader, writer)
     }
 }
 
@@ -195,17 +578,33 @@ impl GpgCryptoEngine {
 
         Ok(GpgCryptoEngine { 
             engine: gpgme::init(),
-            ctx: RefCell::new(ctx),
+            ctx: Mutex::new(SendableContext(ctx)),
             symmetric_key: None,
+            cipher: OnceLock::new(),
+            pending_symmetric_key: None,
+            pending_cipher: OnceLock::new(),
         })
     }
 
-    fn create_symmetric_key<L: Location>(self, loc: &L, key_id: &<Self as CryptoEngine>::KeyId) -> Result<Self> {
+    fn create_symmetric_key<L: Location>(self, loc: &L, key_ids: &[<Self as CryptoEngine>::KeyId]) -> Result<Self> {
         //
-        // Check if key exists and suitable for encryption
+        // Check if keys exist and suitable for encryption,
+        // and at least one of them can be used for decryption
         //
 
-        let key = self.lookup_key(key_id)?;
+        let keys = key_ids
+            .iter()
+            .map(|key_id| self.lookup_recipient(key_id))
+            .collect::<Result<Vec<_>>>()?;
+
+        if !key_ids.iter().any(|key_id| self.lookup_key(key_id).is_ok()) {
+            let ids: Vec<String> = key_ids
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+
+            return Err(Error::new_with_extra(MISSING_SECRET_KEY, ids.join(", ")));
+        }
 
         //
         // Create a random key using standard PRNG (cryptographically secure)
@@ -216,7 +615,7 @@ impl GpgCryptoEngine {
         Prng::new()
             .generate(symmetric_key.as_mut_bytes())?;
 
-        let encrypted_key = self.encrypt_asymmetric(&key, symmetric_key.as_bytes())?;
+        let encrypted_key = self.encrypt_asymmetric(&keys, symmetric_key.as_bytes())?;
         std::fs::write(Self::symmetric_key_file(loc), encrypted_key.as_bytes())?;
 
         //
@@ -227,8 +626,21 @@ impl GpgCryptoEngine {
     }
 
     fn open_symmetric_key<L: Location>(mut self, loc: &L) -> Result<Self> {
-        let encrypted_symmetric_key = EncryptedKey::new(&Self::symmetric_key_file(loc))?;
-        self.symmetric_key = Some(RefCell::new(encrypted_symmetric_key));
+        let path = Self::symmetric_key_file(loc);
+
+        let encrypted_symmetric_key = EncryptedKey::new(&path)?;
+        self.symmetric_key = Some(Mutex::new(encrypted_symmetric_key));
+
+        //
+        // If new key exists, then key rotation was interrupted
+        // and it is still in progress
+        //
+
+        let pending_path = Self::pending_symmetric_key_file(&path);
+        if pending_path.exists() {
+            let pending_symmetric_key = EncryptedKey::new(&pending_path)?;
+            self.pending_symmetric_key = Some(Mutex::new(pending_symmetric_key));
+        }
 
         Ok(self)
     }
@@ -237,16 +649,72 @@ impl GpgCryptoEngine {
         loc.root()
             .join(SYMMETRIC_KEY_FILE)
     }
+
+    fn pending_symmetric_key_file(symmetric_key_file: &std::path::Path) -> std::path::PathBuf {
+        symmetric_key_file
+            .with_file_name(PENDING_SYMMETRIC_KEY_FILE)
+    }
+
+    fn data_key_checksum(data: &[u8]) -> [u8; DATA_KEY_BACKUP_CHECKSUM_SIZE] {
+        let digest = Sha256::digest(data);
+
+        let mut checksum = [0u8; DATA_KEY_BACKUP_CHECKSUM_SIZE];
+        checksum.copy_from_slice(&digest[..DATA_KEY_BACKUP_CHECKSUM_SIZE]);
+
+        checksum
+    }
+
+    fn decode_data_key_backup(wrapped: &[u8]) -> Result<CryptoBuffer> {
+        let wrapped = std::str::from_utf8(wrapped)
+            .map_err(|_| Error::new(MALFORMED_DATA_KEY_BACKUP))?;
+
+        //
+        // Backup may be re-entered manually, hence I ignore
+        // whitespaces and armor lines
+        //
+
+        let encoded: String = wrapped
+            .lines()
+            .map(str::trim)
+            .filter(|line| *line != DATA_KEY_BACKUP_BEGIN && *line != DATA_KEY_BACKUP_END)
+            .flat_map(|line| line.chars().filter(|c| !c.is_whitespace()))
+            .collect();
+
+        let payload = CryptoBuffer::from(Base64::decode_vec(&encoded)
+            .map_err(|_| Error::new(MALFORMED_DATA_KEY_BACKUP))?);
+
+        if payload.as_bytes().len() <= DATA_KEY_BACKUP_CHECKSUM_SIZE {
+            return Err(Error::new(MALFORMED_DATA_KEY_BACKUP));
+        }
+
+        let (encrypted_key, checksum) = payload
+            .as_bytes()
+            .split_at(payload.as_bytes().len() - DATA_KEY_BACKUP_CHECKSUM_SIZE);
+
+        let expected_checksum = CryptoBuffer::from(Self::data_key_checksum(encrypted_key).as_slice());
+        if !expected_checksum.ct_eq(&CryptoBuffer::from(checksum)) {
+            return Err(Error::new(MALFORMED_DATA_KEY_BACKUP));
+        }
+
+        Ok(CryptoBuffer::from(encrypted_key))
+    }
+
+    fn symmetric_key_path(&self) -> std::path::PathBuf {
+        self.symmetric_key
+            .as_ref()
+            .map(|symmetric_key| lock(symmetric_key).path.clone())
+            .unwrap_or_default()
+    }
 }
 
 
 impl GpgCryptoEngine {
     fn verify_key(&self, key: <Self as CryptoEngine>::Key) -> Result<<Self as CryptoEngine>::Key> {
         //
-        // Borrow context for the entire function life
+        // Lock context for the entire function life
         //
 
-        let mut borrowed_ctx = self.ctx.borrow_mut();
+        let mut locked_ctx = self.context();
 
         //
         // Check if there is corresponding private key
@@ -257,10 +725,10 @@ impl GpgCryptoEngine {
             .clone();
 
         let key_ids = [id.native_id()];
-        let secret_keys = borrowed_ctx.find_secret_keys(key_ids)?;
+        let secret_keys = locked_ctx.find_secret_keys(key_ids)?;
 
         if 0 == secret_keys.count() {
-            return Err(Error::from_message_with_extra(MISSING_SECRET_KEY, id.to_string()));
+            return Err(Error::new_with_extra(MISSING_SECRET_KEY, id.to_string()));
         }
 
         //
@@ -269,31 +737,160 @@ impl GpgCryptoEngine {
 
         key.is_suitable()
             .then_some(key)
-            .ok_or(Error::from_message_with_extra(KEY_IS_NOT_SUITABLE, id.to_string()))
+            .ok_or(Error::new_with_extra(KEY_IS_NOT_SUITABLE, id.to_string()))
     }
 
-    fn decrypt_symmetric_key(&self, key: &<Self as CryptoEngine>::Key) -> Result<RefMut<'_, EncryptedKey>> {
-        if self.symmetric_key.is_none() {
-            return Err(Error::from_message(INVALID_ENGINE_STATE));
+    fn unsuitability_reason(key: &NativeHandle, has_secret_key: bool) -> Option<&'static str> {
+        //
+        // The same checks as in verify_key, but with detailed reason
+        //
+
+        if !has_secret_key {
+            return Some(MISSING_SECRET_KEY.text());
+        }
+
+        if key.is_revoked() {
+            return Some(KEY_IS_REVOKED);
+        }
+
+        if key.is_expired() {
+            return Some(KEY_IS_EXPIRED);
         }
 
-        let mut borrowed_symmetric_key = self.symmetric_key
+        if key.is_disabled() {
+            return Some(KEY_IS_DISABLED);
+        }
+
+        if key.is_invalid() {
+            return Some(KEY_IS_INVALID);
+        }
+
+        if !KeyHandle::can_encrypt(key) {
+            return Some(KEY_CANNOT_ENCRYPT);
+        }
+
+        None
+    }
+
+    fn context(&self) -> MutexGuard<'_, SendableContext> {
+        lock(&self.ctx)
+    }
+
+    fn decrypt_symmetric_key<'a>(&self, symmetric_key: &'a Option<Mutex<EncryptedKey>>, 
+        key: &<Self as CryptoEngine>::Key) -> Result<MutexGuard<'a, EncryptedKey>> 
+    {
+        if symmetric_key.is_none() {
+            return Err(Error::new(INVALID_ENGINE_STATE));
+        }
+
+        let mut locked_symmetric_key = lock(symmetric_key
             .as_ref()
-            .unwrap()
-            .borrow_mut();
+            .unwrap());
 
-        borrowed_symmetric_key
+        locked_symmetric_key
             .decrypt(key, self)?;
 
-        Ok(borrowed_symmetric_key)
+        Ok(locked_symmetric_key)
+    }
+
+    fn with_cipher<F, R>(&self, key: &<Self as CryptoEngine>::Key, f: F) -> Result<R>
+    where
+        F: FnOnce(&SymmetricCipher) -> Result<R>
+    {
+        Self::with_cipher_of(&self.cipher, || self.decrypt_symmetric_key(&self.symmetric_key, key), f)
+    }
+
+    fn with_encryption_cipher<F, R>(&self, key: &<Self as CryptoEngine>::Key, f: F) -> Result<R>
+    where
+        F: FnOnce(&SymmetricCipher) -> Result<R>
+    {
+        //
+        // During key rotation data is encrypted using new key
+        //
+
+        match self.rotation_in_progress() {
+            true => self.with_pending_cipher(key, f),
+            false => self.with_cipher(key, f)
+        }
+    }
+
+    fn with_pending_cipher<F, R>(&self, key: &<Self as CryptoEngine>::Key, f: F) -> Result<R>
+    where
+        F: FnOnce(&SymmetricCipher) -> Result<R>
+    {
+        Self::with_cipher_of(&self.pending_cipher, || self.decrypt_symmetric_key(&self.pending_symmetric_key, key), f)
     }
 
-    fn encrypt_asymmetric(&self, key: &<Self as CryptoEngine>::Key, plaintext: &[u8]) -> Result<CryptoBuffer> {
-        let keys = [key.native_handle()];
+    fn with_cipher_of<'a, K, F, R>(cipher: &OnceLock<SymmetricCipher>, symmetric_key: K, f: F) -> Result<R>
+    where
+        K: FnOnce() -> Result<MutexGuard<'a, EncryptedKey>>,
+        F: FnOnce(&SymmetricCipher) -> Result<R>
+    {
+        if let Some(cipher) = cipher.get() {
+            return f(cipher);
+        }
+
+        //
+        // Cipher is constructed once, because symmetric key never
+        // changes during engine's life. Several threads may construct
+        // it at once, but only the first one is kept, since all of
+        // them are the same anyway
+        //
+
+        let constructed = {
+            let symmetric_key = symmetric_key()?;
+            SymmetricCipher::new(symmetric_key.decrypted_buffer.as_bytes())?
+        };
+
+        f(cipher.get_or_init(|| constructed))
+    }
+
+    fn lookup_recipient(&self, id: &<Self as CryptoEngine>::KeyId) -> Result<<Self as CryptoEngine>::Key> {
+        //
+        // Recipient may have no secret key locally, hence
+        // only key properties are verified here
+        //
+
+        let internal_key = self.context()
+            .get_key(id.native_id())?;
+
+        let key = Key::new(internal_key, id);
+
+        key.is_suitable()
+            .then_some(key)
+            .ok_or(Error::new_with_extra(KEY_IS_NOT_SUITABLE, id.to_string()))
+    }
+
+    fn decrypt_with_recipients(&self, ciphertext: &[u8]) -> Result<(CryptoBuffer, Vec<<Self as CryptoEngine>::Key>)> {
+        let mut plaintext = Vec::new();
+
+        let result = self.context()
+            .decrypt(ciphertext, &mut plaintext)?;
+
+        let recipient_ids: Vec<String> = result
+            .recipients()
+            .filter_map(|recipient| recipient.key_id().ok().map(str::to_owned))
+            .collect();
+
+        Self::check_decryption_result(result)?;
+
+        let recipients = recipient_ids
+            .iter()
+            .map(|id| self.lookup_recipient(&KeyId::from_str(id)))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok((CryptoBuffer::from(plaintext), recipients))
+    }
+
+    fn encrypt_asymmetric(&self, keys: &[<Self as CryptoEngine>::Key], plaintext: &[u8]) -> Result<CryptoBuffer> {
+        let keys: Vec<_> = keys
+            .iter()
+            .map(|key| key.native_handle())
+            .collect();
+
         let mut ciphertext = Vec::new();
 
-        self.ctx
-            .borrow_mut()
+        self.context()
             .encrypt(keys, plaintext, &mut ciphertext)
             .map_err(Error::from)
             .and_then(Self::check_encryption_result)
@@ -303,8 +900,7 @@ impl GpgCryptoEngine {
     fn decrypt_asymmetric(&self, _key: &<Self as CryptoEngine>::Key, ciphertext: &[u8]) -> Result<CryptoBuffer> {
         let mut plaintext = Vec::new();
 
-        self.ctx
-            .borrow_mut()
+        self.context()
             .decrypt(ciphertext, &mut plaintext)
             .map_err(Error::from)
             .and_then(Self::check_decryption_result)
@@ -318,7 +914,7 @@ impl GpgCryptoEngine {
 
         (0 == invalid_count)
             .then_some(())
-            .ok_or(Error::from_message(ENCRYPTION_ERROR))
+            .ok_or(Error::new(ENCRYPTION_ERROR))
     }
 
     fn check_decryption_result(result: gpgme::DecryptionResult) -> Result<()> {
@@ -326,6 +922,6 @@ impl GpgCryptoEngine {
 
         correct
             .then_some(())
-            .ok_or(Error::from_message(DECRYPTION_ERROR))
+            .ok_or(Error::new(DECRYPTION_ERROR))
     }
 }
@@ -1,5 +1,125 @@
-use crate::error::Result;
+use crate::error::{Error, Result};
 use super::buffer::CryptoBuffer;
+use super::MALFORMED_KDF_PARAMS;
+
+
+/// Key derivation algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KdfAlgorithm {
+    /// Scrypt algorithm
+    Scrypt,
+
+    /// Argon2id algorithm
+    Argon2id,
+}
+
+
+/// Parameters of key derivation.
+///
+/// Meaning of cost parameters depends on algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Key derivation algorithm
+    pub algorithm: KdfAlgorithm,
+
+    /// Scrypt: logarithm of CPU/memory cost (`log2(N)`),
+    /// Argon2id: memory size in KiB
+    pub memory_cost: u32,
+
+    /// Scrypt: block size (`r`),
+    /// Argon2id: number of iterations
+    pub time_cost: u32,
+
+    /// Degree of parallelism (`p`) for both algorithms
+    pub parallelism: u32,
+}
+
+
+impl KdfParams {
+    /// Size of binary representation of parameters.
+    pub(crate) const SIZE: usize = 1 + 3 * std::mem::size_of::<u32>();
+
+    /// Constructs Scrypt parameters.
+    ///
+    /// * `log_n` - logarithm of CPU/memory cost
+    /// * `r` - block size
+    /// * `p` - parallelism
+    pub fn scrypt(log_n: u8, r: u32, p: u32) -> Self {
+        KdfParams {
+            algorithm: KdfAlgorithm::Scrypt,
+            memory_cost: log_n as u32,
+            time_cost: r,
+            parallelism: p
+        }
+    }
+
+    /// Constructs Argon2id parameters.
+    ///
+    /// * `memory_kib` - memory size in KiB
+    /// * `iterations` - number of iterations
+    /// * `parallelism` - degree of parallelism
+    pub fn argon2id(memory_kib: u32, iterations: u32, parallelism: u32) -> Self {
+        KdfParams {
+            algorithm: KdfAlgorithm::Argon2id,
+            memory_cost: memory_kib,
+            time_cost: iterations,
+            parallelism
+        }
+    }
+
+    /// Converts parameters into binary representation.
+    pub(crate) fn to_bytes(self) -> [u8; Self::SIZE] {
+        let algorithm = match self.algorithm {
+            KdfAlgorithm::Scrypt => 0u8,
+            KdfAlgorithm::Argon2id => 1u8,
+        };
+
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0] = algorithm;
+        bytes[1..5].copy_from_slice(&self.memory_cost.to_le_bytes());
+        bytes[5..9].copy_from_slice(&self.time_cost.to_le_bytes());
+        bytes[9..13].copy_from_slice(&self.parallelism.to_le_bytes());
+
+        bytes
+    }
+
+    /// Restores parameters from binary representation.
+    ///
+    /// * `bytes` - binary representation of parameters
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::SIZE {
+            return Err(Error::new(MALFORMED_KDF_PARAMS));
+        }
+
+        let algorithm = match bytes[0] {
+            0 => KdfAlgorithm::Scrypt,
+            1 => KdfAlgorithm::Argon2id,
+            _ => return Err(Error::new(MALFORMED_KDF_PARAMS))
+        };
+
+        let read_u32 = |offset: usize| {
+            let mut value = [0u8; 4];
+            value.copy_from_slice(&bytes[offset..offset + 4]);
+            u32::from_le_bytes(value)
+        };
+
+        Ok(KdfParams {
+            algorithm,
+            memory_cost: read_u32(1),
+            time_cost: read_u32(5),
+            parallelism: read_u32(9)
+        })
+    }
+}
+
+
+impl Default for KdfParams {
+    /// Recommended Scrypt parameters, that are used by older versions.
+    fn default() -> Self {
+        Self::scrypt(scrypt::Params::RECOMMENDED_LOG_N, scrypt::Params::RECOMMENDED_R,
+            scrypt::Params::RECOMMENDED_P)
+    }
+}
 
 
 /// KDF implementation struct.
@@ -7,16 +127,34 @@ pub(crate) struct Kdf;
 
 
 impl Kdf {
-    /// Derives a symmetric key from password using Scrypt algorithm.
-    /// 
+    /// Derives a symmetric key from password.
+    ///
     /// * `pass` - password to derive key from
     /// * `salt` - salt to use for key derivation
     /// * `key_size` - size of key to derive in bytes
-    pub(crate) fn derive_key(pass: &[u8], salt: &[u8], key_size: usize) -> Result<CryptoBuffer> {
+    /// * `params` - key derivation algorithm and its parameters
+    pub(crate) fn derive_key(pass: &[u8], salt: &[u8], key_size: usize, params: &KdfParams) -> Result<CryptoBuffer> {
         let mut result = CryptoBuffer::new_with_size(key_size);
-        scrypt::scrypt(pass, salt, &scrypt::Params::recommended(), 
-            result.as_mut_bytes())?;
+
+        match params.algorithm {
+            KdfAlgorithm::Scrypt => {
+                let log_n = u8::try_from(params.memory_cost)
+                    .map_err(|_| Error::new(MALFORMED_KDF_PARAMS))?;
+
+                let scrypt_params = scrypt::Params::new(log_n, params.time_cost,
+                    params.parallelism, key_size)?;
+
+                scrypt::scrypt(pass, salt, &scrypt_params, result.as_mut_bytes())?;
+            },
+            KdfAlgorithm::Argon2id => {
+                let argon2_params = argon2::Params::new(params.memory_cost, params.time_cost,
+                    params.parallelism, Some(key_size))?;
+
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, argon2_params)
+                    .hash_password_into(pass, salt, result.as_mut_bytes())?;
+            }
+        }
 
         Ok(result)
     }
-}
\ No newline at end of file
+}
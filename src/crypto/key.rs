@@ -1,5 +1,7 @@
 use std::fmt::{Display, Formatter, Debug};
 
+use crate::datetime::Timestamp;
+
 
 /// Key identifier trait.
 /// 
@@ -25,6 +27,9 @@ pub trait KeyHandle {
 
     /// Checks if key is suitable for encryption.
     fn can_encrypt(&self) -> bool;
+
+    /// Returns full fingerprint of the key, if key has one.
+    fn fingerprint(&self) -> Option<String>;
 }
 
 
@@ -102,12 +107,13 @@ where
     /// * `id` - key identifier
     pub(crate) fn new(key: NativeHandle, id: &KeyId<NativeId>) -> Self {
         Key { 
-            key: key,
+            key,
             id: id.clone()
         }
     }
 
     /// Returns a native key handle.
+    #[cfg_attr(not(feature = "gpg"), allow(dead_code))]
     pub(crate) fn native_handle(&self) -> &NativeHandle {
         &self.key
     }
@@ -116,6 +122,7 @@ where
     /// 
     /// Key MUST NOT be expired, revoked nor disabled, and MUST be able 
     /// to perform encryption.
+    #[cfg_attr(not(feature = "gpg"), allow(dead_code))]
     pub(crate) fn is_suitable(&self) -> bool {
         let is_good = self.key.is_good();
         let can_encrypt = self.key.can_encrypt();
@@ -127,4 +134,34 @@ where
     pub fn id(&self) -> &KeyId<NativeId> {
         &self.id
     }
+
+    /// Returns full fingerprint of the key, if key has one.
+    /// 
+    /// Unlike identifier, fingerprint cannot be chosen by user,
+    /// hence it identifies key material unambiguously.
+    pub fn fingerprint(&self) -> Option<String> {
+        self.key.fingerprint()
+    }
+}
+
+
+/// Information about a key available to engine.
+/// 
+/// Used to present keys to user before engine is created.
+#[derive(Clone, Debug)]
+pub struct KeyInfo {
+    /// Key identifier as string (can be passed to [`KeyId::new`])
+    pub id: String,
+
+    /// User identifiers bound to the key
+    pub user_ids: Vec<String>,
+
+    /// Expiration time of the key, if any
+    pub expires: Option<Timestamp>,
+
+    /// Whether the key is suitable for bdgt
+    pub is_suitable: bool,
+
+    /// Human-readable reason, why the key is not suitable
+    pub unsuitable_reason: Option<String>,
 }
@@ -0,0 +1,74 @@
+use std::io::Read;
+
+use hmac::{Hmac, Mac as _};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use super::buffer::CryptoBuffer;
+use super::INVALID_SYMMETRIC_KEY;
+
+
+/// HMAC instance used internally.
+type HmacSha256 = Hmac<Sha256>;
+
+
+/// Size of a chunk of data read at once during authentication.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+
+/// Message authentication implementation struct.
+pub(crate) struct Mac;
+
+
+impl Mac {
+    /// Size of authentication tag and digest in bytes.
+    pub(crate) const TAG_SIZE: usize = 32;
+
+    /// Derives a subkey from a master key for a specific purpose.
+    ///
+    /// * `master_key` - key to derive subkey from
+    /// * `label` - purpose of a subkey, different labels produce independent subkeys
+    pub(crate) fn derive_subkey(master_key: &[u8], label: &[u8]) -> Result<CryptoBuffer> {
+        let mut mac = Self::keyed(master_key)?;
+        mac.update(label);
+
+        Ok(CryptoBuffer::from(mac.finalize().into_bytes().as_slice()))
+    }
+
+    /// Computes an unkeyed digest and an authentication tag over a prefix 
+    /// and data from a reader in one pass. Returns digest and tag.
+    ///
+    /// * `key` - authentication key
+    /// * `prefix` - data to authenticate before reader's data
+    /// * `reader` - source of data to authenticate (read until the end)
+    pub(crate) fn authenticate_stream<R: Read>(key: &[u8], prefix: &[u8], mut reader: R) -> Result<(CryptoBuffer, CryptoBuffer)> {
+        let mut mac = Self::keyed(key)?;
+        let mut hasher = Sha256::new();
+
+        mac.update(prefix);
+        hasher.update(prefix);
+
+        let mut chunk = vec![0u8; READ_CHUNK_SIZE];
+        loop {
+            let read = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into())
+            };
+
+            mac.update(&chunk[..read]);
+            hasher.update(&chunk[..read]);
+        }
+
+        Ok((
+            CryptoBuffer::from(hasher.finalize().as_slice()),
+            CryptoBuffer::from(mac.finalize().into_bytes().as_slice())
+        ))
+    }
+
+    fn keyed(key: &[u8]) -> Result<HmacSha256> {
+        HmacSha256::new_from_slice(key)
+            .map_err(|_| Error::new(INVALID_SYMMETRIC_KEY))
+    }
+}
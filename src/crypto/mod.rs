@@ -1,34 +1,87 @@
 mod key;
 mod kdf;
+mod mac;
 mod prng;
 mod buffer;
 mod engine;
 mod symmetric;
+#[cfg(feature = "gpg")]
 mod gpg_engine;
+mod password_engine;
+#[cfg(any(test, feature = "plain"))]
+mod plain_engine;
 
 pub use self::engine::CryptoEngine;
 pub use self::buffer::CryptoBuffer;
+#[cfg(feature = "gpg")]
 pub use self::gpg_engine::GpgCryptoEngine;
-pub use self::key::{Key, KeyId};
+pub use self::password_engine::PasswordCryptoEngine;
+#[cfg(any(test, feature = "plain"))]
+pub use self::plain_engine::PlainCryptoEngine;
+pub use self::key::{Key, KeyId, KeyInfo};
+pub use self::kdf::{KdfParams, KdfAlgorithm};
 
 pub(crate) use self::kdf::Kdf;
+pub(crate) use self::mac::Mac;
+pub(crate) use self::prng::Prng;
 pub(crate) use self::key::KeyIdentifier;
 
+use crate::error::{ErrorKind, ErrorMessage};
+
 
 /// Error message for missing secret key.
-const MISSING_SECRET_KEY: &str = "Secret key is missing";
+#[cfg(feature = "gpg")]
+const MISSING_SECRET_KEY: ErrorMessage = ErrorMessage::new(ErrorKind::KeyNotFound, "Secret key is missing");
 
 /// Error message for invalid key.
-const KEY_IS_NOT_SUITABLE: &str = "Key is not suitable for bdgt";
+#[cfg(feature = "gpg")]
+const KEY_IS_NOT_SUITABLE: ErrorMessage = ErrorMessage::new(ErrorKind::Crypto, "Key is not suitable for bdgt");
+
+/// Reason of unsuitability for revoked key.
+#[cfg(feature = "gpg")]
+const KEY_IS_REVOKED: &str = "Key is revoked";
+
+/// Reason of unsuitability for expired key.
+#[cfg(feature = "gpg")]
+const KEY_IS_EXPIRED: &str = "Key is expired";
+
+/// Reason of unsuitability for disabled key.
+#[cfg(feature = "gpg")]
+const KEY_IS_DISABLED: &str = "Key is disabled";
+
+/// Reason of unsuitability for invalid key.
+#[cfg(feature = "gpg")]
+const KEY_IS_INVALID: &str = "Key is invalid";
+
+/// Reason of unsuitability for key, that cannot encrypt.
+#[cfg(feature = "gpg")]
+const KEY_CANNOT_ENCRYPT: &str = "Key cannot be used for encryption";
+
+/// Error message for a data key backup with wrong format or checksum.
+#[cfg(feature = "gpg")]
+const MALFORMED_DATA_KEY_BACKUP: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Data key backup is malformed or contains a typo");
 
 /// Error message for invalid engine state.
-const INVALID_ENGINE_STATE: &str = "Engine is in invalid state";
+const INVALID_ENGINE_STATE: ErrorMessage = ErrorMessage::new(ErrorKind::Crypto, "Engine is in invalid state");
 
 /// Error message for encryption error.
-const ENCRYPTION_ERROR: &str = "An error occurred during encryption";
+#[cfg(feature = "gpg")]
+const ENCRYPTION_ERROR: ErrorMessage = ErrorMessage::new(ErrorKind::Crypto, "An error occurred during encryption");
 
 /// Error message for decryption error.
-const DECRYPTION_ERROR: &str = "An error occurred during decryption";
+const DECRYPTION_ERROR: ErrorMessage = ErrorMessage::new(ErrorKind::Crypto, "An error occurred during decryption");
 
 /// Malformed symmetric key.
-const INVALID_SYMMETRIC_KEY: &str = "Invalid symmetric key provided";
+const INVALID_SYMMETRIC_KEY: ErrorMessage = ErrorMessage::new(ErrorKind::Crypto, "Invalid symmetric key provided");
+
+/// Error message for wrong passphrase.
+const WRONG_PASSPHRASE: ErrorMessage = ErrorMessage::new(ErrorKind::Crypto, "Wrong passphrase");
+
+/// Error message for a stream, that has too many chunks to encrypt.
+const STREAM_IS_TOO_LONG: ErrorMessage = ErrorMessage::new(ErrorKind::Crypto, "Stream is too long to be encrypted");
+
+/// Error message for malformed key derivation parameters.
+const MALFORMED_KDF_PARAMS: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Malformed key derivation parameters");
+
+/// Error message for operation, that engine does not support.
+const UNSUPPORTED_OPERATION: ErrorMessage = ErrorMessage::new(ErrorKind::Crypto, "Operation is not supported by engine");
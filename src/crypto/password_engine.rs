@@ -0,0 +1,250 @@
+use crate::error::{Error, Result};
+use crate::location::Location;
+use super::kdf::{Kdf, KdfParams};
+use super::prng::Prng;
+use super::engine::CryptoEngine;
+use super::buffer::CryptoBuffer;
+use super::symmetric::SymmetricCipher;
+use super::key::{Key, KeyId, KeyHandle, KeyIdentifier};
+use super::{INVALID_ENGINE_STATE, WRONG_PASSPHRASE, UNSUPPORTED_OPERATION};
+
+
+/// Homan-friendly name of passphrase engine.
+const ENGINE_NAME: &str = "Passphrase";
+
+/// Name of file with key derivation salt and passphrase verifier.
+const PASSPHRASE_FILE: &str = "passphrase";
+
+/// Length of key derivation salt.
+const SALT_LENGTH: usize = 32;
+
+/// Known plaintext, which is encrypted to verify passphrase.
+const VERIFIER: &[u8] = b"bdgt";
+
+
+/// Engine-specific key identifier type. Key identifier is just a label.
+type NativeId = String;
+
+impl KeyIdentifier for NativeId {
+    fn from_str(id: &str) -> Self {
+        id.to_owned()
+    }
+
+    fn as_string(&self) -> String {
+        self.clone()
+    }
+}
+
+
+/// Engine-specific key handle type. There is no actual key behind a label.
+type NativeHandle = ();
+
+impl KeyHandle for NativeHandle {
+    fn is_good(&self) -> bool {
+        true
+    }
+
+    fn can_encrypt(&self) -> bool {
+        true
+    }
+
+    fn fingerprint(&self) -> Option<String> {
+        None
+    }
+}
+
+
+/// Passphrase-based cryptographic engine.
+///
+/// This engine derives a symmetric key from user's passphrase and
+/// a random salt, that is generated at creation stage and saved next
+/// to the database. No external tools are required.
+///
+/// Keys are just labels, all of them are bound to the same passphrase.
+pub struct PasswordCryptoEngine {
+    /// Symmetric cipher with derived key. Absent for dummy engine.
+    cipher: Option<SymmetricCipher>,
+}
+
+
+impl PasswordCryptoEngine {
+    /// Creates a cryptographic engine for information queries.
+    /// This engine cannot be used for performing cryptographic operations.
+    pub fn new_dummy() -> Result<Self> {
+        Ok(PasswordCryptoEngine {
+            cipher: None
+        })
+    }
+
+    /// Creates a cryptographic engine for bdgt and initializes it.
+    ///
+    /// * `loc` - storage location provider
+    /// * `passphrase` - passphrase to derive key from
+    pub fn create<L: Location>(loc: &L, passphrase: &[u8]) -> Result<Self> {
+        //
+        // Location for config may be absent
+        //
+
+        loc.create_if_absent()?;
+
+        //
+        // Generate random salt and save it with encrypted known
+        // plaintext, that is used to verify passphrase later
+        //
+
+        let mut salt = CryptoBuffer::new_with_size(SALT_LENGTH);
+        Prng::new()
+            .generate(salt.as_mut_bytes())?;
+
+        let cipher = Self::derive_cipher(passphrase, salt.as_bytes())?;
+        let verifier = cipher.encrypt(VERIFIER, &[])?;
+
+        std::fs::write(Self::passphrase_file(loc), salt.append(verifier).as_bytes())?;
+
+        Self::open(loc, passphrase)
+    }
+
+    /// Opens a cryptographic engine for bdgt.
+    ///
+    /// * `loc` - storage location provider
+    /// * `passphrase` - passphrase to derive key from
+    pub fn open<L: Location>(loc: &L, passphrase: &[u8]) -> Result<Self> {
+        let content = std::fs::read(Self::passphrase_file(loc))?;
+        if content.len() < SALT_LENGTH {
+            return Err(Error::new(INVALID_ENGINE_STATE));
+        }
+
+        let (salt, verifier) = content.split_at(SALT_LENGTH);
+        let cipher = Self::derive_cipher(passphrase, salt)?;
+
+        //
+        // Wrong passphrase leads to authentication failure here
+        //
+
+        let verified = cipher
+            .decrypt(verifier, &[])
+            .is_ok_and(|plaintext| plaintext.as_bytes() == VERIFIER);
+
+        if !verified {
+            return Err(Error::new(WRONG_PASSPHRASE));
+        }
+
+        Ok(PasswordCryptoEngine {
+            cipher: Some(cipher)
+        })
+    }
+}
+
+
+impl CryptoEngine for PasswordCryptoEngine {
+    type Key = Key<NativeHandle, NativeId>;
+    type KeyId = KeyId<NativeId>;
+
+    fn engine(&self) -> &'static str {
+        ENGINE_NAME
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn symmetric_key_length(&self) -> usize {
+        SymmetricCipher::key_size()
+    }
+
+    fn key_fingerprint(&self, key: &Self::Key) -> Option<String> {
+        key.fingerprint()
+    }
+
+    fn lookup_key(&self, id: &Self::KeyId) -> Result<Self::Key> {
+        Ok(Key::new((), id))
+    }
+
+    fn preload(&self, _key: &Self::Key) -> Result<()> {
+        self.cipher()
+            .map(|_| ())
+    }
+
+    fn begin_rotation(&mut self, _new_key: &Self::Key) -> Result<()> {
+        Err(Error::new_with_extra(UNSUPPORTED_OPERATION, ENGINE_NAME))
+    }
+
+    fn rotation_in_progress(&self) -> bool {
+        false
+    }
+
+    fn commit_rotation(&mut self) -> Result<()> {
+        Err(Error::new(INVALID_ENGINE_STATE))
+    }
+
+    fn abort_rotation(&mut self) -> Result<()> {
+        Err(Error::new(INVALID_ENGINE_STATE))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = plaintext.len())))]
+    fn encrypt(&self, _key: &Self::Key, plaintext: &[u8]) -> Result<CryptoBuffer> {
+        self.cipher()?
+            .encrypt(plaintext, &[])
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = ciphertext.len())))]
+    fn decrypt(&self, _key: &Self::Key, ciphertext: &[u8]) -> Result<CryptoBuffer> {
+        self.cipher()?
+            .decrypt(ciphertext, &[])
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = plaintext.len())))]
+    fn encrypt_with_context(&self, _key: &Self::Key, plaintext: &[u8], context: &[u8]) -> Result<CryptoBuffer> {
+        self.cipher()?
+            .encrypt(plaintext, context)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = ciphertext.len())))]
+    fn decrypt_with_context(&self, _key: &Self::Key, ciphertext: &[u8], context: &[u8]) -> Result<CryptoBuffer> {
+        self.cipher()?
+            .decrypt_compatible(ciphertext, context)
+    }
+
+    fn decrypt_pending_with_context(&self, _key: &Self::Key, _ciphertext: &[u8], _context: &[u8]) -> Result<CryptoBuffer> {
+        Err(Error::new(INVALID_ENGINE_STATE))
+    }
+
+    fn encrypt_symmetric(&self, key: &[u8], plaintext: &[u8]) -> Result<CryptoBuffer> {
+        let cipher = SymmetricCipher::new(key)?;
+        cipher.encrypt(plaintext, &[])
+    }
+
+    fn decrypt_symmetric(&self, key: &[u8], ciphertext: &[u8]) -> Result<CryptoBuffer> {
+        let cipher = SymmetricCipher::new(key)?;
+        cipher.decrypt(ciphertext, &[])
+    }
+
+    fn encrypt_symmetric_stream<R: std::io::Read, W: std::io::Write>(&self, key: &[u8], reader: R, writer: W) -> Result<()> {
+        let cipher = SymmetricCipher::new(key)?;
+        cipher.encrypt_stream(reader, writer)
+    }
+
+    fn decrypt_symmetric_stream<R: std::io::Read, W: std::io::Write>(&self, key: &[u8], reader: R, writer: W) -> Result<()> {
+        let cipher = SymmetricCipher::new(key)?;
+        cipher.decrypt_stream(reader, writer)
+    }
+}
+
+
+impl PasswordCryptoEngine {
+    fn cipher(&self) -> Result<&SymmetricCipher> {
+        self.cipher
+            .as_ref()
+            .ok_or(Error::new(INVALID_ENGINE_STATE))
+    }
+
+    fn derive_cipher(passphrase: &[u8], salt: &[u8]) -> Result<SymmetricCipher> {
+        let key = Kdf::derive_key(passphrase, salt, SymmetricCipher::key_size(), &KdfParams::default())?;
+        SymmetricCipher::new(key.as_bytes())
+    }
+
+    fn passphrase_file<L: Location>(loc: &L) -> std::path::PathBuf {
+        loc.root()
+            .join(PASSPHRASE_FILE)
+    }
+}
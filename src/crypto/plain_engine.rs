@@ -0,0 +1,311 @@
+use std::io::{Read, Write};
+
+use crate::error::{Error, Result};
+use super::engine::CryptoEngine;
+use super::buffer::CryptoBuffer;
+use super::key::{Key, KeyId};
+use super::{INVALID_ENGINE_STATE, INVALID_SYMMETRIC_KEY};
+
+
+/// Homan-friendly name of plain engine.
+const ENGINE_NAME: &str = "Plain (insecure)";
+
+/// Length of a key for symmetric transform.
+const SYMMETRIC_KEY_LENGTH: usize = 32;
+
+/// Byte, that all data is XOR-ed with.
+const MASK: u8 = 0x5a;
+
+/// Size of a chunk of data processed at once in streaming mode.
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+
+/// Engine-specific key identifier type. Key identifier is just a label.
+///
+/// [`super::key::KeyIdentifier`] is implemented for it in passphrase engine.
+type NativeId = String;
+
+
+/// Engine-specific key handle type. There is no actual key behind a label.
+///
+/// [`super::key::KeyHandle`] is implemented for it in passphrase engine.
+type NativeHandle = ();
+
+
+/// Deterministic cryptographic engine, that performs NO ENCRYPTION.
+///
+/// **This engine is insecure and MUST NOT be used to store real data.**
+/// It is intended for tests of code, that uses [`crate::core::Budget`],
+/// because it requires no keyring, passphrase or any other system
+/// dependency.
+///
+/// Data is just XOR-ed with a constant byte, symmetric transform
+/// XOR-s data with a key. Keys are just labels, any of them can be
+/// looked up.
+#[derive(Default)]
+pub struct PlainCryptoEngine {
+    /// Flag of a started key rotation.
+    rotation_in_progress: bool,
+}
+
+
+impl PlainCryptoEngine {
+    /// Creates a new plain engine.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+
+impl CryptoEngine for PlainCryptoEngine {
+    type Key = Key<NativeHandle, NativeId>;
+    type KeyId = KeyId<NativeId>;
+
+    fn engine(&self) -> &'static str {
+        ENGINE_NAME
+    }
+
+    fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn symmetric_key_length(&self) -> usize {
+        SYMMETRIC_KEY_LENGTH
+    }
+
+    fn key_fingerprint(&self, key: &Self::Key) -> Option<String> {
+        key.fingerprint()
+    }
+
+    fn lookup_key(&self, id: &Self::KeyId) -> Result<Self::Key> {
+        Ok(Key::new((), id))
+    }
+
+    fn preload(&self, _key: &Self::Key) -> Result<()> {
+        Ok(())
+    }
+
+    fn begin_rotation(&mut self, _new_key: &Self::Key) -> Result<()> {
+        if self.rotation_in_progress {
+            return Err(Error::new(INVALID_ENGINE_STATE));
+        }
+
+        self.rotation_in_progress = true;
+        Ok(())
+    }
+
+    fn rotation_in_progress(&self) -> bool {
+        self.rotation_in_progress
+    }
+
+    fn commit_rotation(&mut self) -> Result<()> {
+        self.finish_rotation()
+    }
+
+    fn abort_rotation(&mut self) -> Result<()> {
+        self.finish_rotation()
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = plaintext.len())))]
+    fn encrypt(&self, _key: &Self::Key, plaintext: &[u8]) -> Result<CryptoBuffer> {
+        Ok(Self::transform(plaintext, &[MASK]))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = ciphertext.len())))]
+    fn decrypt(&self, _key: &Self::Key, ciphertext: &[u8]) -> Result<CryptoBuffer> {
+        Ok(Self::transform(ciphertext, &[MASK]))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = plaintext.len())))]
+    fn encrypt_with_context(&self, key: &Self::Key, plaintext: &[u8], _context: &[u8]) -> Result<CryptoBuffer> {
+        self.encrypt(key, plaintext)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = ciphertext.len())))]
+    fn decrypt_with_context(&self, key: &Self::Key, ciphertext: &[u8], _context: &[u8]) -> Result<CryptoBuffer> {
+        self.decrypt(key, ciphertext)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(size = ciphertext.len())))]
+    fn decrypt_pending_with_context(&self, key: &Self::Key, ciphertext: &[u8], _context: &[u8]) -> Result<CryptoBuffer> {
+        if !self.rotation_in_progress {
+            return Err(Error::new(INVALID_ENGINE_STATE));
+        }
+
+        self.decrypt(key, ciphertext)
+    }
+
+    fn encrypt_symmetric(&self, key: &[u8], plaintext: &[u8]) -> Result<CryptoBuffer> {
+        Self::check_symmetric_key(key)?;
+        Ok(Self::transform(plaintext, key))
+    }
+
+    fn decrypt_symmetric(&self, key: &[u8], ciphertext: &[u8]) -> Result<CryptoBuffer> {
+        Self::check_symmetric_key(key)?;
+        Ok(Self::transform(ciphertext, key))
+    }
+
+    fn encrypt_symmetric_stream<R: Read, W: Write>(&self, key: &[u8], reader: R, writer: W) -> Result<()> {
+        Self::check_symmetric_key(key)?;
+        Self::transform_stream(reader, writer, key)
+    }
+
+    fn decrypt_symmetric_stream<R: Read, W: Write>(&self, key: &[u8], reader: R, writer: W) -> Result<()> {
+        Self::check_symmetric_key(key)?;
+        Self::transform_stream(reader, writer, key)
+    }
+}
+
+
+impl PlainCryptoEngine {
+    fn finish_rotation(&mut self) -> Result<()> {
+        if !self.rotation_in_progress {
+            return Err(Error::new(INVALID_ENGINE_STATE));
+        }
+
+        self.rotation_in_progress = false;
+        Ok(())
+    }
+
+    fn check_symmetric_key(key: &[u8]) -> Result<()> {
+        match key.len() {
+            SYMMETRIC_KEY_LENGTH => Ok(()),
+            _ => Err(Error::new(INVALID_SYMMETRIC_KEY))
+        }
+    }
+
+    fn transform(data: &[u8], key: &[u8]) -> CryptoBuffer {
+        Self::transform_at(data, key, 0)
+    }
+
+    fn transform_at(data: &[u8], key: &[u8], offset: usize) -> CryptoBuffer {
+        let transformed: Vec<u8> = data
+            .iter()
+            .zip(key.iter().cycle().skip(offset % key.len()))
+            .map(|(byte, key_byte)| byte ^ key_byte)
+            .collect();
+
+        CryptoBuffer::from(transformed)
+    }
+
+    fn transform_stream<R: Read, W: Write>(mut reader: R, mut writer: W, key: &[u8]) -> Result<()> {
+        //
+        // Reader can return chunks of any size, hence I track
+        // position in stream to continue with a proper key byte
+        //
+
+        let mut offset = 0usize;
+        let mut chunk = vec![0u8; STREAM_CHUNK_SIZE];
+        loop {
+            let read = match reader.read(&mut chunk) {
+                Ok(0) => return Ok(()),
+                Ok(read) => read,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into())
+            };
+
+            let transformed = Self::transform_at(&chunk[..read], key, offset);
+            writer.write_all(transformed.as_bytes())?;
+
+            offset += read;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(engine: &PlainCryptoEngine) -> Key<NativeHandle, NativeId> {
+        engine.lookup_key(&KeyId::new("test"))
+            .expect("Any key can be looked up")
+    }
+
+    #[test]
+    fn asymmetric_round_trip() {
+        let engine = PlainCryptoEngine::new();
+        let key = key(&engine);
+
+        let ciphertext = engine.encrypt(&key, b"plaintext").unwrap();
+        assert_ne!(ciphertext.as_bytes(), b"plaintext");
+        assert_eq!(engine.decrypt(&key, ciphertext.as_bytes()).unwrap().as_bytes(), b"plaintext");
+
+        let ciphertext = engine.encrypt_with_context(&key, b"plaintext", b"context").unwrap();
+        assert_eq!(engine.decrypt_with_context(&key, ciphertext.as_bytes(), b"context").unwrap().as_bytes(), b"plaintext");
+    }
+
+    #[test]
+    fn batch_round_trip() {
+        let engine = PlainCryptoEngine::new();
+        let key = key(&engine);
+
+        let plaintexts: [&[u8]; 3] = [b"", b"first", b"second"];
+        let ciphertexts = engine.encrypt_many(&key, &plaintexts).unwrap();
+        let ciphertexts: Vec<&[u8]> = ciphertexts.iter().map(CryptoBuffer::as_bytes).collect();
+
+        let decrypted = engine.decrypt_many(&key, &ciphertexts).unwrap();
+        let decrypted: Vec<&[u8]> = decrypted.iter().map(CryptoBuffer::as_bytes).collect();
+        assert_eq!(decrypted, plaintexts);
+    }
+
+    #[test]
+    fn symmetric_round_trip() {
+        let engine = PlainCryptoEngine::new();
+        let key = vec![7u8; engine.symmetric_key_length()];
+
+        let ciphertext = engine.encrypt_symmetric(&key, b"plaintext").unwrap();
+        assert_ne!(ciphertext.as_bytes(), b"plaintext");
+        assert_eq!(engine.decrypt_symmetric(&key, ciphertext.as_bytes()).unwrap().as_bytes(), b"plaintext");
+
+        assert!(engine.encrypt_symmetric(&key[1..], b"plaintext").is_err());
+        assert!(engine.decrypt_symmetric(&key[1..], ciphertext.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn symmetric_stream_round_trip() {
+        let engine = PlainCryptoEngine::new();
+        let key: Vec<u8> = (0..engine.symmetric_key_length() as u8).collect();
+        let plaintext: Vec<u8> = (0..3 * STREAM_CHUNK_SIZE + 5).map(|i| i as u8).collect();
+
+        let mut ciphertext = Vec::new();
+        engine.encrypt_symmetric_stream(&key, plaintext.as_slice(), &mut ciphertext).unwrap();
+
+        //
+        // Stream is the same as a single BLOB, hence
+        // both of modes are interchangeable
+        //
+
+        assert_eq!(ciphertext, engine.encrypt_symmetric(&key, &plaintext).unwrap().as_bytes());
+
+        let mut decrypted = Vec::new();
+        engine.decrypt_symmetric_stream(&key, ciphertext.as_slice(), &mut decrypted).unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn rotation() {
+        let mut engine = PlainCryptoEngine::new();
+        let key = key(&engine);
+
+        assert!(engine.commit_rotation().is_err());
+        assert!(engine.abort_rotation().is_err());
+
+        let ciphertext = engine.encrypt_with_context(&key, b"data", b"context").unwrap();
+        assert!(engine.decrypt_pending_with_context(&key, ciphertext.as_bytes(), b"context").is_err());
+
+        engine.begin_rotation(&key).unwrap();
+        assert!(engine.rotation_in_progress());
+        assert!(engine.begin_rotation(&key).is_err());
+
+        let decrypted = engine.decrypt_pending_with_context(&key, ciphertext.as_bytes(), b"context").unwrap();
+        assert_eq!(decrypted.as_bytes(), b"data");
+
+        engine.commit_rotation().unwrap();
+        assert!(!engine.rotation_in_progress());
+
+        engine.begin_rotation(&key).unwrap();
+        engine.abort_rotation().unwrap();
+        assert!(!engine.rotation_in_progress());
+    }
+}
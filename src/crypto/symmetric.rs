@@ -1,11 +1,13 @@
+use std::io::{Read, Write};
+
 use typenum::Unsigned;
-use aes_gcm::aead::Aead;
+use aes_gcm::aead::{Aead, Payload};
 use aes_gcm::{KeySizeUser, AeadCore, KeyInit};
 
 use crate::error::{Result, Error};
 use super::prng::Prng;
 use super::buffer::CryptoBuffer;
-use super::INVALID_SYMMETRIC_KEY;
+use super::{INVALID_SYMMETRIC_KEY, DECRYPTION_ERROR, STREAM_IS_TOO_LONG};
 
 
 /// Actual internal cipher implementation.
@@ -32,6 +34,17 @@ type NonceSize = <Cipher as AeadCore>::NonceSize;
 type Nonce = aes_gcm::Nonce<NonceSize>;
 
 
+/// Size of plaintext chunk in streaming mode.
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Size of authentication tag appended to each encrypted chunk.
+const STREAM_TAG_SIZE: usize = 16;
+
+/// Size of random nonce prefix in streaming mode. The rest of nonce
+/// is occupied by 32-bit chunk counter and last chunk flag.
+const STREAM_NONCE_PREFIX_SIZE: usize = 7;
+
+
 /// Symmetric cipher interface. 
 pub(crate) struct SymmetricCipher {
     /// Internal cipher implementation.
@@ -47,11 +60,11 @@ impl SymmetricCipher {
     /// * `key` - key used to encrypt or decrypt data
     pub fn new(key: &[u8]) -> Result<Self> {
         if key.len() != Self::key_size() {
-            return Err(Error::from_message(INVALID_SYMMETRIC_KEY));
+            return Err(Error::new(INVALID_SYMMETRIC_KEY));
         }
 
         Ok(SymmetricCipher { 
-            cipher: Cipher::new(&Key::from_slice(key)) 
+            cipher: Cipher::new(Key::from_slice(key)) 
         })
     }
 
@@ -63,11 +76,12 @@ impl SymmetricCipher {
     /// Encrypt a BLOB.
     /// 
     /// * `plaintext` - data to encrypt.
-    pub fn encrypt(&self, plaintext: &[u8]) -> Result<CryptoBuffer> {
+    /// * `aad` - associated data, that is authenticated, but not encrypted
+    pub fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<CryptoBuffer> {
         let nonce = Cipher::generate_nonce(Prng::new());
 
         let ciphertext = self.cipher
-            .encrypt(&nonce, plaintext)?;
+            .encrypt(&nonce, Payload { msg: plaintext, aad })?;
         
         Ok(
             CryptoBuffer::from(nonce.as_slice())
@@ -78,13 +92,222 @@ impl SymmetricCipher {
     /// Decrypt a BLOB.
     /// 
     /// * `ciphertext` - data to decrypt.
-    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<CryptoBuffer> {
+    /// * `aad` - associated data used during encryption
+    pub fn decrypt(&self, ciphertext: &[u8], aad: &[u8]) -> Result<CryptoBuffer> {
+        if ciphertext.len() < NonceSize::USIZE {
+            return Err(Error::new(DECRYPTION_ERROR));
+        }
+
         let (nonce, ciphertext) = ciphertext.split_at(NonceSize::USIZE);
         let nonce = Nonce::from_slice(nonce);
 
         let plaintext = self.cipher
-            .decrypt(&nonce, ciphertext)?;
+            .decrypt(nonce, Payload { msg: ciphertext, aad })?;
         
         Ok(CryptoBuffer::from(plaintext))
     }
+
+    /// Decrypt a BLOB, that can be encrypted without associated data.
+    /// 
+    /// Data encrypted by older versions has no associated data, hence
+    /// decryption without it is tried if decryption with it fails.
+    /// 
+    /// * `ciphertext` - data to decrypt.
+    /// * `aad` - associated data used during encryption
+    pub fn decrypt_compatible(&self, ciphertext: &[u8], aad: &[u8]) -> Result<CryptoBuffer> {
+        self.decrypt(ciphertext, aad)
+            .or_else(|_| self.decrypt(ciphertext, &[]))
+    }
+
+    /// Encrypt a stream of data chunk by chunk.
+    /// 
+    /// Nonce of each chunk consists of a random prefix, chunk counter and
+    /// a flag of the last chunk, hence chunks cannot be reordered, and
+    /// truncation is detected. Last chunk is always shorter than others
+    /// (it can be empty).
+    /// 
+    /// * `reader` - source of data to encrypt
+    /// * `writer` - destination of encrypted data
+    pub fn encrypt_stream<R: Read, W: Write>(&self, mut reader: R, mut writer: W) -> Result<()> {
+        let mut prefix = [0u8; STREAM_NONCE_PREFIX_SIZE];
+        Prng::new().generate(&mut prefix)?;
+
+        writer.write_all(&prefix)?;
+
+        let mut chunk = CryptoBuffer::new_with_size(STREAM_CHUNK_SIZE);
+        let mut counter = 0u32;
+
+        loop {
+            let read = Self::read_chunk(&mut reader, chunk.as_mut_bytes())?;
+            let is_last = read < STREAM_CHUNK_SIZE;
+
+            let nonce = Self::stream_nonce(&prefix, counter, is_last);
+            let ciphertext = self.cipher
+                .encrypt(&nonce, &chunk.as_bytes()[..read])?;
+
+            writer.write_all(&ciphertext)?;
+
+            if is_last {
+                return Ok(());
+            }
+
+            counter = counter.checked_add(1)
+                .ok_or(Error::new(STREAM_IS_TOO_LONG))?;
+        }
+    }
+
+    /// Decrypt a stream of data encrypted by [`SymmetricCipher::encrypt_stream`].
+    /// 
+    /// Plaintext of a chunk is written only after its authentication,
+    /// but if an error is returned, some chunks can be written already.
+    /// 
+    /// * `reader` - source of data to decrypt
+    /// * `writer` - destination of decrypted data
+    pub fn decrypt_stream<R: Read, W: Write>(&self, mut reader: R, mut writer: W) -> Result<()> {
+        let mut prefix = [0u8; STREAM_NONCE_PREFIX_SIZE];
+        reader.read_exact(&mut prefix)
+            .map_err(|_| Error::new(DECRYPTION_ERROR))?;
+
+        let mut chunk = vec![0u8; STREAM_CHUNK_SIZE + STREAM_TAG_SIZE];
+        let mut counter = 0u32;
+
+        loop {
+            //
+            // Full chunk cannot be the last one, hence stream truncated
+            // at chunk boundary fails to decrypt as an empty last chunk
+            //
+
+            let read = Self::read_chunk(&mut reader, &mut chunk)?;
+            let is_last = read < chunk.len();
+
+            let nonce = Self::stream_nonce(&prefix, counter, is_last);
+            let plaintext = CryptoBuffer::from(self.cipher
+                .decrypt(&nonce, &chunk[..read])?);
+
+            writer.write_all(plaintext.as_bytes())?;
+
+            if is_last {
+                return Ok(());
+            }
+
+            counter = counter.checked_add(1)
+                .ok_or(Error::new(STREAM_IS_TOO_LONG))?;
+        }
+    }
+}
+
+
+impl SymmetricCipher {
+    fn stream_nonce(prefix: &[u8; STREAM_NONCE_PREFIX_SIZE], counter: u32, is_last: bool) -> Nonce {
+        let mut nonce = Nonce::default();
+        nonce[..STREAM_NONCE_PREFIX_SIZE].copy_from_slice(prefix);
+        nonce[STREAM_NONCE_PREFIX_SIZE..NonceSize::USIZE - 1].copy_from_slice(&counter.to_be_bytes());
+        nonce[NonceSize::USIZE - 1] = is_last as u8;
+
+        nonce
+    }
+
+    fn read_chunk<R: Read>(reader: &mut R, chunk: &mut [u8]) -> Result<usize> {
+        //
+        // Reader can return less data than requested even 
+        // if it is not exhausted yet, hence I read in a loop
+        //
+
+        let mut filled = 0;
+        while filled < chunk.len() {
+            match reader.read(&mut chunk[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into())
+            }
+        }
+
+        Ok(filled)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher() -> SymmetricCipher {
+        SymmetricCipher::new(&[42u8; 32])
+            .expect("Key has proper size")
+    }
+
+    fn encrypt(plaintext: &[u8]) -> Vec<u8> {
+        let mut ciphertext = Vec::new();
+        cipher().encrypt_stream(plaintext, &mut ciphertext).unwrap();
+
+        ciphertext
+    }
+
+    fn decrypt(ciphertext: &[u8]) -> Result<CryptoBuffer> {
+        let mut plaintext = CryptoBuffer::new();
+        cipher().decrypt_stream(ciphertext, &mut plaintext)?;
+
+        Ok(plaintext)
+    }
+
+    fn round_trip(size: usize) {
+        let plaintext: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let ciphertext = encrypt(&plaintext);
+
+        //
+        // Each chunk (including the last, possibly empty one) has a tag
+        //
+
+        let chunks = size / STREAM_CHUNK_SIZE + 1;
+        assert_eq!(ciphertext.len(), STREAM_NONCE_PREFIX_SIZE + size + chunks * STREAM_TAG_SIZE);
+
+        assert_eq!(decrypt(&ciphertext).unwrap().as_bytes(), plaintext);
+    }
+
+    #[test]
+    fn stream_of_empty_input() {
+        round_trip(0);
+    }
+
+    #[test]
+    fn stream_of_exactly_one_chunk() {
+        round_trip(STREAM_CHUNK_SIZE);
+    }
+
+    #[test]
+    fn stream_of_several_chunks() {
+        round_trip(1);
+        round_trip(STREAM_CHUNK_SIZE - 1);
+        round_trip(3 * STREAM_CHUNK_SIZE + STREAM_CHUNK_SIZE / 2);
+    }
+
+    #[test]
+    fn stream_truncation_is_detected() {
+        let plaintext = vec![7u8; 2 * STREAM_CHUNK_SIZE + 10];
+        let ciphertext = encrypt(&plaintext);
+
+        //
+        // Truncation inside of the last chunk, exactly at chunk
+        // boundary and inside of the prefix
+        //
+
+        let last_chunk = STREAM_NONCE_PREFIX_SIZE + 2 * (STREAM_CHUNK_SIZE + STREAM_TAG_SIZE);
+        for length in [ciphertext.len() - 1, last_chunk, last_chunk - 1, STREAM_NONCE_PREFIX_SIZE, 1] {
+            assert!(decrypt(&ciphertext[..length]).is_err(), "truncated at {}", length);
+        }
+
+        let exact = encrypt(&vec![7u8; STREAM_CHUNK_SIZE]);
+        assert!(decrypt(&exact[..exact.len() - STREAM_TAG_SIZE]).is_err());
+    }
+
+    #[test]
+    fn stream_tampering_is_detected() {
+        let mut ciphertext = encrypt(&vec![7u8; 2 * STREAM_CHUNK_SIZE]);
+
+        let middle = ciphertext.len() / 2;
+        ciphertext[middle] ^= 1;
+
+        assert!(decrypt(&ciphertext).is_err());
+    }
 }
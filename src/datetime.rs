@@ -1,3 +1,17 @@
+use chrono::Datelike;
+
+use crate::error::{Result, Error, ErrorKind, ErrorMessage};
+use crate::storage::PlanPeriod;
+
+
+/// Error shown in case of date, that cannot be parsed.
+const MALFORMED_DATE: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Malformed date");
+
+
+/// Number of seconds in a day without daylight saving time transitions.
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+
 /// Clock used for all timestamps.
 pub type Clock = chrono::Utc;
 
@@ -16,3 +30,459 @@ pub(crate) static ref FIRST_AFTER_JANUARY_1970: Timestamp = Timestamp::from_time
     .expect("One second after January 1970 is a valid timestamp");
 
 );
+
+
+/// Returns the start of a day, which a timestamp belongs to.
+/// 
+/// * `timestamp` - point in time
+pub fn start_of_day(timestamp: &Timestamp) -> Timestamp {
+    timestamp
+        .date_naive()
+        .and_time(chrono::NaiveTime::MIN)
+        .and_utc()
+}
+
+
+/// Returns the start of an ISO week (Monday), which a timestamp belongs to.
+/// 
+/// * `timestamp` - point in time
+pub fn start_of_week(timestamp: &Timestamp) -> Timestamp {
+    start_of_week_on(timestamp, chrono::Weekday::Mon)
+}
+
+
+/// Returns the start of a week, which a timestamp belongs to.
+/// 
+/// * `timestamp` - point in time
+/// * `first_day` - first day of week
+pub fn start_of_week_on(timestamp: &Timestamp, first_day: chrono::Weekday) -> Timestamp {
+    let days_from_first = (7 + timestamp.weekday().num_days_from_monday() - first_day.num_days_from_monday()) % 7;
+    start_of_day(timestamp) - chrono::Duration::days(days_from_first.into())
+}
+
+
+/// Returns the start of a month, which a timestamp belongs to.
+/// 
+/// * `timestamp` - point in time
+pub fn start_of_month(timestamp: &Timestamp) -> Timestamp {
+    start_of_day(timestamp)
+        .with_day(1)
+        .expect("First day exists in every month")
+}
+
+
+/// Returns the start of a quarter, which a timestamp belongs to.
+/// 
+/// * `timestamp` - point in time
+pub fn start_of_quarter(timestamp: &Timestamp) -> Timestamp {
+    let first_month = timestamp.month0() / 3 * 3 + 1;
+
+    start_of_month(timestamp)
+        .with_month(first_month)
+        .expect("First day exists in every month")
+}
+
+
+/// Returns the start of a year, which a timestamp belongs to.
+/// 
+/// * `timestamp` - point in time
+pub fn start_of_year(timestamp: &Timestamp) -> Timestamp {
+    start_of_month(timestamp)
+        .with_month(1)
+        .expect("First day exists in every month")
+}
+
+
+/// Adds a number of months to a timestamp. Day of month is clamped
+/// to the last day of resulting month if necessary.
+/// 
+/// * `timestamp` - point in time
+/// * `months` - number of months to add
+pub fn add_months(timestamp: &Timestamp, months: u32) -> Option<Timestamp> {
+    timestamp.checked_add_months(chrono::Months::new(months))
+}
+
+
+/// Returns bounds of a month: its first moment (inclusive) and the first
+/// moment of the next month (exclusive). Returns `None` for invalid month.
+/// 
+/// * `year` - calendar year
+/// * `month` - month number (starting from 1)
+pub fn month_bounds(year: i32, month: u32) -> Option<(Timestamp, Timestamp)> {
+    let start = chrono::NaiveDate::from_ymd_opt(year, month, 1)?
+        .and_time(chrono::NaiveTime::MIN)
+        .and_utc();
+
+    Some((start, add_months(&start, 1)?))
+}
+
+
+/// Converts wall-clock time in a timezone into a timestamp.
+/// 
+/// If the time is skipped by a daylight saving time transition, then
+/// the first moment after the transition is returned. Unknown timezone
+/// and absent timezone support (`tz` feature) mean UTC.
+/// 
+/// * `local` - wall-clock time
+/// * `timezone` - IANA timezone name, `None` means UTC
+pub fn from_local(local: &chrono::NaiveDateTime, timezone: Option<&str>) -> Option<Timestamp> {
+    #[cfg(feature = "tz")]
+    if let Some(timezone) = timezone.and_then(|timezone| timezone.parse::<chrono_tz::Tz>().ok()) {
+        use chrono::TimeZone;
+
+        //
+        // Transitions shift clocks by an hour at most (with very rare
+        // exceptions), hence I look for the next hour if time is skipped
+        //
+
+        return timezone.from_local_datetime(local)
+            .earliest()
+            .or_else(|| timezone.from_local_datetime(&(*local + chrono::Duration::hours(1))).earliest())
+            .map(|timestamp| timestamp.with_timezone(&chrono::Utc));
+    }
+
+    #[cfg(not(feature = "tz"))]
+    let _ = timezone;
+
+    Some(local.and_utc())
+}
+
+
+/// Converts a timestamp into wall-clock time in a timezone.
+/// 
+/// Unknown timezone and absent timezone support (`tz` feature) mean UTC.
+/// 
+/// * `timestamp` - point in time
+/// * `timezone` - IANA timezone name, `None` means UTC
+pub fn to_local(timestamp: &Timestamp, timezone: Option<&str>) -> chrono::NaiveDateTime {
+    #[cfg(feature = "tz")]
+    if let Some(timezone) = timezone.and_then(|timezone| timezone.parse::<chrono_tz::Tz>().ok()) {
+        return timestamp.with_timezone(&timezone).naive_local();
+    }
+
+    #[cfg(not(feature = "tz"))]
+    let _ = timezone;
+
+    timestamp.naive_utc()
+}
+
+
+/// Returns bounds of a day in a timezone: its first moment (inclusive) and
+/// the first moment of the next day (exclusive).
+/// 
+/// * `date` - calendar date
+/// * `timezone` - IANA timezone name, `None` means UTC
+pub fn local_day_bounds(date: chrono::NaiveDate, timezone: Option<&str>) -> Option<(Timestamp, Timestamp)> {
+    let start = from_local(&date.and_time(chrono::NaiveTime::MIN), timezone)?;
+    let end = from_local(&date.succ_opt()?.and_time(chrono::NaiveTime::MIN), timezone)?;
+
+    Some((start, end))
+}
+
+
+/// Returns bounds of a month in a timezone: its first moment (inclusive)
+/// and the first moment of the next month (exclusive). Returns `None` for
+/// invalid month.
+/// 
+/// * `year` - calendar year
+/// * `month` - month number (starting from 1)
+/// * `timezone` - IANA timezone name, `None` means UTC
+pub fn local_month_bounds(year: i32, month: u32, timezone: Option<&str>) -> Option<(Timestamp, Timestamp)> {
+    let (start, end) = month_bounds(year, month)?;
+    Some((from_local(&start.naive_utc(), timezone)?, from_local(&end.naive_utc(), timezone)?))
+}
+
+
+/// Adds a duration to a timestamp in a timezone.
+/// 
+/// Whole number of days is added to wall-clock time, hence the result
+/// has the same local time of day even if daylight saving time changes
+/// in between. Other durations are added as is.
+/// 
+/// * `timestamp` - point in time
+/// * `duration` - duration to add
+/// * `timezone` - IANA timezone name, `None` means UTC
+pub fn add_local(timestamp: &Timestamp, duration: chrono::Duration, timezone: Option<&str>) -> Option<Timestamp> {
+    if timezone.is_none() || duration.num_seconds() % SECONDS_PER_DAY != 0 || duration.subsec_nanos() != 0 {
+        return timestamp.checked_add_signed(duration);
+    }
+
+    from_local(&to_local(timestamp, timezone).checked_add_signed(duration)?, timezone)
+}
+
+
+/// Returns bounds of a week: its first moment (inclusive) and the first
+/// moment of the next week (exclusive).
+/// 
+/// * `date` - calendar date, which belongs to the week
+/// * `week_start` - first day of week
+pub fn week_bounds(date: chrono::NaiveDate, week_start: chrono::Weekday) -> Option<(Timestamp, Timestamp)> {
+    let days_from_first = (7 + date.weekday().num_days_from_monday() - week_start.num_days_from_monday()) % 7;
+    let start = date
+        .checked_sub_days(chrono::Days::new(days_from_first.into()))?
+        .and_time(chrono::NaiveTime::MIN)
+        .and_utc();
+
+    Some((start, start.checked_add_signed(chrono::Duration::weeks(1))?))
+}
+
+
+/// Returns bounds of a quarter: its first moment (inclusive) and the first
+/// moment of the next quarter (exclusive). Returns `None` for invalid quarter.
+/// 
+/// * `year` - calendar year
+/// * `quarter` - quarter number (from 1 to 4)
+pub fn quarter_bounds(year: i32, quarter: u32) -> Option<(Timestamp, Timestamp)> {
+    if !(1..=4).contains(&quarter) {
+        return None;
+    }
+
+    let (start, _) = month_bounds(year, (quarter - 1) * 3 + 1)?;
+    Some((start, add_months(&start, 3)?))
+}
+
+
+/// Returns bounds of a plan period: its first moment (inclusive) and the
+/// first moment of the next period (exclusive). Returns `None` for
+/// custom period of zero length.
+/// 
+/// Calendar periods start at local midnight in a timezone. Custom
+/// periods have fixed length and are counted from January 1970.
+/// 
+/// * `timestamp` - point in time, which belongs to the period
+/// * `period` - kind of period
+/// * `week_start` - first day of week used for weekly periods
+/// * `timezone` - IANA timezone name, `None` means UTC
+pub fn period_containing(timestamp: &Timestamp, period: PlanPeriod, week_start: chrono::Weekday, 
+    timezone: Option<&str>) -> Option<(Timestamp, Timestamp)> 
+{
+    let local = to_local(timestamp, timezone);
+    let (start, end) = match period {
+        PlanPeriod::Weekly => week_bounds(local.date(), week_start)?,
+        PlanPeriod::Monthly => month_bounds(local.year(), local.month())?,
+        PlanPeriod::Quarterly => quarter_bounds(local.year(), local.month0() / 3 + 1)?,
+        PlanPeriod::Yearly => {
+            let (start, _) = month_bounds(local.year(), 1)?;
+            (start, add_months(&start, 12)?)
+        },
+        PlanPeriod::Custom(0) => return None,
+        PlanPeriod::Custom(days) => {
+            let length = chrono::Duration::days(days.into());
+            let index = (*timestamp - *JANUARY_1970).num_seconds()
+                .div_euclid(length.num_seconds());
+
+            let start = JANUARY_1970.checked_add_signed(chrono::Duration::seconds(index * length.num_seconds()))?;
+            return Some((start, start.checked_add_signed(length)?));
+        }
+    };
+
+    //
+    // Bounds above are computed in wall-clock time
+    //
+
+    Some((from_local(&start.naive_utc(), timezone)?, from_local(&end.naive_utc(), timezone)?))
+}
+
+
+/// Parses a date typed by user and returns its first moment.
+/// 
+/// Supported formats are:
+/// * `2024-05-01` - full date
+/// * `05/01` - month and day of the current year
+/// * `today`, `yesterday` and `tomorrow`
+/// * `-3d`, `+2w`, `-1m` - number of days, weeks or months relative to today
+/// 
+/// Dates are resolved in a timezone: relative dates are counted from
+/// the local date of `today`, and the first moment of the local day
+/// is returned.
+/// 
+/// * `input` - date typed by user
+/// * `today` - current point in time
+/// * `timezone` - IANA timezone name, `None` means UTC
+pub fn parse_user_date(input: &str, today: Timestamp, timezone: Option<&str>) -> Result<Timestamp> {
+    let input = input.trim();
+    let today = to_local(&today, timezone).date();
+
+    parse_local_date(input, today)
+        .and_then(|date| from_local(&date.and_time(chrono::NaiveTime::MIN), timezone))
+        .ok_or_else(|| Error::new_with_extra(MALFORMED_DATE, input))
+}
+
+
+fn parse_local_date(input: &str, today: chrono::NaiveDate) -> Option<chrono::NaiveDate> {
+    match input.to_lowercase().as_str() {
+        "today" => return Some(today),
+        "yesterday" => return today.pred_opt(),
+        "tomorrow" => return today.succ_opt(),
+        _ => ()
+    }
+
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Some(date);
+    }
+
+    if let Some((month, day)) = input.split_once('/') {
+        return chrono::NaiveDate::from_ymd_opt(today.year(), month.parse().ok()?, day.parse().ok()?);
+    }
+
+    //
+    // Relative dates start with a sign followed by a number and a unit
+    //
+
+    let (sign, rest) = match input.split_at_checked(1) {
+        Some(("-", rest)) => (-1, rest),
+        Some(("+", rest)) => (1, rest),
+        _ => return None
+    };
+
+    let (amount, unit) = rest.split_at_checked(rest.len().saturating_sub(1))?;
+    let amount: u32 = amount.parse().ok()?;
+
+    match (unit, sign) {
+        ("d", _) => today.checked_add_signed(chrono::Duration::days(sign * i64::from(amount))),
+        ("w", _) => today.checked_add_signed(chrono::Duration::weeks(sign * i64::from(amount))),
+        ("m", 1) => today.checked_add_months(chrono::Months::new(amount)),
+        ("m", _) => today.checked_sub_months(chrono::Months::new(amount)),
+        _ => None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> chrono::NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn midnight(year: i32, month: u32, day: u32) -> Timestamp {
+        date(year, month, day).and_time(chrono::NaiveTime::MIN).and_utc()
+    }
+
+    #[test]
+    fn leap_day_bounds() {
+        assert_eq!(month_bounds(2024, 2), Some((midnight(2024, 2, 1), midnight(2024, 3, 1))));
+        assert_eq!(month_bounds(2023, 2), Some((midnight(2023, 2, 1), midnight(2023, 3, 1))));
+        assert_eq!(month_bounds(2024, 13), None);
+
+        assert_eq!(week_bounds(date(2024, 2, 29), chrono::Weekday::Mon), 
+            Some((midnight(2024, 2, 26), midnight(2024, 3, 4))));
+
+        assert_eq!(add_months(&midnight(2024, 1, 31), 1), Some(midnight(2024, 2, 29)));
+        assert_eq!(add_months(&midnight(2024, 2, 29), 12), Some(midnight(2025, 2, 28)));
+    }
+
+    #[test]
+    fn year_boundary_bounds() {
+        assert_eq!(week_bounds(date(2024, 12, 31), chrono::Weekday::Mon), 
+            Some((midnight(2024, 12, 30), midnight(2025, 1, 6))));
+
+        assert_eq!(week_bounds(date(2025, 1, 1), chrono::Weekday::Sun), 
+            Some((midnight(2024, 12, 29), midnight(2025, 1, 5))));
+
+        assert_eq!(month_bounds(2024, 12), Some((midnight(2024, 12, 1), midnight(2025, 1, 1))));
+        assert_eq!(quarter_bounds(2024, 4), Some((midnight(2024, 10, 1), midnight(2025, 1, 1))));
+        assert_eq!(quarter_bounds(2024, 5), None);
+
+        let last_moment = midnight(2025, 1, 1) - chrono::Duration::seconds(1);
+        assert_eq!(period_containing(&last_moment, PlanPeriod::Quarterly, chrono::Weekday::Mon, None), 
+            Some((midnight(2024, 10, 1), midnight(2025, 1, 1))));
+
+        assert_eq!(period_containing(&last_moment, PlanPeriod::Yearly, chrono::Weekday::Mon, None), 
+            Some((midnight(2024, 1, 1), midnight(2025, 1, 1))));
+
+        assert_eq!(period_containing(&midnight(2025, 1, 1), PlanPeriod::Monthly, chrono::Weekday::Mon, None), 
+            Some((midnight(2025, 1, 1), midnight(2025, 2, 1))));
+    }
+
+    #[test]
+    fn custom_period_is_counted_from_epoch() {
+        assert_eq!(period_containing(&midnight(1970, 1, 10), PlanPeriod::Custom(7), chrono::Weekday::Mon, None), 
+            Some((midnight(1970, 1, 8), midnight(1970, 1, 15))));
+
+        assert_eq!(period_containing(&midnight(1970, 1, 10), PlanPeriod::Custom(0), chrono::Weekday::Mon, None), None);
+    }
+
+    #[test]
+    fn user_dates_are_parsed() {
+        let today = midnight(2024, 5, 15) + chrono::Duration::hours(15);
+
+        assert_eq!(parse_user_date("2024-05-01", today, None).unwrap(), midnight(2024, 5, 1));
+        assert_eq!(parse_user_date(" 05/01 ", today, None).unwrap(), midnight(2024, 5, 1));
+        assert_eq!(parse_user_date("Today", today, None).unwrap(), midnight(2024, 5, 15));
+        assert_eq!(parse_user_date("yesterday", today, None).unwrap(), midnight(2024, 5, 14));
+        assert_eq!(parse_user_date("-3d", today, None).unwrap(), midnight(2024, 5, 12));
+        assert_eq!(parse_user_date("+2w", today, None).unwrap(), midnight(2024, 5, 29));
+        assert_eq!(parse_user_date("-1m", today, None).unwrap(), midnight(2024, 4, 15));
+
+        for malformed in ["", "someday", "13/01", "2024-02-30", "3d", "-d", "-3y", "+-3d"] {
+            assert_eq!(parse_user_date(malformed, today, None).unwrap_err().kind(), ErrorKind::Malformed);
+        }
+    }
+
+    #[test]
+    fn user_dates_around_leap_day() {
+        let leap = midnight(2024, 2, 28);
+        assert_eq!(parse_user_date("tomorrow", leap, None).unwrap(), midnight(2024, 2, 29));
+        assert_eq!(parse_user_date("02/29", leap, None).unwrap(), midnight(2024, 2, 29));
+        assert_eq!(parse_user_date("-1m", midnight(2024, 3, 31), None).unwrap(), midnight(2024, 2, 29));
+
+        let common = midnight(2023, 2, 28);
+        assert_eq!(parse_user_date("tomorrow", common, None).unwrap(), midnight(2023, 3, 1));
+        assert!(parse_user_date("02/29", common, None).is_err());
+    }
+
+    #[test]
+    fn user_dates_across_year_boundary() {
+        let new_year_eve = midnight(2024, 12, 31);
+        assert_eq!(parse_user_date("tomorrow", new_year_eve, None).unwrap(), midnight(2025, 1, 1));
+        assert_eq!(parse_user_date("+1m", new_year_eve, None).unwrap(), midnight(2025, 1, 31));
+        assert_eq!(parse_user_date("01/01", new_year_eve, None).unwrap(), midnight(2024, 1, 1));
+
+        let new_year = midnight(2025, 1, 1);
+        assert_eq!(parse_user_date("yesterday", new_year, None).unwrap(), midnight(2024, 12, 31));
+        assert_eq!(parse_user_date("-1w", new_year, None).unwrap(), midnight(2024, 12, 25));
+    }
+
+    #[cfg(feature = "tz")]
+    #[test]
+    fn periods_are_bounded_in_timezone() {
+        //
+        // Late evening of May 31 in New York (UTC-4) is June 1 in UTC
+        //
+
+        let evening = midnight(2024, 6, 1) + chrono::Duration::hours(2);
+        let timezone = Some("America/New_York");
+
+        assert_eq!(period_containing(&evening, PlanPeriod::Monthly, chrono::Weekday::Mon, timezone), 
+            Some((midnight(2024, 5, 1) + chrono::Duration::hours(4), midnight(2024, 6, 1) + chrono::Duration::hours(4))));
+
+        assert_eq!(period_containing(&evening, PlanPeriod::Monthly, chrono::Weekday::Mon, None), 
+            Some((midnight(2024, 6, 1), midnight(2024, 7, 1))));
+
+        //
+        // Winter quarter crosses daylight saving time transition
+        //
+
+        assert_eq!(period_containing(&midnight(2024, 2, 1), PlanPeriod::Quarterly, chrono::Weekday::Mon, timezone), 
+            Some((midnight(2024, 1, 1) + chrono::Duration::hours(5), midnight(2024, 4, 1) + chrono::Duration::hours(4))));
+    }
+
+    #[cfg(feature = "tz")]
+    #[test]
+    fn user_dates_are_resolved_in_timezone() {
+        //
+        // It is already May 16 in Tokyo (UTC+9), and the day starts
+        // there at 15:00 UTC of the previous day
+        //
+
+        let today = midnight(2024, 5, 15) + chrono::Duration::hours(20);
+        let timezone = Some("Asia/Tokyo");
+
+        assert_eq!(parse_user_date("today", today, timezone).unwrap(), midnight(2024, 5, 15) + chrono::Duration::hours(15));
+        assert_eq!(parse_user_date("yesterday", today, timezone).unwrap(), midnight(2024, 5, 14) + chrono::Duration::hours(15));
+        assert_eq!(parse_user_date("2024-05-01", today, timezone).unwrap(), midnight(2024, 4, 30) + chrono::Duration::hours(15));
+        assert_eq!(parse_user_date("today", today, None).unwrap(), midnight(2024, 5, 15));
+    }
+}
@@ -1,8 +1,110 @@
+/// Kind of an error, that allows to handle errors programmatically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Storage failure, e.g. database is locked or item is missing
+    Storage,
+
+    /// Operation violates consistency of stored data, 
+    /// e.g. removed item is referenced by another items
+    Consistency,
+
+    /// Operation is prohibited for predefined item
+    PredefinedItem,
+
+    /// Requested item is missing or removed
+    NotFound,
+
+    /// Item with the same identifier already exists
+    AlreadyExists,
+
+    /// Cryptographic failure
+    Crypto,
+
+    /// Required key is missing
+    KeyNotFound,
+
+    /// Synchronization failure
+    Sync,
+
+    /// Local and remote repositories have conflicting changes
+    RemoteConflict,
+
+    /// Remote rejected provided credentials
+    Authentication,
+
+    /// Another synchronization holds the lock right now
+    SyncInProgress,
+
+    /// Synchronized files cannot be authenticated or decrypted,
+    /// i.e. passphrase is wrong or remote data is tampered
+    SyncAuthentication,
+
+    /// Synchronization is not configured for the instance
+    SyncNotConfigured,
+
+    /// Configuration is partially written or malformed
+    ConfigCorrupted,
+
+    /// Data location is used by another process
+    InstanceRunning,
+
+    /// Input/output failure
+    Io,
+
+    /// Failure during serialization or deserialization
+    Serialization,
+
+    /// Malformed data
+    Malformed,
+
+    /// Any other error
+    Other,
+}
+
+
+/// Static error message with its kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ErrorMessage {
+    kind: ErrorKind,
+    text: &'static str
+}
+
+
+impl ErrorMessage {
+    /// Constructs an error message.
+    /// 
+    /// * `kind` - kind of an error
+    /// * `text` - human-readable message
+    pub const fn new(kind: ErrorKind, text: &'static str) -> Self {
+        ErrorMessage { 
+            kind, 
+            text 
+        }
+    }
+
+    /// Returns kind of an error.
+    pub const fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns human-readable message.
+    pub const fn text(&self) -> &'static str {
+        self.text
+    }
+}
+
+
+/// Original error, that caused an error in libbdgt.
+type Source = Box<dyn std::error::Error + Send + Sync>;
+
+
 /// Structure, that describes all errors in libbdgt.
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub struct Error {
+    kind: ErrorKind,
     msg: String,
-    extra: String
+    extra: String,
+    source: Option<Source>
 }
 
 
@@ -12,20 +114,84 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 
 impl Error {
-    /// Constructs an error from message.
+    /// Constructs an error from predefined message.
+    /// 
+    /// * `msg` - predefined error message
+    pub fn new(msg: ErrorMessage) -> Self {
+        Self::from_kind(msg.kind, msg.text)
+    }
+
+    /// Constructs an error from predefined message with some extra information.
+    /// 
+    /// * `msg` - predefined error message
+    /// * `extra` - extra information as something convertible into a [`alloc::string::String`]
+    pub fn new_with_extra<E>(msg: ErrorMessage, extra: E) -> Self
+    where
+        E: Into<String>
+    {
+        Self::from_kind_with_extra(msg.kind, msg.text, extra)
+    }
+
+    /// Constructs an error of specific kind from message.
     /// 
+    /// * `kind` - kind of an error
     /// * `msg` - error message as something convertible into a [`alloc::string::String`]
-    pub fn from_message<M>(msg: M) -> Self 
+    pub fn from_kind<M>(kind: ErrorKind, msg: M) -> Self 
     where
         M: Into<String>
+    {
+        Self::from_kind_with_extra(kind, msg, String::new())
+    }
+
+    /// Constructs an error of specific kind from message with some extra information.
+    /// 
+    /// * `kind` - kind of an error
+    /// * `msg` - error message as something convertible into a [`alloc::string::String`]
+    /// * `extra` - extra information as something convertible into a [`alloc::string::String`]
+    pub fn from_kind_with_extra<M, E>(kind: ErrorKind, msg: M, extra: E) -> Self
+    where
+        M: Into<String>,
+        E: Into<String>
     {
         Error { 
+            kind,
             msg: msg.into(), 
-            extra: String::new() 
+            extra: extra.into(),
+            source: None
+        }
+    }
+
+    /// Constructs an error of specific kind from an original error.
+    /// Message of the error is taken from the original one.
+    /// 
+    /// * `kind` - kind of an error
+    /// * `source` - original error
+    /// * `extra` - extra information as something convertible into a [`alloc::string::String`]
+    fn from_source<S, E>(kind: ErrorKind, source: S, extra: E) -> Self
+    where
+        S: std::error::Error + Send + Sync + 'static,
+        E: Into<String>
+    {
+        Error { 
+            kind,
+            msg: source.to_string(), 
+            extra: extra.into(),
+            source: Some(Box::new(source))
         }
     }
 
+    /// Constructs an error from message. Kind of the error is [`ErrorKind::Other`].
+    /// 
+    /// * `msg` - error message as something convertible into a [`alloc::string::String`]
+    pub fn from_message<M>(msg: M) -> Self 
+    where
+        M: Into<String>
+    {
+        Self::from_kind(ErrorKind::Other, msg)
+    }
+
     /// Constructs an error from message with some extra information.
+    /// Kind of the error is [`ErrorKind::Other`].
     /// 
     /// * `msg` - error message as something convertible into a [`alloc::string::String`]
     /// * `extra` - extra information as something convertible into a [`alloc::string::String`]
@@ -34,10 +200,112 @@ impl Error {
         M: Into<String>,
         E: Into<String>
     {
-        Error { 
-            msg: msg.into(), 
-            extra: extra.into() 
-        }
+        Self::from_kind_with_extra(ErrorKind::Other, msg, extra)
+    }
+
+    /// Returns kind of the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Checks if the error is caused by violation of stored data consistency.
+    pub fn is_consistency_violation(&self) -> bool {
+        self.kind == ErrorKind::Consistency
+    }
+
+    /// Checks if the error is caused by an operation on predefined item.
+    pub fn is_predefined_item(&self) -> bool {
+        self.kind == ErrorKind::PredefinedItem
+    }
+
+    /// Checks if the error is caused by a missing or removed item.
+    pub fn is_not_found(&self) -> bool {
+        self.kind == ErrorKind::NotFound
+    }
+
+    /// Checks if the error is caused by an item with already used identifier.
+    pub fn is_already_exists(&self) -> bool {
+        self.kind == ErrorKind::AlreadyExists
+    }
+
+    /// Checks if the error is caused by a missing key.
+    pub fn is_key_not_found(&self) -> bool {
+        self.kind == ErrorKind::KeyNotFound
+    }
+
+    /// Checks if the error is caused by conflicting remote changes.
+    pub fn is_remote_conflict(&self) -> bool {
+        self.kind == ErrorKind::RemoteConflict
+    }
+
+    /// Checks if the error is caused by failed authentication with remote.
+    pub fn is_authentication_failure(&self) -> bool {
+        self.kind == ErrorKind::Authentication
+    }
+
+    /// Checks if the error is caused by another synchronization in progress.
+    pub fn is_sync_in_progress(&self) -> bool {
+        self.kind == ErrorKind::SyncInProgress
+    }
+
+    /// Checks if the error is caused by failed authentication of synchronized files.
+    pub fn is_sync_authentication_failure(&self) -> bool {
+        self.kind == ErrorKind::SyncAuthentication
+    }
+
+    /// Checks if the error is caused by synchronization, that is not configured.
+    pub fn is_sync_not_configured(&self) -> bool {
+        self.kind == ErrorKind::SyncNotConfigured
+    }
+
+    /// Checks if the error is caused by another process, that uses the same location.
+    pub fn is_instance_running(&self) -> bool {
+        self.kind == ErrorKind::InstanceRunning
+    }
+
+    /// Checks if the error is caused by corrupted configuration.
+    pub fn is_config_corrupted(&self) -> bool {
+        self.kind == ErrorKind::ConfigCorrupted
+    }
+
+    /// Returns SQLite result code if the error is caused by SQLite.
+    pub fn sqlite_code(&self) -> Option<rusqlite::ErrorCode> {
+        self.sqlite_error()
+            .map(|error| error.code)
+    }
+
+    /// Returns SQLite extended result code if the error is caused by SQLite.
+    pub fn sqlite_extended_code(&self) -> Option<std::os::raw::c_int> {
+        self.sqlite_error()
+            .map(|error| error.extended_code)
+    }
+
+    /// Checks if the error is caused by a locked database, i.e.
+    /// an operation can succeed if it is retried later.
+    pub fn is_busy(&self) -> bool {
+        matches!(self.sqlite_code(), 
+            Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked))
+    }
+
+    fn sqlite_error(&self) -> Option<&rusqlite::ffi::Error> {
+        self.source
+            .as_ref()?
+            .downcast_ref::<rusqlite::Error>()?
+            .sqlite_error()
+    }
+}
+
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        //
+        // Original errors are not comparable, hence I compare
+        // only information, that is visible to user
+        //
+
+        self.kind == other.kind &&
+        self.msg == other.msg &&
+        self.extra == other.extra
     }
 }
 
@@ -53,15 +321,20 @@ impl std::error::Error for Error {
     fn description(&self) -> &str {
         &self.msg
     }
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| source.as_ref() as &(dyn std::error::Error + 'static))
+    }
 }
 
 
+#[cfg(feature = "gpg")]
 impl From<gpgme::Error> for Error {
     fn from(value: gpgme::Error) -> Self {
-        let msg = value.to_string();
         let extra = format!("code: {}", value.code());
-
-        Error::from_message_with_extra(msg, extra)
+        Error::from_source(ErrorKind::Crypto, value, extra)
     }
 }
 
@@ -69,30 +342,49 @@ impl From<gpgme::Error> for Error {
 /// Macro for implementing [`From<SomeError>`] in a beautiful way.
 /// It simplifies implementing the trait for a new error type
 /// to writing only one line of code.
+/// 
+/// Some errors do not implement [`std::error::Error`], they are
+/// marked with `message_only` and only their message is preserved.
 macro_rules! implement_from_error {
-    ($err_type:ty, $($err_types:ty),+ $(,)?) => {
-        implement_from_error!($err_type);
-        implement_from_error!($($err_types, )+);
+    ($kind:ident $(, $mode:ident)? => $err_type:ty, $($err_types:ty),+ $(,)?) => {
+        implement_from_error!($kind $(, $mode)? => $err_type);
+        implement_from_error!($kind $(, $mode)? => $($err_types, )+);
+    };
+    ($kind:ident => $err_type:ty $(,)?) => {
+        impl From<$err_type> for Error {
+            fn from(value: $err_type) -> Self {
+                Error::from_source(ErrorKind::$kind, value, String::new())
+            }
+        }
     };
-    ($err_type:ty $(,)?) => {
+    ($kind:ident, message_only => $err_type:ty $(,)?) => {
         impl From<$err_type> for Error {
             fn from(value: $err_type) -> Self {
-                let msg = value.to_string();
-                Error::from_message(msg)
+                Error::from_kind(ErrorKind::$kind, value.to_string())
             }
         }
     }
 }
 
-implement_from_error!(
-    rusqlite::Error,
-    std::io::Error,
-    rand::Error,
+implement_from_error!(Storage => rusqlite::Error);
+implement_from_error!(Io => std::io::Error);
+implement_from_error!(Sync => git2::Error);
+implement_from_error!(Other => std::convert::Infallible);
+
+implement_from_error!(Crypto => rand::Error);
+
+implement_from_error!(Crypto, message_only => 
     aes_gcm::Error,
-    std::convert::Infallible,
-    git2::Error,
     scrypt::errors::InvalidOutputLen,
+    scrypt::errors::InvalidParams,
+    argon2::Error,
+);
+
+implement_from_error!(Serialization => 
     flexbuffers::DeserializationError,
     flexbuffers::SerializationError,
-    uuid::Error,
+    csv::Error,
+    serde_json::Error,
 );
+
+implement_from_error!(Malformed => uuid::Error);
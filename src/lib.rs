@@ -6,14 +6,36 @@ extern crate dirs;
 extern crate git2;
 extern crate uuid;
 extern crate rand;
+#[cfg(feature = "gpg")]
 extern crate gpgme;
 extern crate scrypt;
+extern crate argon2;
+extern crate base64ct;
 extern crate chrono;
+#[cfg(feature = "tz")]
+extern crate chrono_tz;
+#[cfg(feature = "async")]
+extern crate tokio;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 extern crate typenum;
 extern crate aes_gcm;
 extern crate rusqlite;
 extern crate lazy_static;
 extern crate flexbuffers;
+extern crate csv;
+extern crate serde_json;
+
+//
+// Private modules, macros are declared first
+// to be available in all other modules
+//
+
+#[macro_use]
+mod trace;
+
+#[cfg(test)]
+mod testing;
 
 //
 // Public modules
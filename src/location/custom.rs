@@ -0,0 +1,42 @@
+use crate::error::Result;
+use super::location::Location;
+
+
+/// App's location at an arbitrary path.
+pub struct CustomLocation {
+    /// Root path of app's data.
+    root: std::path::PathBuf,
+}
+
+
+impl CustomLocation {
+    /// Creates a location at a path.
+    /// 
+    /// * `path` - root path of app's data
+    pub fn new(path: std::path::PathBuf) -> Self {
+        CustomLocation { 
+            root: path 
+        }
+    }
+}
+
+
+impl Location for CustomLocation {
+    fn root(&self) -> std::path::PathBuf {
+        self.root
+            .clone()
+    }
+
+    fn exists(&self) -> bool {
+        self.root
+            .exists()
+    }
+
+    fn create_if_absent(&self) -> Result<()> {
+        if !self.exists() {
+            std::fs::create_dir_all(&self.root)?;
+        }
+
+        Ok(())
+    }
+}
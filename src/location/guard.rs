@@ -0,0 +1,59 @@
+use std::io::Write;
+
+use crate::error::{Result, Error};
+use super::INSTANCE_RUNNING;
+
+
+/// Lock of app's data location, that is released on drop.
+///
+/// Lock is advisory and is held by the operating system, hence
+/// it is released even if its holder crashes. Lock file contains
+/// identifier of the process, that took the lock last.
+pub struct LocationGuard {
+    /// Locked file.
+    file: std::fs::File,
+}
+
+
+impl LocationGuard {
+    /// Takes lock or fails immediately if it is held by another process.
+    ///
+    /// * `path` - path to lock file
+    /// * `shared` - whether lock can be shared with other shared holders
+    pub(super) fn acquire(path: &std::path::Path, shared: bool) -> Result<Self> {
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let locked = match shared {
+            true => file.try_lock_shared(),
+            false => file.try_lock()
+        };
+
+        match locked {
+            Ok(_) => (),
+            Err(std::fs::TryLockError::WouldBlock) => {
+                let holder = std::fs::read_to_string(path)
+                    .unwrap_or_default();
+
+                return Err(Error::new_with_extra(INSTANCE_RUNNING, format!("pid: {}", holder.trim())));
+            },
+            Err(std::fs::TryLockError::Error(error)) => return Err(error.into())
+        }
+
+        file.set_len(0)?;
+        file.write_all(std::process::id().to_string().as_bytes())?;
+
+        Ok(LocationGuard { file })
+    }
+}
+
+
+impl Drop for LocationGuard {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
@@ -1,9 +1,10 @@
-use crate::error::Result;
+use crate::error::{Result, Error};
 use super::location::Location;
+use super::LOCATION_NOT_FOUND;
 
 
 /// Root folder for app's data.
-const ROOT_FOLDER: &str = ".bdgt";
+pub(super) const ROOT_FOLDER: &str = ".bdgt";
 
 
 /// App's location based on current user's home directory.
@@ -18,21 +19,39 @@ impl HomeLocation {
 }
 
 
+impl Default for HomeLocation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
 impl Location for HomeLocation {
     fn root(&self) -> std::path::PathBuf {
+        //
+        // Relative path is returned if there is no home directory,
+        // creation of location reports an error in this case
+        //
+
+        self.try_root()
+            .unwrap_or_else(|_| std::path::PathBuf::from(ROOT_FOLDER))
+    }
+
+    fn try_root(&self) -> Result<std::path::PathBuf> {
         dirs::home_dir()
-            .unwrap()
-            .join(ROOT_FOLDER)
+            .map(|home| home.join(ROOT_FOLDER))
+            .ok_or(Error::new_with_extra(LOCATION_NOT_FOUND, "home directory is unknown"))
     }
 
     fn exists(&self) -> bool {
-        self.root()
-            .exists()
+        self.try_root()
+            .is_ok_and(|root| root.exists())
     }
 
     fn create_if_absent(&self) -> Result<()> {
-        if !self.exists() {
-            std::fs::create_dir_all(self.root())?;
+        let root = self.try_root()?;
+        if !root.exists() {
+            std::fs::create_dir_all(root)?;
         }
 
         Ok(())
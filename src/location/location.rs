@@ -1,4 +1,6 @@
 use crate::error::Result;
+use super::guard::LocationGuard;
+use super::LOCK_FILE;
 
 
 /// Traits, that manages application's data location.
@@ -6,9 +8,31 @@ pub trait Location {
     /// Get root path of app's data location.
     fn root(&self) -> std::path::PathBuf;
 
+    /// Get root path of app's data location or fail
+    /// if it cannot be determined.
+    fn try_root(&self) -> Result<std::path::PathBuf> {
+        Ok(self.root())
+    }
+
     /// Checks if root directory is present.
     fn exists(&self) -> bool;
 
     /// Create root directory if it doesn't exist.
     fn create_if_absent(&self) -> Result<()>;
+
+    /// Locks location exclusively, i.e. no other process can lock
+    /// it until the guard is dropped. Root directory is created
+    /// if it doesn't exist.
+    fn lock(&self) -> Result<LocationGuard> {
+        self.create_if_absent()?;
+        LocationGuard::acquire(&self.try_root()?.join(LOCK_FILE), false)
+    }
+
+    /// Locks location for reading, i.e. other readers can lock it
+    /// too, but exclusive lock cannot be taken until the guard is
+    /// dropped. Root directory is created if it doesn't exist.
+    fn lock_shared(&self) -> Result<LocationGuard> {
+        self.create_if_absent()?;
+        LocationGuard::acquire(&self.try_root()?.join(LOCK_FILE), true)
+    }
 }
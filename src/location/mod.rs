@@ -1,5 +1,45 @@
 mod home;
+mod xdg;
+mod custom;
+mod temp;
+mod guard;
+#[allow(clippy::module_inception)]
 mod location;
 
 pub use self::location::Location;
 pub use self::home::HomeLocation;
+pub use self::xdg::XdgLocation;
+pub use self::custom::CustomLocation;
+pub use self::temp::TempLocation;
+pub use self::guard::LocationGuard;
+
+use crate::error::{Result, ErrorKind, ErrorMessage};
+
+
+/// Environment variable, that overrides default location.
+const HOME_VARIABLE: &str = "BDGT_HOME";
+
+/// Lock file of app's data location.
+const LOCK_FILE: &str = "lock";
+
+
+/// Error shown if default location cannot be determined.
+const LOCATION_NOT_FOUND: ErrorMessage = ErrorMessage::new(ErrorKind::Io, "Location of app's data cannot be determined");
+
+/// Error shown if location is locked by another process.
+const INSTANCE_RUNNING: ErrorMessage = ErrorMessage::new(ErrorKind::InstanceRunning, "Another bdgt instance is running");
+
+
+/// Resolves default location of app's data.
+/// 
+/// Path from `BDGT_HOME` environment variable is used if it is set.
+/// Otherwise location inside of user's data directory is used
+/// (see [`XdgLocation`]).
+pub fn resolve_default() -> Result<CustomLocation> {
+    let root = match std::env::var_os(HOME_VARIABLE) {
+        Some(root) if !root.is_empty() => std::path::PathBuf::from(root),
+        _ => XdgLocation::new().try_root()?
+    };
+
+    Ok(CustomLocation::new(root))
+}
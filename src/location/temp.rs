@@ -0,0 +1,112 @@
+use crate::error::Result;
+use super::location::Location;
+
+
+/// Prefix of temporary folder name.
+const TEMP_FOLDER_PREFIX: &str = "bdgt-test-";
+
+
+/// Throwaway app's location in a unique temporary folder, e.g.
+/// for tests or dry runs. Folder is removed on drop.
+pub struct TempLocation {
+    /// Root path of app's data.
+    root: std::path::PathBuf,
+
+    /// Whether folder is kept after drop.
+    keep: bool,
+}
+
+
+impl TempLocation {
+    /// Creates a location in a new unique temporary folder.
+    /// Folder itself is created by [`Location::create_if_absent`].
+    pub fn new() -> Self {
+        let folder = format!("{}{}", TEMP_FOLDER_PREFIX, uuid::Uuid::new_v4());
+
+        TempLocation {
+            root: std::env::temp_dir().join(folder),
+            keep: false
+        }
+    }
+
+    /// Keeps folder after drop, e.g. to inspect it while debugging.
+    pub fn keep(&mut self) {
+        self.keep = true;
+    }
+}
+
+
+impl Default for TempLocation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Location for TempLocation {
+    fn root(&self) -> std::path::PathBuf {
+        self.root
+            .clone()
+    }
+
+    fn exists(&self) -> bool {
+        self.root
+            .exists()
+    }
+
+    fn create_if_absent(&self) -> Result<()> {
+        if !self.exists() {
+            std::fs::create_dir_all(&self.root)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+impl Drop for TempLocation {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folder_is_removed_on_drop() {
+        let loc = TempLocation::new();
+        let root = loc.root();
+        assert!(root.starts_with(std::env::temp_dir()));
+        assert!(!loc.exists());
+
+        loc.create_if_absent().unwrap();
+        std::fs::create_dir_all(root.join("nested")).unwrap();
+        std::fs::write(root.join("nested").join("file"), b"data").unwrap();
+
+        drop(loc);
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn kept_folder_survives_drop() {
+        let mut loc = TempLocation::new();
+        loc.create_if_absent().unwrap();
+        loc.keep();
+
+        let root = loc.root();
+        drop(loc);
+        assert!(root.exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn folders_are_unique() {
+        assert_ne!(TempLocation::new().root(), TempLocation::new().root());
+    }
+}
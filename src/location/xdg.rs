@@ -0,0 +1,64 @@
+use crate::error::{Result, Error};
+use super::location::Location;
+use super::home::ROOT_FOLDER;
+use super::LOCATION_NOT_FOUND;
+
+
+/// Root folder for app's data inside of user's data directory.
+const DATA_FOLDER: &str = "bdgt";
+
+
+/// App's location inside of user's data directory, e.g.
+/// `$XDG_DATA_HOME/bdgt`. Legacy location in home directory
+/// is used instead if it already exists.
+#[derive(Default)]
+pub struct XdgLocation;
+
+
+impl XdgLocation {
+    /// Just creates an instance.
+    pub fn new() -> Self {
+        XdgLocation
+    }
+}
+
+
+impl Location for XdgLocation {
+    fn root(&self) -> std::path::PathBuf {
+        //
+        // Relative path is returned if there is no data directory,
+        // creation of location reports an error in this case
+        //
+
+        self.try_root()
+            .unwrap_or_else(|_| std::path::PathBuf::from(DATA_FOLDER))
+    }
+
+    fn try_root(&self) -> Result<std::path::PathBuf> {
+        let legacy_root = dirs::home_dir()
+            .map(|home| home.join(ROOT_FOLDER))
+            .filter(|legacy_root| legacy_root.exists());
+
+        if let Some(legacy_root) = legacy_root {
+            return Ok(legacy_root);
+        }
+
+        dirs::data_dir()
+            .map(|data| data.join(DATA_FOLDER))
+            .ok_or(Error::new_with_extra(LOCATION_NOT_FOUND, "data directory is unknown"))
+    }
+
+    fn exists(&self) -> bool {
+        self.try_root()
+            .is_ok_and(|root| root.exists())
+    }
+
+    fn create_if_absent(&self) -> Result<()> {
+        let root = self.try_root()?;
+        if !root.exists() {
+            std::fs::create_dir_all(root)?;
+        }
+
+        Ok(())
+    }
+}
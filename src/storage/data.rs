@@ -2,10 +2,111 @@ use serde::{Serialize, Deserialize};
 
 use crate::core::InstanceId;
 use crate::datetime::Timestamp;
+use crate::error::Error;
+use super::MALFORMED_IDENTIFIER;
 
 
 /// Identifier type.
-pub type Id = [u8; 16];
+/// 
+/// It is displayed and parsed as 32 hexadecimal digits. Human-readable
+/// formats (e.g. JSON) serialize it as such string, binary formats keep
+/// raw bytes.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id([u8; 16]);
+
+
+impl Id {
+    /// Constructs an identifier from raw bytes.
+    /// 
+    /// * `bytes` - raw identifier
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Id(bytes)
+    }
+
+    /// Generates a new random identifier.
+    pub fn random() -> Self {
+        Id(uuid::Uuid::new_v4().into_bytes())
+    }
+
+    /// Obtain raw bytes of the identifier.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    /// Consumes the identifier and returns its raw bytes.
+    pub fn into_bytes(self) -> [u8; 16] {
+        self.0
+    }
+}
+
+
+impl From<[u8; 16]> for Id {
+    fn from(bytes: [u8; 16]) -> Self {
+        Id(bytes)
+    }
+}
+
+
+impl From<Id> for [u8; 16] {
+    fn from(id: Id) -> Self {
+        id.0
+    }
+}
+
+
+impl std::fmt::Display for Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", uuid::Uuid::from_bytes(self.0).simple())
+    }
+}
+
+
+impl std::fmt::Debug for Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Id({})", self)
+    }
+}
+
+
+impl std::str::FromStr for Id {
+    type Err = Error;
+
+    /// Parses an identifier from hexadecimal digits. Hyphenated
+    /// form (as in exported data) is accepted as well.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        uuid::Uuid::parse_str(s)
+            .map(|id| Id(id.into_bytes()))
+            .map_err(|_| Error::new_with_extra(MALFORMED_IDENTIFIER, s))
+    }
+}
+
+
+impl Serialize for Id {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        //
+        // Binary formats keep raw bytes as before, hence
+        // existing changelogs and backups remain readable
+        //
+
+        match serializer.is_human_readable() {
+            true => serializer.collect_str(self),
+            false => self.0.serialize(serializer)
+        }
+    }
+}
+
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match deserializer.is_human_readable() {
+            true => String::deserialize(deserializer)?
+                .parse()
+                .map_err(serde::de::Error::custom),
+            false => <[u8; 16]>::deserialize(deserializer)
+                .map(Id)
+        }
+    }
+}
 
 
 /// Identifier for primary keys in structure.
@@ -16,7 +117,7 @@ pub type PrimaryId = Option<Id>;
 
 
 /// Types of categories.
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum CategoryType {
     /// Incomes
     Income,
@@ -26,8 +127,76 @@ pub enum CategoryType {
 }
 
 
+/// Types of accounts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum AccountType {
+    /// Cash
+    Cash,
+
+    /// Checking (current) account
+    #[default]
+    Checking,
+
+    /// Credit card or credit line, negative balance is a debt
+    Credit,
+
+    /// Savings, that are not intended for everyday spendings
+    Savings,
+}
+
+
+impl AccountType {
+    /// Checks if negative balance of accounts of this type is normal,
+    /// i.e. it means a debt instead of overdraft.
+    pub fn is_liability(&self) -> bool {
+        matches!(self, AccountType::Credit)
+    }
+
+    /// Checks if money on accounts of this type can be spent,
+    /// i.e. it counts in spendable totals.
+    pub fn is_spendable(&self) -> bool {
+        !matches!(self, AccountType::Savings)
+    }
+}
+
+
+/// Periods of recurring transactions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecurrencePeriod {
+    /// Every week
+    Weekly,
+
+    /// Every month
+    Monthly,
+
+    /// Every year
+    Yearly,
+}
+
+
+/// Periods, which plans' limits apply to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PlanPeriod {
+    /// Week (starting on the day configured in settings)
+    Weekly,
+
+    /// Calendar month
+    #[default]
+    Monthly,
+
+    /// Calendar quarter
+    Quarterly,
+
+    /// Calendar year
+    Yearly,
+
+    /// Custom number of days (counted from January 1970)
+    Custom(u32),
+}
+
+
 /// Meta information about an entity
-#[derive(Serialize, Deserialize, Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MetaInfo {
     // Origin (instance, where an object was created)
     pub origin: Option<[u8; 16]>,
@@ -62,15 +231,21 @@ impl MetaInfo {
     }
 
     pub(crate) fn set_origin_if_absent(&mut self, origin: &InstanceId) {
-        if let None = self.origin {
+        if self.origin.is_none() {
             self.origin = Some(origin.into_bytes());
         }
     }
+
+    pub(crate) fn set_changed_timestamp_if_absent(&mut self, timestamp: Timestamp) {
+        if self.changed_timestamp.is_none() {
+            self.changed_timestamp = Some(timestamp);
+        }
+    }
 }
 
 
 /// User-friendly transaction structure.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Transaction {
     /// Identifier
     pub id: PrimaryId,
@@ -90,6 +265,19 @@ pub struct Transaction {
     /// Amount of money affected
     pub amount: isize,
 
+    /// Identifier of a transfer, which the transaction is a part of
+    #[serde(default)]
+    pub transfer_id: Option<Id>,
+
+    /// Arbitrary labels (must not contain line breaks)
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// If `true`, the transaction is scheduled and its amount
+    /// is not applied to account's balance yet
+    #[serde(default)]
+    pub pending: bool,
+
     /// Meta info
     pub meta_info: MetaInfo
 }
@@ -98,7 +286,7 @@ pub struct Transaction {
 /// Protected transaction structure.
 /// 
 /// For fields description refer to [`Transaction`].
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct EncryptedTransaction {
     pub id: PrimaryId,
     pub timestamp: Timestamp,
@@ -106,12 +294,15 @@ pub struct EncryptedTransaction {
     pub account_id: Id,
     pub category_id: Id,
     pub amount: Vec<u8>,
+    pub transfer_id: Option<Id>,
+    pub tags: Option<Vec<u8>>,
+    pub pending: bool,
     pub meta_info: MetaInfo
 }
 
 
 /// User-friendly category structure.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Category {
     /// Identifier
     pub id: PrimaryId,
@@ -130,7 +321,7 @@ pub struct Category {
 /// Protected category structure.
 /// 
 /// For fields description refer to [`Category`].
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct EncryptedCategory {
     pub id: PrimaryId,
     pub name: Vec<u8>,
@@ -140,7 +331,7 @@ pub struct EncryptedCategory {
 
 
 /// User-friendly account structure.
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Account {
     /// Identifier
     pub id: PrimaryId,
@@ -154,6 +345,20 @@ pub struct Account {
     /// Initial account balance
     pub initial_balance: isize,
 
+    /// ISO 4217 currency code, if empty, then default
+    /// instance-wide currency is used
+    #[serde(default)]
+    pub currency: String,
+
+    /// If `true`, the account is closed and no transactions
+    /// can be added to it
+    #[serde(default)]
+    pub archived: bool,
+
+    /// Type of account
+    #[serde(default)]
+    pub account_type: AccountType,
+
     /// Meta info
     pub meta_info: MetaInfo
 }
@@ -162,18 +367,21 @@ pub struct Account {
 /// Protected account structure.
 /// 
 /// For fields description refer to [`Account`].
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct EncryptedAccount {
     pub id: PrimaryId,
     pub name: Vec<u8>,
     pub balance: Vec<u8>,
     pub initial_balance: Vec<u8>,
+    pub currency: Option<Vec<u8>>,
+    pub archived: bool,
+    pub account_type: AccountType,
     pub meta_info: MetaInfo
 }
 
 
 /// User-friendly plan structure.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Plan {
     /// Identifier
     pub id: PrimaryId,
@@ -187,6 +395,14 @@ pub struct Plan {
     /// Current plan balance
     pub amount_limit: isize,
 
+    /// Period, which the limit applies to
+    #[serde(default)]
+    pub period: PlanPeriod,
+
+    /// If `true`, unused allowance is carried over to the next period
+    #[serde(default)]
+    pub rollover: bool,
+
     /// Meta info
     pub meta_info: MetaInfo
 }
@@ -195,11 +411,218 @@ pub struct Plan {
 /// Protected plan structure.
 /// 
 /// For fields description refer to [`Plan`].
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct EncryptedPlan {
     pub id: PrimaryId,
     pub category_id: Id,
     pub name: Vec<u8>,
     pub amount_limit: Vec<u8>,
+    pub period: PlanPeriod,
+    pub rollover: bool,
     pub meta_info: MetaInfo
 }
+
+
+/// User-friendly recurring transaction structure.
+/// 
+/// It is a template, which concrete transactions are created from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecurringTransaction {
+    /// Identifier
+    pub id: PrimaryId,
+
+    /// Brief description of created transactions
+    pub description: String,
+
+    /// Identifier of an account, which created transactions belong to
+    pub account_id: Id,
+
+    /// Identifier of a category of created transactions
+    pub category_id: Id,
+
+    /// Amount of money affected by each transaction
+    pub amount: isize,
+
+    /// Period of recurrence
+    pub period: RecurrencePeriod,
+
+    /// Time of the first occurrence
+    pub start_timestamp: Timestamp,
+
+    /// Time, after which no occurrences are created
+    pub end_timestamp: Option<Timestamp>,
+
+    /// Time of the last created occurrence
+    pub materialized_timestamp: Option<Timestamp>,
+
+    /// Meta info
+    pub meta_info: MetaInfo
+}
+
+
+/// Protected recurring transaction structure.
+/// 
+/// For fields description refer to [`RecurringTransaction`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedRecurringTransaction {
+    pub id: PrimaryId,
+    pub description: Vec<u8>,
+    pub account_id: Id,
+    pub category_id: Id,
+    pub amount: Vec<u8>,
+    pub period: RecurrencePeriod,
+    pub start_timestamp: Timestamp,
+    pub end_timestamp: Option<Timestamp>,
+    pub materialized_timestamp: Option<Timestamp>,
+    pub meta_info: MetaInfo
+}
+
+
+/// Reference to a missing item found in storage.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForeignKeyViolation {
+    /// Name of a table with referencing item
+    pub table: String,
+
+    /// Identifier of referencing item
+    pub item: Id,
+
+    /// Name of a column with reference
+    pub column: String,
+
+    /// Name of a table with referenced item
+    pub parent_table: String,
+
+    /// Identifier of missing referenced item
+    pub parent: Id,
+}
+
+
+/// Kind of a problem found during integrity verification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IntegrityIssueKind {
+    /// Storage is physically damaged
+    Corruption,
+
+    /// Item references an item, that does not exist
+    MissingReference,
+
+    /// Alive item references a removed item
+    RemovedReference,
+
+    /// Stored balance of an account differs from the one computed from transactions
+    BalanceMismatch,
+}
+
+
+/// Problem found during integrity verification.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntegrityIssue {
+    /// Kind of the problem
+    pub kind: IntegrityIssueKind,
+
+    /// Name of a table with problematic item (empty if not applicable)
+    pub table: String,
+
+    /// Identifier of problematic item (if applicable)
+    pub item: Option<Id>,
+
+    /// Identifier of referenced item (if applicable)
+    pub reference: Option<Id>,
+
+    /// Human-readable description of the problem
+    pub details: String,
+}
+
+
+/// Result of integrity verification.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    /// All found problems
+    pub issues: Vec<IntegrityIssue>,
+}
+
+
+impl IntegrityReport {
+    /// Checks if no problems are found.
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+
+/// Result of storage compaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompactStats {
+    /// Size of storage in bytes before compaction
+    pub size_before: u64,
+
+    /// Size of storage in bytes after compaction
+    pub size_after: u64,
+}
+
+
+impl CompactStats {
+    /// Returns number of reclaimed bytes.
+    pub fn reclaimed(&self) -> u64 {
+        self.size_before.saturating_sub(self.size_after)
+    }
+}
+
+
+/// Number of items in a single table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TableStats {
+    /// Number of alive items
+    pub alive: usize,
+
+    /// Number of removed (but not deleted permanently yet) items
+    pub removed: usize,
+}
+
+
+/// Statistics of stored data, that is collected without decryption.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    /// Transactions count
+    pub transactions: TableStats,
+
+    /// Accounts count
+    pub accounts: TableStats,
+
+    /// Categories count
+    pub categories: TableStats,
+
+    /// Plans count
+    pub plans: TableStats,
+
+    /// Recurring transactions count
+    pub recurrings: TableStats,
+
+    /// Timestamp of the oldest alive transaction
+    pub first_transaction: Option<Timestamp>,
+
+    /// Timestamp of the newest alive transaction
+    pub last_transaction: Option<Timestamp>,
+
+    /// Size of storage in bytes
+    pub size: u64,
+}
+
+
+/// Protected balance of an account at the beginning of a period.
+/// 
+/// Snapshots are derived from transactions, hence they are neither
+/// synchronized nor backed up. Balance is a sum of account's transactions
+/// made strictly before period start, initial balance is not included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedBalanceSnapshot {
+    /// Identifier of an account
+    pub account_id: Id,
+
+    /// Start of a period
+    pub period_start: Timestamp,
+
+    /// Encrypted sum of transactions
+    pub balance: Vec<u8>,
+}
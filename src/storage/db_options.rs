@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+
+/// Default time to wait for a lock held by another connection.
+const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default number of prepared statements kept in cache.
+const DEFAULT_STATEMENT_CACHE_CAPACITY: usize = 64;
+
+
+/// Options of database connection used by [`super::DbStorage`].
+///
+/// By default foreign keys are enforced, write-ahead log is used
+/// and locked database is waited for 5 seconds. Up to 64 prepared
+/// statements are cached.
+#[derive(Clone, Copy, Debug)]
+pub struct DbStorageOptions {
+    /// Enforce foreign key constraints
+    pub(super) foreign_keys: bool,
+
+    /// Use write-ahead log instead of rollback journal
+    pub(super) write_ahead_log: bool,
+
+    /// Time to wait for a lock held by another connection
+    pub(super) busy_timeout: Duration,
+
+    /// Maximum number of cached prepared statements
+    pub(super) statement_cache_capacity: usize,
+}
+
+
+impl DbStorageOptions {
+    /// Creates default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables or disables enforcement of foreign key constraints.
+    ///
+    /// * `enabled` - enforce constraints if `true`
+    pub fn foreign_keys(mut self, enabled: bool) -> Self {
+        self.foreign_keys = enabled;
+        self
+    }
+
+    /// Enables or disables write-ahead log.
+    ///
+    /// * `enabled` - use write-ahead log if `true`
+    pub fn write_ahead_log(mut self, enabled: bool) -> Self {
+        self.write_ahead_log = enabled;
+        self
+    }
+
+    /// Sets time to wait for a lock held by another connection.
+    ///
+    /// * `timeout` - time to wait, zero means to fail immediately
+    pub fn busy_timeout(mut self, timeout: Duration) -> Self {
+        self.busy_timeout = timeout;
+        self
+    }
+
+    /// Sets maximum number of cached prepared statements.
+    ///
+    /// * `capacity` - number of statements, zero disables caching
+    pub fn statement_cache_capacity(mut self, capacity: usize) -> Self {
+        self.statement_cache_capacity = capacity;
+        self
+    }
+}
+
+
+impl Default for DbStorageOptions {
+    fn default() -> Self {
+        DbStorageOptions {
+            foreign_keys: true,
+            write_ahead_log: true,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            statement_cache_capacity: DEFAULT_STATEMENT_CACHE_CAPACITY
+        }
+    }
+}
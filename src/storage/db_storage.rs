@@ -1,14 +1,178 @@
 use crate::location::Location;
 use crate::error::{Result, Error};
 use crate::datetime::Timestamp;
-use super::data::{EncryptedTransaction, EncryptedCategory, EncryptedAccount, EncryptedPlan, Id, CategoryType, MetaInfo};
+use super::data::{EncryptedTransaction, EncryptedCategory, EncryptedAccount, EncryptedPlan, EncryptedRecurringTransaction, Id, CategoryType, AccountType, RecurrencePeriod, PlanPeriod, MetaInfo};
+use super::data::{ForeignKeyViolation, IntegrityReport, IntegrityIssue, IntegrityIssueKind, CompactStats};
+use super::data::{StorageStats, TableStats, EncryptedBalanceSnapshot};
+use super::db_options::DbStorageOptions;
 use super::storage::DataStorage;
-use super::{CONSISTENCY_VIOLATION, CANNOT_DELETE_PREDEFINED};
+use super::{CONSISTENCY_VIOLATION, MISSING_REFERENCE, CANNOT_DELETE_PREDEFINED, CANNOT_UPDATE_PREDEFINED, NOT_FOUND, ALREADY_EXISTS};
+use super::STORAGE_IS_READ_ONLY;
+use super::item_not_found;
+use super::migrations;
 
 
 /// Name of DB file.
 const DB_FILE: &str = "database";
 
+/// Number of attempts to execute a statement, when database is locked.
+const BUSY_RETRY_COUNT: u32 = 5;
+
+/// Delay before the first retry, it grows with each attempt.
+const BUSY_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(50);
+
+
+/// Select statements for each table with appended modifiers.
+/// 
+/// Statements are built at compile time, hence the same query 
+/// always has the same text and prepared statement cache hits.
+macro_rules! select_from_transactions {
+    ($modifiers:literal) => {
+        concat!(r#"
+            SELECT transaction_id, timestamp, description, account_id, category_id, amount, transfer_id, tags, pending,
+                   _origin, _creation_timestamp, _change_timestamp, _removal_timestamp
+              FROM transactions
+        "#, $modifiers)
+    }
+}
+
+macro_rules! select_from_accounts {
+    ($modifiers:literal) => {
+        concat!(r#"
+            SELECT account_id, name, balance, initial_balance, currency, archived, type, _origin, _creation_timestamp, _change_timestamp, _removal_timestamp
+              FROM accounts
+        "#, $modifiers)
+    }
+}
+
+macro_rules! select_from_categories {
+    ($modifiers:literal) => {
+        concat!(r#"
+            SELECT category_id, name, type, _origin, _creation_timestamp, _change_timestamp, _removal_timestamp
+              FROM categories
+        "#, $modifiers)
+    }
+}
+
+macro_rules! select_from_plans {
+    ($modifiers:literal) => {
+        concat!(r#"
+            SELECT plan_id, category_id, name, amount_limit, period, rollover, _origin, _creation_timestamp, _change_timestamp, _removal_timestamp
+              FROM plans
+        "#, $modifiers)
+    }
+}
+
+macro_rules! select_from_recurrings {
+    ($modifiers:literal) => {
+        concat!(r#"
+            SELECT recurring_id, description, account_id, category_id, amount, period, start_timestamp, 
+                   end_timestamp, materialized_timestamp, _origin, _creation_timestamp, _change_timestamp, _removal_timestamp
+              FROM recurrings
+        "#, $modifiers)
+    }
+}
+
+
+/// Table with statements, that are built for it at compile time.
+struct Table {
+    /// Counts alive and removed items
+    stats: &'static str,
+
+    /// Deletes items removed before an optional cutoff
+    delete_removed: &'static str,
+}
+
+macro_rules! table {
+    ($table:literal) => {
+        Table {
+            stats: concat!(r#"
+                SELECT COUNT(*) - COUNT(_removal_timestamp), COUNT(_removal_timestamp) FROM "#, $table),
+            delete_removed: concat!(r#"
+                DELETE FROM "#, $table, r#"
+                 WHERE _removal_timestamp IS NOT NULL AND
+                       (?1 IS NULL OR _removal_timestamp < ?1)
+            "#),
+        }
+    }
+}
+
+const TRANSACTIONS: Table = table!("transactions");
+const ACCOUNTS: Table = table!("accounts");
+const CATEGORIES: Table = table!("categories");
+const PLANS: Table = table!("plans");
+const RECURRINGS: Table = table!("recurrings");
+
+
+/// Column of a table with statements, that are built for it at compile time.
+struct Column {
+    /// Name of table
+    table: &'static str,
+
+    /// Name of column
+    name: &'static str,
+
+    /// Counts alive items with a given value in the column
+    count_alive: &'static str,
+
+    /// Restores removed item with a given value in the column
+    restore: &'static str,
+}
+
+macro_rules! column {
+    ($table:literal, $name:literal) => {
+        Column {
+            table: $table,
+            name: $name,
+            count_alive: concat!(r#"
+                SELECT COUNT(*) FROM "#, $table, r#"
+                 WHERE _removal_timestamp IS NULL
+                   AND "#, $name, r#" = ?1
+            "#),
+            restore: concat!(r#"
+                UPDATE "#, $table, r#"
+                   SET _removal_timestamp = NULL,
+                       _change_timestamp = ?1
+                 WHERE "#, $name, r#" = ?2 AND
+                       _removal_timestamp IS NOT NULL
+            "#),
+        }
+    }
+}
+
+const TRANSACTION_ID: Column = column!("transactions", "transaction_id");
+const TRANSACTION_ACCOUNT_ID: Column = column!("transactions", "account_id");
+const TRANSACTION_CATEGORY_ID: Column = column!("transactions", "category_id");
+const ACCOUNT_ID: Column = column!("accounts", "account_id");
+const CATEGORY_ID: Column = column!("categories", "category_id");
+const PLAN_ID: Column = column!("plans", "plan_id");
+const PLAN_CATEGORY_ID: Column = column!("plans", "category_id");
+const RECURRING_ACCOUNT_ID: Column = column!("recurrings", "account_id");
+const RECURRING_CATEGORY_ID: Column = column!("recurrings", "category_id");
+
+
+/// Implementation of [`rusqlite::types::ToSql`] trait for [`Id`].
+/// 
+/// Identifier is stored as a blob of raw bytes.
+impl rusqlite::types::ToSql for Id {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(
+            rusqlite::types::ValueRef::Blob(self.as_bytes())
+        ))
+    }
+}
+
+
+/// Implementation of [`rusqlite::types::FromSql`] for [`Id`].
+/// 
+/// Checks for blobs of invalid size.
+impl rusqlite::types::FromSql for Id {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        <[u8; 16]>::column_result(value)
+            .map(Id::from_bytes)
+    }
+}
+
 
 /// Implementation of [`rusqlite::types::ToSql`] trait for [`CategoryType`].
 /// 
@@ -43,10 +207,157 @@ impl rusqlite::types::FromSql for CategoryType {
 }
 
 
+/// Implementation of [`rusqlite::types::ToSql`] trait for [`AccountType`].
+/// 
+/// [`AccountType::Cash`] translates into 0, [`AccountType::Checking`] -- into 1,
+/// [`AccountType::Credit`] -- into 2, [`AccountType::Savings`] -- into 3.
+impl rusqlite::types::ToSql for AccountType {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        let internal_value = match self {
+            AccountType::Cash     => 0i64,
+            AccountType::Checking => 1i64,
+            AccountType::Credit   => 2i64,
+            AccountType::Savings  => 3i64,
+        };
+
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(
+            rusqlite::types::ValueRef::Integer(internal_value)
+        ))
+    }
+}
+
+
+/// Implementation of [`rusqlite::types::FromSql`] for [`AccountType`].
+/// 
+/// Checks for invalid values in database, translates only valid values.
+impl rusqlite::types::FromSql for AccountType {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        match value.as_i64()? {
+            0 => Ok(AccountType::Cash),
+            1 => Ok(AccountType::Checking),
+            2 => Ok(AccountType::Credit),
+            3 => Ok(AccountType::Savings),
+            
+            // Other integer values are wrong!
+            v => Err(rusqlite::types::FromSqlError::OutOfRange(v)),
+        }
+    }
+}
+
+
+/// Implementation of [`rusqlite::types::ToSql`] trait for [`RecurrencePeriod`].
+/// 
+/// [`RecurrencePeriod::Weekly`] translates into 0, [`RecurrencePeriod::Monthly`] -- into 1,
+/// [`RecurrencePeriod::Yearly`] -- into 2.
+impl rusqlite::types::ToSql for RecurrencePeriod {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        let internal_value = match self {
+            RecurrencePeriod::Weekly  => 0i64,
+            RecurrencePeriod::Monthly => 1i64,
+            RecurrencePeriod::Yearly  => 2i64,
+        };
+
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(
+            rusqlite::types::ValueRef::Integer(internal_value)
+        ))
+    }
+}
+
+
+/// Implementation of [`rusqlite::types::FromSql`] for [`RecurrencePeriod`].
+/// 
+/// Checks for invalid values in database, translates only valid values.
+impl rusqlite::types::FromSql for RecurrencePeriod {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        match value.as_i64()? {
+            0 => Ok(RecurrencePeriod::Weekly),
+            1 => Ok(RecurrencePeriod::Monthly),
+            2 => Ok(RecurrencePeriod::Yearly),
+            
+            // Other integer values are wrong!
+            v => Err(rusqlite::types::FromSqlError::OutOfRange(v)),
+        }
+    }
+}
+
+
+/// Implementation of [`rusqlite::types::ToSql`] trait for [`PlanPeriod`].
+/// 
+/// [`PlanPeriod::Custom`] translates into its (positive) number of days,
+/// predefined periods translate into negative values: [`PlanPeriod::Weekly`] 
+/// into -1, [`PlanPeriod::Monthly`] -- into -2, [`PlanPeriod::Quarterly`] -- 
+/// into -3 and [`PlanPeriod::Yearly`] -- into -4.
+impl rusqlite::types::ToSql for PlanPeriod {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        let internal_value = match self {
+            PlanPeriod::Weekly       => -1i64,
+            PlanPeriod::Monthly      => -2i64,
+            PlanPeriod::Quarterly    => -3i64,
+            PlanPeriod::Yearly       => -4i64,
+            PlanPeriod::Custom(days) => i64::from(*days),
+        };
+
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(
+            rusqlite::types::ValueRef::Integer(internal_value)
+        ))
+    }
+}
+
+
+/// Implementation of [`rusqlite::types::FromSql`] for [`PlanPeriod`].
+/// 
+/// Checks for invalid values in database, translates only valid values.
+impl rusqlite::types::FromSql for PlanPeriod {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        match value.as_i64()? {
+            -1 => Ok(PlanPeriod::Weekly),
+            -2 => Ok(PlanPeriod::Monthly),
+            -3 => Ok(PlanPeriod::Quarterly),
+            -4 => Ok(PlanPeriod::Yearly),
+            v if 0 < v && v <= u32::MAX.into() => Ok(PlanPeriod::Custom(v as u32)),
+            
+            // Other integer values are wrong!
+            v => Err(rusqlite::types::FromSqlError::OutOfRange(v)),
+        }
+    }
+}
+
+
+/// Foreign key declared in database schema.
+struct ForeignKey {
+    /// Name of referencing table
+    table: &'static str,
+
+    /// Primary key of referencing table
+    key: String,
+
+    /// Column with reference
+    column: String,
+
+    /// Name of referenced table
+    parent_table: String,
+
+    /// Referenced column
+    parent_key: String,
+}
+
+
 /// Storage implemented using SQLite.
+/// 
+/// Storage can be shared between threads, but all operations are
+/// serialized on an internal lock of database connection. Transaction
+/// started by [`DataStorage::with_transaction`] holds the lock until it
+/// is finished, hence other threads neither observe nor join it.
 pub struct DbStorage {
     /// Database connection
-    db: rusqlite::Connection
+    db: parking_lot::ReentrantMutex<rusqlite::Connection>,
+
+    /// Depth of nested [`DataStorage::with_transaction`] calls,
+    /// it is changed only while connection is locked
+    transaction_depth: std::sync::atomic::AtomicUsize,
+
+    /// Database is opened in read-only mode
+    read_only: bool,
 } 
 
 
@@ -54,7 +365,8 @@ impl DbStorage {
     /// Creates a database in provided location.
     /// 
     /// * `loc` - storage location provider
-    pub fn create<L: Location>(loc: &L) -> Result<Self> {
+    /// * `options` - options of database connection
+    pub fn create<L: Location>(loc: &L, options: DbStorageOptions) -> Result<Self> {
         //
         // Create home path if it doesn't exist
         //
@@ -65,7 +377,7 @@ impl DbStorage {
         // Now I just open DB and create schema
         //
 
-        let storage = Self::open(loc)?;
+        let storage = Self::connect(loc, options, false)?;
         storage
             .create_db()
             .and(Ok(storage))
@@ -73,227 +385,522 @@ impl DbStorage {
 
     /// Opens an existing database in provided location.
     /// 
+    /// Schema of a database created by an older version is 
+    /// migrated to the current one.
+    /// 
     /// * `loc` - storage location provider
-    pub fn open<L: Location>(loc: &L) -> Result<Self> {
-        Ok(DbStorage { 
-            db: rusqlite::Connection::open(Self::db_path(loc))?
-        })
+    /// * `options` - options of database connection
+    pub fn open<L: Location>(loc: &L, options: DbStorageOptions) -> Result<Self> {
+        let storage = Self::connect(loc, options, false)?;
+        storage
+            .migrate()
+            .and(Ok(storage))
+    }
+
+    /// Opens an existing database in provided location in read-only mode.
+    /// 
+    /// All modifying operations fail, no write locks are taken, hence
+    /// the database can be used by another process simultaneously. Schema
+    /// cannot be migrated, therefore it must be up-to-date already.
+    /// Journal mode is a property of database file, hence write-ahead
+    /// log option is not applied.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `options` - options of database connection
+    pub fn open_read_only<L: Location>(loc: &L, options: DbStorageOptions) -> Result<Self> {
+        let storage = Self::connect(loc, options, true)?;
+        if !migrations::is_up_to_date(&storage.connection())? {
+            return Err(Error::new_with_extra(STORAGE_IS_READ_ONLY, 
+                "database schema must be upgraded first"));
+        }
+
+        Ok(storage)
+    }
+
+    /// Finds references to missing items.
+    /// 
+    /// Databases created before enforcement of foreign keys may contain
+    /// such references. They are not checked on open, but prevent
+    /// updates of referencing items, hence they should be repaired.
+    pub fn check_foreign_keys(&self) -> Result<Vec<ForeignKeyViolation>> {
+        let mut violations = Vec::new();
+        for foreign_key in self.foreign_keys()? {
+            let statement = format!(r#"
+                SELECT {}, {} FROM {}
+                 WHERE {} IS NOT NULL AND
+                       {} NOT IN (SELECT {} FROM {})
+            "#, foreign_key.key, foreign_key.column, foreign_key.table, foreign_key.column, 
+                foreign_key.column, foreign_key.parent_key, foreign_key.parent_table);
+
+            let found = self.query(statement, |row| Ok(ForeignKeyViolation {
+                table: foreign_key.table.to_owned(),
+                item: row.get(0)?,
+                column: foreign_key.column.clone(),
+                parent_table: foreign_key.parent_table.clone(),
+                parent: row.get(1)?
+            }))?;
+
+            violations.extend(found);
+        }
+
+        Ok(violations)
     }
 }
 
 
 impl DataStorage for DbStorage {
-    const TRANSFER_INCOME_ID: Id = [0x00; 16];
-
-    const TRANSFER_OUTCOME_ID: Id = [0xFF; 16];
-
-    fn add_transaction(&self, transaction: EncryptedTransaction) -> Result<()> {
-        let statement_fmt = match transaction.id {
-            None => r#"
-                INSERT INTO transactions (timestamp, description, account_id, category_id, amount, _origin, _creation_timestamp)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
-            "#,
-            Some(_) => r#"
-                INSERT INTO transactions (transaction_id, timestamp, description, account_id, category_id, amount, _origin, _creation_timestamp)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
-            "#
-        };
-        
-        match transaction.id {
-            None => self.db.execute(statement_fmt, 
-                rusqlite::params![transaction.timestamp, transaction.description, transaction.account_id, 
-                    transaction.category_id, transaction.amount, transaction.meta_info.origin, 
-                    transaction.meta_info.added_timestamp])?,
-                
-            Some(id) => self.db.execute(statement_fmt, 
-                rusqlite::params![id, transaction.timestamp, transaction.description, transaction.account_id, 
-                    transaction.category_id, transaction.amount, transaction.meta_info.origin,
-                    transaction.meta_info.added_timestamp])?
-        };
+    const TRANSFER_INCOME_ID: Id = Id::from_bytes([0x00; 16]);
 
-        Ok(())
+    const TRANSFER_OUTCOME_ID: Id = Id::from_bytes([0xFF; 16]);
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn with_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Self) -> Result<T>
+    {
+        //
+        // Only the outermost call begins and finishes DB's transaction,
+        // nested ones are just executed as a part of it
+        //
+
+        //
+        // Connection is locked until the end of transaction,
+        // nested calls from the same thread just lock it again
+        //
+
+        let _db = self.connection();
+
+        let depth = self.transaction_depth.load(std::sync::atomic::Ordering::Relaxed);
+        if 0 < depth {
+            return f(self);
+        }
+
+        //
+        // Write lock is taken at once to avoid deadlocks between writers,
+        // but read-only connection must not take it at all
+        //
+
+        let begin_statement = if self.read_only { "BEGIN" } else { "BEGIN IMMEDIATE" };
+        self.execute_with_retry(begin_statement)?;
+
+        self.transaction_depth.store(depth + 1, std::sync::atomic::Ordering::Relaxed);
+        let result = f(self);
+        self.transaction_depth.store(depth, std::sync::atomic::Ordering::Relaxed);
+
+        match result {
+            Ok(value) => {
+                //
+                // Failed commit may leave transaction active, hence it is
+                // rolled back to release the lock. SQLite may have already
+                // rolled it back, so the original error is more important
+                //
+
+                if let Err(error) = self.execute_with_retry("COMMIT") {
+                    self.connection().execute_batch("ROLLBACK").ok();
+                    return Err(error);
+                }
+
+                Ok(value)
+            },
+            Err(error) => {
+                self.connection().execute_batch("ROLLBACK")?;
+                Err(error)
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_transaction(&self, transaction: EncryptedTransaction) -> Result<Id> {
+        self.ensure_writable()?;
+
+        //
+        // Identifier is generated here instead of database,
+        // hence it can be returned to caller
+        //
+
+        let id = transaction.id
+            .unwrap_or_else(Id::random);
+
+        let statement_fmt = r#"
+            INSERT INTO transactions (transaction_id, timestamp, description, account_id, category_id, amount, transfer_id, tags, pending, _origin, _creation_timestamp)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+        "#;
+
+        self.execute_insert(statement_fmt, rusqlite::params![id, transaction.timestamp, transaction.description, transaction.account_id, 
+            transaction.category_id, transaction.amount, transaction.transfer_id, transaction.tags, transaction.pending,
+            transaction.meta_info.origin, transaction.meta_info.added_timestamp])?;
+
+        self.invalidate_snapshots(transaction.account_id, transaction.timestamp)?;
+
+        Ok(id)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn update_transaction(&self, transaction: EncryptedTransaction) -> Result<()> {
+        self.ensure_writable()?;
+
+        //
+        // Snapshots are invalidated for both previous and new
+        // account and timestamp of the transaction
+        //
+
+        self.invalidate_snapshots_of(transaction.id)?;
+
+        //
+        // Change timestamp is updated only if it is specified,
+        // otherwise previous value is kept
+        //
+
+        let statement_fmt = r#"
+            UPDATE transactions
+               SET timestamp = ?1,
+                   description = ?2,
+                   account_id = ?3,
+                   category_id = ?4,
+                   amount = ?5,
+                   tags = ?6,
+                   pending = ?7,
+                   _change_timestamp = COALESCE(?8, _change_timestamp)
+             WHERE transaction_id = ?9 AND 
+                   _removal_timestamp IS NULL
+        "#;
+
+        let updated = self
+            .execute_cached(statement_fmt, rusqlite::params![transaction.timestamp, transaction.description, 
+                transaction.account_id, transaction.category_id, transaction.amount, transaction.tags,
+                transaction.pending, transaction.meta_info.changed_timestamp, transaction.id])?;
+
+        Self::ensure_updated(updated)?;
+
+        self.invalidate_snapshots(transaction.account_id, transaction.timestamp)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn remove_transaction(&self, transaction: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.ensure_writable()?;
+
+        self.invalidate_snapshots_of(Some(transaction))?;
+
         let statement_fmt = r#"
             UPDATE transactions
                SET _removal_timestamp = ?1
              WHERE transaction_id = ?2
         "#;
 
-        self.db
-            .execute(statement_fmt, rusqlite::params![removal_timestamp, transaction])?;
+        self
+            .execute_cached(statement_fmt, rusqlite::params![removal_timestamp, transaction])?;
 
         Ok(())
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transaction(&self, transaction: Id) -> Result<EncryptedTransaction> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
+        let statement_fmt = select_from_transactions!(r#"
             WHERE transaction_id = ?1 AND 
                   _removal_timestamp IS NULL
-        "#));
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![transaction], Self::transaction_from_row)?;
+
+        //
+        // The only row is returned here (if any)
+        //
+
+        Self::single_item(result, transaction)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn removed_transaction(&self, transaction: Id) -> Result<EncryptedTransaction> {
+        let statement_fmt = select_from_transactions!(r#"
+            WHERE transaction_id = ?1 AND 
+                  _removal_timestamp IS NOT NULL
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![transaction], Self::transaction_from_row)?;
+
+        Self::single_item(result, transaction)
+    }
 
-        let mut result = self.query_with_params(statement_fmt, 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transaction_any(&self, transaction: Id) -> Result<EncryptedTransaction> {
+        let statement_fmt = select_from_transactions!(r#"
+            WHERE transaction_id = ?1
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
             rusqlite::params![transaction], Self::transaction_from_row)?;
 
+        Self::single_item(result, transaction)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn restore_transaction(&self, transaction: Id, change_timestamp: Timestamp) -> Result<()> {
+        self.ensure_writable()?;
+
         //
-        // The only row is returned here
+        // Referenced items must be alive, otherwise restored
+        // item would break consistency
         //
 
-        Ok(result.remove(0))
+        let encrypted_transaction = self.removed_transaction(transaction)?;
+        self.ensure_exists(&ACCOUNT_ID, encrypted_transaction.account_id)?;
+        self.ensure_exists(&CATEGORY_ID, encrypted_transaction.category_id)?;
+
+        self.restore_item(&TRANSACTION_ID, transaction, change_timestamp)?;
+        self.invalidate_snapshots(encrypted_transaction.account_id, encrypted_transaction.timestamp)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transactions(&self) -> Result<Vec<EncryptedTransaction>> {
-        let statement = Self::select_from_transactions(Some(r#"
+        let statement = select_from_transactions!(r#"
             WHERE _removal_timestamp IS NULL
             ORDER BY timestamp DESC
-        "#));
+        "#);
 
         self.query(statement, Self::transaction_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_page(&self, offset: usize, limit: usize) -> Result<Vec<EncryptedTransaction>> {
+        let statement_fmt = select_from_transactions!(r#"
+            WHERE _removal_timestamp IS NULL
+            ORDER BY timestamp DESC
+            LIMIT ?1 OFFSET ?2
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![limit, offset], Self::transaction_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_count(&self) -> Result<usize> {
+        let statement = r#"
+            SELECT COUNT(*) FROM transactions
+             WHERE _removal_timestamp IS NULL
+        "#;
+
+        self.connection()
+            .query_row(statement, [], |row| row.get(0))
+            .map_err(Error::from)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transactions_after(&self, start_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
+        let statement_fmt = select_from_transactions!(r#"
             WHERE timestamp >= ?1 AND 
                   _removal_timestamp IS NULL
             ORDER BY timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![start_timestamp], Self::transaction_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transactions_between(&self, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
+        let statement_fmt = select_from_transactions!(r#"
             WHERE timestamp >= ?1 AND 
                   timestamp < ?2 AND 
                   _removal_timestamp IS NULL
             ORDER BY timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![start_timestamp, end_timestamp], Self::transaction_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transactions_of(&self, account: Id) -> Result<Vec<EncryptedTransaction>> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
+        let statement_fmt = select_from_transactions!(r#"
             WHERE account_id = ?1 AND 
                   _removal_timestamp IS NULL
             ORDER BY timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![account], Self::transaction_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_of_page(&self, account: Id, offset: usize, limit: usize) -> Result<Vec<EncryptedTransaction>> {
+        let statement_fmt = select_from_transactions!(r#"
+            WHERE account_id = ?1 AND 
+                  _removal_timestamp IS NULL
+            ORDER BY timestamp DESC
+            LIMIT ?2 OFFSET ?3
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![account, limit, offset], Self::transaction_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transactions_of_after(&self, account: Id, start_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
+        let statement_fmt = select_from_transactions!(r#"
             WHERE account_id = ?1 AND
                   timestamp >= ?2 AND 
                   _removal_timestamp IS NULL
             ORDER BY timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![account, start_timestamp], Self::transaction_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transactions_of_between(&self, account: Id, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
+        let statement_fmt = select_from_transactions!(r#"
             WHERE account_id = ?1 AND
                   timestamp >= ?2 AND
                   timestamp < ?3 AND 
                   _removal_timestamp IS NULL
             ORDER BY timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![account, start_timestamp, end_timestamp], Self::transaction_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transactions_with(&self, category: Id) -> Result<Vec<EncryptedTransaction>> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
+        let statement_fmt = select_from_transactions!(r#"
             WHERE category_id = ?1 AND 
                   _removal_timestamp IS NULL
             ORDER BY timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![category], Self::transaction_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_with_page(&self, category: Id, offset: usize, limit: usize) -> Result<Vec<EncryptedTransaction>> {
+        let statement_fmt = select_from_transactions!(r#"
+            WHERE category_id = ?1 AND 
+                  _removal_timestamp IS NULL
+            ORDER BY timestamp DESC
+            LIMIT ?2 OFFSET ?3
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![category, limit, offset], Self::transaction_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transactions_with_after(&self, category: Id, start_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
+        let statement_fmt = select_from_transactions!(r#"
             WHERE category_id = ?1 AND
                   timestamp >= ?2 AND 
                   _removal_timestamp IS NULL
             ORDER BY timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![category, start_timestamp], Self::transaction_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transactions_with_between(&self, category: Id, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
+        let statement_fmt = select_from_transactions!(r#"
             WHERE category_id = ?1 AND
                   timestamp >= ?2 AND
                   timestamp < ?3 AND 
                   _removal_timestamp IS NULL
             ORDER BY timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![category, start_timestamp, end_timestamp], Self::transaction_from_row)
     }
 
-    fn transactions_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedTransaction>> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
-            WHERE _creation_timestamp > ?1
-            ORDER BY _creation_timestamp DESC
-        "#));
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_of_transfer(&self, transfer: Id) -> Result<Vec<EncryptedTransaction>> {
+        let statement_fmt = select_from_transactions!(r#"
+            WHERE transfer_id = ?1 AND 
+                  _removal_timestamp IS NULL
+            ORDER BY timestamp DESC
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![transfer], Self::transaction_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn pending_transactions(&self) -> Result<Vec<EncryptedTransaction>> {
+        let statement = select_from_transactions!(r#"
+            WHERE pending = 1 AND 
+                  _removal_timestamp IS NULL
+            ORDER BY timestamp
+        "#);
+
+        self.query(statement, Self::transaction_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedTransaction>> {
+        let statement_fmt = select_from_transactions!(r#"
+            WHERE _creation_timestamp > ?1
+            ORDER BY _creation_timestamp DESC
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![base], Self::transaction_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transactions_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedTransaction>> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
+        let statement_fmt = select_from_transactions!(r#"
             WHERE _change_timestamp IS NOT NULL AND
                   _change_timestamp > ?1
             ORDER BY _change_timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![base], Self::transaction_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn transactions_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedTransaction>> {
-        let statement_fmt = Self::select_from_transactions(Some(r#"
+        let statement_fmt = select_from_transactions!(r#"
             WHERE _removal_timestamp IS NOT NULL AND
                   _removal_timestamp > ?1
             ORDER BY _removal_timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![base], Self::transaction_from_row)
     }
 
-    fn add_account(&self, account: EncryptedAccount) -> Result<()> {
-        let statement_fmt = match account.id {
-            None => r#"
-                INSERT INTO accounts (name, balance, initial_balance, _origin, _creation_timestamp)
-                VALUES (?1, ?2, ?3, ?4, ?5)
-            "#,
-            Some(_) => r#"
-                INSERT INTO accounts (account_id, name, balance, initial_balance, _origin, _creation_timestamp)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-            "#
-        };
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_removed(&self) -> Result<Vec<EncryptedTransaction>> {
+        let statement = select_from_transactions!(r#"
+            WHERE _removal_timestamp IS NOT NULL
+            ORDER BY _removal_timestamp DESC
+        "#);
 
-        match account.id {
-            None => self.db.execute(statement_fmt, rusqlite::params![account.name, 
-                account.balance, account.initial_balance, account.meta_info.origin,
-                account.meta_info.added_timestamp])?,
+        self.query(statement, Self::transaction_from_row)
+    }
 
-            Some(id) => self.db.execute(statement_fmt, rusqlite::params![id, account.name, 
-                account.balance, account.initial_balance, account.meta_info.origin,
-                account.meta_info.added_timestamp])?
-        };
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_account(&self, account: EncryptedAccount) -> Result<Id> {
+        self.ensure_writable()?;
 
-        Ok(())
+        //
+        // Identifier is generated here instead of database,
+        // hence it can be returned to caller
+        //
+
+        let id = account.id
+            .unwrap_or_else(Id::random);
+
+        let statement_fmt = r#"
+            INSERT INTO accounts (account_id, name, balance, initial_balance, currency, archived, type, _origin, _creation_timestamp)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "#;
+
+        self.execute_insert(statement_fmt, rusqlite::params![id, account.name, 
+            account.balance, account.initial_balance, account.currency, account.archived, 
+            account.account_type, account.meta_info.origin, account.meta_info.added_timestamp])?;
+
+        Ok(id)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn update_account(&self, account: EncryptedAccount) -> Result<()> {
+        self.ensure_writable()?;
+
         //
         // For now I don't set _change_timestamp here
         // It is reserved for future use
@@ -307,20 +914,49 @@ impl DataStorage for DbStorage {
                    _removal_timestamp IS NULL
         "#;
 
-        self.db
-            .execute(statement_fmt, rusqlite::params![account.name, 
+        self
+            .execute_cached(statement_fmt, rusqlite::params![account.name, 
                 account.balance, account.id])?;
 
         Ok(())
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn update_account_with_timestamp(&self, account: EncryptedAccount, change_timestamp: Timestamp) -> Result<()> {
+        self.ensure_writable()?;
+
+        let statement_fmt = r#"
+            UPDATE accounts
+               SET name = ?1,
+                   balance = ?2,
+                   initial_balance = ?3,
+                   currency = ?4,
+                   archived = ?5,
+                   type = ?6,
+                   _change_timestamp = ?7
+             WHERE account_id = ?8 AND 
+                   _removal_timestamp IS NULL
+        "#;
+
+        let updated = self
+            .execute_cached(statement_fmt, rusqlite::params![account.name, account.balance, 
+                account.initial_balance, account.currency, account.archived, account.account_type, 
+                change_timestamp, account.id])?;
+
+        Self::ensure_updated(updated)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn remove_account(&self, account: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.ensure_writable()?;
+
         //
         // Check if we can delete account: no transaction should belong to it.
         // Only after that I can remove account
         //
 
-        self.ensure_consistency("transactions", "account_id", account)?;
+        self.ensure_consistency(&TRANSACTION_ACCOUNT_ID, account)?;
+        self.ensure_consistency(&RECURRING_ACCOUNT_ID, account)?;
 
         let statement_fmt = r#"
             UPDATE accounts
@@ -328,307 +964,1115 @@ impl DataStorage for DbStorage {
              WHERE account_id = ?2
         "#;
 
-        self.db
-            .execute(statement_fmt, rusqlite::params![removal_timestamp, account])?;
+        self
+            .execute_cached(statement_fmt, rusqlite::params![removal_timestamp, account])?;
 
         Ok(())
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn account(&self, account: Id) -> Result<EncryptedAccount> {
-        let statement_fmt = Self::select_from_accounts(Some(r#"
+        let statement_fmt = select_from_accounts!(r#"
             WHERE account_id = ?1 AND 
                   _removal_timestamp IS NULL
-        "#));
+        "#);
 
-        let mut result = self.query_with_params(statement_fmt, 
+        let result = self.query_with_params(statement_fmt, 
             rusqlite::params![account], Self::account_from_row)?;
 
         //
-        // The only row is returned here
+        // The only row is returned here (if any)
         //
 
-        Ok(result.remove(0))
+        Self::single_item(result, account)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn removed_account(&self, account: Id) -> Result<EncryptedAccount> {
+        let statement_fmt = select_from_accounts!(r#"
+            WHERE account_id = ?1 AND 
+                  _removal_timestamp IS NOT NULL
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![account], Self::account_from_row)?;
+
+        Self::single_item(result, account)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn account_any(&self, account: Id) -> Result<EncryptedAccount> {
+        let statement_fmt = select_from_accounts!(r#"
+            WHERE account_id = ?1
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![account], Self::account_from_row)?;
+
+        Self::single_item(result, account)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn restore_account(&self, account: Id, change_timestamp: Timestamp) -> Result<()> {
+        self.ensure_writable()?;
+
+        self.restore_item(&ACCOUNT_ID, account, change_timestamp)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn accounts(&self) -> Result<Vec<EncryptedAccount>> {
-        let statement = Self::select_from_accounts(Some(r#"
+        let statement = select_from_accounts!(r#"
+            WHERE archived = 0 AND
+                  _removal_timestamp IS NULL
+        "#);
+
+        self.query(statement, Self::account_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn accounts_of_type(&self, account_type: AccountType) -> Result<Vec<EncryptedAccount>> {
+        let statement_fmt = select_from_accounts!(r#"
+            WHERE type = ?1 AND
+                  archived = 0 AND
+                  _removal_timestamp IS NULL
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![account_type], Self::account_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn accounts_including_archived(&self) -> Result<Vec<EncryptedAccount>> {
+        let statement = select_from_accounts!(r#"
             WHERE _removal_timestamp IS NULL
-        "#));
+        "#);
 
         self.query(statement, Self::account_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn accounts_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedAccount>> {
-        let statement_fmt = Self::select_from_accounts(Some(r#"
+        let statement_fmt = select_from_accounts!(r#"
             WHERE _creation_timestamp > ?1
             ORDER BY _creation_timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![base], Self::account_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn accounts_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedAccount>> {
-        let statement_fmt = Self::select_from_accounts(Some(r#"
+        let statement_fmt = select_from_accounts!(r#"
             WHERE _change_timestamp IS NOT NULL AND
                   _change_timestamp > ?1
             ORDER BY _change_timestamp DESC
-        "#));
+        "#);
 
         self.query_with_params(statement_fmt, rusqlite::params![base], Self::account_from_row)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
     fn accounts_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedAccount>> {
-        let statement_fmt = Self::select_from_accounts(Some(r#"
+        let statement_fmt = select_from_accounts!(r#"
+            WHERE _removal_timestamp IS NOT NULL AND
+                  _removal_timestamp > ?1
+            ORDER BY _removal_timestamp DESC
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![base], Self::account_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn accounts_removed(&self) -> Result<Vec<EncryptedAccount>> {
+        let statement = select_from_accounts!(r#"
+            WHERE _removal_timestamp IS NOT NULL
+            ORDER BY _removal_timestamp DESC
+        "#);
+
+        self.query(statement, Self::account_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_category(&self, category: EncryptedCategory) -> Result<Id> {
+        self.ensure_writable()?;
+
+        //
+        // Identifier is generated here instead of database,
+        // hence it can be returned to caller
+        //
+
+        let id = category.id
+            .unwrap_or_else(Id::random);
+
+        let statement_fmt = r#"
+            INSERT INTO categories (category_id, name, type, _origin, _creation_timestamp)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+        "#;
+
+        self.execute_insert(statement_fmt, rusqlite::params![id, category.name, 
+            category.category_type, category.meta_info.origin, category.meta_info.added_timestamp])?;
+
+        Ok(id)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn update_category(&self, category: EncryptedCategory) -> Result<()> {
+        self.ensure_writable()?;
+
+        //
+        // Predefined categories are kept untouched, and
+        // category type is never updated
+        //
+
+        if category.id.is_some_and(Self::is_predefined_category) {
+            return Err(Error::new(CANNOT_UPDATE_PREDEFINED));
+        }
+
+        let statement_fmt = r#"
+            UPDATE categories
+               SET name = ?1,
+                   _change_timestamp = COALESCE(?2, _change_timestamp)
+             WHERE category_id = ?3 AND 
+                   _removal_timestamp IS NULL
+        "#;
+
+        let updated = self
+            .execute_cached(statement_fmt, rusqlite::params![category.name, 
+                category.meta_info.changed_timestamp, category.id])?;
+
+        Self::ensure_updated(updated)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn remove_category(&self, category: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.ensure_writable()?;
+
+        //
+        // Check if no transactions and plans reference this category
+        //
+
+        if Self::is_predefined_category(category) {
+            return Err(Error::new(CANNOT_DELETE_PREDEFINED));
+        }
+
+        self.ensure_consistency(&TRANSACTION_CATEGORY_ID, category)?;
+        self.ensure_consistency(&PLAN_CATEGORY_ID, category)?;
+        self.ensure_consistency(&RECURRING_CATEGORY_ID, category)?;
+
+        let statement_fmt = r#"
+            UPDATE categories
+               SET _removal_timestamp = ?1
+             WHERE category_id = ?2
+        "#;
+
+        self
+            .execute_cached(statement_fmt, rusqlite::params![removal_timestamp, category])?;
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn category(&self, category: Id) -> Result<EncryptedCategory> {
+        let statement_fmt = select_from_categories!(r#"
+            WHERE category_id = ?1 AND 
+                  _removal_timestamp IS NULL
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![category], Self::category_from_row)?;
+
+        //
+        // The only row is returned here (if any)
+        //
+
+        Self::single_item(result, category)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn removed_category(&self, category: Id) -> Result<EncryptedCategory> {
+        let statement_fmt = select_from_categories!(r#"
+            WHERE category_id = ?1 AND 
+                  _removal_timestamp IS NOT NULL
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![category], Self::category_from_row)?;
+
+        Self::single_item(result, category)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn category_any(&self, category: Id) -> Result<EncryptedCategory> {
+        let statement_fmt = select_from_categories!(r#"
+            WHERE category_id = ?1
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![category], Self::category_from_row)?;
+
+        Self::single_item(result, category)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn restore_category(&self, category: Id, change_timestamp: Timestamp) -> Result<()> {
+        self.ensure_writable()?;
+
+        self.restore_item(&CATEGORY_ID, category, change_timestamp)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories(&self) -> Result<Vec<EncryptedCategory>> {
+        let statement = select_from_categories!(r#"
+            WHERE _removal_timestamp IS NULL
+            ORDER BY type
+        "#);
+
+        self.query(statement, Self::category_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories_of(&self, category_type: CategoryType) -> Result<Vec<EncryptedCategory>> {
+        let statement_fmt = select_from_categories!(r#"
+            WHERE type = ?1 AND 
+                  _removal_timestamp IS NULL
+            ORDER BY type
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![category_type], Self::category_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>> {
+        let statement_fmt = select_from_categories!(r#"
+            WHERE _creation_timestamp > ?1
+            ORDER BY _creation_timestamp DESC
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![base], Self::category_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>> {
+        let statement_fmt = select_from_categories!(r#"
+            WHERE _change_timestamp IS NOT NULL AND
+                  _change_timestamp > ?1
+            ORDER BY _change_timestamp DESC
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![base], Self::category_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>> {
+        let statement_fmt = select_from_categories!(r#"
+            WHERE _removal_timestamp IS NOT NULL AND
+                  _removal_timestamp > ?1
+            ORDER BY _removal_timestamp DESC
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![base], Self::category_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories_removed(&self) -> Result<Vec<EncryptedCategory>> {
+        let statement = select_from_categories!(r#"
+            WHERE _removal_timestamp IS NOT NULL
+            ORDER BY _removal_timestamp DESC
+        "#);
+
+        self.query(statement, Self::category_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_plan(&self, plan: EncryptedPlan) -> Result<Id> {
+        self.ensure_writable()?;
+
+        //
+        // Identifier is generated here instead of database,
+        // hence it can be returned to caller
+        //
+
+        let id = plan.id
+            .unwrap_or_else(Id::random);
+
+        let statement_fmt = r#"
+            INSERT INTO plans (plan_id, category_id, name, amount_limit, period, rollover, _origin, _creation_timestamp)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        "#;
+
+        self.execute_insert(statement_fmt, rusqlite::params![id, plan.category_id, 
+            plan.name, plan.amount_limit, plan.period, plan.rollover, plan.meta_info.origin, 
+            plan.meta_info.added_timestamp])?;
+
+        Ok(id)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn update_plan(&self, plan: EncryptedPlan) -> Result<()> {
+        self.ensure_writable()?;
+
+        //
+        // Plan can be moved to another category, hence I need
+        // to check if this category is alive
+        //
+
+        self.ensure_exists(&CATEGORY_ID, plan.category_id)?;
+
+        let statement_fmt = r#"
+            UPDATE plans
+               SET category_id = ?1,
+                   name = ?2,
+                   amount_limit = ?3,
+                   period = ?4,
+                   rollover = ?5,
+                   _change_timestamp = COALESCE(?6, _change_timestamp)
+             WHERE plan_id = ?7 AND 
+                   _removal_timestamp IS NULL
+        "#;
+
+        let updated = self
+            .execute_cached(statement_fmt, rusqlite::params![plan.category_id, plan.name, 
+                plan.amount_limit, plan.period, plan.rollover, plan.meta_info.changed_timestamp, plan.id])?;
+
+        Self::ensure_updated(updated)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn remove_plan(&self, plan: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.ensure_writable()?;
+
+        let statement_fmt = r#"
+            UPDATE plans
+               SET _removal_timestamp = ?1
+             WHERE plan_id = ?2
+        "#;
+
+        self
+            .execute_cached(statement_fmt, rusqlite::params![removal_timestamp, plan])?;
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plan(&self, plan: Id) -> Result<EncryptedPlan> {
+        let statement_fmt = select_from_plans!(r#"
+            WHERE plan_id = ?1 AND 
+                  _removal_timestamp IS NULL
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![plan], Self::plan_from_row)?;
+
+        //
+        // The only row is returned here (if any)
+        //
+
+        Self::single_item(result, plan)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn removed_plan(&self, plan: Id) -> Result<EncryptedPlan> {
+        let statement_fmt = select_from_plans!(r#"
+            WHERE plan_id = ?1 AND 
+                  _removal_timestamp IS NOT NULL
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![plan], Self::plan_from_row)?;
+
+        Self::single_item(result, plan)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plan_any(&self, plan: Id) -> Result<EncryptedPlan> {
+        let statement_fmt = select_from_plans!(r#"
+            WHERE plan_id = ?1
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![plan], Self::plan_from_row)?;
+
+        Self::single_item(result, plan)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn restore_plan(&self, plan: Id, change_timestamp: Timestamp) -> Result<()> {
+        self.ensure_writable()?;
+
+        //
+        // Referenced items must be alive, otherwise restored
+        // item would break consistency
+        //
+
+        let encrypted_plan = self.removed_plan(plan)?;
+        self.ensure_exists(&CATEGORY_ID, encrypted_plan.category_id)?;
+
+        self.restore_item(&PLAN_ID, plan, change_timestamp)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans(&self) -> Result<Vec<EncryptedPlan>> {
+        let statement = select_from_plans!(r#"
+            WHERE _removal_timestamp IS NULL
+            ORDER BY category_id
+        "#);
+
+        self.query(statement, Self::plan_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans_for(&self, category: Id) -> Result<Vec<EncryptedPlan>> {
+        let statement_fmt = select_from_plans!(r#"
+            WHERE category_id = ?1 AND 
+                  _removal_timestamp IS NULL
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![category], Self::plan_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>> {
+        let statement_fmt = select_from_plans!(r#"
+            WHERE _creation_timestamp > ?1
+            ORDER BY _creation_timestamp DESC
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![base], Self::plan_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>> {
+        let statement_fmt = select_from_plans!(r#"
+            WHERE _change_timestamp IS NOT NULL AND
+                  _change_timestamp > ?1
+            ORDER BY _change_timestamp DESC
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![base], Self::plan_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>> {
+        let statement_fmt = select_from_plans!(r#"
             WHERE _removal_timestamp IS NOT NULL AND
                   _removal_timestamp > ?1
             ORDER BY _removal_timestamp DESC
-        "#));
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![base], Self::plan_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans_removed(&self) -> Result<Vec<EncryptedPlan>> {
+        let statement = select_from_plans!(r#"
+            WHERE _removal_timestamp IS NOT NULL
+            ORDER BY _removal_timestamp DESC
+        "#);
+
+        self.query(statement, Self::plan_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_recurring(&self, recurring: EncryptedRecurringTransaction) -> Result<Id> {
+        self.ensure_writable()?;
+
+        //
+        // Identifier is generated here instead of database,
+        // hence it can be returned to caller
+        //
+
+        let id = recurring.id
+            .unwrap_or_else(Id::random);
+
+        let statement_fmt = r#"
+            INSERT INTO recurrings (recurring_id, description, account_id, category_id, amount, period, start_timestamp, 
+                                    end_timestamp, materialized_timestamp, _origin, _creation_timestamp)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+        "#;
+
+        self.execute_insert(statement_fmt, rusqlite::params![id, recurring.description, 
+            recurring.account_id, recurring.category_id, recurring.amount, recurring.period, 
+            recurring.start_timestamp, recurring.end_timestamp, recurring.materialized_timestamp,
+            recurring.meta_info.origin, recurring.meta_info.added_timestamp])?;
+
+        Ok(id)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn update_recurring(&self, recurring: EncryptedRecurringTransaction) -> Result<()> {
+        self.ensure_writable()?;
+
+        self.ensure_exists(&ACCOUNT_ID, recurring.account_id)?;
+        self.ensure_exists(&CATEGORY_ID, recurring.category_id)?;
+
+        let statement_fmt = r#"
+            UPDATE recurrings
+               SET description = ?1,
+                   account_id = ?2,
+                   category_id = ?3,
+                   amount = ?4,
+                   period = ?5,
+                   start_timestamp = ?6,
+                   end_timestamp = ?7,
+                   materialized_timestamp = ?8,
+                   _change_timestamp = COALESCE(?9, _change_timestamp)
+             WHERE recurring_id = ?10 AND 
+                   _removal_timestamp IS NULL
+        "#;
+
+        let updated = self
+            .execute_cached(statement_fmt, rusqlite::params![recurring.description, recurring.account_id, 
+                recurring.category_id, recurring.amount, recurring.period, recurring.start_timestamp, 
+                recurring.end_timestamp, recurring.materialized_timestamp, 
+                recurring.meta_info.changed_timestamp, recurring.id])?;
+
+        Self::ensure_updated(updated)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn remove_recurring(&self, recurring: Id, removal_timestamp: Timestamp) -> Result<()> {
+        self.ensure_writable()?;
+
+        let statement_fmt = r#"
+            UPDATE recurrings
+               SET _removal_timestamp = ?1
+             WHERE recurring_id = ?2
+        "#;
+
+        self
+            .execute_cached(statement_fmt, rusqlite::params![removal_timestamp, recurring])?;
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurring(&self, recurring: Id) -> Result<EncryptedRecurringTransaction> {
+        let statement_fmt = select_from_recurrings!(r#"
+            WHERE recurring_id = ?1 AND 
+                  _removal_timestamp IS NULL
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![recurring], Self::recurring_from_row)?;
+
+        Self::single_item(result, recurring)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurring_any(&self, recurring: Id) -> Result<EncryptedRecurringTransaction> {
+        let statement_fmt = select_from_recurrings!(r#"
+            WHERE recurring_id = ?1
+        "#);
+
+        let result = self.query_with_params(statement_fmt, 
+            rusqlite::params![recurring], Self::recurring_from_row)?;
+
+        Self::single_item(result, recurring)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurrings(&self) -> Result<Vec<EncryptedRecurringTransaction>> {
+        let statement = select_from_recurrings!(r#"
+            WHERE _removal_timestamp IS NULL
+            ORDER BY start_timestamp
+        "#);
+
+        self.query(statement, Self::recurring_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurrings_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedRecurringTransaction>> {
+        let statement_fmt = select_from_recurrings!(r#"
+            WHERE _creation_timestamp > ?1
+            ORDER BY _creation_timestamp DESC
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![base], Self::recurring_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurrings_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedRecurringTransaction>> {
+        let statement_fmt = select_from_recurrings!(r#"
+            WHERE _change_timestamp IS NOT NULL AND
+                  _change_timestamp > ?1
+            ORDER BY _change_timestamp DESC
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![base], Self::recurring_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurrings_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedRecurringTransaction>> {
+        let statement_fmt = select_from_recurrings!(r#"
+            WHERE _removal_timestamp IS NOT NULL AND
+                  _removal_timestamp > ?1
+            ORDER BY _removal_timestamp DESC
+        "#);
+
+        self.query_with_params(statement_fmt, rusqlite::params![base], Self::recurring_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurrings_removed(&self) -> Result<Vec<EncryptedRecurringTransaction>> {
+        let statement = select_from_recurrings!(r#"
+            WHERE _removal_timestamp IS NOT NULL
+            ORDER BY _removal_timestamp DESC
+        "#);
+
+        self.query(statement, Self::recurring_from_row)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_snapshot(&self, snapshot: EncryptedBalanceSnapshot) -> Result<()> {
+        self.ensure_writable()?;
+
+        let statement_fmt = r#"
+            INSERT OR REPLACE INTO balance_snapshots (account_id, period_start, balance)
+            VALUES (?1, ?2, ?3)
+        "#;
+
+        self
+            .execute_cached(statement_fmt, rusqlite::params![snapshot.account_id, 
+                snapshot.period_start, snapshot.balance])?;
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn nearest_snapshot(&self, account: Id, before: Timestamp) -> Result<Option<EncryptedBalanceSnapshot>> {
+        let statement_fmt = r#"
+            SELECT account_id, period_start, balance FROM balance_snapshots
+             WHERE account_id = ?1 AND
+                   period_start <= ?2
+             ORDER BY period_start DESC
+             LIMIT 1
+        "#;
+
+        let mut result = self.query_with_params(statement_fmt, rusqlite::params![account, before], 
+            |row| Ok(EncryptedBalanceSnapshot {
+                account_id: row.get(0)?,
+                period_start: row.get(1)?,
+                balance: row.get(2)?
+            }))?;
+
+        Ok(result.pop())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn clear_snapshots(&self) -> Result<()> {
+        self.ensure_writable()?;
+
+        self
+            .execute_cached("DELETE FROM balance_snapshots", [])?;
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn clean_removed(&self) -> Result<()> {
+        self.ensure_writable()?;
+
+        self.delete_removed(None)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn clean_removed_before(&self, cutoff: Timestamp) -> Result<()> {
+        self.ensure_writable()?;
+
+        self.delete_removed(Some(cutoff))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn compact(&self) -> Result<CompactStats> {
+        self.ensure_writable()?;
+
+        let size_before = self.file_size()?;
+
+        self.execute_with_retry("VACUUM")?;
+
+        //
+        // In WAL mode vacuumed database is written to the log first,
+        // hence I move it back to the main file and truncate the log
+        //
+
+        let journal_mode: String = self.connection()
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+
+        if journal_mode.eq_ignore_ascii_case("wal") {
+            self.connection().query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        }
+
+        Ok(CompactStats { 
+            size_before, 
+            size_after: self.file_size()?
+        })
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn reclaimable_fraction(&self) -> Result<f64> {
+        let free_pages: u64 = self.connection().pragma_query_value(None, "freelist_count", |row| row.get(0))?;
+        let total_pages: u64 = self.connection().pragma_query_value(None, "page_count", |row| row.get(0))?;
+
+        if 0 == total_pages {
+            return Ok(0.0);
+        }
+
+        Ok(free_pages as f64 / total_pages as f64)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn stats(&self) -> Result<StorageStats> {
+        let statement = r#"
+            SELECT MIN(timestamp), MAX(timestamp) FROM transactions
+             WHERE _removal_timestamp IS NULL
+        "#;
+
+        let (first_transaction, last_transaction) = self.connection()
+            .query_row(statement, [], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        Ok(StorageStats {
+            transactions: self.table_stats(&TRANSACTIONS)?,
+            accounts: self.table_stats(&ACCOUNTS)?,
+            categories: self.table_stats(&CATEGORIES)?,
+            plans: self.table_stats(&PLANS)?,
+            recurrings: self.table_stats(&RECURRINGS)?,
+            first_transaction,
+            last_transaction,
+            size: self.file_size()?
+        })
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn rewrite_transaction(&self, transaction: EncryptedTransaction) -> Result<()> {
+        self.ensure_writable()?;
+
+        let statement_fmt = r#"
+            UPDATE transactions
+               SET description = ?1,
+                   amount = ?2,
+                   tags = ?3
+             WHERE transaction_id = ?4
+        "#;
+
+        let updated = self
+            .execute_cached(statement_fmt, rusqlite::params![transaction.description, transaction.amount, 
+                transaction.tags, transaction.id])?;
 
-        self.query_with_params(statement_fmt, rusqlite::params![base], Self::account_from_row)
+        Self::ensure_updated(updated)
     }
 
-    fn add_category(&self, category: EncryptedCategory) -> Result<()> {
-        let statement_fmt = match category.id {
-            None => r#"
-                    INSERT INTO categories (name, type, _origin, _creation_timestamp)
-                    VALUES (?1, ?2, ?3, ?4)
-                "#,
-
-            Some(_) => r#"
-                    INSERT INTO categories (category_id, name, type, _origin, _creation_timestamp)
-                    VALUES (?1, ?2, ?3, ?4, ?5)
-                "#
-        };
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn rewrite_account(&self, account: EncryptedAccount) -> Result<()> {
+        self.ensure_writable()?;
 
-        match category.id {
-            None => self.db.execute(statement_fmt, rusqlite::params![category.name, 
-                category.category_type, category.meta_info.origin, category.meta_info.added_timestamp])?,
+        let statement_fmt = r#"
+            UPDATE accounts
+               SET name = ?1,
+                   balance = ?2,
+                   initial_balance = ?3,
+                   currency = ?4
+             WHERE account_id = ?5
+        "#;
 
-            Some(id) => self.db.execute(statement_fmt, rusqlite::params![id, category.name, 
-                category.category_type, category.meta_info.origin, category.meta_info.added_timestamp])?
-        };
+        let updated = self
+            .execute_cached(statement_fmt, rusqlite::params![account.name, account.balance, 
+                account.initial_balance, account.currency, account.id])?;
 
-        Ok(())
+        Self::ensure_updated(updated)
     }
 
-    fn remove_category(&self, category: Id, removal_timestamp: Timestamp) -> Result<()> {
-        //
-        // Check if no transactions and plans reference this category
-        //
-
-        if Self::is_predefined_category(category) {
-            return Err(Error::from_message(CANNOT_DELETE_PREDEFINED));
-        }
-
-        self.ensure_consistency("transactions", "category_id", category)?;
-        self.ensure_consistency("plans", "category_id", category)?;
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn rewrite_category(&self, category: EncryptedCategory) -> Result<()> {
+        self.ensure_writable()?;
 
         let statement_fmt = r#"
             UPDATE categories
-               SET _removal_timestamp = ?1
+               SET name = ?1
              WHERE category_id = ?2
         "#;
 
-        self.db
-            .execute(statement_fmt, rusqlite::params![removal_timestamp, category])?;
+        let updated = self
+            .execute_cached(statement_fmt, rusqlite::params![category.name, category.id])?;
 
-        Ok(())
+        Self::ensure_updated(updated)
     }
 
-    fn category(&self, category: Id) -> Result<EncryptedCategory> {
-        let statement_fmt = Self::select_from_categories(Some(r#"
-            WHERE category_id = ?1 AND 
-                  _removal_timestamp IS NULL
-        "#));
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn rewrite_plan(&self, plan: EncryptedPlan) -> Result<()> {
+        self.ensure_writable()?;
+
+        let statement_fmt = r#"
+            UPDATE plans
+               SET name = ?1,
+                   amount_limit = ?2
+             WHERE plan_id = ?3
+        "#;
+
+        let updated = self
+            .execute_cached(statement_fmt, rusqlite::params![plan.name, plan.amount_limit, plan.id])?;
+
+        Self::ensure_updated(updated)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn verify_integrity(&self) -> Result<IntegrityReport> {
+        let mut report = IntegrityReport::default();
 
-        let mut result = self.query_with_params(statement_fmt, 
-            rusqlite::params![category], Self::category_from_row)?;
-        
         //
-        // The only row is returned here
+        // Physical damage is reported by SQLite as a list of 
+        // messages, the only "ok" message means no damage
         //
 
-        Ok(result.remove(0))
-    }
+        let messages = self.query("PRAGMA integrity_check", |row| Ok(row.get::<_, String>(0)?))?;
+        report.issues.extend(messages
+            .into_iter()
+            .filter(|message| message != "ok")
+            .map(|message| IntegrityIssue {
+                kind: IntegrityIssueKind::Corruption,
+                table: String::new(),
+                item: None,
+                reference: None,
+                details: message
+            }));
 
-    fn categories(&self) -> Result<Vec<EncryptedCategory>> {
-        let statement = Self::select_from_categories(Some(r#"
-            WHERE _removal_timestamp IS NULL
-            ORDER BY type
-        "#));
+        //
+        // PRAGMA foreign_key_check does not return keys of tables 
+        // without rowid, hence I check references manually
+        //
 
-        self.query(statement, Self::category_from_row)
-    }
+        report.issues.extend(self
+            .check_foreign_keys()?
+            .into_iter()
+            .map(|violation| IntegrityIssue {
+                kind: IntegrityIssueKind::MissingReference,
+                details: format!("{} references missing item in {}", violation.column, violation.parent_table),
+                table: violation.table,
+                item: Some(violation.item),
+                reference: Some(violation.parent)
+            }));
 
-    fn categories_of(&self, category_type: CategoryType) -> Result<Vec<EncryptedCategory>> {
-        let statement_fmt = Self::select_from_categories(Some(r#"
-            WHERE type = ?1 AND 
-                  _removal_timestamp IS NULL
-            ORDER BY type
-        "#));
+        //
+        // Alive items must not reference removed ones, e.g. removed
+        // account must not have alive transactions
+        //
 
-        self.query_with_params(statement_fmt, rusqlite::params![category_type], Self::category_from_row)
+        for foreign_key in self.foreign_keys()? {
+            let statement = format!(r#"
+                SELECT child.{}, child.{} FROM {} AS child
+                  JOIN {} AS parent ON parent.{} = child.{}
+                 WHERE child._removal_timestamp IS NULL AND
+                       parent._removal_timestamp IS NOT NULL
+            "#, foreign_key.key, foreign_key.column, foreign_key.table, foreign_key.parent_table, 
+                foreign_key.parent_key, foreign_key.column);
+
+            let found = self.query(statement, |row| Ok(IntegrityIssue {
+                kind: IntegrityIssueKind::RemovedReference,
+                table: foreign_key.table.to_owned(),
+                item: Some(row.get(0)?),
+                reference: Some(row.get(1)?),
+                details: format!("{} references removed item in {}", foreign_key.column, foreign_key.parent_table)
+            }))?;
+
+            report.issues.extend(found);
+        }
+
+        Ok(report)
     }
 
-    fn categories_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>> {
-        let statement_fmt = Self::select_from_categories(Some(r#"
-            WHERE _creation_timestamp > ?1
-            ORDER BY _creation_timestamp DESC
-        "#));
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn rewrite_recurring(&self, recurring: EncryptedRecurringTransaction) -> Result<()> {
+        self.ensure_writable()?;
 
-        self.query_with_params(statement_fmt, rusqlite::params![base], Self::category_from_row)
-    }
+        let statement_fmt = r#"
+            UPDATE recurrings
+               SET description = ?1,
+                   amount = ?2
+             WHERE recurring_id = ?3
+        "#;
 
-    fn categories_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>> {
-        let statement_fmt = Self::select_from_categories(Some(r#"
-            WHERE _change_timestamp IS NOT NULL AND
-                  _change_timestamp > ?1
-            ORDER BY _change_timestamp DESC
-        "#));
+        let updated = self
+            .execute_cached(statement_fmt, rusqlite::params![recurring.description, recurring.amount, recurring.id])?;
 
-        self.query_with_params(statement_fmt, rusqlite::params![base], Self::category_from_row)
+        Self::ensure_updated(updated)
     }
+}
 
-    fn categories_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>> {
-        let statement_fmt = Self::select_from_categories(Some(r#"
-            WHERE _removal_timestamp IS NOT NULL AND
-                  _removal_timestamp > ?1
-            ORDER BY _removal_timestamp DESC
-        "#));
 
-        self.query_with_params(statement_fmt, rusqlite::params![base], Self::category_from_row)
+impl DbStorage {
+    fn ensure_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(Error::new(STORAGE_IS_READ_ONLY));
+        }
+
+        Ok(())
     }
 
-    fn add_plan(&self, plan: EncryptedPlan) -> Result<()> {
-        let statement_fmt = match plan.id {
-            None => r#"
-                INSERT INTO plans (category_id, name, amount_limit, _origin, _creation_timestamp)
-                VALUES (?1, ?2, ?3, ?4, ?5)
-            "#,
-            Some(_) => r#"
-                INSERT INTO plans (plan_id, category_id, name, amount_limit, _origin, _creation_timestamp)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-            "#
-        };
+    fn invalidate_snapshots(&self, account: Id, timestamp: Timestamp) -> Result<()> {
+        //
+        // Snapshot includes transactions made strictly before
+        // its period start, hence only later snapshots are affected
+        //
 
-        match plan.id {
-            None => self.db.execute(statement_fmt, rusqlite::params![plan.category_id, 
-                plan.name, plan.amount_limit, plan.meta_info.origin, plan.meta_info.added_timestamp])?,
+        let statement_fmt = r#"
+            DELETE FROM balance_snapshots
+             WHERE account_id = ?1 AND
+                   period_start > ?2
+        "#;
 
-            Some(id) => self.db.execute(statement_fmt, rusqlite::params![id, plan.category_id, 
-                plan.name, plan.amount_limit, plan.meta_info.origin, plan.meta_info.added_timestamp])?
-        };
+        self
+            .execute_cached(statement_fmt, rusqlite::params![account, timestamp])?;
 
         Ok(())
     }
 
-    fn remove_plan(&self, plan: Id, removal_timestamp: Timestamp) -> Result<()> {
+    fn invalidate_snapshots_of(&self, transaction: Option<Id>) -> Result<()> {
         let statement_fmt = r#"
-            UPDATE plans
-               SET _removal_timestamp = ?1
-             WHERE plan_id = ?2
+            DELETE FROM balance_snapshots
+             WHERE EXISTS (
+                SELECT 1 FROM transactions
+                 WHERE transaction_id = ?1 AND
+                       account_id = balance_snapshots.account_id AND
+                       timestamp < balance_snapshots.period_start
+             )
         "#;
 
-        self.db
-            .execute(statement_fmt, rusqlite::params![removal_timestamp, plan])?;
+        self
+            .execute_cached(statement_fmt, rusqlite::params![transaction])?;
 
         Ok(())
     }
 
-    fn plan(&self, plan: Id) -> Result<EncryptedPlan> {
-        let statement_fmt = Self::select_from_plans(Some(r#"
-            WHERE plan_id = ?1 AND 
-                  _removal_timestamp IS NULL
-        "#));
-
-        let mut result = self.query_with_params(statement_fmt, 
-            rusqlite::params![plan], Self::plan_from_row)?;
-        
+    fn execute_with_retry(&self, statement: &str) -> Result<()> {
         //
-        // The only row is returned here
+        // Another process may hold a lock for a short time,
+        // hence transient lock contention is waited out
         //
 
-        Ok(result.remove(0))
+        let mut attempt = 1;
+        loop {
+            match self.connection().execute_batch(statement).map_err(Error::from) {
+                Err(error) if error.is_busy() && attempt < BUSY_RETRY_COUNT => {
+                    std::thread::sleep(BUSY_RETRY_DELAY * attempt);
+                    attempt += 1;
+                },
+                result => return result
+            }
+        }
     }
 
-    fn plans(&self) -> Result<Vec<EncryptedPlan>> {
-        let statement = Self::select_from_plans(Some(r#"
-            WHERE _removal_timestamp IS NULL
-            ORDER BY category_id
-        "#));
+    fn connect<L: Location>(loc: &L, options: DbStorageOptions, read_only: bool) -> Result<Self> {
+        let flags = match read_only {
+            true => rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_URI |
+                rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            false => rusqlite::OpenFlags::default()
+        };
 
-        self.query(statement, Self::plan_from_row)
-    }
+        let db = rusqlite::Connection::open_with_flags(Self::db_path(loc), flags)?;
 
-    fn plans_for(&self, category: Id) -> Result<Vec<EncryptedPlan>> {
-        let statement_fmt = Self::select_from_plans(Some(r#"
-            WHERE category_id = ?1 AND 
-                  _removal_timestamp IS NULL
-        "#));
+        //
+        // Pragmas are per connection, hence they are set on each open.
+        // Foreign keys do not check existing data, only new writes
+        //
 
-        self.query_with_params(statement_fmt, rusqlite::params![category], Self::plan_from_row)
-    }
+        db.busy_timeout(options.busy_timeout)?;
+        db.set_prepared_statement_cache_capacity(options.statement_cache_capacity);
+        db.pragma_update(None, "foreign_keys", options.foreign_keys)?;
 
-    fn plans_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>> {
-        let statement_fmt = Self::select_from_plans(Some(r#"
-            WHERE _creation_timestamp > ?1
-            ORDER BY _creation_timestamp DESC
-        "#));
+        if options.write_ahead_log && !read_only {
+            db.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        }
 
-        self.query_with_params(statement_fmt, rusqlite::params![base], Self::plan_from_row)
+        Ok(DbStorage { 
+            db: parking_lot::ReentrantMutex::new(db),
+            transaction_depth: std::sync::atomic::AtomicUsize::new(0),
+            read_only,
+        })
     }
 
-    fn plans_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>> {
-        let statement_fmt = Self::select_from_plans(Some(r#"
-            WHERE _change_timestamp IS NOT NULL AND
-                  _change_timestamp > ?1
-            ORDER BY _change_timestamp DESC
-        "#));
+    fn connection(&self) -> parking_lot::ReentrantMutexGuard<'_, rusqlite::Connection> {
+        self.db.lock()
+    }
 
-        self.query_with_params(statement_fmt, rusqlite::params![base], Self::plan_from_row)
+    fn table_stats(&self, table: &Table) -> Result<TableStats> {
+        let stats = self.connection()
+            .prepare_cached(table.stats)?
+            .query_row([], |row| Ok(TableStats { 
+                alive: row.get(0)?, 
+                removed: row.get(1)? 
+            }))?;
+
+        Ok(stats)
     }
 
-    fn plans_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>> {
-        let statement_fmt = Self::select_from_plans(Some(r#"
-            WHERE _removal_timestamp IS NOT NULL AND
-                  _removal_timestamp > ?1
-            ORDER BY _removal_timestamp DESC
-        "#));
+    fn file_size(&self) -> Result<u64> {
+        //
+        // Size of write-ahead log is counted too, because
+        // it is a part of the database until checkpoint
+        //
 
-        self.query_with_params(statement_fmt, rusqlite::params![base], Self::plan_from_row)
+        let path = match self.connection().path() {
+            Some(path) if !path.is_empty() => std::path::PathBuf::from(path),
+            _ => return Ok(0)
+        };
+
+        let mut wal_path = path.clone().into_os_string();
+        wal_path.push("-wal");
+
+        let size = [path, wal_path.into()]
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        Ok(size)
     }
 
-    fn clean_removed(&self) -> Result<()> {
-        let statement = r#"
-            DELETE FROM plans
-             WHERE _removal_timestamp IS NOT NULL;
+    fn foreign_keys(&self) -> Result<Vec<ForeignKey>> {
+        let tables = ["transactions", "plans", "recurrings"];
 
-            DELETE FROM transactions
-             WHERE _removal_timestamp IS NOT NULL;
-            
-            DELETE FROM categories
-             WHERE _removal_timestamp IS NOT NULL;
+        let mut foreign_keys = Vec::new();
+        for table in tables {
+            let key: String = self.connection()
+                .query_row("SELECT name FROM pragma_table_info(?1) WHERE pk = 1", 
+                    rusqlite::params![table], |row| row.get(0))?;
 
-            DELETE FROM accounts
-             WHERE _removal_timestamp IS NOT NULL;
-        "#;
+            let statement = r#"
+                SELECT "table", "from", "to" FROM pragma_foreign_key_list(?1)
+            "#;
 
-        self.db
-            .execute_batch(statement)?;
-        
-        Ok(())
+            let found = self.query_with_params(statement, rusqlite::params![table], |row| Ok(ForeignKey {
+                table,
+                key: key.clone(),
+                column: row.get(1)?,
+                parent_table: row.get(0)?,
+                parent_key: row.get(2)?
+            }))?;
+
+            foreign_keys.extend(found);
+        }
+
+        Ok(foreign_keys)
     }
-}
 
+    fn migrate(&self) -> Result<()> {
+        if migrations::is_up_to_date(&self.connection())? {
+            return Ok(());
+        }
+
+        //
+        // Version is checked again inside of transaction, because
+        // another instance may have migrated the database already
+        //
+
+        self.with_transaction(|storage| migrations::apply(&storage.connection()))
+    }
 
-impl DbStorage {
     fn create_db(&self) -> Result<()> {
         //
         // Database will contain table for each entity: transaction, 
-        // account, category and plan.
+        // account, category, plan and recurring transaction.
         // For optimization purposes categories table will be
         // additionally indexed by its type, transactions table --
-        // by timestamp, plans table -- by category.
+        // by timestamp and transfer, plans table -- by category.
         //
         // Each table has two internal columns: `_change_timestamp`
         // and `_removal_timestamp`, that are suitable for syncing
@@ -642,6 +2086,9 @@ impl DbStorage {
                 balance             BYTEA       NOT NULL,
                 initial_balance     BYTEA       NOT NULL,
                 name                BYTEA       NOT NULL,
+                currency            BYTEA       NULL,
+                archived            BOOLEAN     NOT NULL DEFAULT 0,
+                type                TINYINT     NOT NULL DEFAULT 1,
                 _origin             BYTEA       NOT NULL,
                 _creation_timestamp DATETIME    NOT NULL,
                 _change_timestamp   DATETIME    NULL,
@@ -686,6 +2133,9 @@ impl DbStorage {
                 account_id          BLOB        REFERENCES accounts(account_id),
                 category_id         BLOB        REFERENCES categories(category_id),
                 amount              BYTEA       NOT NULL,
+                transfer_id         BLOB        NULL,
+                tags                BYTEA       NULL,
+                pending             BOOLEAN     NOT NULL DEFAULT 0,
                 _origin             BYTEA       NOT NULL,
                 _creation_timestamp DATETIME    NOT NULL,
                 _change_timestamp   DATETIME    NULL,
@@ -695,6 +2145,9 @@ impl DbStorage {
             CREATE INDEX transactions_by_timestamp
                 ON transactions (timestamp);
 
+            CREATE INDEX transactions_by_transfer
+                ON transactions (transfer_id);
+
             CREATE INDEX transactions_by_creation_timestamp
                 ON transactions (_creation_timestamp);
 
@@ -709,6 +2162,8 @@ impl DbStorage {
                 category_id         BLOB        REFERENCES categories(category_id),
                 name                BYTEA       NOT NULL,
                 amount_limit        BYTEA       NOT NULL,
+                period              INTEGER     NOT NULL DEFAULT -2,
+                rollover            BOOLEAN     NOT NULL DEFAULT 0,
                 _origin             BYTEA       NOT NULL,
                 _creation_timestamp DATETIME    NOT NULL,
                 _change_timestamp   DATETIME    NULL,
@@ -726,11 +2181,44 @@ impl DbStorage {
 
             CREATE INDEX plans_by_removal_timestamp
                 ON plans (_removal_timestamp);
+
+            CREATE TABLE recurrings (
+                recurring_id            BLOB        PRIMARY KEY DEFAULT (randomblob(16)),
+                description             BYTEA       NOT NULL,
+                account_id              BLOB        REFERENCES accounts(account_id),
+                category_id             BLOB        REFERENCES categories(category_id),
+                amount                  BYTEA       NOT NULL,
+                period                  INTEGER     NOT NULL,
+                start_timestamp         DATETIME    NOT NULL,
+                end_timestamp           DATETIME    NULL,
+                materialized_timestamp  DATETIME    NULL,
+                _origin                 BYTEA       NOT NULL,
+                _creation_timestamp     DATETIME    NOT NULL,
+                _change_timestamp       DATETIME    NULL,
+                _removal_timestamp      DATETIME    NULL
+            ) WITHOUT ROWID;
+
+            CREATE INDEX recurrings_by_creation_timestamp
+                ON recurrings (_creation_timestamp);
+
+            CREATE INDEX recurrings_by_change_timestamp
+                ON recurrings (_change_timestamp);
+
+            CREATE INDEX recurrings_by_removal_timestamp
+                ON recurrings (_removal_timestamp);
+
+            CREATE TABLE balance_snapshots (
+                account_id          BLOB        NOT NULL,
+                period_start        DATETIME    NOT NULL,
+                balance             BYTEA       NOT NULL,
+                PRIMARY KEY (account_id, period_start)
+            ) WITHOUT ROWID;
         "#;
 
-        self.db
-            .execute_batch(create_statement)
-            .map_err(Error::from)
+        self.connection()
+            .execute_batch(create_statement)?;
+
+        migrations::create_schema_info(&self.connection())
     }
 
     fn db_path<L: Location>(loc: &L) -> std::path::PathBuf {
@@ -744,7 +2232,8 @@ impl DbStorage {
         P: rusqlite::Params,
         C: Fn(&rusqlite::Row<'_>) -> Result<T>
     {
-        let mut statement = self.db.prepare(statement.as_ref())?;
+        let db = self.connection();
+        let mut statement = db.prepare_cached(statement.as_ref())?;
         let mut rows = statement.query(params)?;
 
         let mut result = Vec::new();
@@ -752,9 +2241,34 @@ impl DbStorage {
             result.push(convert(row)?)
         }
 
+        trace_rows!(result.len());
         Ok(result)
     }
 
+    fn execute_cached<P: rusqlite::Params>(&self, statement: &str, params: P) -> Result<usize> {
+        let rows = self.connection()
+            .prepare_cached(statement)?
+            .execute(params)?;
+
+        trace_rows!(rows);
+        Ok(rows)
+    }
+
+    fn execute_insert<P: rusqlite::Params>(&self, statement: &str, params: P) -> Result<()> {
+        //
+        // Primary key violation means, that identifier is already used
+        // by another item (possibly removed one), hence I return a
+        // typed error instead of a generic one from SQLite
+        //
+
+        match self.execute_cached(statement, params) {
+            Ok(_) => Ok(()),
+            Err(error) if error.sqlite_extended_code() == Some(rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY) => 
+                Err(Error::new(ALREADY_EXISTS)),
+            Err(error) => Err(error)
+        }
+    }
+
     fn query<S, T, C>(&self, statement: S, convert: C) -> Result<Vec<T>>
     where
         S: AsRef<str>,
@@ -763,80 +2277,94 @@ impl DbStorage {
         self.query_with_params(statement, [], convert)
     }
 
-    fn ensure_consistency(&self, table: &str, foreign_key: &str, foreign_key_value: Id) -> Result<()> {
-        let statement_fmt = format!(r#"
-            SELECT COUNT(*) FROM {}
-             WHERE _removal_timestamp IS NULL
-               AND {} = ?1
-            "#, table, foreign_key);
-
-        let count: usize = self.db
-            .query_row(statement_fmt.as_str(), rusqlite::params![foreign_key_value], 
-                |row| row.get(0))?;
+    fn ensure_consistency(&self, foreign_key: &Column, foreign_key_value: Id) -> Result<()> {
+        let count: usize = self.connection()
+            .prepare_cached(foreign_key.count_alive)?
+            .query_row(rusqlite::params![foreign_key_value], |row| row.get(0))?;
 
         if 0 < count {
-            return Err(Error::from_message_with_extra(CONSISTENCY_VIOLATION,
-                format!("Table: {}, foreign key: {}", table, foreign_key)));
+            return Err(Error::new_with_extra(CONSISTENCY_VIOLATION,
+                format!("Table: {}, foreign key: {}", foreign_key.table, foreign_key.name)));
         }
 
         Ok(())
     }
 
-    fn is_predefined_category(category: Id) -> bool {
-        let predefined = [
-            Self::TRANSFER_INCOME_ID,
-            Self::TRANSFER_OUTCOME_ID
-        ];
+    fn ensure_exists(&self, key: &Column, key_value: Id) -> Result<()> {
+        let count: usize = self.connection()
+            .prepare_cached(key.count_alive)?
+            .query_row(rusqlite::params![key_value], |row| row.get(0))?;
 
-        predefined.contains(&category)
+        if 0 == count {
+            return Err(Error::new_with_extra(MISSING_REFERENCE,
+                format!("Table: {}, key: {}", key.table, key.name)));
+        }
+
+        Ok(())
     }
-}
 
+    fn delete_removed(&self, cutoff: Option<Timestamp>) -> Result<()> {
+        //
+        // Referencing items are deleted first. If cutoff is not
+        // specified, all removed items are deleted
+        //
 
-impl DbStorage {
-    fn select_from_transactions<S: Into<String>>(modifiers: Option<S>) -> String {
-        let modifiers = modifiers
-            .map_or(String::new(), S::into);
+        let tables = [RECURRINGS, PLANS, TRANSACTIONS, CATEGORIES, ACCOUNTS];
 
-        return format!(r#"
-            SELECT transaction_id, timestamp, description, account_id, category_id, amount, 
-                   _origin, _creation_timestamp, _change_timestamp, _removal_timestamp
-              FROM transactions
-                {}
-        "#, modifiers);
+        self.with_transaction(|storage| {
+            for table in tables {
+                storage
+                    .execute_cached(table.delete_removed, rusqlite::params![cutoff])?;
+            }
+
+            //
+            // Snapshots of deleted accounts are useless
+            //
+
+            let statement = r#"
+                DELETE FROM balance_snapshots
+                 WHERE account_id NOT IN (SELECT account_id FROM accounts)
+            "#;
+
+            storage
+                .execute_cached(statement, [])?;
+
+            Ok(())
+        })
     }
 
-    fn select_from_accounts<S: Into<String>>(modifiers: Option<S>) -> String {
-        let modifiers = modifiers
-            .map_or(String::new(), S::into);
+    fn restore_item(&self, key: &Column, key_value: Id, change_timestamp: Timestamp) -> Result<()> {
+        let updated = self
+            .execute_cached(key.restore, rusqlite::params![change_timestamp, key_value])?;
 
-        return format!(r#"
-            SELECT account_id, name, balance, initial_balance, _origin, _creation_timestamp, _change_timestamp, _removal_timestamp
-              FROM accounts
-                {}
-        "#, modifiers);
+        Self::ensure_updated(updated)
     }
 
-    fn select_from_categories<S: Into<String>>(modifiers: Option<S>) -> String {
-        let modifiers = modifiers
-            .map_or(String::new(), S::into);
+    fn single_item<T>(mut items: Vec<T>, id: Id) -> Result<T> {
+        items
+            .pop()
+            .ok_or_else(|| item_not_found(id))
+    }
 
-        return format!(r#"
-            SELECT category_id, name, type, _origin, _creation_timestamp, _change_timestamp, _removal_timestamp
-              FROM categories
-                {}
-        "#, modifiers);
+    fn ensure_updated(updated_rows: usize) -> Result<()> {
+        //
+        // Nothing is updated if an item is missing or removed
+        //
+
+        if 0 == updated_rows {
+            return Err(Error::new(NOT_FOUND));
+        }
+
+        Ok(())
     }
 
-    fn select_from_plans<S: Into<String>>(modifiers: Option<S>) -> String {
-        let modifiers = modifiers
-            .map_or(String::new(), S::into);
+    fn is_predefined_category(category: Id) -> bool {
+        let predefined = [
+            Self::TRANSFER_INCOME_ID,
+            Self::TRANSFER_OUTCOME_ID
+        ];
 
-        return format!(r#"
-            SELECT plan_id, category_id, name, amount_limit, _origin, _creation_timestamp, _change_timestamp, _removal_timestamp
-              FROM plans
-                {}
-        "#, modifiers);
+        predefined.contains(&category)
     }
 }
 
@@ -854,16 +2382,16 @@ impl DbStorage {
             id: row.get(0)?, 
             name: row.get(1)?, 
             category_type: row.get(2)?,
-            meta_info: meta_info
+            meta_info
         })
     }
 
     fn account_from_row(row: &rusqlite::Row<'_>) -> Result<EncryptedAccount> {
         let meta_info = MetaInfo {
-            origin: row.get(4)?,
-            added_timestamp: row.get(5)?,
-            changed_timestamp: row.get(6)?,
-            removed_timestamp: row.get(7)?
+            origin: row.get(7)?,
+            added_timestamp: row.get(8)?,
+            changed_timestamp: row.get(9)?,
+            removed_timestamp: row.get(10)?
         };
 
         Ok(EncryptedAccount { 
@@ -871,16 +2399,19 @@ impl DbStorage {
             name: row.get(1)?, 
             balance: row.get(2)?,
             initial_balance: row.get(3)?,
-            meta_info: meta_info
+            currency: row.get(4)?,
+            archived: row.get(5)?,
+            account_type: row.get(6)?,
+            meta_info
         })
     }
 
     fn transaction_from_row(row: &rusqlite::Row<'_>) -> Result<EncryptedTransaction> {
         let meta_info = MetaInfo {
-            origin: row.get(6)?,
-            added_timestamp: row.get(7)?,
-            changed_timestamp: row.get(8)?,
-            removed_timestamp: row.get(9)?
+            origin: row.get(9)?,
+            added_timestamp: row.get(10)?,
+            changed_timestamp: row.get(11)?,
+            removed_timestamp: row.get(12)?
         };
 
         Ok(EncryptedTransaction { 
@@ -890,16 +2421,19 @@ impl DbStorage {
             account_id: row.get(3)?, 
             category_id: row.get(4)?, 
             amount: row.get(5)?,
-            meta_info: meta_info
+            transfer_id: row.get(6)?,
+            tags: row.get(7)?,
+            pending: row.get(8)?,
+            meta_info
         })
     }
 
     fn plan_from_row(row: &rusqlite::Row<'_>) -> Result<EncryptedPlan> {
         let meta_info = MetaInfo {
-            origin: row.get(4)?,
-            added_timestamp: row.get(5)?,
-            changed_timestamp: row.get(6)?,
-            removed_timestamp: row.get(7)?
+            origin: row.get(6)?,
+            added_timestamp: row.get(7)?,
+            changed_timestamp: row.get(8)?,
+            removed_timestamp: row.get(9)?
         };
 
         Ok(EncryptedPlan {
@@ -907,7 +2441,145 @@ impl DbStorage {
             category_id: row.get(1)?,
             name: row.get(2)?,
             amount_limit: row.get(3)?,
-            meta_info: meta_info
+            period: row.get(4)?,
+            rollover: row.get(5)?,
+            meta_info
         })
     }
+
+    fn recurring_from_row(row: &rusqlite::Row<'_>) -> Result<EncryptedRecurringTransaction> {
+        let meta_info = MetaInfo {
+            origin: row.get(9)?,
+            added_timestamp: row.get(10)?,
+            changed_timestamp: row.get(11)?,
+            removed_timestamp: row.get(12)?
+        };
+
+        Ok(EncryptedRecurringTransaction {
+            id: row.get(0)?,
+            description: row.get(1)?,
+            account_id: row.get(2)?,
+            category_id: row.get(3)?,
+            amount: row.get(4)?,
+            period: row.get(5)?,
+            start_timestamp: row.get(6)?,
+            end_timestamp: row.get(7)?,
+            materialized_timestamp: row.get(8)?,
+            meta_info
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::at;
+    use crate::storage::tests::{db_storage, account, category, transaction};
+    use super::*;
+
+    /// Returns address and text of each statement prepared
+    /// on connection.
+    fn prepared_statements(storage: &DbStorage) -> Vec<(usize, String)> {
+        let connection = storage.connection();
+        let mut statements = Vec::new();
+
+        //
+        // SAFETY: connection is locked, hence statements are
+        // neither finalized nor prepared while I walk them
+        //
+
+        unsafe {
+            let db = connection.handle();
+            let mut statement = rusqlite::ffi::sqlite3_next_stmt(db, std::ptr::null_mut());
+            while !statement.is_null() {
+                let sql = std::ffi::CStr::from_ptr(rusqlite::ffi::sqlite3_sql(statement));
+                statements.push((statement as usize, sql.to_string_lossy().into_owned()));
+
+                statement = rusqlite::ffi::sqlite3_next_stmt(db, statement);
+            }
+        }
+
+        statements
+    }
+
+    fn exercise(storage: &DbStorage, account: Id, category: Id) {
+        let id = storage.add_transaction(transaction(account, category)).unwrap();
+        storage.transaction(id).unwrap();
+        storage.transactions_of(account).unwrap();
+
+        assert!(storage.remove_account(account, at(2024, 6, 1)).is_err());
+
+        storage.remove_transaction(id, at(2024, 6, 1)).unwrap();
+        storage.restore_transaction(id, at(2024, 6, 2)).unwrap();
+        storage.stats().unwrap();
+    }
+
+    #[test]
+    fn statements_are_prepared_once() {
+        let (_loc, storage) = db_storage();
+        let account = storage.add_account(account()).unwrap();
+        let category = storage.add_category(category()).unwrap();
+
+        exercise(&storage, account, category);
+        let prepared = prepared_statements(&storage);
+
+        //
+        // Cache trims statements before preparing them
+        //
+
+        assert!(prepared.iter().any(|(_, sql)| sql == TRANSACTION_ACCOUNT_ID.count_alive.trim()));
+        assert!(prepared.iter().any(|(_, sql)| sql == TRANSACTION_ID.restore.trim()));
+        assert!(prepared.iter().any(|(_, sql)| sql == TRANSACTIONS.stats.trim()));
+
+        //
+        // The same statements are reused on every iteration,
+        // nothing is prepared again
+        //
+
+        for _ in 0..100 {
+            exercise(&storage, account, category);
+        }
+
+        assert_eq!(prepared_statements(&storage), prepared);
+    }
+
+    #[test]
+    fn cache_capacity_is_configurable() {
+        let loc = crate::location::TempLocation::new();
+        let storage = DbStorage::create(&loc, DbStorageOptions::new().statement_cache_capacity(0)).unwrap();
+        let account = storage.add_account(account()).unwrap();
+        let category = storage.add_category(category()).unwrap();
+
+        exercise(&storage, account, category);
+        assert!(prepared_statements(&storage).is_empty());
+    }
+
+    #[test]
+    fn read_only_open_while_writer_holds_database() {
+        let (loc, writer) = db_storage();
+        writer.add_account(account()).unwrap();
+
+        writer.with_transaction(|writer| {
+            writer.add_account(account())?;
+
+            //
+            // Reader sees only committed data and cannot modify anything
+            //
+
+            let reader = DbStorage::open_read_only(&loc, DbStorageOptions::default())?;
+            assert!(reader.is_read_only());
+            assert_eq!(reader.accounts()?.len(), 1);
+
+            let error = reader.add_account(account()).unwrap_err();
+            assert!(error.to_string().contains(STORAGE_IS_READ_ONLY.text()));
+
+            let error = reader.remove_account(Id::random(), at(2024, 6, 1)).unwrap_err();
+            assert!(error.to_string().contains(STORAGE_IS_READ_ONLY.text()));
+
+            Ok(())
+        }).unwrap();
+
+        let reader = DbStorage::open_read_only(&loc, DbStorageOptions::default()).unwrap();
+        assert_eq!(reader.accounts().unwrap().len(), 2);
+    }
 }
@@ -0,0 +1,1184 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::BTreeMap;
+
+use crate::error::{Result, Error};
+use crate::datetime::Timestamp;
+use super::data::{EncryptedTransaction, EncryptedCategory, EncryptedAccount, EncryptedPlan, EncryptedRecurringTransaction, Id, CategoryType, AccountType, MetaInfo};
+use super::data::{IntegrityReport, IntegrityIssue, IntegrityIssueKind, CompactStats};
+use super::data::{StorageStats, TableStats, EncryptedBalanceSnapshot};
+use super::storage::DataStorage;
+use super::{CONSISTENCY_VIOLATION, MISSING_REFERENCE, CANNOT_DELETE_PREDEFINED, CANNOT_UPDATE_PREDEFINED, NOT_FOUND};
+use super::{ALREADY_EXISTS, MISSING_META_INFO};
+use super::item_not_found;
+
+
+/// Table with items indexed by their identifiers.
+///
+/// Items are ordered by identifier, that matches order of rows
+/// in tables of [`super::DbStorage`], which are keyed by identifier.
+type Table<T> = BTreeMap<Id, T>;
+
+
+/// Common access to fields of stored items.
+trait Item: Clone {
+    fn id(&self) -> Option<Id>;
+
+    fn set_id(&mut self, id: Id);
+
+    fn meta_info(&self) -> &MetaInfo;
+
+    fn meta_info_mut(&mut self) -> &mut MetaInfo;
+
+    fn is_alive(&self) -> bool {
+        self.meta_info().removed_timestamp.is_none()
+    }
+}
+
+
+/// Implementation of [`Item`] for encrypted structures,
+/// that all have the same identifier and meta information fields.
+macro_rules! implement_item {
+    ($($item_type:ty),+ $(,)?) => {
+        $(
+            impl Item for $item_type {
+                fn id(&self) -> Option<Id> {
+                    self.id
+                }
+
+                fn set_id(&mut self, id: Id) {
+                    self.id = Some(id);
+                }
+
+                fn meta_info(&self) -> &MetaInfo {
+                    &self.meta_info
+                }
+
+                fn meta_info_mut(&mut self) -> &mut MetaInfo {
+                    &mut self.meta_info
+                }
+            }
+        )+
+    }
+}
+
+implement_item!(
+    EncryptedTransaction,
+    EncryptedAccount,
+    EncryptedCategory,
+    EncryptedPlan,
+    EncryptedRecurringTransaction,
+);
+
+
+/// All stored data.
+#[derive(Clone, Default)]
+struct Tables {
+    transactions: Table<EncryptedTransaction>,
+    accounts: Table<EncryptedAccount>,
+    categories: Table<EncryptedCategory>,
+    plans: Table<EncryptedPlan>,
+    recurrings: Table<EncryptedRecurringTransaction>,
+    snapshots: BTreeMap<(Id, Timestamp), EncryptedBalanceSnapshot>,
+}
+
+
+/// Storage, that keeps all data in memory.
+///
+/// Semantics is the same as of [`super::DbStorage`]: items are
+/// removed softly, consistency is checked before removal and
+/// query results are sorted in the same way. Nothing is persisted,
+/// hence the storage is suitable for tests and ephemeral use.
+/// 
+/// Storage can be shared between threads, operations are serialized
+/// in the same way as in [`super::DbStorage`].
+#[derive(Default)]
+pub struct MemoryStorage {
+    /// Stored data
+    tables: parking_lot::ReentrantMutex<RefCell<Tables>>,
+
+    /// Depth of nested [`DataStorage::with_transaction`] calls,
+    /// it is changed only while data is locked
+    transaction_depth: AtomicUsize,
+}
+
+
+impl MemoryStorage {
+    /// Creates an empty storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+
+impl DataStorage for MemoryStorage {
+    const TRANSFER_INCOME_ID: Id = Id::from_bytes([0x00; 16]);
+
+    const TRANSFER_OUTCOME_ID: Id = Id::from_bytes([0xFF; 16]);
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn with_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Self) -> Result<T>
+    {
+        //
+        // Only the outermost call makes a snapshot of data and restores
+        // it on failure, nested ones are just executed as a part of it
+        //
+
+        let locked_tables = self.tables.lock();
+
+        let depth = self.transaction_depth.load(Ordering::Relaxed);
+        if 0 < depth {
+            return f(self);
+        }
+
+        let snapshot = locked_tables.borrow().clone();
+
+        self.transaction_depth.store(depth + 1, Ordering::Relaxed);
+        let result = f(self);
+        self.transaction_depth.store(depth, Ordering::Relaxed);
+
+        if result.is_err() {
+            *locked_tables.borrow_mut() = snapshot;
+        }
+
+        result
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_transaction(&self, transaction: EncryptedTransaction) -> Result<Id> {
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        let (account, timestamp) = (transaction.account_id, transaction.timestamp);
+
+        let id = Self::insert(&mut tables.transactions, transaction)?;
+        Self::invalidate_snapshots(&mut tables, account, timestamp);
+
+        Ok(id)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn update_transaction(&self, transaction: EncryptedTransaction) -> Result<()> {
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+
+        //
+        // Snapshots are invalidated for both previous and new
+        // account and timestamp of the transaction
+        //
+
+        Self::invalidate_snapshots_of(&mut tables, transaction.id);
+        Self::invalidate_snapshots(&mut tables, transaction.account_id, transaction.timestamp);
+
+        let stored = Self::alive_mut(&mut tables.transactions, transaction.id)?;
+
+        stored.timestamp = transaction.timestamp;
+        stored.description = transaction.description;
+        stored.account_id = transaction.account_id;
+        stored.category_id = transaction.category_id;
+        stored.amount = transaction.amount;
+        stored.tags = transaction.tags;
+        stored.pending = transaction.pending;
+        Self::update_changed_timestamp(stored, transaction.meta_info.changed_timestamp);
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn remove_transaction(&self, transaction: Id, removal_timestamp: Timestamp) -> Result<()> {
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+
+        Self::invalidate_snapshots_of(&mut tables, Some(transaction));
+        Self::mark_removed(&mut tables.transactions, transaction, removal_timestamp);
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transaction(&self, transaction: Id) -> Result<EncryptedTransaction> {
+        Self::find(&self.tables.lock().borrow().transactions, transaction, true)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn removed_transaction(&self, transaction: Id) -> Result<EncryptedTransaction> {
+        Self::find(&self.tables.lock().borrow().transactions, transaction, false)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transaction_any(&self, transaction: Id) -> Result<EncryptedTransaction> {
+        Self::find_any(&self.tables.lock().borrow().transactions, transaction)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn restore_transaction(&self, transaction: Id, change_timestamp: Timestamp) -> Result<()> {
+        //
+        // Referenced items must be alive, otherwise restored
+        // item would break consistency
+        //
+
+        let encrypted_transaction = self.removed_transaction(transaction)?;
+
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        Self::ensure_exists(&tables.accounts, "accounts", "account_id", encrypted_transaction.account_id)?;
+        Self::ensure_exists(&tables.categories, "categories", "category_id", encrypted_transaction.category_id)?;
+
+        Self::restore_item(&mut tables.transactions, transaction, change_timestamp)?;
+        Self::invalidate_snapshots(&mut tables, encrypted_transaction.account_id, encrypted_transaction.timestamp);
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions(&self) -> Result<Vec<EncryptedTransaction>> {
+        Ok(self.transactions_where(|_| true))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_page(&self, offset: usize, limit: usize) -> Result<Vec<EncryptedTransaction>> {
+        Ok(Self::page(self.transactions_where(|_| true), offset, limit))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_count(&self) -> Result<usize> {
+        Ok(Self::alive(&self.tables.lock().borrow().transactions).count())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_after(&self, start_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
+        Ok(self.transactions_where(|transaction| transaction.timestamp >= start_timestamp))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_between(&self, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
+        Ok(self.transactions_where(|transaction| Self::is_between(transaction, start_timestamp, end_timestamp)))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_of(&self, account: Id) -> Result<Vec<EncryptedTransaction>> {
+        Ok(self.transactions_where(|transaction| transaction.account_id == account))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_of_page(&self, account: Id, offset: usize, limit: usize) -> Result<Vec<EncryptedTransaction>> {
+        Ok(Self::page(self.transactions_of(account)?, offset, limit))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_of_after(&self, account: Id, start_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
+        Ok(self.transactions_where(|transaction| transaction.account_id == account &&
+            transaction.timestamp >= start_timestamp))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_of_between(&self, account: Id, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
+        Ok(self.transactions_where(|transaction| transaction.account_id == account &&
+            Self::is_between(transaction, start_timestamp, end_timestamp)))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_with(&self, category: Id) -> Result<Vec<EncryptedTransaction>> {
+        Ok(self.transactions_where(|transaction| transaction.category_id == category))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_with_page(&self, category: Id, offset: usize, limit: usize) -> Result<Vec<EncryptedTransaction>> {
+        Ok(Self::page(self.transactions_with(category)?, offset, limit))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_with_after(&self, category: Id, start_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
+        Ok(self.transactions_where(|transaction| transaction.category_id == category &&
+            transaction.timestamp >= start_timestamp))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_with_between(&self, category: Id, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>> {
+        Ok(self.transactions_where(|transaction| transaction.category_id == category &&
+            Self::is_between(transaction, start_timestamp, end_timestamp)))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_of_transfer(&self, transfer: Id) -> Result<Vec<EncryptedTransaction>> {
+        Ok(self.transactions_where(|transaction| transaction.transfer_id == Some(transfer)))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn pending_transactions(&self) -> Result<Vec<EncryptedTransaction>> {
+        let mut result = self.transactions_where(|transaction| transaction.pending);
+        result.sort_by_key(|transaction| transaction.timestamp);
+
+        Ok(result)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedTransaction>> {
+        Ok(Self::added_since(&self.tables.lock().borrow().transactions, base))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedTransaction>> {
+        Ok(Self::changed_since(&self.tables.lock().borrow().transactions, base))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedTransaction>> {
+        Ok(Self::removed_since(&self.tables.lock().borrow().transactions, Some(base)))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn transactions_removed(&self) -> Result<Vec<EncryptedTransaction>> {
+        Ok(Self::removed_since(&self.tables.lock().borrow().transactions, None))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_account(&self, account: EncryptedAccount) -> Result<Id> {
+        Self::insert(&mut self.tables.lock().borrow_mut().accounts, account)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn update_account(&self, account: EncryptedAccount) -> Result<()> {
+        //
+        // Like in database, missing account is silently ignored here
+        //
+
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        if let Ok(stored) = Self::alive_mut(&mut tables.accounts, account.id) {
+            stored.name = account.name;
+            stored.balance = account.balance;
+        }
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn update_account_with_timestamp(&self, account: EncryptedAccount, change_timestamp: Timestamp) -> Result<()> {
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        let stored = Self::alive_mut(&mut tables.accounts, account.id)?;
+
+        stored.name = account.name;
+        stored.balance = account.balance;
+        stored.initial_balance = account.initial_balance;
+        stored.currency = account.currency;
+        stored.archived = account.archived;
+        stored.account_type = account.account_type;
+        stored.meta_info.changed_timestamp = Some(change_timestamp);
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn remove_account(&self, account: Id, removal_timestamp: Timestamp) -> Result<()> {
+        //
+        // Check if we can delete account: no transaction should belong to it.
+        // Only after that I can remove account
+        //
+
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        Self::ensure_consistency(&tables.transactions, "transactions", "account_id",
+            |transaction| transaction.account_id == account)?;
+        Self::ensure_consistency(&tables.recurrings, "recurrings", "account_id",
+            |recurring| recurring.account_id == account)?;
+
+        Self::mark_removed(&mut tables.accounts, account, removal_timestamp);
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn account(&self, account: Id) -> Result<EncryptedAccount> {
+        Self::find(&self.tables.lock().borrow().accounts, account, true)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn removed_account(&self, account: Id) -> Result<EncryptedAccount> {
+        Self::find(&self.tables.lock().borrow().accounts, account, false)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn account_any(&self, account: Id) -> Result<EncryptedAccount> {
+        Self::find_any(&self.tables.lock().borrow().accounts, account)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn restore_account(&self, account: Id, change_timestamp: Timestamp) -> Result<()> {
+        Self::restore_item(&mut self.tables.lock().borrow_mut().accounts, account, change_timestamp)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn accounts(&self) -> Result<Vec<EncryptedAccount>> {
+        Ok(Self::alive(&self.tables.lock().borrow().accounts)
+            .filter(|account| !account.archived)
+            .cloned()
+            .collect())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn accounts_of_type(&self, account_type: AccountType) -> Result<Vec<EncryptedAccount>> {
+        Ok(Self::alive(&self.tables.lock().borrow().accounts)
+            .filter(|account| !account.archived && account.account_type == account_type)
+            .cloned()
+            .collect())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn accounts_including_archived(&self) -> Result<Vec<EncryptedAccount>> {
+        Ok(Self::alive(&self.tables.lock().borrow().accounts)
+            .cloned()
+            .collect())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn accounts_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedAccount>> {
+        Ok(Self::added_since(&self.tables.lock().borrow().accounts, base))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn accounts_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedAccount>> {
+        Ok(Self::changed_since(&self.tables.lock().borrow().accounts, base))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn accounts_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedAccount>> {
+        Ok(Self::removed_since(&self.tables.lock().borrow().accounts, Some(base)))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn accounts_removed(&self) -> Result<Vec<EncryptedAccount>> {
+        Ok(Self::removed_since(&self.tables.lock().borrow().accounts, None))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_category(&self, category: EncryptedCategory) -> Result<Id> {
+        Self::insert(&mut self.tables.lock().borrow_mut().categories, category)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn update_category(&self, category: EncryptedCategory) -> Result<()> {
+        //
+        // Predefined categories are kept untouched, and
+        // category type is never updated
+        //
+
+        if category.id.is_some_and(Self::is_predefined_category) {
+            return Err(Error::new(CANNOT_UPDATE_PREDEFINED));
+        }
+
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        let stored = Self::alive_mut(&mut tables.categories, category.id)?;
+
+        stored.name = category.name;
+        Self::update_changed_timestamp(stored, category.meta_info.changed_timestamp);
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn remove_category(&self, category: Id, removal_timestamp: Timestamp) -> Result<()> {
+        //
+        // Check if no transactions and plans reference this category
+        //
+
+        if Self::is_predefined_category(category) {
+            return Err(Error::new(CANNOT_DELETE_PREDEFINED));
+        }
+
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        Self::ensure_consistency(&tables.transactions, "transactions", "category_id",
+            |transaction| transaction.category_id == category)?;
+        Self::ensure_consistency(&tables.plans, "plans", "category_id",
+            |plan| plan.category_id == category)?;
+        Self::ensure_consistency(&tables.recurrings, "recurrings", "category_id",
+            |recurring| recurring.category_id == category)?;
+
+        Self::mark_removed(&mut tables.categories, category, removal_timestamp);
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn category(&self, category: Id) -> Result<EncryptedCategory> {
+        Self::find(&self.tables.lock().borrow().categories, category, true)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn removed_category(&self, category: Id) -> Result<EncryptedCategory> {
+        Self::find(&self.tables.lock().borrow().categories, category, false)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn category_any(&self, category: Id) -> Result<EncryptedCategory> {
+        Self::find_any(&self.tables.lock().borrow().categories, category)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn restore_category(&self, category: Id, change_timestamp: Timestamp) -> Result<()> {
+        Self::restore_item(&mut self.tables.lock().borrow_mut().categories, category, change_timestamp)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories(&self) -> Result<Vec<EncryptedCategory>> {
+        let mut result: Vec<_> = Self::alive(&self.tables.lock().borrow().categories)
+            .cloned()
+            .collect();
+
+        result.sort_by_key(|category| category.category_type);
+        Ok(result)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories_of(&self, category_type: CategoryType) -> Result<Vec<EncryptedCategory>> {
+        Ok(Self::alive(&self.tables.lock().borrow().categories)
+            .filter(|category| category.category_type == category_type)
+            .cloned()
+            .collect())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>> {
+        Ok(Self::added_since(&self.tables.lock().borrow().categories, base))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>> {
+        Ok(Self::changed_since(&self.tables.lock().borrow().categories, base))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>> {
+        Ok(Self::removed_since(&self.tables.lock().borrow().categories, Some(base)))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn categories_removed(&self) -> Result<Vec<EncryptedCategory>> {
+        Ok(Self::removed_since(&self.tables.lock().borrow().categories, None))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_plan(&self, plan: EncryptedPlan) -> Result<Id> {
+        Self::insert(&mut self.tables.lock().borrow_mut().plans, plan)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn update_plan(&self, plan: EncryptedPlan) -> Result<()> {
+        //
+        // Plan can be moved to another category, hence I need
+        // to check if this category is alive
+        //
+
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        Self::ensure_exists(&tables.categories, "categories", "category_id", plan.category_id)?;
+
+        let stored = Self::alive_mut(&mut tables.plans, plan.id)?;
+
+        stored.category_id = plan.category_id;
+        stored.name = plan.name;
+        stored.amount_limit = plan.amount_limit;
+        stored.period = plan.period;
+        stored.rollover = plan.rollover;
+        Self::update_changed_timestamp(stored, plan.meta_info.changed_timestamp);
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn remove_plan(&self, plan: Id, removal_timestamp: Timestamp) -> Result<()> {
+        Self::mark_removed(&mut self.tables.lock().borrow_mut().plans, plan, removal_timestamp);
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plan(&self, plan: Id) -> Result<EncryptedPlan> {
+        Self::find(&self.tables.lock().borrow().plans, plan, true)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn removed_plan(&self, plan: Id) -> Result<EncryptedPlan> {
+        Self::find(&self.tables.lock().borrow().plans, plan, false)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plan_any(&self, plan: Id) -> Result<EncryptedPlan> {
+        Self::find_any(&self.tables.lock().borrow().plans, plan)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn restore_plan(&self, plan: Id, change_timestamp: Timestamp) -> Result<()> {
+        //
+        // Referenced items must be alive, otherwise restored
+        // item would break consistency
+        //
+
+        let encrypted_plan = self.removed_plan(plan)?;
+
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        Self::ensure_exists(&tables.categories, "categories", "category_id", encrypted_plan.category_id)?;
+
+        Self::restore_item(&mut tables.plans, plan, change_timestamp)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans(&self) -> Result<Vec<EncryptedPlan>> {
+        let mut result: Vec<_> = Self::alive(&self.tables.lock().borrow().plans)
+            .cloned()
+            .collect();
+
+        result.sort_by_key(|plan| plan.category_id);
+        Ok(result)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans_for(&self, category: Id) -> Result<Vec<EncryptedPlan>> {
+        Ok(Self::alive(&self.tables.lock().borrow().plans)
+            .filter(|plan| plan.category_id == category)
+            .cloned()
+            .collect())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>> {
+        Ok(Self::added_since(&self.tables.lock().borrow().plans, base))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>> {
+        Ok(Self::changed_since(&self.tables.lock().borrow().plans, base))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>> {
+        Ok(Self::removed_since(&self.tables.lock().borrow().plans, Some(base)))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn plans_removed(&self) -> Result<Vec<EncryptedPlan>> {
+        Ok(Self::removed_since(&self.tables.lock().borrow().plans, None))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_recurring(&self, recurring: EncryptedRecurringTransaction) -> Result<Id> {
+        Self::insert(&mut self.tables.lock().borrow_mut().recurrings, recurring)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn update_recurring(&self, recurring: EncryptedRecurringTransaction) -> Result<()> {
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        Self::ensure_exists(&tables.accounts, "accounts", "account_id", recurring.account_id)?;
+        Self::ensure_exists(&tables.categories, "categories", "category_id", recurring.category_id)?;
+
+        let stored = Self::alive_mut(&mut tables.recurrings, recurring.id)?;
+
+        stored.description = recurring.description;
+        stored.account_id = recurring.account_id;
+        stored.category_id = recurring.category_id;
+        stored.amount = recurring.amount;
+        stored.period = recurring.period;
+        stored.start_timestamp = recurring.start_timestamp;
+        stored.end_timestamp = recurring.end_timestamp;
+        stored.materialized_timestamp = recurring.materialized_timestamp;
+        Self::update_changed_timestamp(stored, recurring.meta_info.changed_timestamp);
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn remove_recurring(&self, recurring: Id, removal_timestamp: Timestamp) -> Result<()> {
+        Self::mark_removed(&mut self.tables.lock().borrow_mut().recurrings, recurring, removal_timestamp);
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurring(&self, recurring: Id) -> Result<EncryptedRecurringTransaction> {
+        Self::find(&self.tables.lock().borrow().recurrings, recurring, true)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurring_any(&self, recurring: Id) -> Result<EncryptedRecurringTransaction> {
+        Self::find_any(&self.tables.lock().borrow().recurrings, recurring)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurrings(&self) -> Result<Vec<EncryptedRecurringTransaction>> {
+        let mut result: Vec<_> = Self::alive(&self.tables.lock().borrow().recurrings)
+            .cloned()
+            .collect();
+
+        result.sort_by_key(|recurring| recurring.start_timestamp);
+        Ok(result)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurrings_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedRecurringTransaction>> {
+        Ok(Self::added_since(&self.tables.lock().borrow().recurrings, base))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurrings_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedRecurringTransaction>> {
+        Ok(Self::changed_since(&self.tables.lock().borrow().recurrings, base))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurrings_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedRecurringTransaction>> {
+        Ok(Self::removed_since(&self.tables.lock().borrow().recurrings, Some(base)))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn recurrings_removed(&self) -> Result<Vec<EncryptedRecurringTransaction>> {
+        Ok(Self::removed_since(&self.tables.lock().borrow().recurrings, None))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn add_snapshot(&self, snapshot: EncryptedBalanceSnapshot) -> Result<()> {
+        self.tables
+            .lock()
+            .borrow_mut()
+            .snapshots
+            .insert((snapshot.account_id, snapshot.period_start), snapshot);
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn nearest_snapshot(&self, account: Id, before: Timestamp) -> Result<Option<EncryptedBalanceSnapshot>> {
+        let locked_tables = self.tables.lock();
+        let tables = locked_tables.borrow();
+        let nearest = tables.snapshots
+            .range(..=(account, before))
+            .next_back()
+            .map(|(_, snapshot)| snapshot)
+            .filter(|snapshot| snapshot.account_id == account)
+            .cloned();
+
+        Ok(nearest)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn clear_snapshots(&self) -> Result<()> {
+        self.tables
+            .lock()
+            .borrow_mut()
+            .snapshots
+            .clear();
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn clean_removed(&self) -> Result<()> {
+        self.delete_removed(None);
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn clean_removed_before(&self, cutoff: Timestamp) -> Result<()> {
+        self.delete_removed(Some(cutoff));
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn compact(&self) -> Result<CompactStats> {
+        //
+        // Deleted items are freed immediately, nothing to reclaim
+        //
+
+        Ok(CompactStats::default())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn reclaimable_fraction(&self) -> Result<f64> {
+        Ok(0.0)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn stats(&self) -> Result<StorageStats> {
+        let locked_tables = self.tables.lock();
+        let tables = locked_tables.borrow();
+        let timestamps = || Self::alive(&tables.transactions)
+            .map(|transaction| transaction.timestamp);
+
+        Ok(StorageStats {
+            transactions: Self::table_stats(&tables.transactions),
+            accounts: Self::table_stats(&tables.accounts),
+            categories: Self::table_stats(&tables.categories),
+            plans: Self::table_stats(&tables.plans),
+            recurrings: Self::table_stats(&tables.recurrings),
+            first_transaction: timestamps().min(),
+            last_transaction: timestamps().max(),
+            size: 0
+        })
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn rewrite_transaction(&self, transaction: EncryptedTransaction) -> Result<()> {
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        let stored = Self::any_mut(&mut tables.transactions, transaction.id)?;
+
+        stored.description = transaction.description;
+        stored.amount = transaction.amount;
+        stored.tags = transaction.tags;
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn rewrite_account(&self, account: EncryptedAccount) -> Result<()> {
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        let stored = Self::any_mut(&mut tables.accounts, account.id)?;
+
+        stored.name = account.name;
+        stored.balance = account.balance;
+        stored.initial_balance = account.initial_balance;
+        stored.currency = account.currency;
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn rewrite_category(&self, category: EncryptedCategory) -> Result<()> {
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        let stored = Self::any_mut(&mut tables.categories, category.id)?;
+
+        stored.name = category.name;
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn rewrite_plan(&self, plan: EncryptedPlan) -> Result<()> {
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        let stored = Self::any_mut(&mut tables.plans, plan.id)?;
+
+        stored.name = plan.name;
+        stored.amount_limit = plan.amount_limit;
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn rewrite_recurring(&self, recurring: EncryptedRecurringTransaction) -> Result<()> {
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        let stored = Self::any_mut(&mut tables.recurrings, recurring.id)?;
+
+        stored.description = recurring.description;
+        stored.amount = recurring.amount;
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    fn verify_integrity(&self) -> Result<IntegrityReport> {
+        //
+        // Memory cannot be damaged, hence only references are checked
+        //
+
+        let locked_tables = self.tables.lock();
+        let tables = locked_tables.borrow();
+        let mut report = IntegrityReport::default();
+
+        Self::check_references(&mut report, &tables.transactions, "transactions", "account_id", 
+            |transaction| transaction.account_id, &tables.accounts, "accounts");
+        Self::check_references(&mut report, &tables.transactions, "transactions", "category_id", 
+            |transaction| transaction.category_id, &tables.categories, "categories");
+        Self::check_references(&mut report, &tables.plans, "plans", "category_id", 
+            |plan| plan.category_id, &tables.categories, "categories");
+        Self::check_references(&mut report, &tables.recurrings, "recurrings", "account_id", 
+            |recurring| recurring.account_id, &tables.accounts, "accounts");
+        Self::check_references(&mut report, &tables.recurrings, "recurrings", "category_id", 
+            |recurring| recurring.category_id, &tables.categories, "categories");
+
+        Ok(report)
+    }
+}
+
+
+impl MemoryStorage {
+    fn insert<T: Item>(table: &mut Table<T>, mut item: T) -> Result<Id> {
+        //
+        // Database requires origin and creation timestamp, and generates
+        // a random identifier if it is absent. Other timestamps are
+        // not written on creation
+        //
+
+        let meta_info = item.meta_info();
+        if meta_info.origin.is_none() || meta_info.added_timestamp.is_none() {
+            return Err(Error::new(MISSING_META_INFO));
+        }
+
+        let id = item
+            .id()
+            .unwrap_or_else(Id::random);
+
+        if table.contains_key(&id) {
+            return Err(Error::new(ALREADY_EXISTS));
+        }
+
+        item.set_id(id);
+        item.meta_info_mut().changed_timestamp = None;
+        item.meta_info_mut().removed_timestamp = None;
+
+        table.insert(id, item);
+        Ok(id)
+    }
+
+    fn alive<T: Item>(table: &Table<T>) -> impl Iterator<Item = &T> {
+        table
+            .values()
+            .filter(|item| item.is_alive())
+    }
+
+    fn find<T: Item>(table: &Table<T>, id: Id, alive: bool) -> Result<T> {
+        table
+            .get(&id)
+            .filter(|item| item.is_alive() == alive)
+            .cloned()
+            .ok_or_else(|| item_not_found(id))
+    }
+
+    fn table_stats<T: Item>(table: &Table<T>) -> TableStats {
+        let alive = Self::alive(table).count();
+
+        TableStats { 
+            alive, 
+            removed: table.len() - alive
+        }
+    }
+
+    fn find_any<T: Item>(table: &Table<T>, id: Id) -> Result<T> {
+        table
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| item_not_found(id))
+    }
+
+    fn alive_mut<T: Item>(table: &mut Table<T>, id: Option<Id>) -> Result<&mut T> {
+        //
+        // Nothing is updated if an item is missing or removed
+        //
+
+        id.and_then(|id| table.get_mut(&id))
+            .filter(|item| item.is_alive())
+            .ok_or_else(|| Error::new(NOT_FOUND))
+    }
+
+    fn any_mut<T: Item>(table: &mut Table<T>, id: Option<Id>) -> Result<&mut T> {
+        id.and_then(|id| table.get_mut(&id))
+            .ok_or_else(|| Error::new(NOT_FOUND))
+    }
+
+    fn update_changed_timestamp<T: Item>(item: &mut T, changed_timestamp: Option<Timestamp>) {
+        //
+        // Change timestamp is updated only if it is specified,
+        // otherwise previous value is kept
+        //
+
+        if changed_timestamp.is_some() {
+            item.meta_info_mut().changed_timestamp = changed_timestamp;
+        }
+    }
+
+    fn mark_removed<T: Item>(table: &mut Table<T>, id: Id, removal_timestamp: Timestamp) {
+        if let Some(item) = table.get_mut(&id) {
+            item.meta_info_mut().removed_timestamp = Some(removal_timestamp);
+        }
+    }
+
+    fn restore_item<T: Item>(table: &mut Table<T>, id: Id, change_timestamp: Timestamp) -> Result<()> {
+        let item = table
+            .get_mut(&id)
+            .filter(|item| !item.is_alive())
+            .ok_or_else(|| Error::new(NOT_FOUND))?;
+
+        let meta_info = item.meta_info_mut();
+        meta_info.removed_timestamp = None;
+        meta_info.changed_timestamp = Some(change_timestamp);
+
+        Ok(())
+    }
+
+    fn ensure_consistency<T, P>(table: &Table<T>, table_name: &str, foreign_key: &str, references: P) -> Result<()>
+    where
+        T: Item,
+        P: Fn(&T) -> bool
+    {
+        if Self::alive(table).any(references) {
+            return Err(Error::new_with_extra(CONSISTENCY_VIOLATION,
+                format!("Table: {}, foreign key: {}", table_name, foreign_key)));
+        }
+
+        Ok(())
+    }
+
+    fn ensure_exists<T: Item>(table: &Table<T>, table_name: &str, key: &str, key_value: Id) -> Result<()> {
+        if !table.get(&key_value).is_some_and(Item::is_alive) {
+            return Err(Error::new_with_extra(MISSING_REFERENCE,
+                format!("Table: {}, key: {}", table_name, key)));
+        }
+
+        Ok(())
+    }
+
+    fn invalidate_snapshots(tables: &mut Tables, account: Id, timestamp: Timestamp) {
+        //
+        // Snapshot includes transactions made strictly before
+        // its period start, hence only later snapshots are affected
+        //
+
+        tables.snapshots.retain(|(snapshot_account, period_start), _| 
+            *snapshot_account != account || *period_start <= timestamp);
+    }
+
+    fn invalidate_snapshots_of(tables: &mut Tables, transaction: Option<Id>) {
+        let stored = transaction
+            .and_then(|transaction| tables.transactions.get(&transaction))
+            .map(|transaction| (transaction.account_id, transaction.timestamp));
+
+        if let Some((account, timestamp)) = stored {
+            Self::invalidate_snapshots(tables, account, timestamp);
+        }
+    }
+
+    fn check_references<T, P, R>(report: &mut IntegrityReport, table: &Table<T>, table_name: &str, column: &str, 
+        reference: R, parents: &Table<P>, parent_table_name: &str)
+    where
+        T: Item,
+        P: Item,
+        R: Fn(&T) -> Id
+    {
+        for (id, item) in table {
+            let parent_id = reference(item);
+            let kind = match parents.get(&parent_id) {
+                None => IntegrityIssueKind::MissingReference,
+                Some(parent) if item.is_alive() && !parent.is_alive() => IntegrityIssueKind::RemovedReference,
+                Some(_) => continue
+            };
+
+            let details = match kind {
+                IntegrityIssueKind::MissingReference => format!("{} references missing item in {}", column, parent_table_name),
+                _ => format!("{} references removed item in {}", column, parent_table_name)
+            };
+
+            report.issues.push(IntegrityIssue {
+                kind,
+                table: table_name.to_owned(),
+                item: Some(*id),
+                reference: Some(parent_id),
+                details
+            });
+        }
+    }
+
+    fn delete_removed(&self, cutoff: Option<Timestamp>) {
+        //
+        // If cutoff is not specified, all removed items are deleted
+        //
+
+        fn retain<T: Item>(table: &mut Table<T>, cutoff: Option<Timestamp>) {
+            table.retain(|_, item| match (item.meta_info().removed_timestamp, cutoff) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(removed), Some(cutoff)) => removed >= cutoff
+            });
+        }
+
+        let locked_tables = self.tables.lock();
+        let mut tables = locked_tables.borrow_mut();
+        retain(&mut tables.recurrings, cutoff);
+        retain(&mut tables.plans, cutoff);
+        retain(&mut tables.transactions, cutoff);
+        retain(&mut tables.categories, cutoff);
+        retain(&mut tables.accounts, cutoff);
+
+        //
+        // Snapshots of deleted accounts are useless
+        //
+
+        let Tables { accounts, snapshots, .. } = &mut *tables;
+        snapshots.retain(|(account, _), _| accounts.contains_key(account));
+    }
+
+    fn added_since<T: Item>(table: &Table<T>, base: Timestamp) -> Vec<T> {
+        Self::sorted_by_meta(table, |meta_info| meta_info.added_timestamp.filter(|added| *added > base))
+    }
+
+    fn changed_since<T: Item>(table: &Table<T>, base: Timestamp) -> Vec<T> {
+        Self::sorted_by_meta(table, |meta_info| meta_info.changed_timestamp.filter(|changed| *changed > base))
+    }
+
+    fn removed_since<T: Item>(table: &Table<T>, base: Option<Timestamp>) -> Vec<T> {
+        Self::sorted_by_meta(table, |meta_info| meta_info.removed_timestamp
+            .filter(|removed| base.is_none_or(|base| *removed > base)))
+    }
+
+    fn sorted_by_meta<T, K>(table: &Table<T>, key: K) -> Vec<T>
+    where
+        T: Item,
+        K: Fn(&MetaInfo) -> Option<Timestamp>
+    {
+        //
+        // Items are selected by presence of a key and
+        // sorted by it in descending order
+        //
+
+        let mut result: Vec<_> = table
+            .values()
+            .filter_map(|item| key(item.meta_info()).map(|timestamp| (timestamp, item.clone())))
+            .collect();
+
+        result.sort_by(|(lhs, _), (rhs, _)| rhs.cmp(lhs));
+        trace_rows!(result.len());
+
+        result
+            .into_iter()
+            .map(|(_, item)| item)
+            .collect()
+    }
+
+    fn transactions_where<P>(&self, predicate: P) -> Vec<EncryptedTransaction>
+    where
+        P: Fn(&EncryptedTransaction) -> bool
+    {
+        let mut result: Vec<_> = Self::alive(&self.tables.lock().borrow().transactions)
+            .filter(|transaction| predicate(transaction))
+            .cloned()
+            .collect();
+
+        result.sort_by_key(|transaction| std::cmp::Reverse(transaction.timestamp));
+        trace_rows!(result.len());
+
+        result
+    }
+
+    fn is_between(transaction: &EncryptedTransaction, start_timestamp: Timestamp, end_timestamp: Timestamp) -> bool {
+        start_timestamp <= transaction.timestamp && transaction.timestamp < end_timestamp
+    }
+
+    fn page<T>(items: Vec<T>, offset: usize, limit: usize) -> Vec<T> {
+        items
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .collect()
+    }
+
+    fn is_predefined_category(category: Id) -> bool {
+        let predefined = [
+            Self::TRANSFER_INCOME_ID,
+            Self::TRANSFER_OUTCOME_ID
+        ];
+
+        predefined.contains(&category)
+    }
+}
@@ -0,0 +1,211 @@
+use crate::error::{Error, Result};
+use super::DATABASE_IS_NEWER;
+
+
+/// Version of database schema, that is supported by this version of library.
+const SCHEMA_VERSION: u32 = 3;
+
+
+/// Migration of database schema from one version to the next one.
+type Migration = fn(&rusqlite::Connection) -> Result<()>;
+
+
+/// All migrations in order. Migration with index `i` upgrades
+/// schema from version `i` to version `i + 1`.
+///
+/// Databases created before schema versioning have version 0.
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [
+    backfill_schema_info,
+    create_balance_snapshots,
+    add_account_types,
+];
+
+
+/// Creates a table with schema version and writes current version into it.
+/// Used for newly created databases, that have up-to-date schema.
+///
+/// * `db` - database connection
+pub(super) fn create_schema_info(db: &rusqlite::Connection) -> Result<()> {
+    let statement = r#"
+        CREATE TABLE schema_info (
+            version             INTEGER     NOT NULL
+        );
+    "#;
+
+    db.execute_batch(statement)?;
+    db.execute("INSERT INTO schema_info (version) VALUES (?1)", rusqlite::params![SCHEMA_VERSION])?;
+
+    Ok(())
+}
+
+
+/// Checks if database schema has the current version.
+/// Fails if database is created by a newer version of library.
+///
+/// * `db` - database connection
+pub(super) fn is_up_to_date(db: &rusqlite::Connection) -> Result<bool> {
+    Ok(checked_schema_version(db)? == SCHEMA_VERSION)
+}
+
+
+/// Applies all migrations, that are required to upgrade database
+/// schema to the current version. Must be called inside of transaction.
+///
+/// * `db` - database connection
+pub(super) fn apply(db: &rusqlite::Connection) -> Result<()> {
+    let version = checked_schema_version(db)?;
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(db)?;
+    }
+
+    db.execute("UPDATE schema_info SET version = ?1", rusqlite::params![SCHEMA_VERSION])?;
+
+    Ok(())
+}
+
+
+fn checked_schema_version(db: &rusqlite::Connection) -> Result<u32> {
+    let version = schema_version(db)?;
+    if SCHEMA_VERSION < version {
+        return Err(Error::new_with_extra(DATABASE_IS_NEWER,
+            format!("schema version: {}, supported: {}", version, SCHEMA_VERSION)));
+    }
+
+    Ok(version)
+}
+
+
+fn schema_version(db: &rusqlite::Connection) -> Result<u32> {
+    //
+    // Databases created before versioning have no version table
+    //
+
+    if !has_table(db, "schema_info")? {
+        return Ok(0);
+    }
+
+    db.query_row("SELECT version FROM schema_info", [], |row| row.get(0))
+        .map_err(Error::from)
+}
+
+
+fn has_table(db: &rusqlite::Connection, table: &str) -> Result<bool> {
+    let statement = r#"
+        SELECT COUNT(*) FROM sqlite_master
+         WHERE type = 'table' AND
+               name = ?1
+    "#;
+
+    let count: usize = db.query_row(statement, rusqlite::params![table], |row| row.get(0))?;
+    Ok(0 < count)
+}
+
+
+fn add_column_if_absent(db: &rusqlite::Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let statement = r#"
+        SELECT COUNT(*) FROM pragma_table_info(?1)
+         WHERE name = ?2
+    "#;
+
+    let count: usize = db.query_row(statement, rusqlite::params![table, column], |row| row.get(0))?;
+    if 0 == count {
+        db.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {};", table, column, definition))?;
+    }
+
+    Ok(())
+}
+
+
+/// Migration #1.
+///
+/// Databases created before versioning may lack columns and tables added
+/// since the first release, hence they are added here if absent.
+/// After that the version table is created.
+fn backfill_schema_info(db: &rusqlite::Connection) -> Result<()> {
+    add_column_if_absent(db, "accounts", "currency", "BYTEA NULL")?;
+    add_column_if_absent(db, "accounts", "archived", "BOOLEAN NOT NULL DEFAULT 0")?;
+    add_column_if_absent(db, "transactions", "transfer_id", "BLOB NULL")?;
+    add_column_if_absent(db, "transactions", "tags", "BYTEA NULL")?;
+    add_column_if_absent(db, "transactions", "pending", "BOOLEAN NOT NULL DEFAULT 0")?;
+    add_column_if_absent(db, "plans", "period", "INTEGER NOT NULL DEFAULT -2")?;
+    add_column_if_absent(db, "plans", "rollover", "BOOLEAN NOT NULL DEFAULT 0")?;
+
+    let statement = r#"
+        CREATE INDEX IF NOT EXISTS transactions_by_transfer
+            ON transactions (transfer_id);
+
+        CREATE TABLE IF NOT EXISTS recurrings (
+            recurring_id            BLOB        PRIMARY KEY DEFAULT (randomblob(16)),
+            description             BYTEA       NOT NULL,
+            account_id              BLOB        REFERENCES accounts(account_id),
+            category_id             BLOB        REFERENCES categories(category_id),
+            amount                  BYTEA       NOT NULL,
+            period                  INTEGER     NOT NULL,
+            start_timestamp         DATETIME    NOT NULL,
+            end_timestamp           DATETIME    NULL,
+            materialized_timestamp  DATETIME    NULL,
+            _origin                 BYTEA       NOT NULL,
+            _creation_timestamp     DATETIME    NOT NULL,
+            _change_timestamp       DATETIME    NULL,
+            _removal_timestamp      DATETIME    NULL
+        ) WITHOUT ROWID;
+
+        CREATE INDEX IF NOT EXISTS recurrings_by_creation_timestamp
+            ON recurrings (_creation_timestamp);
+
+        CREATE INDEX IF NOT EXISTS recurrings_by_change_timestamp
+            ON recurrings (_change_timestamp);
+
+        CREATE INDEX IF NOT EXISTS recurrings_by_removal_timestamp
+            ON recurrings (_removal_timestamp);
+    "#;
+
+    db.execute_batch(statement)?;
+
+    //
+    // Version is written by caller after all migrations
+    //
+
+    db.execute_batch(r#"
+        CREATE TABLE schema_info (
+            version             INTEGER     NOT NULL
+        );
+
+        INSERT INTO schema_info (version) VALUES (0);
+    "#)?;
+
+    Ok(())
+}
+
+
+/// Migration #2.
+///
+/// Adds a table with balance snapshots. Snapshots are derived data,
+/// hence the table is left empty.
+fn create_balance_snapshots(db: &rusqlite::Connection) -> Result<()> {
+    let statement = r#"
+        CREATE TABLE IF NOT EXISTS balance_snapshots (
+            account_id          BLOB        NOT NULL,
+            period_start        DATETIME    NOT NULL,
+            balance             BYTEA       NOT NULL,
+            PRIMARY KEY (account_id, period_start)
+        ) WITHOUT ROWID;
+    "#;
+
+    db.execute_batch(statement)?;
+
+    Ok(())
+}
+
+
+/// Migration #3.
+///
+/// Adds a column with account type. Existing accounts
+/// become checking ones.
+fn add_account_types(db: &rusqlite::Connection) -> Result<()> {
+    add_column_if_absent(db, "accounts", "type", "TINYINT NOT NULL DEFAULT 1")
+}
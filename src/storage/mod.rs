@@ -1,14 +1,60 @@
 mod data;
+#[allow(clippy::module_inception)]
 mod storage;
 mod db_storage;
+mod db_options;
+mod memory_storage;
+mod migrations;
 
 pub use self::storage::DataStorage;
 pub use self::db_storage::DbStorage;
+pub use self::db_options::DbStorageOptions;
+pub use self::memory_storage::MemoryStorage;
 pub use self::data::*;
 
+use crate::error::{Error, ErrorKind, ErrorMessage};
+
 
 /// Error message for DB consistency violation.
-const CONSISTENCY_VIOLATION: &str = "Cannot remove item from DB because of another items referencing it";
+const CONSISTENCY_VIOLATION: ErrorMessage = ErrorMessage::new(ErrorKind::Consistency, "Cannot remove item from DB because of another items referencing it");
+
+/// Error message for referencing missing or removed item.
+const MISSING_REFERENCE: ErrorMessage = ErrorMessage::new(ErrorKind::Consistency, "Cannot reference item, that is missing or removed");
 
 /// Error message for removing of predefined item prohibition.
-const CANNOT_DELETE_PREDEFINED: &str = "Cannot remove predefined item";
+const CANNOT_DELETE_PREDEFINED: ErrorMessage = ErrorMessage::new(ErrorKind::PredefinedItem, "Cannot remove predefined item");
+
+/// Error message for updating of predefined item prohibition.
+const CANNOT_UPDATE_PREDEFINED: ErrorMessage = ErrorMessage::new(ErrorKind::PredefinedItem, "Cannot update predefined item");
+
+/// Error message for missing (or already removed) item.
+const NOT_FOUND: ErrorMessage = ErrorMessage::new(ErrorKind::NotFound, "Item is not found");
+
+/// Error message for adding of an item with already used identifier.
+const ALREADY_EXISTS: ErrorMessage = ErrorMessage::new(ErrorKind::AlreadyExists, "Item with the same identifier already exists");
+
+/// Error message for adding of an item without origin or creation timestamp.
+const MISSING_META_INFO: ErrorMessage = ErrorMessage::new(ErrorKind::Storage, "Item has no origin or creation timestamp");
+
+
+/// Error message for identifier, that cannot be parsed.
+const MALFORMED_IDENTIFIER: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Malformed identifier");
+
+
+/// Error message for opening of a database with unsupported schema version.
+const DATABASE_IS_NEWER: ErrorMessage = ErrorMessage::new(ErrorKind::Storage, "Database is created by a newer version of bdgt");
+
+/// Error message for modification of a storage opened in read-only mode.
+const STORAGE_IS_READ_ONLY: ErrorMessage = ErrorMessage::new(ErrorKind::Storage, "Storage is opened in read-only mode");
+
+
+/// Constructs an error for missing item, identifier is used as extra information.
+/// 
+/// * `id` - identifier of missing item
+fn item_not_found(id: Id) -> Error {
+    Error::new_with_extra(NOT_FOUND, id.to_string())
+}
+
+
+#[cfg(test)]
+mod tests;
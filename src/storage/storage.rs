@@ -1,6 +1,7 @@
 use crate::error::Result;
 use crate::datetime::Timestamp;
-use super::data::{EncryptedTransaction, EncryptedCategory, EncryptedAccount, EncryptedPlan, Id, CategoryType};
+use super::data::{EncryptedTransaction, EncryptedCategory, EncryptedAccount, EncryptedPlan, EncryptedRecurringTransaction, Id, CategoryType, AccountType};
+use super::data::{IntegrityReport, CompactStats, StorageStats, EncryptedBalanceSnapshot};
 
 
 /// Storage trait, that provides protected data reading and writing.
@@ -10,6 +11,10 @@ use super::data::{EncryptedTransaction, EncryptedCategory, EncryptedAccount, Enc
 /// - creation operation. It writes creation timestamp from data meta
 ///   information if present. Otherwise, an error is occurred.
 ///
+/// - update operation. It writes change timestamp from data meta
+///   information if present. Otherwise, change timestamp is left
+///   untouched.
+///
 /// - removal operation. It writes removal timestamp always.
 ///
 /// - query operation. It does not update any timestamps, just reads all
@@ -26,10 +31,35 @@ pub trait DataStorage {
     ///Predefined outcome transfer category identifier.
     const TRANSFER_OUTCOME_ID: Id;
 
-    /// Add a new transaction.
+    /// Checks if storage is opened in read-only mode. 
+    /// 
+    /// All modifying operations of such storage fail.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// Execute a function atomically.
+    /// 
+    /// If the function fails, all changes made by it are discarded.
+    /// Nested calls are joined with the outermost one.
+    /// 
+    /// * `f` - function to execute, receives the storage itself
+    fn with_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Self) -> Result<T>;
+
+    /// Add a new transaction. Returns identifier of the added transaction,
+    /// a random one is generated if it is absent.
     /// 
     /// * `transaction` - protected transaction data
-    fn add_transaction(&self, transaction: EncryptedTransaction) -> Result<()>;
+    fn add_transaction(&self, transaction: EncryptedTransaction) -> Result<Id>;
+
+    /// Update transaction.
+    /// 
+    /// Fails if transaction does not exist or is removed.
+    /// 
+    /// * `transaction` - transaction to update (with updated data)
+    fn update_transaction(&self, transaction: EncryptedTransaction) -> Result<()>;
 
     /// Remove transaction.
     /// 
@@ -42,9 +72,36 @@ pub trait DataStorage {
     /// * `transaction` - identifier to return record for
     fn transaction(&self, transaction: Id) -> Result<EncryptedTransaction>;
 
+    /// Return removed (but not deleted permanently yet) transaction with a given identifier.
+    /// 
+    /// * `transaction` - identifier to return record for
+    fn removed_transaction(&self, transaction: Id) -> Result<EncryptedTransaction>;
+
+    /// Return transaction with a given identifier regardless of its removal state.
+    /// 
+    /// * `transaction` - identifier to return record for
+    fn transaction_any(&self, transaction: Id) -> Result<EncryptedTransaction>;
+
+    /// Restore removed transaction.
+    /// 
+    /// Fails if transaction is not removed or if its account or category is removed.
+    /// 
+    /// * `transaction` - identifier of a transaction to restore
+    /// * `change_timestamp` - this value will be written as change timestamp
+    fn restore_transaction(&self, transaction: Id, change_timestamp: Timestamp) -> Result<()>;
+
     /// Return all transactions sorted by timestamp in descending order.
     fn transactions(&self) -> Result<Vec<EncryptedTransaction>>;
 
+    /// Return a page of transactions sorted by timestamp in descending order.
+    /// 
+    /// * `offset` - number of transactions to skip
+    /// * `limit` - maximum number of transactions to return
+    fn transactions_page(&self, offset: usize, limit: usize) -> Result<Vec<EncryptedTransaction>>;
+
+    /// Return number of transactions (removed ones are not counted).
+    fn transactions_count(&self) -> Result<usize>;
+
     /// Return all transactions starting from a given time point sorted by 
     /// timestamp in descending order.
     /// 
@@ -71,6 +128,14 @@ pub trait DataStorage {
     /// * `account` - account identifier to return transactions for
     fn transactions_of(&self, account: Id) -> Result<Vec<EncryptedTransaction>>;
 
+    /// Return a page of transactions bound with a given account sorted by 
+    /// timestamp in descending order.
+    /// 
+    /// * `account` - account identifier to return transactions for
+    /// * `offset` - number of transactions to skip
+    /// * `limit` - maximum number of transactions to return
+    fn transactions_of_page(&self, account: Id, offset: usize, limit: usize) -> Result<Vec<EncryptedTransaction>>;
+
     /// Return all transactions starting from a given time point bound with 
     /// a given account sorted by timestamp in descending order.
     /// 
@@ -99,6 +164,14 @@ pub trait DataStorage {
     /// * `category` - category to return transactions with
     fn transactions_with(&self, category: Id) -> Result<Vec<EncryptedTransaction>>;
 
+    /// Return a page of transactions with given category sorted by timestamp
+    /// in descending order.
+    /// 
+    /// * `category` - category to return transactions with
+    /// * `offset` - number of transactions to skip
+    /// * `limit` - maximum number of transactions to return
+    fn transactions_with_page(&self, category: Id, offset: usize, limit: usize) -> Result<Vec<EncryptedTransaction>>;
+
     /// Return all transactions starting from a given time point and with 
     /// given category sorted by timestamp in descending order.
     /// 
@@ -119,31 +192,56 @@ pub trait DataStorage {
     /// * `end_timestamp` - point in time to end before
     fn transactions_with_between(&self, category: Id, start_timestamp: Timestamp, end_timestamp: Timestamp) -> Result<Vec<EncryptedTransaction>>;
 
-    /// Returns all transactions added to storage since a given time point.
+    /// Return both transactions of a transfer.
+    /// 
+    /// * `transfer` - transfer identifier to return transactions for
+    fn transactions_of_transfer(&self, transfer: Id) -> Result<Vec<EncryptedTransaction>>;
+
+    /// Return all pending transactions sorted by timestamp.
+    fn pending_transactions(&self) -> Result<Vec<EncryptedTransaction>>;
+
+    /// Returns all transactions added to storage since a given time point
+    /// sorted by creation timestamp in descending order.
     /// 
     /// * `base` - point in time. All transactions added strictly after this time point are returned.
     fn transactions_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedTransaction>>;
 
-    /// Returns all transactions changed in storage since a given time point.
+    /// Returns all transactions changed in storage since a given time point
+    /// sorted by change timestamp in descending order.
     /// 
     /// * `base` - point in time. All transactions changed strictly after this time point are returned.
     fn transactions_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedTransaction>>;
 
-    /// Returns all transactions removed from storage since a given time point.
+    /// Returns all transactions removed from storage since a given time point
+    /// sorted by removal timestamp in descending order.
     /// 
     /// * `base` - point in time. All transactions removed strictly after this time point are returned.
     fn transactions_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedTransaction>>;
 
-    /// Add a new account.
+    /// Returns all removed transactions, that are not deleted permanently yet,
+    /// sorted by removal timestamp in descending order.
+    fn transactions_removed(&self) -> Result<Vec<EncryptedTransaction>>;
+
+    /// Add a new account. Returns identifier of the added account,
+    /// a random one is generated if it is absent.
     /// 
     /// * `account` - protected account data
-    fn add_account(&self, account: EncryptedAccount) -> Result<()>;
+    fn add_account(&self, account: EncryptedAccount) -> Result<Id>;
 
     /// Update account.
     /// 
     /// * `account` - account to update (with updated data)
     fn update_account(&self, account: EncryptedAccount) -> Result<()>;
 
+    /// Update account and write change timestamp.
+    /// 
+    /// Unlike [`DataStorage::update_account`], also updates initial balance.
+    /// Fails if account does not exist or is removed.
+    /// 
+    /// * `account` - account to update (with updated data)
+    /// * `change_timestamp` - this value will be written as change timestamp
+    fn update_account_with_timestamp(&self, account: EncryptedAccount, change_timestamp: Timestamp) -> Result<()>;
+
     /// Remove an account if possible (or forced).
     /// 
     /// If account has transaction and `force` is false, then this function fails.
@@ -157,28 +255,70 @@ pub trait DataStorage {
     /// * `account` - identifier to return record for
     fn account(&self, account: Id) -> Result<EncryptedAccount>;
 
-    /// Return all accounts.
+    /// Return removed (but not deleted permanently yet) account with a given identifier.
+    /// 
+    /// * `account` - identifier to return record for
+    fn removed_account(&self, account: Id) -> Result<EncryptedAccount>;
+
+    /// Return account with a given identifier regardless of its removal state.
+    /// 
+    /// * `account` - identifier to return record for
+    fn account_any(&self, account: Id) -> Result<EncryptedAccount>;
+
+    /// Restore removed account.
+    /// 
+    /// Fails if account is not removed.
+    /// 
+    /// * `account` - identifier of an account to restore
+    /// * `change_timestamp` - this value will be written as change timestamp
+    fn restore_account(&self, account: Id, change_timestamp: Timestamp) -> Result<()>;
+
+    /// Return all accounts except archived ones.
     fn accounts(&self) -> Result<Vec<EncryptedAccount>>;
 
-    /// Returns all accounts added to storage since a given time point.
+    /// Return all accounts of specific type except archived ones.
+    /// 
+    /// * `account_type` - type to return accounts of
+    fn accounts_of_type(&self, account_type: AccountType) -> Result<Vec<EncryptedAccount>>;
+
+    /// Return all accounts including archived ones.
+    fn accounts_including_archived(&self) -> Result<Vec<EncryptedAccount>>;
+
+    /// Returns all accounts added to storage since a given time point
+    /// sorted by creation timestamp in descending order.
     /// 
     /// * `base` - point in time. All accounts added strictly after this time point are returned.
     fn accounts_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedAccount>>;
 
-    /// Returns all accounts changed in storage since a given time point.
+    /// Returns all accounts changed in storage since a given time point
+    /// sorted by change timestamp in descending order.
     /// 
     /// * `base` - point in time. All accounts changed strictly after this time point are returned.
     fn accounts_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedAccount>>;
 
-    /// Returns all accounts removed from storage since a given time point.
+    /// Returns all accounts removed from storage since a given time point
+    /// sorted by removal timestamp in descending order.
     /// 
     /// * `base` - point in time. All accounts removed strictly after this time point are returned.
     fn accounts_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedAccount>>;
 
-    /// Add a new category.
+    /// Returns all removed accounts, that are not deleted permanently yet,
+    /// sorted by removal timestamp in descending order.
+    fn accounts_removed(&self) -> Result<Vec<EncryptedAccount>>;
+
+    /// Add a new category. Returns identifier of the added category,
+    /// a random one is generated if it is absent.
     /// 
     /// * `category` - protected category data
-    fn add_category(&self, category: EncryptedCategory) -> Result<()>;
+    fn add_category(&self, category: EncryptedCategory) -> Result<Id>;
+
+    /// Update category.
+    /// 
+    /// Category type is never changed. Predefined categories cannot be
+    /// updated. Fails if category does not exist or is removed.
+    /// 
+    /// * `category` - category to update (with updated data)
+    fn update_category(&self, category: EncryptedCategory) -> Result<()>;
 
     /// Remove category if possible.
     /// 
@@ -195,6 +335,24 @@ pub trait DataStorage {
     /// * `category` - identifier to return record for
     fn category(&self, category: Id) -> Result<EncryptedCategory>;
 
+    /// Return removed (but not deleted permanently yet) category with a given identifier.
+    /// 
+    /// * `category` - identifier to return record for
+    fn removed_category(&self, category: Id) -> Result<EncryptedCategory>;
+
+    /// Return category with a given identifier regardless of its removal state.
+    /// 
+    /// * `category` - identifier to return record for
+    fn category_any(&self, category: Id) -> Result<EncryptedCategory>;
+
+    /// Restore removed category.
+    /// 
+    /// Fails if category is not removed.
+    /// 
+    /// * `category` - identifier of a category to restore
+    /// * `change_timestamp` - this value will be written as change timestamp
+    fn restore_category(&self, category: Id, change_timestamp: Timestamp) -> Result<()>;
+
     /// Return all categories sorted by type.
     fn categories(&self) -> Result<Vec<EncryptedCategory>>;
 
@@ -203,25 +361,41 @@ pub trait DataStorage {
     /// * `category_type` - type to return categories of
     fn categories_of(&self, category_type: CategoryType) -> Result<Vec<EncryptedCategory>>;
 
-    /// Returns all categories added to storage since a given time point.
+    /// Returns all categories added to storage since a given time point
+    /// sorted by creation timestamp in descending order.
     /// 
     /// * `base` - point in time. All categories added strictly after this time point are returned.
     fn categories_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>>;
 
-    /// Returns all categories changed in storage since a given time point.
+    /// Returns all categories changed in storage since a given time point
+    /// sorted by change timestamp in descending order.
     /// 
     /// * `base` - point in time. All categories changed strictly after this time point are returned.
     fn categories_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>>;
 
-    /// Returns all categories removed from storage since a given time point.
+    /// Returns all categories removed from storage since a given time point
+    /// sorted by removal timestamp in descending order.
     /// 
     /// * `base` - point in time. All categories removed strictly after this time point are returned.
     fn categories_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedCategory>>;
 
-    /// Add a new plan.
+    /// Returns all removed categories, that are not deleted permanently yet,
+    /// sorted by removal timestamp in descending order.
+    fn categories_removed(&self) -> Result<Vec<EncryptedCategory>>;
+
+    /// Add a new plan. Returns identifier of the added plan,
+    /// a random one is generated if it is absent.
     /// 
     /// * `plan` - protected plan data
-    fn add_plan(&self, plan: EncryptedPlan) -> Result<()>;
+    fn add_plan(&self, plan: EncryptedPlan) -> Result<Id>;
+
+    /// Update plan.
+    /// 
+    /// Plan can be reassigned to another category, which must exist
+    /// and must not be removed. Fails if plan does not exist or is removed.
+    /// 
+    /// * `plan` - plan to update (with updated data)
+    fn update_plan(&self, plan: EncryptedPlan) -> Result<()>;
 
     /// Remove plan.
     /// 
@@ -234,6 +408,24 @@ pub trait DataStorage {
     /// * `plan` - identifier to return record for
     fn plan(&self, plan: Id) -> Result<EncryptedPlan>;
 
+    /// Return removed (but not deleted permanently yet) plan with a given identifier.
+    /// 
+    /// * `plan` - identifier to return record for
+    fn removed_plan(&self, plan: Id) -> Result<EncryptedPlan>;
+
+    /// Return plan with a given identifier regardless of its removal state.
+    /// 
+    /// * `plan` - identifier to return record for
+    fn plan_any(&self, plan: Id) -> Result<EncryptedPlan>;
+
+    /// Restore removed plan.
+    /// 
+    /// Fails if plan is not removed or if its category is removed.
+    /// 
+    /// * `plan` - identifier of a plan to restore
+    /// * `change_timestamp` - this value will be written as change timestamp
+    fn restore_plan(&self, plan: Id, change_timestamp: Timestamp) -> Result<()>;
+
     /// Return all plans sorted by category.
     fn plans(&self) -> Result<Vec<EncryptedPlan>>;
 
@@ -242,25 +434,158 @@ pub trait DataStorage {
     /// * `category` - category to return plans for
     fn plans_for(&self, category: Id) -> Result<Vec<EncryptedPlan>>;
 
-    /// Returns all plans added to storage since a given time point.
+    /// Returns all plans added to storage since a given time point
+    /// sorted by creation timestamp in descending order.
     /// 
     /// * `base` - point in time. All plans added strictly after this time point are returned.
     fn plans_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>>;
 
-    /// Returns all plans changed in storage since a given time point.
+    /// Returns all plans changed in storage since a given time point
+    /// sorted by change timestamp in descending order.
     /// 
     /// * `base` - point in time. All plans changed strictly after this time point are returned.
     fn plans_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>>;
 
-    /// Returns all plans removed from storage since a given time point.
+    /// Returns all plans removed from storage since a given time point
+    /// sorted by removal timestamp in descending order.
     /// 
     /// * `base` - point in time. All plans removed strictly after this time point are returned.
     fn plans_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedPlan>>;
 
+    /// Returns all removed plans, that are not deleted permanently yet,
+    /// sorted by removal timestamp in descending order.
+    fn plans_removed(&self) -> Result<Vec<EncryptedPlan>>;
+
+    /// Add a new recurring transaction. Returns identifier of the added recurring transaction,
+    /// a random one is generated if it is absent.
+    /// 
+    /// * `recurring` - protected recurring transaction data
+    fn add_recurring(&self, recurring: EncryptedRecurringTransaction) -> Result<Id>;
+
+    /// Update recurring transaction.
+    /// 
+    /// Fails if recurring transaction does not exist or is removed.
+    /// 
+    /// * `recurring` - recurring transaction to update (with updated data)
+    fn update_recurring(&self, recurring: EncryptedRecurringTransaction) -> Result<()>;
+
+    /// Remove recurring transaction.
+    /// 
+    /// * `recurring` - identifier of recurring transaction to remove
+    /// * `removal_timestamp` - this value will be written as removal timestamp
+    fn remove_recurring(&self, recurring: Id, removal_timestamp: Timestamp) -> Result<()>;
+
+    /// Return recurring transaction with a given identifier.
+    /// 
+    /// * `recurring` - identifier to return record for
+    fn recurring(&self, recurring: Id) -> Result<EncryptedRecurringTransaction>;
+
+    /// Return recurring transaction with a given identifier regardless of its removal state.
+    /// 
+    /// * `recurring` - identifier to return record for
+    fn recurring_any(&self, recurring: Id) -> Result<EncryptedRecurringTransaction>;
+
+    /// Return all recurring transactions sorted by start timestamp.
+    fn recurrings(&self) -> Result<Vec<EncryptedRecurringTransaction>>;
+
+    /// Returns all recurring transactions added to storage since a given time point
+    /// sorted by creation timestamp in descending order.
+    /// 
+    /// * `base` - point in time. All recurring transactions added strictly after this time point are returned.
+    fn recurrings_added_since(&self, base: Timestamp) -> Result<Vec<EncryptedRecurringTransaction>>;
+
+    /// Returns all recurring transactions changed in storage since a given time point
+    /// sorted by change timestamp in descending order.
+    /// 
+    /// * `base` - point in time. All recurring transactions changed strictly after this time point are returned.
+    fn recurrings_changed_since(&self, base: Timestamp) -> Result<Vec<EncryptedRecurringTransaction>>;
+
+    /// Returns all recurring transactions removed from storage since a given time point
+    /// sorted by removal timestamp in descending order.
+    /// 
+    /// * `base` - point in time. All recurring transactions removed strictly after this time point are returned.
+    fn recurrings_removed_since(&self, base: Timestamp) -> Result<Vec<EncryptedRecurringTransaction>>;
+
+    /// Returns all removed recurring transactions, that are not deleted permanently yet,
+    /// sorted by removal timestamp in descending order.
+    fn recurrings_removed(&self) -> Result<Vec<EncryptedRecurringTransaction>>;
+
+    /// Add a balance snapshot. Existing snapshot of the same account
+    /// for the same period is replaced.
+    /// 
+    /// Snapshots, that cover changed transactions, are deleted by
+    /// storage automatically when transactions are added, updated,
+    /// removed or restored.
+    /// 
+    /// * `snapshot` - snapshot data
+    fn add_snapshot(&self, snapshot: EncryptedBalanceSnapshot) -> Result<()>;
+
+    /// Return the latest snapshot of an account, which period starts
+    /// not later than a given time point.
+    /// 
+    /// * `account` - identifier of an account to return snapshot of
+    /// * `before` - point in time
+    fn nearest_snapshot(&self, account: Id, before: Timestamp) -> Result<Option<EncryptedBalanceSnapshot>>;
+
+    /// Delete all balance snapshots.
+    fn clear_snapshots(&self) -> Result<()>;
+
     /// Delete permanently all previously removed items.
     /// 
     /// Actually `remove_*` functions can perform no removal, e.g.
     /// just mark items as removed. This function therefore permanently
     /// deletes such marked items.
     fn clean_removed(&self) -> Result<()>;
+
+    /// Delete permanently all items removed strictly before a given time point.
+    /// 
+    /// * `cutoff` - point in time. Items removed later are kept.
+    fn clean_removed_before(&self, cutoff: Timestamp) -> Result<()>;
+
+    /// Reclaims space left after deleted items.
+    /// 
+    /// Must not be called inside of [`DataStorage::with_transaction`].
+    fn compact(&self) -> Result<CompactStats>;
+
+    /// Returns fraction of storage space (from 0 to 1), that
+    /// can be reclaimed by [`DataStorage::compact`].
+    fn reclaimable_fraction(&self) -> Result<f64>;
+
+    /// Returns numbers of items and other statistics of stored data.
+    fn stats(&self) -> Result<StorageStats>;
+
+    /// Replaces encrypted fields of a transaction (alive or removed) keeping
+    /// meta information untouched. Used to re-encrypt storage with a new key.
+    /// 
+    /// * `transaction` - transaction with re-encrypted fields
+    fn rewrite_transaction(&self, transaction: EncryptedTransaction) -> Result<()>;
+
+    /// Replaces encrypted fields of an account (alive or removed) keeping
+    /// meta information untouched. Used to re-encrypt storage with a new key.
+    /// 
+    /// * `account` - account with re-encrypted fields
+    fn rewrite_account(&self, account: EncryptedAccount) -> Result<()>;
+
+    /// Replaces encrypted fields of a category (alive, removed or predefined) 
+    /// keeping meta information untouched. Used to re-encrypt storage with a new key.
+    /// 
+    /// * `category` - category with re-encrypted fields
+    fn rewrite_category(&self, category: EncryptedCategory) -> Result<()>;
+
+    /// Replaces encrypted fields of a plan (alive or removed) keeping
+    /// meta information untouched. Used to re-encrypt storage with a new key.
+    /// 
+    /// * `plan` - plan with re-encrypted fields
+    fn rewrite_plan(&self, plan: EncryptedPlan) -> Result<()>;
+
+    /// Replaces encrypted fields of a recurring transaction (alive or removed) 
+    /// keeping meta information untouched. Used to re-encrypt storage with a new key.
+    /// 
+    /// * `recurring` - recurring transaction with re-encrypted fields
+    fn rewrite_recurring(&self, recurring: EncryptedRecurringTransaction) -> Result<()>;
+
+    /// Checks storage for damage and broken references between items.
+    /// 
+    /// Nothing is modified, all found problems are returned in a report.
+    fn verify_integrity(&self) -> Result<IntegrityReport>;
 }
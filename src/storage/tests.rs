@@ -0,0 +1,456 @@
+use crate::error::ErrorKind;
+use crate::location::TempLocation;
+use crate::testing::{at, new_meta_info};
+use super::*;
+
+
+//
+// Both storages are expected to behave identically, hence every
+// check is written once against the trait and run on each of them
+//
+
+macro_rules! on_each_storage {
+    ($check:ident) => {{
+        $check(&MemoryStorage::new());
+        $check(&db_storage().1);
+    }};
+}
+
+
+pub(super) fn db_storage() -> (TempLocation, DbStorage) {
+    let loc = TempLocation::new();
+    let storage = DbStorage::create(&loc, DbStorageOptions::default())
+        .expect("Database is created");
+
+    (loc, storage)
+}
+
+
+pub(super) fn meta_info() -> MetaInfo {
+    MetaInfo {
+        origin: Some([1; 16]),
+        ..new_meta_info()
+    }
+}
+
+
+pub(super) fn account() -> EncryptedAccount {
+    EncryptedAccount {
+        id: None,
+        name: b"account".to_vec(),
+        balance: b"balance".to_vec(),
+        initial_balance: b"initial balance".to_vec(),
+        currency: None,
+        archived: false,
+        account_type: AccountType::Checking,
+        meta_info: meta_info()
+    }
+}
+
+
+pub(super) fn category() -> EncryptedCategory {
+    EncryptedCategory {
+        id: None,
+        name: b"category".to_vec(),
+        category_type: CategoryType::Outcome,
+        meta_info: meta_info()
+    }
+}
+
+
+pub(super) fn transaction(account: Id, category: Id) -> EncryptedTransaction {
+    EncryptedTransaction {
+        id: None,
+        timestamp: at(2024, 5, 1),
+        description: b"description".to_vec(),
+        account_id: account,
+        category_id: category,
+        amount: b"amount".to_vec(),
+        transfer_id: None,
+        tags: None,
+        pending: false,
+        meta_info: meta_info()
+    }
+}
+
+
+pub(super) fn plan(category: Id) -> EncryptedPlan {
+    EncryptedPlan {
+        id: None,
+        category_id: category,
+        name: b"plan".to_vec(),
+        amount_limit: b"limit".to_vec(),
+        period: PlanPeriod::Monthly,
+        rollover: false,
+        meta_info: meta_info()
+    }
+}
+
+
+pub(super) fn recurring(account: Id, category: Id) -> EncryptedRecurringTransaction {
+    EncryptedRecurringTransaction {
+        id: None,
+        description: b"description".to_vec(),
+        account_id: account,
+        category_id: category,
+        amount: b"amount".to_vec(),
+        period: RecurrencePeriod::Monthly,
+        start_timestamp: at(2024, 5, 1),
+        end_timestamp: None,
+        materialized_timestamp: None,
+        meta_info: meta_info()
+    }
+}
+
+
+fn assert_not_found<T: std::fmt::Debug>(result: crate::error::Result<T>) {
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+
+#[test]
+fn unknown_items_are_not_found() {
+    fn check<S: DataStorage>(storage: &S) {
+        assert_not_found(storage.transaction(Id::random()));
+        assert_not_found(storage.account(Id::random()));
+        assert_not_found(storage.category(Id::random()));
+        assert_not_found(storage.plan(Id::random()));
+        assert_not_found(storage.recurring(Id::random()));
+
+        assert_not_found(storage.transaction_any(Id::random()));
+        assert_not_found(storage.account_any(Id::random()));
+        assert_not_found(storage.category_any(Id::random()));
+        assert_not_found(storage.plan_any(Id::random()));
+        assert_not_found(storage.recurring_any(Id::random()));
+    }
+
+    on_each_storage!(check);
+}
+
+
+#[test]
+fn removed_items_are_not_found() {
+    fn check<S: DataStorage>(storage: &S) {
+        let account = storage.add_account(account()).unwrap();
+        let category = storage.add_category(category()).unwrap();
+        let transaction = storage.add_transaction(transaction(account, category)).unwrap();
+        let plan = storage.add_plan(plan(category)).unwrap();
+        let recurring = storage.add_recurring(recurring(account, category)).unwrap();
+
+        storage.remove_recurring(recurring, at(2024, 6, 1)).unwrap();
+        storage.remove_plan(plan, at(2024, 6, 1)).unwrap();
+        storage.remove_transaction(transaction, at(2024, 6, 1)).unwrap();
+        storage.remove_category(category, at(2024, 6, 1)).unwrap();
+        storage.remove_account(account, at(2024, 6, 1)).unwrap();
+
+        assert_not_found(storage.transaction(transaction));
+        assert_not_found(storage.account(account));
+        assert_not_found(storage.category(category));
+        assert_not_found(storage.plan(plan));
+        assert_not_found(storage.recurring(recurring));
+
+        //
+        // Removed items are still reachable explicitly
+        //
+
+        assert!(storage.removed_transaction(transaction).is_ok());
+        assert!(storage.removed_account(account).is_ok());
+        assert!(storage.removed_category(category).is_ok());
+        assert!(storage.removed_plan(plan).is_ok());
+        assert!(storage.recurring_any(recurring).is_ok());
+    }
+
+    on_each_storage!(check);
+}
+
+
+#[test]
+fn alive_items_are_not_found_as_removed() {
+    fn check<S: DataStorage>(storage: &S) {
+        let account = storage.add_account(account()).unwrap();
+        let category = storage.add_category(category()).unwrap();
+        let transaction = storage.add_transaction(transaction(account, category)).unwrap();
+        let plan = storage.add_plan(plan(category)).unwrap();
+
+        assert_not_found(storage.removed_transaction(transaction));
+        assert_not_found(storage.removed_account(account));
+        assert_not_found(storage.removed_category(category));
+        assert_not_found(storage.removed_plan(plan));
+    }
+
+    on_each_storage!(check);
+}
+
+
+//
+// Randomized operation sequences are applied to both storages
+// and their observable states are compared after each step
+//
+
+#[derive(Debug, PartialEq)]
+struct State {
+    accounts: Vec<EncryptedAccount>,
+    categories: Vec<EncryptedCategory>,
+    transactions: Vec<EncryptedTransaction>,
+    plans: Vec<EncryptedPlan>,
+    added: Vec<EncryptedTransaction>,
+    changed: Vec<EncryptedTransaction>,
+    removed: Vec<EncryptedTransaction>,
+    accounts_removed: Vec<EncryptedAccount>,
+    categories_removed: Vec<EncryptedCategory>,
+}
+
+
+fn state_of<S: DataStorage>(storage: &S) -> State {
+    fn sorted<T, F: Fn(&T) -> PrimaryId>(mut items: Vec<T>, id: F) -> Vec<T> {
+        items.sort_by_key(id);
+        items
+    }
+
+    let base = at(2024, 1, 1);
+    State {
+        accounts: sorted(storage.accounts().unwrap(), |a| a.id),
+        categories: sorted(storage.categories().unwrap(), |c| c.id),
+        transactions: sorted(storage.transactions().unwrap(), |t| t.id),
+        plans: sorted(storage.plans().unwrap(), |p| p.id),
+        added: storage.transactions_added_since(base).unwrap(),
+        changed: storage.transactions_changed_since(base).unwrap(),
+        removed: storage.transactions_removed_since(base).unwrap(),
+        accounts_removed: storage.accounts_removed_since(base).unwrap(),
+        categories_removed: storage.categories_removed_since(base).unwrap(),
+    }
+}
+
+
+#[test]
+fn memory_storage_matches_database() {
+    use rand::{Rng, SeedableRng, seq::SliceRandom};
+
+    for seed in 0..8 {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let memory = MemoryStorage::new();
+        let (_loc, db) = db_storage();
+
+        let (mut accounts, mut categories, mut transactions) = (Vec::new(), Vec::new(), Vec::new());
+
+        for step in 0..200 {
+            //
+            // Every step has its own timestamp, hence ordering
+            // by timestamps is unambiguous
+            //
+
+            let now = at(2024, 1, 2) + chrono::Duration::minutes(step);
+            let meta_info = MetaInfo { added_timestamp: Some(now), ..meta_info() };
+
+            let (in_memory, in_db) = match rng.gen_range(0..8) {
+                0 => {
+                    let item = EncryptedAccount { meta_info, ..account() };
+                    let id = memory.add_account(item.clone()).unwrap();
+                    accounts.push(id);
+                    (Ok(()), db.add_account(EncryptedAccount { id: Some(id), ..item }).map(|_| ()))
+                },
+                1 => {
+                    let item = EncryptedCategory { meta_info, ..category() };
+                    let id = memory.add_category(item.clone()).unwrap();
+                    categories.push(id);
+                    (Ok(()), db.add_category(EncryptedCategory { id: Some(id), ..item }).map(|_| ()))
+                },
+                2 | 3 if !accounts.is_empty() && !categories.is_empty() => {
+                    let account = *accounts.choose(&mut rng).unwrap();
+                    let category = *categories.choose(&mut rng).unwrap();
+                    let item = EncryptedTransaction { timestamp: now, meta_info, ..transaction(account, category) };
+
+                    match memory.add_transaction(item.clone()) {
+                        Ok(id) => {
+                            transactions.push(id);
+                            (Ok(()), db.add_transaction(EncryptedTransaction { id: Some(id), ..item }).map(|_| ()))
+                        },
+                        Err(error) => (Err(error.kind()), db.add_transaction(item).map(|_| ()))
+                    }
+                },
+                4 if !transactions.is_empty() => {
+                    let id = *transactions.choose(&mut rng).unwrap();
+                    let stored = memory.transaction_any(id).unwrap();
+                    let mut item = EncryptedTransaction { id: Some(id), timestamp: now, ..transaction(stored.account_id, stored.category_id) };
+
+                    item.amount = vec![rng.gen(); 4];
+                    item.meta_info.changed_timestamp = Some(now);
+                    (memory.update_transaction(item.clone()).map_err(|e| e.kind()), db.update_transaction(item))
+                },
+                5 if !transactions.is_empty() => {
+                    let id = *transactions.choose(&mut rng).unwrap();
+                    (memory.remove_transaction(id, now).map_err(|e| e.kind()), db.remove_transaction(id, now))
+                },
+                6 if !accounts.is_empty() => {
+                    let id = *accounts.choose(&mut rng).unwrap();
+                    (memory.remove_account(id, now).map_err(|e| e.kind()), db.remove_account(id, now))
+                },
+                7 if !categories.is_empty() => {
+                    let id = *categories.choose(&mut rng).unwrap();
+                    (memory.remove_category(id, now).map_err(|e| e.kind()), db.remove_category(id, now))
+                },
+                _ => continue
+            };
+
+            assert_eq!(in_memory, in_db.map_err(|e| e.kind()), "seed {}, step {}", seed, step);
+            assert_eq!(state_of(&memory), state_of(&db), "seed {}, step {}", seed, step);
+        }
+    }
+}
+
+
+//
+// Items of each kind are added, changed and removed in different
+// order, and `*_since` queries return the latest ones first
+//
+
+fn added_at(day: u32) -> MetaInfo {
+    MetaInfo { 
+        added_timestamp: Some(at(2024, 5, day)), 
+        ..meta_info() 
+    }
+}
+
+
+fn changed_at(meta_info: MetaInfo, day: u32) -> MetaInfo {
+    MetaInfo { 
+        changed_timestamp: Some(at(2024, 5, day)), 
+        ..meta_info 
+    }
+}
+
+
+fn ids<T, F: Fn(&T) -> PrimaryId>(items: Vec<T>, id: F) -> Vec<Id> {
+    items
+        .iter()
+        .filter_map(id)
+        .collect()
+}
+
+
+#[test]
+fn accounts_since_are_ordered() {
+    fn check<S: DataStorage>(storage: &S) {
+        let base = at(2024, 4, 30);
+        let first = storage.add_account(EncryptedAccount { meta_info: added_at(1), ..account() }).unwrap();
+        let second = storage.add_account(EncryptedAccount { meta_info: added_at(2), ..account() }).unwrap();
+        assert_eq!(ids(storage.accounts_added_since(base).unwrap(), |a| a.id), [second, first]);
+
+        storage.update_account_with_timestamp(storage.account(second).unwrap(), at(2024, 5, 3)).unwrap();
+        storage.update_account_with_timestamp(storage.account(first).unwrap(), at(2024, 5, 4)).unwrap();
+        assert_eq!(ids(storage.accounts_changed_since(base).unwrap(), |a| a.id), [first, second]);
+
+        storage.remove_account(second, at(2024, 5, 5)).unwrap();
+        storage.remove_account(first, at(2024, 5, 6)).unwrap();
+        assert_eq!(ids(storage.accounts_removed_since(base).unwrap(), |a| a.id), [first, second]);
+        assert!(storage.accounts_removed_since(at(2024, 5, 7)).unwrap().is_empty());
+    }
+
+    on_each_storage!(check);
+}
+
+
+#[test]
+fn categories_since_are_ordered() {
+    fn check<S: DataStorage>(storage: &S) {
+        let base = at(2024, 4, 30);
+        let first = storage.add_category(EncryptedCategory { meta_info: added_at(1), ..category() }).unwrap();
+        let second = storage.add_category(EncryptedCategory { meta_info: added_at(2), ..category() }).unwrap();
+        assert_eq!(ids(storage.categories_added_since(base).unwrap(), |c| c.id), [second, first]);
+
+        for (id, day) in [(second, 3), (first, 4)] {
+            let category = storage.category(id).unwrap();
+            storage.update_category(EncryptedCategory { meta_info: changed_at(category.meta_info, day), ..category }).unwrap();
+        }
+
+        assert_eq!(ids(storage.categories_changed_since(base).unwrap(), |c| c.id), [first, second]);
+
+        storage.remove_category(second, at(2024, 5, 5)).unwrap();
+        storage.remove_category(first, at(2024, 5, 6)).unwrap();
+        assert_eq!(ids(storage.categories_removed_since(base).unwrap(), |c| c.id), [first, second]);
+        assert!(storage.categories_removed_since(at(2024, 5, 7)).unwrap().is_empty());
+    }
+
+    on_each_storage!(check);
+}
+
+
+#[test]
+fn plans_since_are_ordered() {
+    fn check<S: DataStorage>(storage: &S) {
+        let base = at(2024, 4, 30);
+        let category = storage.add_category(category()).unwrap();
+        let first = storage.add_plan(EncryptedPlan { meta_info: added_at(1), ..plan(category) }).unwrap();
+        let second = storage.add_plan(EncryptedPlan { meta_info: added_at(2), ..plan(category) }).unwrap();
+        assert_eq!(ids(storage.plans_added_since(base).unwrap(), |p| p.id), [second, first]);
+
+        for (id, day) in [(second, 3), (first, 4)] {
+            let plan = storage.plan(id).unwrap();
+            storage.update_plan(EncryptedPlan { meta_info: changed_at(plan.meta_info, day), ..plan }).unwrap();
+        }
+
+        assert_eq!(ids(storage.plans_changed_since(base).unwrap(), |p| p.id), [first, second]);
+
+        storage.remove_plan(second, at(2024, 5, 5)).unwrap();
+        storage.remove_plan(first, at(2024, 5, 6)).unwrap();
+        assert_eq!(ids(storage.plans_removed_since(base).unwrap(), |p| p.id), [first, second]);
+        assert!(storage.plans_removed_since(at(2024, 5, 7)).unwrap().is_empty());
+    }
+
+    on_each_storage!(check);
+}
+
+
+#[test]
+fn added_items_return_their_ids() {
+    fn check<S: DataStorage>(storage: &S) {
+        let account = storage.add_account(account()).unwrap();
+        let category = storage.add_category(category()).unwrap();
+        let added = storage.add_transaction(transaction(account, category)).unwrap();
+        let plan = storage.add_plan(plan(category)).unwrap();
+        let recurring = storage.add_recurring(recurring(account, category)).unwrap();
+
+        assert_eq!(storage.account(account).unwrap().id, Some(account));
+        assert_eq!(storage.category(category).unwrap().id, Some(category));
+        assert_eq!(storage.transaction(added).unwrap().id, Some(added));
+        assert_eq!(storage.plan(plan).unwrap().id, Some(plan));
+        assert_eq!(storage.recurring(recurring).unwrap().id, Some(recurring));
+
+        //
+        // Identifier is preserved if it is specified, 
+        // and it cannot be used twice
+        //
+
+        let id = Id::random();
+        let item = EncryptedTransaction { id: Some(id), ..transaction(account, category) };
+        assert_eq!(storage.add_transaction(item.clone()).unwrap(), id);
+        assert_eq!(storage.add_transaction(item).unwrap_err().kind(), ErrorKind::AlreadyExists);
+
+        let ids: std::collections::HashSet<_> = (0..100)
+            .map(|_| storage.add_transaction(transaction(account, category)).unwrap())
+            .collect();
+
+        assert_eq!(ids.len(), 100);
+    }
+
+    on_each_storage!(check);
+}
+
+
+#[test]
+fn since_queries_exclude_base() {
+    fn check<S: DataStorage>(storage: &S) {
+        let base = at(2024, 5, 1);
+        let boundary = storage.add_account(EncryptedAccount { meta_info: added_at(1), ..account() }).unwrap();
+        let after = storage.add_account(EncryptedAccount { meta_info: added_at(2), ..account() }).unwrap();
+        assert_eq!(ids(storage.accounts_added_since(base).unwrap(), |a| a.id), [after]);
+
+        storage.update_account_with_timestamp(storage.account(boundary).unwrap(), base).unwrap();
+        assert!(storage.accounts_changed_since(base).unwrap().is_empty());
+
+        storage.remove_account(boundary, base).unwrap();
+        assert!(storage.accounts_removed_since(base).unwrap().is_empty());
+    }
+
+    on_each_storage!(check);
+}
@@ -0,0 +1,57 @@
+/// Credentials used to authenticate with synchronization remote.
+#[derive(Clone, Default)]
+pub enum SyncCredentials {
+    /// Ambient credentials: SSH agent, default SSH keys
+    /// and git credential helpers
+    #[default]
+    Default,
+
+    /// Specific private SSH key
+    SshKeyFile {
+        /// Path to private key file
+        path: std::path::PathBuf,
+
+        /// Passphrase of private key if it is encrypted
+        passphrase: Option<String>,
+    },
+
+    /// Username and password (or access token) for HTTPS
+    UserPassword {
+        /// Username
+        user: String,
+
+        /// Password or access token
+        password: String,
+    },
+}
+
+
+impl SyncCredentials {
+    /// Builds an authenticator, that tries only these credentials.
+    pub(super) fn authenticator(&self) -> auth_git2::GitAuthenticator {
+        match self {
+            SyncCredentials::Default => auth_git2::GitAuthenticator::default(),
+            SyncCredentials::SshKeyFile { path, passphrase } => auth_git2::GitAuthenticator::new_empty()
+                .add_default_username()
+                .add_ssh_key_from_file(path, passphrase.clone()),
+            SyncCredentials::UserPassword { user, password } => auth_git2::GitAuthenticator::new_empty()
+                .add_plaintext_credentials("*", user, password)
+        }
+    }
+}
+
+
+impl std::fmt::Display for SyncCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        //
+        // Secrets are never shown, since description
+        // is a part of error messages
+        //
+
+        match self {
+            SyncCredentials::Default => write!(f, "default credentials"),
+            SyncCredentials::SshKeyFile { path, .. } => write!(f, "SSH key file {}", path.display()),
+            SyncCredentials::UserPassword { user, .. } => write!(f, "password of user {}", user)
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use std::io::Write;
+
+use crate::location::Location;
+use crate::error::{Result, Error};
+use crate::datetime::{Clock, Timestamp, FIRST_AFTER_JANUARY_1970};
+use super::engine::SyncEngine;
+use super::syncable::{Syncable, MergeSummary};
+use super::last_sync;
+use super::lock::{SyncLock, DEFAULT_LOCK_TIMEOUT};
+use super::progress::SyncProgress;
+use super::{REMOTE_ALREADY_EXIST, NO_REMOTE, SYNC_FOLDER_LOCKED, SINGLE_REMOTE_ONLY, DEFAULT_REMOTE_NAME};
+
+
+/// Synchronization folder.
+const SYNC_FORDER: &str = "sync";
+
+/// File that holds last synchronization time.
+const LAST_SYNC_FILE: &str = "last-sync";
+
+/// File that holds path to synchronized directory.
+const REMOTE_FILE: &str = "remote";
+
+/// File with last synchronization timestamp.
+const TIMESTAMP_FILE: &str = "timestamp";
+
+/// File with last synchronized instance timestamp.
+const LAST_INSTANCE_FILE: &str = "instance";
+
+/// File with full changelog.
+const CHANGELOG_FILE: &str = "changelog";
+
+/// File with authentication tag of synchronized files.
+const MAC_FILE: &str = "mac";
+
+/// Lock file, that exists while some instance synchronizes.
+const LOCK_FILE: &str = "lock";
+
+/// Extension of temporary files, that are renamed after writing.
+const TEMPORARY_EXTENSION: &str = "tmp";
+
+
+/// Synchronization engine, that keeps synchronized files in a plain
+/// directory. The directory is expected to be shared between machines
+/// by some external tool, e.g. Dropbox, Syncthing or a network share.
+///
+/// Files are replaced atomically, and only one instance can synchronize
+/// at a time, since a lock file is created in the directory. Lock left
+/// by a crashed instance is broken after a timeout.
+pub struct DirSyncEngine {
+    /// Path to file with synchronized directory path.
+    remote_path: std::path::PathBuf,
+
+    /// Path to last sync timestamp file.
+    last_sync_path: std::path::PathBuf,
+
+    /// Time, after which lock of synchronized directory is broken.
+    lock_timeout: std::time::Duration,
+}
+
+
+impl DirSyncEngine {
+    pub fn create<L: Location>(loc: &L, remote: Option<&str>) -> Result<Self> {
+        //
+        // Check is root location exists and create it if necessary.
+        // Sync folder should be created manually
+        //
+
+        loc.create_if_absent()?;
+        std::fs::create_dir(Self::sync_folder(loc))?;
+
+        //
+        // Create last sync file
+        // I write first nonzero timestamp after January 1970 to
+        // ensure, that all predefined items will not by
+        // synced between instances
+        //
+
+        let last_sync_path = Self::sync_last_sync_path(loc);
+        let mut file = std::fs::File::create(last_sync_path)?;
+
+        last_sync::write(&mut file, &FIRST_AFTER_JANUARY_1970)?;
+
+        let engine = Self::open(loc)?;
+        if let Some(remote) = remote {
+            engine.add_remote(remote)?;
+        }
+
+        Ok(engine)
+    }
+
+    pub fn open<L: Location>(loc: &L) -> Result<Self> {
+        Ok(DirSyncEngine {
+            remote_path: Self::sync_folder(loc).join(REMOTE_FILE),
+            last_sync_path: Self::sync_last_sync_path(loc),
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+        })
+    }
+
+    /// Sets time, after which lock of synchronized directory is
+    /// considered to be left by a crashed instance and is broken.
+    /// 
+    /// * `timeout` - lock timeout
+    pub fn with_lock_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+}
+
+
+impl SyncEngine for DirSyncEngine {
+    fn perform_sync<S: Syncable>(&self, current_instance: &S::InstanceId, syncable: &S, context: &S::Context) -> Result<S::Summary> {
+        self.perform_sync_with_progress(current_instance, syncable, context, &|_| ())
+    }
+
+    fn perform_sync_with_progress<S: Syncable>(&self, current_instance: &S::InstanceId, syncable: &S, context: &S::Context, 
+        progress: &dyn Fn(SyncProgress)) -> Result<S::Summary> 
+    {
+        //
+        // Lock is held until the end of synchronization,
+        // hence other instances cannot overwrite files
+        //
+
+        let remote = self.existing_remote()?;
+        let _lock = SyncLock::acquire(remote.join(LOCK_FILE), current_instance, 
+            self.lock_timeout, SYNC_FOLDER_LOCKED)?;
+
+        let mut timestamp_file = Self::read_syncable_file(&remote, TIMESTAMP_FILE)?;
+        let mut last_instance_file = Self::read_syncable_file(&remote, LAST_INSTANCE_FILE)?;
+        let mut changelog_file = Self::read_syncable_file(&remote, CHANGELOG_FILE)?;
+        let mut mac_file = Self::read_syncable_file(&remote, MAC_FILE)?;
+
+        progress(SyncProgress::Merging);
+
+        let summary = syncable.merge_and_export_changes(&mut timestamp_file, &mut last_instance_file,
+            &mut changelog_file, &mut mac_file, &self.last_sync_timestamp()?, context)?;
+
+        let sync_timestamp = Clock::now();
+
+        //
+        // Files are untouched if there is nothing to sync.
+        // Otherwise each of them is replaced atomically
+        //
+
+        if !summary.is_empty() {
+            progress(SyncProgress::Committing);
+
+            Self::write_syncable_file(&remote, TIMESTAMP_FILE, timestamp_file)?;
+            Self::write_syncable_file(&remote, LAST_INSTANCE_FILE, last_instance_file)?;
+            Self::write_syncable_file(&remote, CHANGELOG_FILE, changelog_file)?;
+            Self::write_syncable_file(&remote, MAC_FILE, mac_file)?;
+        }
+
+        let mut last_sync_file = std::fs::File::create(&self.last_sync_path)?;
+        last_sync::write(&mut last_sync_file, &sync_timestamp)?;
+
+        Ok(summary)
+    }
+
+    fn perform_sync_with<S: Syncable>(&self, remote: &str, current_instance: &S::InstanceId, syncable: &S, 
+        context: &S::Context) -> Result<S::Summary> 
+    {
+        Self::ensure_default_remote(remote)?;
+        self.perform_sync(current_instance, syncable, context)
+    }
+
+    fn preview_sync<S: Syncable>(&self, syncable: &S, context: &S::Context) -> Result<S::Preview> {
+        let remote = self.existing_remote()?;
+
+        let mut timestamp_file = Self::read_syncable_file(&remote, TIMESTAMP_FILE)?;
+        let mut last_instance_file = Self::read_syncable_file(&remote, LAST_INSTANCE_FILE)?;
+        let mut changelog_file = Self::read_syncable_file(&remote, CHANGELOG_FILE)?;
+        let mut mac_file = Self::read_syncable_file(&remote, MAC_FILE)?;
+
+        syncable.preview_changes(&mut timestamp_file, &mut last_instance_file,
+            &mut changelog_file, &mut mac_file, &self.last_sync_timestamp()?, context)
+    }
+
+    fn known_instances<S: Syncable>(&self, syncable: &S) -> Result<S::Instances> {
+        let remote = self.existing_remote()?;
+
+        let mut timestamp_file = Self::read_syncable_file(&remote, TIMESTAMP_FILE)?;
+        let mut last_instance_file = Self::read_syncable_file(&remote, LAST_INSTANCE_FILE)?;
+
+        syncable.read_instances(&mut timestamp_file, &mut last_instance_file)
+    }
+
+    fn last_sync(&self) -> Result<Option<Timestamp>> {
+        let last_sync = self.last_sync_timestamp()?;
+        Ok(Some(last_sync).filter(|last_sync| *last_sync != *FIRST_AFTER_JANUARY_1970))
+    }
+
+    fn last_sync_with(&self, remote: &str) -> Result<Option<Timestamp>> {
+        Self::ensure_default_remote(remote)?;
+        self.last_sync()
+    }
+
+    fn remotes(&self) -> Result<Vec<(String, String)>> {
+        //
+        // The only remote is always named by default
+        //
+
+        let remotes = self.remote_url()?
+            .map(|remote| (DEFAULT_REMOTE_NAME.to_owned(), remote))
+            .into_iter()
+            .collect();
+
+        Ok(remotes)
+    }
+
+    fn remote_url(&self) -> Result<Option<String>> {
+        match std::fs::read_to_string(&self.remote_path) {
+            Ok(remote) => Ok(Some(remote)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into())
+        }
+    }
+
+    fn add_remote(&self, remote: &str) -> Result<()> {
+        if self.remote_url()?.is_some() {
+            return Err(Error::new(REMOTE_ALREADY_EXIST));
+        }
+
+        std::fs::write(&self.remote_path, remote)
+            .map_err(Error::from)
+    }
+
+    fn remove_remote(&self) -> Result<()> {
+        self.existing_remote()?;
+
+        std::fs::remove_file(&self.remote_path)
+            .map_err(Error::from)
+    }
+
+    fn add_named_remote(&self, name: &str, remote: &str) -> Result<()> {
+        match name == DEFAULT_REMOTE_NAME {
+            true => self.add_remote(remote),
+            false => Err(Error::new(SINGLE_REMOTE_ONLY))
+        }
+    }
+
+    fn remove_named_remote(&self, name: &str) -> Result<()> {
+        Self::ensure_default_remote(name)?;
+        self.remove_remote()
+    }
+
+    fn change_remote(&self, remote: &str) -> Result<()> {
+        self.existing_remote()?;
+
+        std::fs::write(&self.remote_path, remote)
+            .map_err(Error::from)
+    }
+
+    fn reset_to_remote(&self) -> Result<()> {
+        //
+        // There is no local copy of synchronized files,
+        // hence only last synchronization time is rewound
+        //
+
+        self.existing_remote()?;
+
+        let mut last_sync_file = std::fs::File::create(&self.last_sync_path)?;
+        last_sync::write(&mut last_sync_file, &FIRST_AFTER_JANUARY_1970)
+    }
+
+    fn gc(&self, _force_push: bool) -> Result<()> {
+        //
+        // Only the latest revision of files is stored
+        //
+
+        Ok(())
+    }
+}
+
+
+impl DirSyncEngine {
+    fn ensure_default_remote(name: &str) -> Result<()> {
+        match name == DEFAULT_REMOTE_NAME {
+            true => Ok(()),
+            false => Err(Error::new_with_extra(NO_REMOTE, format!("remote: {}", name)))
+        }
+    }
+
+    fn existing_remote(&self) -> Result<std::path::PathBuf> {
+        self.remote_url()?
+            .map(std::path::PathBuf::from)
+            .ok_or(Error::new(NO_REMOTE))
+    }
+
+    fn last_sync_timestamp(&self) -> Result<Timestamp> {
+        let mut last_sync_file = std::fs::File::open(&self.last_sync_path)?;
+        last_sync::read(&mut last_sync_file)
+    }
+
+    fn read_syncable_file(remote: &std::path::Path, name: &str) -> Result<std::io::Cursor<Vec<u8>>> {
+        //
+        // Missing files mean, that nothing is synchronized yet
+        //
+
+        match std::fs::read(remote.join(name)) {
+            Ok(content) => Ok(std::io::Cursor::new(content)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(std::io::Cursor::new(Vec::new())),
+            Err(error) => Err(error.into())
+        }
+    }
+
+    fn write_syncable_file(remote: &std::path::Path, name: &str, file: std::io::Cursor<Vec<u8>>) -> Result<()> {
+        //
+        // Content is written into a temporary file first and then
+        // it replaces the original one, hence other machines never
+        // observe a partially written file. Content ends at the
+        // current position, the rest is left from the old version
+        //
+
+        let length = file.position() as usize;
+        let content = &file.get_ref()[..length];
+
+        let path = remote.join(name);
+        let temporary_path = path.with_extension(TEMPORARY_EXTENSION);
+
+        let mut file = std::fs::File::create(&temporary_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+
+        std::fs::rename(&temporary_path, &path)
+            .map_err(Error::from)
+    }
+}
+
+
+impl DirSyncEngine {
+    fn sync_folder<L: Location>(loc: &L) -> std::path::PathBuf {
+        loc.root()
+            .join(SYNC_FORDER)
+    }
+
+    fn sync_last_sync_path<L: Location>(loc: &L) -> std::path::PathBuf {
+        Self::sync_folder(loc)
+            .join(LAST_SYNC_FILE)
+    }
+}
+
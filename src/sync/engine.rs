@@ -1,5 +1,7 @@
 use crate::error::Result;
+use crate::datetime::Timestamp;
 use super::syncable::Syncable;
+use super::progress::SyncProgress;
 
 
 /// Synchronization engine.
@@ -7,10 +9,72 @@ pub trait SyncEngine {
     /// Perform synchronization.
     /// 
     /// Receives remote updates, sends local updates and applies remote ones.
+    /// Nothing is sent if there are no changes at all. Local changes are
+    /// considered synchronized only after they are sent.
     /// 
     /// * `current_instance` - name of current app instance
     /// * `syncable` - object to perform syncronization for
-    fn perform_sync<S: Syncable>(&self, current_instance: &S::InstanceId, syncable: &S, context: &S::Context) -> Result<()>;
+    fn perform_sync<S: Syncable>(&self, current_instance: &S::InstanceId, syncable: &S, context: &S::Context) -> Result<S::Summary>;
+
+    /// Perform synchronization and report its progress.
+    /// 
+    /// Works the same way as [`SyncEngine::perform_sync`], but progress
+    /// is reported to the provided callback.
+    /// 
+    /// * `current_instance` - name of current app instance
+    /// * `syncable` - object to perform syncronization for
+    /// * `progress` - callback, that receives progress
+    fn perform_sync_with_progress<S: Syncable>(&self, current_instance: &S::InstanceId, syncable: &S, context: &S::Context, 
+        progress: &dyn Fn(SyncProgress)) -> Result<S::Summary>;
+
+    /// Perform synchronization with a named remote.
+    /// 
+    /// Works the same way as [`SyncEngine::perform_sync`], but last
+    /// synchronization time is tracked for each remote separately.
+    /// 
+    /// * `remote` - name of remote
+    /// * `current_instance` - name of current app instance
+    /// * `syncable` - object to perform syncronization for
+    fn perform_sync_with<S: Syncable>(&self, remote: &str, current_instance: &S::InstanceId, syncable: &S, 
+        context: &S::Context) -> Result<S::Summary>;
+
+    /// Preview synchronization.
+    /// 
+    /// Receives remote updates and describes changes, that would be applied
+    /// by synchronization. Nothing is applied, committed or sent.
+    /// 
+    /// * `syncable` - object to preview syncronization for
+    fn preview_sync<S: Syncable>(&self, syncable: &S, context: &S::Context) -> Result<S::Preview>;
+
+    /// Return instances, that participate in synchronization.
+    /// 
+    /// Files received during the last synchronization are read,
+    /// hence no network operations are performed.
+    /// 
+    /// * `syncable` - object to read instances for
+    fn known_instances<S: Syncable>(&self, syncable: &S) -> Result<S::Instances>;
+
+    /// Checks if the engine can synchronize at all.
+    fn is_configured(&self) -> bool {
+        true
+    }
+
+    /// Return time of the last successful synchronization,
+    /// `None` if synchronization was never performed.
+    fn last_sync(&self) -> Result<Option<Timestamp>>;
+
+    /// Return time of the last successful synchronization with a named
+    /// remote, `None` if synchronization with it was never performed.
+    /// 
+    /// * `remote` - name of remote
+    fn last_sync_with(&self, remote: &str) -> Result<Option<Timestamp>>;
+
+    /// Return names and urls of all remotes.
+    fn remotes(&self) -> Result<Vec<(String, String)>>;
+
+    /// Return remote url or another remote identifier,
+    /// `None` if there is no remote associated.
+    fn remote_url(&self) -> Result<Option<String>>;
 
     /// Add a remote. Note, that there can be only one remote. Therefore,
     /// the function fails, if there's already a remote associated.
@@ -21,8 +85,41 @@ pub trait SyncEngine {
     /// Remove existing remote.
     fn remove_remote(&self) -> Result<()>;
 
+    /// Add a named remote. Fails if there is a remote with the same name.
+    /// 
+    /// * `name` - name of remote
+    /// * `remote` - url or another remote identifier
+    fn add_named_remote(&self, name: &str, remote: &str) -> Result<()>;
+
+    /// Remove existing named remote.
+    /// 
+    /// * `name` - name of remote
+    fn remove_named_remote(&self, name: &str) -> Result<()>;
+
     /// Changes existing remote.
     /// 
     /// * `remote` - url or another remote identifier
     fn change_remote(&self, remote: &str) -> Result<()>;
+
+    /// Discards local synchronization state and makes it match remote.
+    /// 
+    /// Local copy of synchronized files is replaced with remote one
+    /// and last synchronization time is rewound, hence the next
+    /// synchronization exports all local items again.
+    fn reset_to_remote(&self) -> Result<()>;
+
+    /// Trims history of synchronized files, only their latest revision
+    /// is kept. Engines without history do nothing.
+    /// 
+    /// Remote history is rewritten only if force push is allowed, other
+    /// instances should reset their state to remote after it.
+    /// 
+    /// * `force_push` - whether remote history can be rewritten
+    fn gc(&self, force_push: bool) -> Result<()>;
+
+    /// Return size of local copy of synchronized files in bytes,
+    /// `None` if engine keeps no local copy.
+    fn local_size(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
 }
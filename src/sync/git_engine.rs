@@ -1,13 +1,18 @@
+use std::io::{Seek, Write};
+
 use crate::location::Location;
 use crate::error::{Result, Error};
 use crate::datetime::{Clock, Timestamp, FIRST_AFTER_JANUARY_1970};
 use super::engine::SyncEngine;
-use super::syncable::Syncable;
-use super::{REMOTE_ALREADY_EXIST, MALFORMED_LAST_SYNC_TIMESTAMP, REMOTE_CONFLICT};
-
+use super::syncable::{Syncable, MergeSummary};
+use super::last_sync;
+use super::lock::{SyncLock, DEFAULT_LOCK_TIMEOUT};
+use super::progress::SyncProgress;
+use super::credentials::SyncCredentials;
+use super::{REMOTE_ALREADY_EXIST, REMOTE_CONFLICT, REMOTE_MOVED, AUTHENTICATION_FAILED, SYNC_IN_PROGRESS, NO_REMOTE, INVALID_REMOTE_NAME,
+    FORCE_PUSH_REQUIRED, REMOTE_NOT_MERGED};
+use super::DEFAULT_REMOTE_NAME;
 
-/// Name of git's remote for the repository.
-const REMOTE_NAME: &str = "origin";
 
 /// Name of reference to update on commit.
 const REF_NAME: &str = "HEAD";
@@ -15,6 +20,21 @@ const REF_NAME: &str = "HEAD";
 /// Name of reference to fetched head.
 const FETCH_REF_NAME: &str = "FETCH_HEAD";
 
+/// Name of reference to head before dangerous operations.
+const ORIG_REF_NAME: &str = "ORIG_HEAD";
+
+/// File with commits, that history of shallow clone starts from.
+const SHALLOW_FILE: &str = "shallow";
+
+/// Folder with git's objects.
+const OBJECTS_FOLDER: &str = "objects";
+
+/// Folder with git's pack files.
+const PACK_FOLDER: &str = "pack";
+
+/// Folder with git's reference logs.
+const LOGS_FOLDER: &str = "logs";
+
 /// Branch name.
 const BRANCH_NAME: &str = "main";
 
@@ -30,6 +50,12 @@ const SYNC_FORDER: &str = "sync";
 /// File that holds last synchronization time.
 const LAST_SYNC_FILE: &str = "last-sync";
 
+/// Lock file, that exists while some process synchronizes.
+const LOCK_FILE: &str = "lock";
+
+/// Lock file of git's index, that can be left by an interrupted checkout.
+const INDEX_LOCK_FILE: &str = "index.lock";
+
 /// Repository folder.
 const SYNC_REPO: &str = "repository";
 
@@ -42,6 +68,16 @@ const LAST_INSTANCE_FILE: &str = "instance";
 /// File with full changelog.
 const CHANGELOG_FILE: &str = "changelog";
 
+/// File with authentication tag of synchronized files.
+const MAC_FILE: &str = "mac";
+
+/// All synchronized files.
+const SYNCABLE_FILES: [&str; 4] = [TIMESTAMP_FILE, LAST_INSTANCE_FILE, CHANGELOG_FILE, MAC_FILE];
+
+/// Default number of synchronization attempts, if remote
+/// is changed by another instance during synchronization.
+const DEFAULT_SYNC_ATTEMPTS: usize = 3;
+
 
 /// Synchronization engine that uses git internally.
 pub struct GitSyncEngine {
@@ -54,14 +90,39 @@ pub struct GitSyncEngine {
     /// Path to last sync timestamp file.
     last_sync_path: std::path::PathBuf,
 
-    /// Default authenticator
+    /// Path to lock file.
+    lock_path: std::path::PathBuf,
+
+    /// Credentials used to authenticate with remote.
+    credentials: SyncCredentials,
+
+    /// Authenticator, that tries the credentials above
     /// Usually it is used with `config`
     authenticator: auth_git2::GitAuthenticator,
+
+    /// Number of synchronization attempts before giving up.
+    sync_attempts: usize,
+
+    /// Time, after which lock of repository is broken.
+    lock_timeout: std::time::Duration,
+
+    /// Callback, that receives progress of synchronization.
+    progress: Option<Box<dyn Fn(SyncProgress) + Send>>,
 }
 
 
 impl GitSyncEngine {
     pub fn create<L: Location>(loc: &L, remote: Option<&str>) -> Result<Self> {
+        Self::create_with_credentials(loc, remote, SyncCredentials::default())
+    }
+
+    /// Creates engine and clones remote repository using specific credentials.
+    /// Credentials are used for further synchronizations as well.
+    /// 
+    /// * `loc` - storage location provider
+    /// * `remote` - url of remote repository, empty repository is created if absent
+    /// * `credentials` - credentials used to authenticate with remote
+    pub fn create_with_credentials<L: Location>(loc: &L, remote: Option<&str>, credentials: SyncCredentials) -> Result<Self> {
         //
         // Check is root location exists and create it if necessary.
         // Sync folder should be created manually
@@ -77,8 +138,25 @@ impl GitSyncEngine {
         let repo_path = Self::sync_repo_path(loc);
         match remote {
             Some(remote) => {
-                auth_git2::GitAuthenticator::default()
-                    .clone_repo(remote, repo_path)?
+                let authenticator = credentials.authenticator();
+                let config = git2::Config::open_default()?;
+                let auth_failed = std::cell::Cell::new(false);
+
+                //
+                // Only the latest revision of files matters,
+                // hence history is not cloned
+                //
+
+                let mut fetch_options = git2::FetchOptions::default();
+                fetch_options.remote_callbacks(Self::credentials_callbacks(&authenticator, &config, &auth_failed));
+                fetch_options.depth(1);
+
+                let mut builder = git2::build::RepoBuilder::new();
+                builder.fetch_options(fetch_options);
+
+                builder
+                    .clone(remote, &repo_path)
+                    .map_err(|error| Self::remote_error(error, &auth_failed, &credentials))?
             }
             None => {
                 git2::Repository::init(repo_path)?
@@ -95,13 +173,14 @@ impl GitSyncEngine {
         let last_sync_path = Self::sync_last_sync_path(loc);
         let mut file = std::fs::File::create(last_sync_path)?;
 
-        Self::write_last_sync(&mut file, &FIRST_AFTER_JANUARY_1970)?;
+        last_sync::write(&mut file, &FIRST_AFTER_JANUARY_1970)?;
 
         //
         // Now I can just open repository and build engine
         //
 
         Self::open(loc)
+            .map(|engine| engine.with_credentials(credentials))
     }
 
     pub fn open<L: Location>(loc: &L) -> Result<Self> {
@@ -110,40 +189,454 @@ impl GitSyncEngine {
 
         Ok(GitSyncEngine {
             repo: git2::Repository::open(&repo_path)?,
-            repo_path: repo_path,
-            last_sync_path: last_sync_path,
+            repo_path,
+            last_sync_path,
+            lock_path: Self::sync_folder(loc).join(LOCK_FILE),
+            credentials: SyncCredentials::default(),
             authenticator: auth_git2::GitAuthenticator::default(),
+            sync_attempts: DEFAULT_SYNC_ATTEMPTS,
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+            progress: None,
         })
     }
+
+    /// Sets number of synchronization attempts. Synchronization is
+    /// repeated, if remote is changed by another instance during it.
+    /// 
+    /// * `attempts` - number of attempts, at least one attempt is made
+    pub fn with_sync_attempts(mut self, attempts: usize) -> Self {
+        self.sync_attempts = attempts.max(1);
+        self
+    }
+
+    /// Sets time, after which lock of repository is considered
+    /// to be left by a crashed process and is broken.
+    /// 
+    /// * `timeout` - lock timeout
+    pub fn with_lock_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Sets credentials used to authenticate with remote.
+    /// Only these credentials are tried.
+    /// 
+    /// * `credentials` - credentials to use
+    pub fn with_credentials(mut self, credentials: SyncCredentials) -> Self {
+        self.authenticator = credentials.authenticator();
+        self.credentials = credentials;
+        self
+    }
+
+    /// Sets callback, that receives progress of synchronization,
+    /// e.g. phases of synchronization and transferred objects.
+    /// 
+    /// * `progress` - progress callback
+    pub fn with_progress<P>(mut self, progress: P) -> Self 
+    where
+        P: Fn(SyncProgress) + Send + 'static
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
 }
 
 
 impl SyncEngine for GitSyncEngine {
-    fn perform_sync<S: Syncable>(&self, current_instance: &S::InstanceId, syncable: &S, context: &S::Context) -> Result<()> {
+    fn perform_sync<S: Syncable>(&self, current_instance: &S::InstanceId, syncable: &S, context: &S::Context) -> Result<S::Summary> {
+        self.perform_sync_with_progress(current_instance, syncable, context, &self.configured_progress())
+    }
+
+    fn perform_sync_with_progress<S: Syncable>(&self, current_instance: &S::InstanceId, syncable: &S, context: &S::Context, 
+        progress: &dyn Fn(SyncProgress)) -> Result<S::Summary> 
+    {
+        self.sync_with(DEFAULT_REMOTE_NAME, current_instance, syncable, context, progress)
+    }
+
+    fn perform_sync_with<S: Syncable>(&self, remote: &str, current_instance: &S::InstanceId, syncable: &S, 
+        context: &S::Context) -> Result<S::Summary> 
+    {
+        //
+        // Only the default remote can be absent, then
+        // repository is moved between machines manually
+        //
+
+        if remote != DEFAULT_REMOTE_NAME && self.named_remote_url(remote)?.is_none() {
+            return Err(Error::new_with_extra(NO_REMOTE, format!("remote: {}", remote)));
+        }
+
+        self.sync_with(remote, current_instance, syncable, context, &self.configured_progress())
+    }
+
+    fn preview_sync<S: Syncable>(&self, syncable: &S, context: &S::Context) -> Result<S::Preview> {
+        //
+        // Remote changes are received as usual, but files are only read.
+        // If histories diverged, remote versions of files are read from
+        // remote commit. Missing files mean, that nothing is pushed yet.
+        // Pull updates working tree, hence it is locked as well
+        //
+
+        let _lock = SyncLock::acquire(self.lock_path.clone(), &std::process::id(), 
+            self.lock_timeout, SYNC_IN_PROGRESS)?;
+
+        let progress = self.configured_progress();
+        let remote_head = match self.remote_url()?.is_some() {
+            true => {
+                progress(SyncProgress::Fetching);
+                self.pull_remote(DEFAULT_REMOTE_NAME, &progress)?
+            },
+            false => None
+        };
+
+        let mut timestamp_file = self.read_syncable_file(TIMESTAMP_FILE, remote_head)?;
+        let mut last_instance_file = self.read_syncable_file(LAST_INSTANCE_FILE, remote_head)?;
+        let mut changelog_file = self.read_syncable_file(CHANGELOG_FILE, remote_head)?;
+        let mut mac_file = self.read_syncable_file(MAC_FILE, remote_head)?;
+
+        syncable.preview_changes(&mut timestamp_file, &mut last_instance_file, 
+            &mut changelog_file, &mut mac_file, &self.last_sync_timestamp()?, context)
+    }
+
+    fn known_instances<S: Syncable>(&self, syncable: &S) -> Result<S::Instances> {
+        let mut timestamp_file = self.read_syncable_file(TIMESTAMP_FILE, None)?;
+        let mut last_instance_file = self.read_syncable_file(LAST_INSTANCE_FILE, None)?;
+
+        syncable.read_instances(&mut timestamp_file, &mut last_instance_file)
+    }
+
+    fn last_sync(&self) -> Result<Option<Timestamp>> {
+        //
+        // Initial value is written on creation and is never
+        // written after synchronization
+        //
+
+        let last_sync = self.last_sync_timestamp()?;
+        Ok(Some(last_sync).filter(|last_sync| *last_sync != *FIRST_AFTER_JANUARY_1970))
+    }
+
+    fn last_sync_with(&self, remote: &str) -> Result<Option<Timestamp>> {
+        //
+        // Missing file means, that remote is not synchronized yet
+        //
+
+        let mut last_sync_file = match std::fs::File::open(self.last_sync_path_of(remote)) {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into())
+        };
+
+        let last_sync = last_sync::read(&mut last_sync_file)?;
+        Ok(Some(last_sync).filter(|last_sync| *last_sync != *FIRST_AFTER_JANUARY_1970))
+    }
+
+    fn remotes(&self) -> Result<Vec<(String, String)>> {
+        let mut remotes = Vec::new();
+        for name in self.repo.remotes()?.iter().flatten() {
+            if let Some(url) = self.named_remote_url(name)? {
+                remotes.push((name.to_owned(), url));
+            }
+        }
+
+        Ok(remotes)
+    }
+
+    fn remote_url(&self) -> Result<Option<String>> {
+        self.named_remote_url(DEFAULT_REMOTE_NAME)
+    }
+
+    fn add_remote(&self, remote: &str) -> Result<()> {
+        self.add_named_remote(DEFAULT_REMOTE_NAME, remote)
+    }
+
+    fn remove_remote(&self) -> Result<()> {
+        self.remove_named_remote(DEFAULT_REMOTE_NAME)
+    }
+
+    fn add_named_remote(&self, name: &str, remote: &str) -> Result<()> {
+        //
+        // Name is a part of last sync file name,
+        // hence path separators are not allowed
+        //
+
+        if !git2::Remote::is_valid_name(name) || name.contains(std::path::is_separator) {
+            return Err(Error::new_with_extra(INVALID_REMOTE_NAME, format!("remote: {}", name)));
+        }
+
+        if self.repo.find_remote(name).is_ok() {
+            return Err(Error::new(REMOTE_ALREADY_EXIST));
+        }
+
+        self.repo
+            .remote(name, remote)?;
+
+        //
+        // Default remote shares last sync file with the instance,
+        // the other ones start from the very beginning
+        //
+
+        if name != DEFAULT_REMOTE_NAME {
+            let mut file = std::fs::File::create(self.last_sync_path_of(name))?;
+            last_sync::write(&mut file, &FIRST_AFTER_JANUARY_1970)?;
+        }
+
+        Ok(())
+    }
+
+    fn remove_named_remote(&self, name: &str) -> Result<()> {
+        self.repo
+            .remote_delete(name)?;
+
+        if name != DEFAULT_REMOTE_NAME {
+            match std::fs::remove_file(self.last_sync_path_of(name)) {
+                Ok(_) => (),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+                Err(error) => return Err(error.into())
+            }
+        }
+
+        Ok(())
+    }
+
+    fn change_remote(&self, remote: &str) -> Result<()> {
+        //
+        // Url is replaced in place, hence existing remote
+        // is never lost if the new url is rejected
+        //
+
+        self.repo
+            .find_remote(DEFAULT_REMOTE_NAME)?;
+
+        self.repo
+            .remote_set_url(DEFAULT_REMOTE_NAME, remote)?;
+
+        Ok(())
+    }
+
+    fn reset_to_remote(&self) -> Result<()> {
+        //
+        // Working tree is rewritten, hence it is locked as during
+        // synchronization. Since no other process uses repository
+        // now, index lock left by an interrupted checkout is stale
+        //
+
+        let _lock = SyncLock::acquire(self.lock_path.clone(), &std::process::id(), 
+            self.lock_timeout, SYNC_IN_PROGRESS)?;
+
+        if self.remote_url()?.is_none() {
+            return Err(Error::new(NO_REMOTE));
+        }
+
+        match std::fs::remove_file(self.repo.path().join(INDEX_LOCK_FILE)) {
+            Ok(_) => (),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+            Err(error) => return Err(error.into())
+        }
+
+        self.repo.cleanup_state()?;
+
+        let progress = self.configured_progress();
+        progress(SyncProgress::Fetching);
+
+        let ref_name = format!("refs/heads/{}", BRANCH_NAME);
+        match self.fetch_remote(DEFAULT_REMOTE_NAME, &progress)? {
+            Some(fetch_commit) => {
+                //
+                // Local commits are dropped, and both index and
+                // working tree are overwritten with remote state
+                //
+
+                let reflog_msg = format!("Reset: Setting {} to {}", 
+                    ref_name, fetch_commit.id());
+
+                self.repo.reference(&ref_name, fetch_commit.id(), true, &reflog_msg)?;
+                self.repo.set_head(&ref_name)?;
+
+                let commit = self.repo.find_commit(fetch_commit.id())?;
+                self.repo.reset(commit.as_object(), git2::ResetType::Hard, None)?;
+            },
+            None => {
+                //
+                // Nothing is pushed to remote yet, hence the whole
+                // local history is dropped and branch becomes unborn
+                //
+
+                if let Ok(mut branch_ref) = self.repo.find_reference(&ref_name) {
+                    branch_ref.delete()?;
+                }
+
+                self.repo.set_head(&ref_name)?;
+
+                let mut index = self.repo.index()?;
+                index.clear()?;
+                index.write()?;
+
+                for file in SYNCABLE_FILES {
+                    match std::fs::remove_file(self.syncable_file_path(file)) {
+                        Ok(_) => (),
+                        Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+                        Err(error) => return Err(error.into())
+                    }
+                }
+            }
+        }
+
+        let mut last_sync_file = std::fs::File::create(&self.last_sync_path)?;
+        last_sync::write(&mut last_sync_file, &FIRST_AFTER_JANUARY_1970)
+    }
+
+    fn gc(&self, force_push: bool) -> Result<()> {
+        let _lock = SyncLock::acquire(self.lock_path.clone(), &std::process::id(), 
+            self.lock_timeout, SYNC_IN_PROGRESS)?;
+
+        let has_remote = self.remote_url()?.is_some();
+        if has_remote && !force_push {
+            return Err(Error::new(FORCE_PUSH_REQUIRED));
+        }
+
+        let head = match self.repo.refname_to_id(REF_NAME) {
+            Ok(head) => self.repo.find_commit(head)?,
+            Err(error) if error.code() == git2::ErrorCode::UnbornBranch => return Ok(()),
+            Err(error) => return Err(error.into())
+        };
+
+        //
+        // Remote history is replaced, hence changes, that are not
+        // received yet, would be lost. Another instance still can
+        // push something between the check and push, then its
+        // changes are exported again after it resets to remote
+        //
+
+        let progress = self.configured_progress();
+        if has_remote {
+            progress(SyncProgress::Fetching);
+
+            if let Some(fetch_commit) = self.fetch_remote(DEFAULT_REMOTE_NAME, &progress)? {
+                let is_merged = fetch_commit.id() == head.id() || 
+                    self.repo.graph_descendant_of(head.id(), fetch_commit.id())?;
+
+                if !is_merged {
+                    return Err(Error::new(REMOTE_NOT_MERGED));
+                }
+            }
+        }
+
+        //
+        // The latest revision becomes the only commit without parents
+        //
+
+        progress(SyncProgress::Committing);
+
+        let signature = self.signature()?;
+        let squashed = self.repo.commit(None, &signature, &signature, 
+            &format!("Trimmed history at {}", head.id()), &head.tree()?, &[])?;
+
+        let squashed = self.repo.find_commit(squashed)?;
+        let branch_ref = self.update_branch_pointer(&squashed)?;
+
+        if has_remote {
+            progress(SyncProgress::Pushing);
+
+            let pushed = self.push_remote(DEFAULT_REMOTE_NAME, &format!("+{}", branch_ref), &progress);
+            if !matches!(pushed, Ok(true)) {
+                //
+                // Local history is restored, hence it still matches remote
+                //
+
+                self.update_branch_pointer(&head)?;
+                pushed?;
+
+                return Err(Error::new(REMOTE_MOVED));
+            }
+        }
+
+        self.prune_history(&branch_ref, squashed.id())
+    }
+
+    fn local_size(&self) -> Result<Option<u64>> {
+        Self::directory_size(&self.repo_path)
+            .map(Some)
+    }
+}
+
+
+impl GitSyncEngine {
+    fn sync_with<S: Syncable>(&self, remote: &str, current_instance: &S::InstanceId, syncable: &S, context: &S::Context, 
+        progress: &dyn Fn(SyncProgress)) -> Result<S::Summary> 
+    {
+        //
+        // Working tree is shared by all processes of the instance, hence
+        // lock is held until changes are committed and pushed. Another
+        // instance can push its changes between my pull and push,
+        // hence synchronization is repeated on top of the new remote state
+        //
+
+        let _lock = SyncLock::acquire(self.lock_path.clone(), &std::process::id(), 
+            self.lock_timeout, SYNC_IN_PROGRESS)?;
+
+        for _ in 0..self.sync_attempts {
+            if let Some(summary) = self.try_sync(remote, current_instance, syncable, context, progress)? {
+                return Ok(summary);
+            }
+        }
+
+        Err(Error::new_with_extra(REMOTE_MOVED, format!("attempts: {}", self.sync_attempts)))
+    }
+
+    fn try_sync<S: Syncable>(&self, remote: &str, current_instance: &S::InstanceId, syncable: &S, context: &S::Context, 
+        progress: &dyn Fn(SyncProgress)) -> Result<Option<S::Summary>> 
+    {
         //
-        // Get all changes from remote and open raw files
+        // Get all changes from remote. If local and remote histories
+        // diverged, remote versions of files are taken: changelog is
+        // cumulative and local changes are not marked as synchronized
+        // until they are pushed, hence they are exported again.
+        // Repository without remote is moved between machines manually,
+        // hence nothing is pulled and changes are only committed.
+        // All remotes share the same branch, but each of them has
+        // its own last sync time
         //
 
-        self.pull_remote()?;
+        let has_remote = self.named_remote_url(remote)?.is_some();
+        let remote_head = match has_remote {
+            true => {
+                progress(SyncProgress::Fetching);
+                trace_phase!("fetch", self.pull_remote(remote, progress))?
+            },
+            false => None
+        };
+
+        if let Some(remote_head) = remote_head {
+            self.checkout_syncable_files(remote_head)?;
+        }
 
         let mut timestamp_file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(self.syncable_file_path(TIMESTAMP_FILE))?;
 
         let mut last_instance_file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(self.syncable_file_path(LAST_INSTANCE_FILE))?;
 
         let mut changelog_file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(self.syncable_file_path(CHANGELOG_FILE))?;
 
+        let mut mac_file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.syncable_file_path(MAC_FILE))?;
+
         //
         // Perform actual synchronization (read last sync timestamp just before and
         // write right after the process)
@@ -152,69 +645,142 @@ impl SyncEngine for GitSyncEngine {
         let mut last_sync_file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
-            .open(&self.last_sync_path)?;
+            .open(self.last_sync_path_of(remote))?;
 
-        syncable.merge_and_export_changes(&mut timestamp_file, &mut last_instance_file, 
-            &mut changelog_file, &Self::read_last_sync(&mut last_sync_file)?, context)?;
+        progress(SyncProgress::Merging);
 
-        Self::prepare_for_overwrite(&mut last_sync_file)?;
-        Self::write_last_sync(&mut last_sync_file, &Clock::now())?;
+        let last_sync = last_sync::read(&mut last_sync_file)?;
+        let mut summary = trace_phase!("merge", syncable.merge_and_export_changes(&mut timestamp_file, 
+            &mut last_instance_file, &mut changelog_file, &mut mac_file, &last_sync, context))?;
+
+        if !has_remote {
+            summary.mark_local_only();
+        }
+
+        if !summary.is_empty() {
+            Self::truncate_at_position(&mut timestamp_file)?;
+            Self::truncate_at_position(&mut last_instance_file)?;
+            Self::truncate_at_position(&mut changelog_file)?;
+            Self::truncate_at_position(&mut mac_file)?;
+        }
+
+        let sync_timestamp = Clock::now();
 
         //
-        // Now commit new versions of files and push to remote
+        // Files are untouched if there is nothing to sync,
+        // hence I don't create an empty commit. But merge
+        // of diverged histories is committed anyway
         //
 
-        let branch_ref = self.commit_files([TIMESTAMP_FILE, LAST_INSTANCE_FILE, CHANGELOG_FILE].iter(), 
-            &format!("Updates from {}", current_instance))?;
+        if !summary.is_empty() || remote_head.is_some() {
+            //
+            // Now commit new versions of files and push to remote
+            // Rejected push means, that remote is changed again
+            //
+
+            progress(SyncProgress::Committing);
 
-        self.push_remote(&branch_ref)
-    }
+            let message = match syncable.instance_name() {
+                Some(name) => format!("Updates from {} ({})", name, current_instance),
+                None => format!("Updates from {}", current_instance)
+            };
 
-    fn add_remote(&self, remote: &str) -> Result<()> {
-        if let Ok(_) = self.repo.find_remote(REMOTE_NAME) {
-            return Err(Error::from_message(REMOTE_ALREADY_EXIST));
+            let branch_ref = trace_phase!("commit", self.commit_files(SYNCABLE_FILES.iter(), &message, remote_head))?;
+
+            if has_remote {
+                progress(SyncProgress::Pushing);
+
+                if !trace_phase!("push", self.push_remote(remote, &branch_ref, progress))? {
+                    return Ok(None);
+                }
+            }
         }
 
-        self.repo
-            .remote(REMOTE_NAME, remote)?;
+        Self::prepare_for_overwrite(&mut last_sync_file)?;
+        last_sync::write(&mut last_sync_file, &sync_timestamp)?;
 
-        Ok(())
+        Ok(Some(summary))
     }
 
-    fn remove_remote(&self) -> Result<()> {
-        self.repo
-            .remote_delete(REMOTE_NAME)?;
+    fn named_remote_url(&self, name: &str) -> Result<Option<String>> {
+        match self.repo.find_remote(name) {
+            Ok(remote) => Ok(remote.url().map(str::to_owned)),
+            Err(error) if error.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(error) => Err(error.into())
+        }
+    }
 
-        Ok(())
+    fn last_sync_path_of(&self, remote: &str) -> std::path::PathBuf {
+        match remote == DEFAULT_REMOTE_NAME {
+            true => self.last_sync_path.clone(),
+            false => self.last_sync_path.with_file_name(format!("{}.{}", LAST_SYNC_FILE, remote))
+        }
     }
 
-    fn change_remote(&self, remote: &str) -> Result<()> {
-        self.remove_remote()?;
-        self.add_remote(remote)
+    fn last_sync_timestamp(&self) -> Result<Timestamp> {
+        let mut last_sync_file = std::fs::File::open(&self.last_sync_path)?;
+        last_sync::read(&mut last_sync_file)
     }
-}
 
+    fn read_syncable_file(&self, name: &str, commit: Option<git2::Oid>) -> Result<std::io::Cursor<Vec<u8>>> {
+        let content = match commit {
+            Some(commit) => {
+                let tree = self.repo
+                    .find_commit(commit)?
+                    .tree()?;
 
-impl GitSyncEngine {
-    fn pull_remote(&self) -> Result<()> {
+                let content = match tree.get_name(name) {
+                    Some(entry) => entry.to_object(&self.repo)?.peel_to_blob()?.content().to_vec(),
+                    None => Vec::new()
+                };
+
+                content
+            },
+            None => match std::fs::read(self.syncable_file_path(name)) {
+                Ok(content) => content,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(error) => return Err(error.into())
+            }
+        };
+
+        Ok(std::io::Cursor::new(content))
+    }
+
+    fn checkout_syncable_files(&self, commit: git2::Oid) -> Result<()> {
         //
-        // Fetch remote changes
+        // HEAD is left untouched, remote commit becomes
+        // the second parent of the merge commit
         //
 
-        let config = self.repo.config()?;
-        let mut fetch_options = git2::FetchOptions::default();
-        fetch_options.remote_callbacks(self.remote_callbacks(&config));
+        let commit = self.repo
+            .find_commit(commit)?;
 
-        self.repo.find_remote(REMOTE_NAME)
-            .and_then(|mut remote| remote.fetch(&[BRANCH_NAME], Some(&mut fetch_options), None))?;
+        let mut checkout = git2::build::CheckoutBuilder::default();
+        checkout.force();
 
-        let fetch_head = match self.repo.find_reference(FETCH_REF_NAME) {
-            Ok(r) => r,
-            _ => return Ok(())  // Pulling an empty repository
-        };
+        for file in SYNCABLE_FILES {
+            checkout.path(file);
+        }
+
+        self.repo
+            .checkout_tree(commit.as_object(), Some(&mut checkout))
+            .map_err(Error::from)
+    }
+
+    fn pull_remote(&self, remote: &str, progress: &dyn Fn(SyncProgress)) -> Result<Option<git2::Oid>> {
+        //
+        // Returns remote head if local and remote histories diverged,
+        // otherwise local branch is fast-forwarded to remote one
+        //
 
-        let fetch_commit = self.repo
-            .reference_to_annotated_commit(&fetch_head)?;
+        //
+        // Fetch remote changes
+        //
+
+        let fetch_commit = match self.fetch_remote(remote, progress)? {
+            Some(fetch_commit) => fetch_commit,
+            None => return Ok(None)  // Pulling an empty repository
+        };
 
         //
         // Perform merge analysis
@@ -224,16 +790,20 @@ impl GitSyncEngine {
             .merge_analysis(&[&fetch_commit])?;
 
         if merge_analysis.is_up_to_date() {
-            return Ok(());
+            return Ok(None);
         }
 
         if !merge_analysis.is_fast_forward() {
             //
-            // Fast-forward is only possible option. If something else
+            // Both local and remote histories have new commits, hence
+            // merge commit is created by caller. If something else
             // is occurred, it is considered to be an error.
             //
 
-            return Err(Error::from_message(REMOTE_CONFLICT));
+            return match merge_analysis.is_normal() {
+                true => Ok(Some(fetch_commit.id())),
+                false => Err(Error::new(REMOTE_CONFLICT))
+            };
         }
 
         //
@@ -280,20 +850,81 @@ impl GitSyncEngine {
             }
         }
 
-        Ok(())
+        Ok(None)
     }
 
-    fn push_remote(&self, branch_ref: &str) -> Result<()> {
+    fn fetch_remote(&self, remote: &str, progress: &dyn Fn(SyncProgress)) -> Result<Option<git2::AnnotatedCommit<'_>>> {
+        //
+        // Returns fetched remote head, `None` if remote is empty
+        //
+
+        let auth_failed = std::cell::Cell::new(false);
+
         let config = self.repo.config()?;
-        let mut push_options = git2::PushOptions::default();
-        push_options.remote_callbacks(self.remote_callbacks(&config));
+        let mut callbacks = self.remote_callbacks(&config, &auth_failed);
+        callbacks.transfer_progress(|stats| {
+            progress(SyncProgress::Received { 
+                objects: stats.received_objects(), 
+                total: stats.total_objects(), 
+                bytes: stats.received_bytes() 
+            });
+
+            true
+        });
 
-        self.repo.find_remote(REMOTE_NAME)
-            .and_then(|mut remote| remote.push(&[branch_ref], Some(&mut push_options)))
+        let mut fetch_options = git2::FetchOptions::default();
+        fetch_options.remote_callbacks(callbacks);
+
+        self.repo.find_remote(remote)
+            .and_then(|mut remote| remote.fetch(&[BRANCH_NAME], Some(&mut fetch_options), None))
+            .map_err(|error| Self::remote_error(error, &auth_failed, &self.credentials))?;
+
+        let fetch_head = match self.repo.find_reference(FETCH_REF_NAME) {
+            Ok(r) => r,
+            _ => return Ok(None)
+        };
+
+        self.repo
+            .reference_to_annotated_commit(&fetch_head)
+            .map(Some)
             .map_err(Error::from)
     }
 
-    fn commit_files<T, I>(&self, pathspecs: I, message: &str) -> Result<String> 
+    fn push_remote(&self, remote: &str, branch_ref: &str, progress: &dyn Fn(SyncProgress)) -> Result<bool> {
+        //
+        // Returns false if push is rejected, because remote branch
+        // cannot be fast-forwarded. Depending on transport it is
+        // reported either as an error or in a callback
+        //
+
+        let rejected = std::cell::Cell::new(false);
+        let auth_failed = std::cell::Cell::new(false);
+
+        let config = self.repo.config()?;
+        let mut callbacks = self.remote_callbacks(&config, &auth_failed);
+        callbacks.push_update_reference(|_, status| {
+            rejected.set(rejected.get() || status.is_some());
+            Ok(())
+        });
+
+        callbacks.push_transfer_progress(|objects, total, bytes| {
+            progress(SyncProgress::Sent { objects, total, bytes });
+        });
+
+        let mut push_options = git2::PushOptions::default();
+        push_options.remote_callbacks(callbacks);
+
+        let pushed = self.repo.find_remote(remote)
+            .and_then(|mut remote| remote.push(&[branch_ref], Some(&mut push_options)));
+
+        match pushed {
+            Ok(_) => Ok(!rejected.get()),
+            Err(error) if error.code() == git2::ErrorCode::NotFastForward => Ok(false),
+            Err(error) => Err(Self::remote_error(error, &auth_failed, &self.credentials))
+        }
+    }
+
+    fn commit_files<T, I>(&self, pathspecs: I, message: &str, merge_parent: Option<git2::Oid>) -> Result<String> 
     where
         T: git2::IntoCString,
         I: Iterator<Item = T>
@@ -317,12 +948,7 @@ impl GitSyncEngine {
         // Create commit changes and author
         //
 
-        let mut config = self.repo.config()?;
-        let config = config.snapshot()?;
-
-        let name = config.get_str(CFG_NAME)?;
-        let email = config.get_str(CFG_EMAIL)?;
-        let signature = git2::Signature::now(name, email)?;
+        let signature = self.signature()?;
 
         //
         // Now let's find out parent commit and perform commit
@@ -333,13 +959,21 @@ impl GitSyncEngine {
             .and_then(|oid| self.repo.find_commit(oid))
             .ok();
 
+        let merge_parent = merge_parent
+            .map(|oid| self.repo.find_commit(oid))
+            .transpose()?;
+
         let mut parents = Vec::new();
         if let Some(head) = head.as_ref() {
             parents.push(head);
         }
 
+        if let Some(merge_parent) = merge_parent.as_ref() {
+            parents.push(merge_parent);
+        }
+
         let commit = self.repo.commit(Some(REF_NAME), &signature, 
-            &signature, &message, &tree, &parents)?;
+            &signature, message, &tree, &parents)?;
 
         //
         // Update branch pointer
@@ -349,53 +983,191 @@ impl GitSyncEngine {
         self.update_branch_pointer(&commit)
     }
 
+    fn signature(&self) -> Result<git2::Signature<'static>> {
+        let mut config = self.repo.config()?;
+        let config = config.snapshot()?;
+
+        let name = config.get_str(CFG_NAME)?;
+        let email = config.get_str(CFG_EMAIL)?;
+
+        git2::Signature::now(name, email)
+            .map_err(Error::from)
+    }
+
+    fn prune_history(&self, branch_ref: &str, head: git2::Oid) -> Result<()> {
+        //
+        // Everything, that refers to the old history, is removed:
+        // other references, reference logs and shallow boundary
+        //
+
+        for reference in self.repo.references()? {
+            let mut reference = reference?;
+            if reference.name() != Some(branch_ref) {
+                reference.delete()?;
+            }
+        }
+
+        let git_path = self.repo.path();
+        for file in [FETCH_REF_NAME, ORIG_REF_NAME, SHALLOW_FILE] {
+            Self::remove_if_exists(&git_path.join(file))?;
+        }
+
+        match std::fs::remove_dir_all(git_path.join(LOGS_FOLDER)) {
+            Ok(_) => (),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => (),
+            Err(error) => return Err(error.into())
+        }
+
+        //
+        // libgit2 cannot collect garbage, hence objects of the new
+        // history are written into a new pack, and then all loose
+        // objects and old packs are removed
+        //
+
+        let objects_path = git_path.join(OBJECTS_FOLDER);
+        let pack_path = objects_path.join(PACK_FOLDER);
+
+        let old_packs = Self::directory_entries(&pack_path)?;
+
+        let mut builder = self.repo.packbuilder()?;
+        builder.insert_commit(head)?;
+
+        let mut pack = git2::Buf::new();
+        builder.write_buf(&mut pack)?;
+
+        let odb = self.repo.odb()?;
+        let mut writer = odb.packwriter()?;
+        writer.write_all(&pack)?;
+        writer.commit()?;
+
+        //
+        // Pack with the same name has the same content, 
+        // hence old packs are kept in this case
+        //
+
+        let new_packs = Self::directory_entries(&pack_path)?;
+        if new_packs.iter().any(|pack| !old_packs.contains(pack)) {
+            for pack in old_packs {
+                std::fs::remove_file(pack)?;
+            }
+        }
+
+        for entry in Self::directory_entries(&objects_path)? {
+            let is_loose = entry
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit()));
+
+            if is_loose {
+                std::fs::remove_dir_all(entry)?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn update_branch_pointer(&self, commit: &git2::Commit<'_>) -> Result<String> {
-        let branch = match self.repo.find_branch(BRANCH_NAME, git2::BranchType::Local) {
-            Ok(branch) => branch,
-            _ => self.repo.branch(BRANCH_NAME, commit, false)?
-        };
-        
-        let branch_ref = branch
-            .into_reference()
-            .name()
-            .expect("Branch MUST have name")
-            .to_owned();
+        //
+        // Commit is made on HEAD, that can point to another branch,
+        // e.g. if an empty repository was cloned. Branch is pushed
+        // to remotes, hence it is moved to the commit and HEAD is
+        // switched to it
+        //
+
+        let branch_ref = format!("refs/heads/{}", BRANCH_NAME);
+        let reflog_msg = format!("Setting {} to {}", branch_ref, commit.id());
+
+        self.repo.reference(&branch_ref, commit.id(), true, &reflog_msg)?;
+        self.repo.set_head(&branch_ref)?;
 
         Ok(branch_ref)
     }
 
-    fn remote_callbacks<'a>(&'a self, config: &'a git2::Config) -> git2::RemoteCallbacks {
+    fn configured_progress(&self) -> impl Fn(SyncProgress) + '_ {
+        move |event| {
+            if let Some(progress) = &self.progress {
+                progress(event);
+            }
+        }
+    }
+
+    fn remote_callbacks<'a>(&'a self, config: &'a git2::Config, auth_failed: &'a std::cell::Cell<bool>) -> git2::RemoteCallbacks<'a> {
+        Self::credentials_callbacks(&self.authenticator, config, auth_failed)
+    }
+
+    fn credentials_callbacks<'a>(authenticator: &'a auth_git2::GitAuthenticator, config: &'a git2::Config, 
+        auth_failed: &'a std::cell::Cell<bool>) -> git2::RemoteCallbacks<'a> 
+    {
+        //
+        // Authenticator fails only when all ways to obtain
+        // credentials are exhausted, I remember it to report
+        // an authentication failure instead of a generic one.
+        // Transport can also give up earlier, but credentials
+        // are requested again only if previous ones are rejected
+        //
+
+        let mut credentials = authenticator.credentials(config);
+        let mut requested = false;
+
         let mut callbacks = git2::RemoteCallbacks::new();
+        callbacks.credentials(move |url, username, allowed| {
+            if allowed != git2::CredentialType::USERNAME {
+                auth_failed.set(auth_failed.get() || requested);
+                requested = true;
+            }
 
-        callbacks.credentials(
-            self.authenticator
-                .credentials(config)
-        );
+            credentials(url, username, allowed)
+                .inspect_err(|_| auth_failed.set(true))
+        });
 
         callbacks
     }
+
+    fn remote_error(error: git2::Error, auth_failed: &std::cell::Cell<bool>, credentials: &SyncCredentials) -> Error {
+        match auth_failed.get() || error.code() == git2::ErrorCode::Auth {
+            true => Error::new_with_extra(AUTHENTICATION_FAILED, format!("{}, tried: {}", error.message(), credentials)),
+            false => Error::from(error)
+        }
+    }
 }
 
 
 impl GitSyncEngine {
-    fn read_last_sync<R: std::io::Read>(last_sync: &mut R) -> Result<Timestamp> {
-        let mut buffer = [0; std::mem::size_of::<i64>()];
-        let seconds = match last_sync.read_exact(&mut buffer) {
-            Ok(_) => i64::from_le_bytes(buffer),
-            _ => 0i64
-        };
+    fn remove_if_exists(path: &std::path::Path) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Ok(_) => Ok(()),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(error.into())
+        }
+    }
 
-        Timestamp::from_timestamp(seconds, 0)
-            .ok_or(Error::from_message(MALFORMED_LAST_SYNC_TIMESTAMP))
+    fn directory_entries(path: &std::path::Path) -> Result<Vec<std::path::PathBuf>> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            entries.push(entry?.path());
+        }
+
+        Ok(entries)
     }
 
-    fn write_last_sync<W: std::io::Write>(last_sync: &mut W, timestamp: &Timestamp) -> Result<()> {
-        let timestamp = timestamp
-            .timestamp()
-            .to_le_bytes();
+    fn directory_size(path: &std::path::Path) -> Result<u64> {
+        let mut size = 0;
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+
+            size += match metadata.is_dir() {
+                true => Self::directory_size(&entry.path())?,
+                false => metadata.len()
+            };
+        }
+
+        Ok(size)
+    }
 
-        last_sync
-            .write_all(&timestamp)
+    fn truncate_at_position(file: &mut std::fs::File) -> Result<()> {
+        let position = file.stream_position()?;
+        file.set_len(position)
             .map_err(Error::from)
     }
 
@@ -427,3 +1199,12 @@ impl GitSyncEngine {
             .join(file)
     }
 }
+
+
+#[cfg(test)]
+impl GitSyncEngine {
+    /// Underlying repository, e.g. to configure committer in tests.
+    pub(crate) fn repository(&self) -> &git2::Repository {
+        &self.repo
+    }
+}
@@ -0,0 +1,34 @@
+use crate::error::{Result, Error};
+use crate::datetime::Timestamp;
+use super::MALFORMED_LAST_SYNC_TIMESTAMP;
+
+
+/// Reads last synchronization timestamp. Empty input is
+/// considered to be the beginning of epoch.
+/// 
+/// * `last_sync` - source of timestamp
+pub(super) fn read<R: std::io::Read>(last_sync: &mut R) -> Result<Timestamp> {
+    let mut buffer = [0; std::mem::size_of::<i64>()];
+    let seconds = match last_sync.read_exact(&mut buffer) {
+        Ok(_) => i64::from_le_bytes(buffer),
+        _ => 0i64
+    };
+
+    Timestamp::from_timestamp(seconds, 0)
+        .ok_or(Error::new(MALFORMED_LAST_SYNC_TIMESTAMP))
+}
+
+
+/// Writes last synchronization timestamp.
+/// 
+/// * `last_sync` - destination of timestamp
+/// * `timestamp` - timestamp to write
+pub(super) fn write<W: std::io::Write>(last_sync: &mut W, timestamp: &Timestamp) -> Result<()> {
+    let timestamp = timestamp
+        .timestamp()
+        .to_le_bytes();
+
+    last_sync
+        .write_all(&timestamp)
+        .map_err(Error::from)
+}
@@ -0,0 +1,84 @@
+use std::io::Write;
+
+use crate::error::{Result, Error, ErrorMessage};
+
+
+/// Default time, after which a lock is considered to be left
+/// by a crashed process and can be broken.
+pub(super) const DEFAULT_LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+
+/// Advisory lock, that exists as a file and is released on drop.
+pub(super) struct SyncLock {
+    /// Path to lock file.
+    path: std::path::PathBuf,
+}
+
+
+impl SyncLock {
+    /// Creates lock file or fails immediately if it already exists.
+    /// Lock older than timeout is broken, since its holder is
+    /// considered to be crashed.
+    ///
+    /// * `path` - path to lock file
+    /// * `holder` - name of lock holder, that is written into the file
+    /// * `timeout` - time, after which existing lock is broken
+    /// * `locked` - error returned if lock is held by someone else
+    pub(super) fn acquire<I>(path: std::path::PathBuf, holder: &I, timeout: std::time::Duration, locked: ErrorMessage) -> Result<Self>
+    where
+        I: std::fmt::Display
+    {
+        //
+        // Creation of a new file is atomic, hence only one
+        // process can succeed. Name of the holder is written
+        // to let user know, who holds the lock
+        //
+
+        let mut file = match Self::create(&path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+                if !Self::is_stale(&path, timeout) {
+                    let holder = std::fs::read_to_string(&path)
+                        .unwrap_or_default();
+
+                    return Err(Error::new_with_extra(locked, format!("locked by: {}", holder)));
+                }
+
+                std::fs::remove_file(&path)?;
+                Self::create(&path)?
+            },
+            Err(error) => return Err(error.into())
+        };
+
+        let lock = SyncLock { path };
+        file.write_all(holder.to_string().as_bytes())?;
+
+        Ok(lock)
+    }
+
+    fn create(path: &std::path::Path) -> std::io::Result<std::fs::File> {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+    }
+
+    fn is_stale(path: &std::path::Path, timeout: std::time::Duration) -> bool {
+        //
+        // Lock, that cannot be inspected, is considered to be alive
+        //
+
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| timeout < age)
+    }
+}
+
+
+impl Drop for SyncLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
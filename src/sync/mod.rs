@@ -1,19 +1,65 @@
 mod git_engine;
+mod dir_engine;
+mod noop_engine;
+mod last_sync;
+mod lock;
 mod syncable;
 mod engine;
+mod progress;
+mod credentials;
 
 pub use self::git_engine::GitSyncEngine;
+pub use self::dir_engine::DirSyncEngine;
+pub use self::noop_engine::NoopSyncEngine;
+pub use self::progress::SyncProgress;
+pub use self::credentials::SyncCredentials;
 
 pub(crate) use self::engine::SyncEngine;
-pub(crate) use self::syncable::Syncable;
+pub(crate) use self::syncable::{Syncable, MergeSummary};
+
+use crate::error::{ErrorKind, ErrorMessage};
+
+
+/// Name of the remote used by single remote API.
+const DEFAULT_REMOTE_NAME: &str = "origin";
 
 
 /// Error message for case of adding of new remote, 
 /// when another one already exists.
-const REMOTE_ALREADY_EXIST: &str = "Remote is already associated with repository";
+const REMOTE_ALREADY_EXIST: ErrorMessage = ErrorMessage::new(ErrorKind::Sync, "Remote is already associated with repository");
 
 /// Error shown in case of malformed timestamp file.
-const MALFORMED_LAST_SYNC_TIMESTAMP: &str = "Last synchronization timestamp file is malformed";
+const MALFORMED_LAST_SYNC_TIMESTAMP: ErrorMessage = ErrorMessage::new(ErrorKind::Malformed, "Last synchronization timestamp file is malformed");
+
+/// Remote history cannot be merged with the local one.
+const REMOTE_CONFLICT: ErrorMessage = ErrorMessage::new(ErrorKind::RemoteConflict, "Conflicting changes are made in local and remote repositories");
+
+/// Remote is changed by other instances during all synchronization attempts.
+const REMOTE_MOVED: ErrorMessage = ErrorMessage::new(ErrorKind::Sync, "Remote is changed by another instance during synchronization, try again later");
+
+/// Remote rejected all available credentials.
+const AUTHENTICATION_FAILED: ErrorMessage = ErrorMessage::new(ErrorKind::Authentication, "Authentication with remote failed");
+
+/// Synchronization is requested, but there is no remote to synchronize with.
+const NO_REMOTE: ErrorMessage = ErrorMessage::new(ErrorKind::Sync, "No remote is associated with synchronization engine");
+
+/// Another instance synchronizes with the same folder right now.
+const SYNC_FOLDER_LOCKED: ErrorMessage = ErrorMessage::new(ErrorKind::SyncInProgress, "Synchronization folder is locked by another instance");
+
+/// Another process synchronizes the same local repository right now.
+const SYNC_IN_PROGRESS: ErrorMessage = ErrorMessage::new(ErrorKind::SyncInProgress, "Synchronization is already in progress");
+
+/// Synchronization is requested from an engine, that never synchronizes.
+const SYNC_NOT_CONFIGURED: ErrorMessage = ErrorMessage::new(ErrorKind::SyncNotConfigured, "Synchronization is not configured");
+
+/// Remote name cannot be used, e.g. it contains path separators.
+const INVALID_REMOTE_NAME: ErrorMessage = ErrorMessage::new(ErrorKind::Sync, "Remote name is invalid");
+
+/// Named remote is requested from an engine, that supports only one remote.
+const SINGLE_REMOTE_ONLY: ErrorMessage = ErrorMessage::new(ErrorKind::Sync, "Synchronization engine supports only a single remote");
+
+/// History with remote is trimmed without permission to rewrite remote history.
+const FORCE_PUSH_REQUIRED: ErrorMessage = ErrorMessage::new(ErrorKind::Sync, "History of repository with remote can be trimmed only if force push is allowed");
 
-/// Merge with remote changes is required, which is not intended to happen.
-const REMOTE_CONFLICT: &str = "Conflicting changes are made in local and remote repositories";
+/// History is trimmed, but remote has changes, that are not received yet.
+const REMOTE_NOT_MERGED: ErrorMessage = ErrorMessage::new(ErrorKind::Sync, "Remote has changes, that are not synchronized yet, synchronize before trimming history");
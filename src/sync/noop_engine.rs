@@ -0,0 +1,98 @@
+use crate::error::{Result, Error};
+use crate::datetime::Timestamp;
+use super::engine::SyncEngine;
+use super::syncable::Syncable;
+use super::progress::SyncProgress;
+use super::SYNC_NOT_CONFIGURED;
+
+
+/// Synchronization engine for instances, that are never synchronized.
+///
+/// It requires no filesystem setup, synchronization and remote
+/// management always fail with an error, that can be checked using
+/// [`crate::error::Error::is_sync_not_configured`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopSyncEngine;
+
+
+impl NoopSyncEngine {
+    /// Creates a new engine.
+    pub fn new() -> Self {
+        NoopSyncEngine
+    }
+}
+
+
+impl SyncEngine for NoopSyncEngine {
+    fn perform_sync<S: Syncable>(&self, _current_instance: &S::InstanceId, _syncable: &S, _context: &S::Context) -> Result<S::Summary> {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn perform_sync_with_progress<S: Syncable>(&self, _current_instance: &S::InstanceId, _syncable: &S, _context: &S::Context, 
+        _progress: &dyn Fn(SyncProgress)) -> Result<S::Summary> 
+    {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn perform_sync_with<S: Syncable>(&self, _remote: &str, _current_instance: &S::InstanceId, _syncable: &S, 
+        _context: &S::Context) -> Result<S::Summary> 
+    {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn preview_sync<S: Syncable>(&self, _syncable: &S, _context: &S::Context) -> Result<S::Preview> {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn known_instances<S: Syncable>(&self, _syncable: &S) -> Result<S::Instances> {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn is_configured(&self) -> bool {
+        false
+    }
+
+    fn last_sync(&self) -> Result<Option<Timestamp>> {
+        Ok(None)
+    }
+
+    fn last_sync_with(&self, _remote: &str) -> Result<Option<Timestamp>> {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn remotes(&self) -> Result<Vec<(String, String)>> {
+        Ok(Vec::new())
+    }
+
+    fn remote_url(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn add_remote(&self, _remote: &str) -> Result<()> {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn remove_remote(&self) -> Result<()> {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn add_named_remote(&self, _name: &str, _remote: &str) -> Result<()> {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn remove_named_remote(&self, _name: &str) -> Result<()> {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn change_remote(&self, _remote: &str) -> Result<()> {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn reset_to_remote(&self) -> Result<()> {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+
+    fn gc(&self, _force_push: bool) -> Result<()> {
+        Err(Error::new(SYNC_NOT_CONFIGURED))
+    }
+}
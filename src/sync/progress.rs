@@ -0,0 +1,39 @@
+/// Progress of synchronization.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncProgress {
+    /// Remote changes are being received
+    Fetching,
+
+    /// Remote changes are being merged with local ones
+    Merging,
+
+    /// Synchronized files are being committed
+    Committing,
+
+    /// Local changes are being sent to remote
+    Pushing,
+
+    /// Some objects are received from remote
+    Received {
+        /// Number of received objects
+        objects: usize,
+
+        /// Total number of objects to receive
+        total: usize,
+
+        /// Number of received bytes
+        bytes: usize,
+    },
+
+    /// Some objects are sent to remote
+    Sent {
+        /// Number of sent objects
+        objects: usize,
+
+        /// Total number of objects to send
+        total: usize,
+
+        /// Number of sent bytes
+        bytes: usize,
+    },
+}
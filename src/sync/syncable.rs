@@ -2,6 +2,17 @@ use crate::error::Result;
 use crate::datetime::Timestamp;
 
 
+/// Result of merge, that allows to skip sending of unchanged data.
+pub trait MergeSummary {
+    /// Checks if nothing is merged and nothing is exported.
+    fn is_empty(&self) -> bool;
+
+    /// Marks, that exported changes are committed locally,
+    /// but not sent anywhere, because there is no remote.
+    fn mark_local_only(&mut self);
+}
+
+
 /// Trait that defines synchronization interface.
 pub trait Syncable {
     /// Type of serialization context.
@@ -10,20 +21,66 @@ pub trait Syncable {
     /// Type of instance identifier representation.
     type InstanceId : std::fmt::Display;
 
+    /// Type of description of changes, that merge would apply.
+    type Preview;
+
+    /// Type of result of merge.
+    type Summary: MergeSummary;
+
+    /// Type of list of known instances.
+    type Instances;
+
+    /// Returns human-readable name of the current instance, if any.
+    fn instance_name(&self) -> Option<&str>;
+
     /// Merges remote changelog and exports the local one.
+    /// If there is nothing to merge and nothing to export,
+    /// files are left untouched. Otherwise each file is left
+    /// positioned at the end of its new content, hence it
+    /// should be truncated there, since content may shrink.
     ///
     /// * `timestamp_rw` - last synchronization time (the function overwrites
-    ///                    this value after performing synchronization)
-    /// * `last_instance_rw` - last synchronized instance identifier (the function
-    ///                        overwrites this value after preforming synchronization)
+    ///   this value after performing synchronization)
+    /// * `last_instance_rw` - last synchronized instance identifier followed by watermarks of all
+    ///   instances (the function overwrites these values after preforming synchronization)
     /// * `changelog_rw` - full changelog to merge (the function appends local changelog
-    ///                    to this value after preforming synchronization)
+    ///   to this value after preforming synchronization)
+    /// * `mac_rw` - authentication tag of files above (overwritten after synchronization)
     /// * `last_sync` - last synchronization timestamp
     /// * `context` - user-provided context
-    fn merge_and_export_changes<Ts, Li, Cl>(&self, timestamp_rw: &mut Ts, last_instance_rw: &mut Li,
-        changelog_rw: &mut Cl, last_sync: &Timestamp, context: &Self::Context) -> Result<()>
+    fn merge_and_export_changes<Ts, Li, Cl, Ma>(&self, timestamp_rw: &mut Ts, last_instance_rw: &mut Li,
+        changelog_rw: &mut Cl, mac_rw: &mut Ma, last_sync: &Timestamp, context: &Self::Context) -> Result<Self::Summary>
     where
         Ts: std::io::Read + std::io::Write + std::io::Seek,
         Li: std::io::Read + std::io::Write + std::io::Seek,
-        Cl: std::io::Read + std::io::Write + std::io::Seek;
+        Cl: std::io::Read + std::io::Write + std::io::Seek,
+        Ma: std::io::Read + std::io::Write + std::io::Seek;
+
+    /// Reads instances, that synchronized, with times of their last
+    /// synchronization. Files are not authenticated, hence no context
+    /// is required.
+    ///
+    /// * `timestamp_r` - last synchronization time
+    /// * `last_instance_r` - last synchronized instance identifier
+    fn read_instances<Ts, Li>(&self, timestamp_r: &mut Ts, last_instance_r: &mut Li) -> Result<Self::Instances>
+    where
+        Ts: std::io::Read + std::io::Seek,
+        Li: std::io::Read + std::io::Seek;
+
+    /// Describes changes, that merge of remote changelog would apply,
+    /// without applying them and without overwriting of files.
+    ///
+    /// * `timestamp_r` - last synchronization time
+    /// * `last_instance_r` - last synchronized instance identifier
+    /// * `changelog_r` - full changelog to merge
+    /// * `mac_r` - authentication tag of files above
+    /// * `last_sync` - last synchronization timestamp
+    /// * `context` - user-provided context
+    fn preview_changes<Ts, Li, Cl, Ma>(&self, timestamp_r: &mut Ts, last_instance_r: &mut Li,
+        changelog_r: &mut Cl, mac_r: &mut Ma, last_sync: &Timestamp, context: &Self::Context) -> Result<Self::Preview>
+    where
+        Ts: std::io::Read + std::io::Seek,
+        Li: std::io::Read + std::io::Seek,
+        Cl: std::io::Read + std::io::Seek,
+        Ma: std::io::Read + std::io::Seek;
 }
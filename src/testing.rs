@@ -0,0 +1,226 @@
+//
+// Helpers shared by unit tests. Budgets are built on deterministic
+// crypto engine and in-memory storage, hence tests need no keyring
+// and leave nothing behind but temporary folders removed on drop
+//
+
+use crate::location::{Location, TempLocation};
+use crate::crypto::{PlainCryptoEngine, KeyId, KdfParams};
+use crate::storage::{MemoryStorage, Id, Account, AccountType, Category, CategoryType, Transaction, Plan, PlanPeriod, RecurringTransaction, RecurrencePeriod, MetaInfo};
+use crate::sync::{SyncEngine, NoopSyncEngine, GitSyncEngine};
+use crate::datetime::{Clock, Timestamp};
+use crate::core::{Budget, Config};
+
+
+/// Budget, that is used in tests.
+pub(crate) type TestBudget<Se = NoopSyncEngine> = Budget<PlainCryptoEngine, Se, MemoryStorage>;
+
+
+/// Passphrase used to synchronize test budgets.
+pub(crate) const PASSPHRASE: &[u8] = b"passphrase";
+
+
+/// Creates an initialized budget, that never synchronizes.
+pub(crate) fn budget() -> (TempLocation, TestBudget) {
+    budget_with(|_| Ok(NoopSyncEngine::new()))
+}
+
+
+/// Creates an initialized budget with a given synchronization engine.
+/// Cheap key derivation is configured to keep synchronization fast.
+///
+/// * `sync_engine` - creates synchronization engine in a location
+pub(crate) fn budget_with<Se, F>(sync_engine: F) -> (TempLocation, TestBudget<Se>)
+where
+    Se: SyncEngine,
+    F: FnOnce(&TempLocation) -> crate::error::Result<Se>
+{
+    let loc = TempLocation::new();
+    let crypto_engine = PlainCryptoEngine::new();
+    let config = config(&loc, &crypto_engine);
+
+    let sync_engine = sync_engine(&loc)
+        .expect("Synchronization engine is created");
+
+    let budget = Budget::new(crypto_engine, sync_engine, MemoryStorage::new(), config)
+        .expect("Budget is created");
+
+    budget.initialize()
+        .expect("Budget is initialized");
+
+    (loc, budget)
+}
+
+
+/// Creates an initialized budget, that synchronizes with a git repository.
+///
+/// * `remote` - remote repository path, `None` means no remote
+pub(crate) fn git_budget(remote: Option<&std::path::Path>) -> (TempLocation, TestBudget<GitSyncEngine>) {
+    let remote = remote.map(|remote| remote.to_string_lossy().into_owned());
+    budget_with(|loc| git_engine(loc, remote.as_deref()))
+}
+
+
+/// Creates a budget, that joins an existing git repository
+/// with [`Budget::join_remote`] instead of initialization.
+///
+/// * `remote` - remote repository path
+pub(crate) fn joined_budget(remote: &std::path::Path) -> (TempLocation, TestBudget<GitSyncEngine>) {
+    let loc = TempLocation::new();
+    let crypto_engine = PlainCryptoEngine::new();
+    let config = config(&loc, &crypto_engine);
+
+    let budget = Budget::join_remote(crypto_engine, |remote| git_engine(&loc, Some(remote)), 
+        MemoryStorage::new(), config, &remote.to_string_lossy(), PASSPHRASE)
+        .expect("Budget joins remote");
+
+    (loc, budget)
+}
+
+
+fn config(loc: &TempLocation, crypto_engine: &PlainCryptoEngine) -> Config<PlainCryptoEngine> {
+    let mut config = Config::create(loc, crypto_engine, &[KeyId::new("test")])
+        .expect("Configuration is created");
+
+    config.set_kdf_params(loc, KdfParams::scrypt(4, 8, 1))
+        .expect("Key derivation parameters are set");
+
+    config
+}
+
+
+fn git_engine(loc: &TempLocation, remote: Option<&str>) -> crate::error::Result<GitSyncEngine> {
+    //
+    // Committer is configured per repository, hence
+    // tests don't depend on global git configuration
+    //
+
+    let engine = GitSyncEngine::create(loc, remote)?;
+    let mut config = engine.repository().config()?;
+    config.set_str("user.name", "libbdgt")?;
+    config.set_str("user.email", "libbdgt@localhost")?;
+
+    Ok(engine)
+}
+
+
+/// Creates a bare repository, that is used as a remote.
+pub(crate) fn bare_remote() -> TempLocation {
+    let remote = TempLocation::new();
+    git2::Repository::init_bare(remote.root())
+        .expect("Bare repository is created");
+
+    remote
+}
+
+
+/// Returns noon of a given day.
+///
+/// * `year` - calendar year
+/// * `month` - month number (starting from 1)
+/// * `day` - day of month
+pub(crate) fn at(year: i32, month: u32, day: u32) -> Timestamp {
+    chrono::NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|date| date.and_hms_opt(12, 0, 0))
+        .expect("Valid date is used")
+        .and_utc()
+}
+
+
+/// Returns meta information of a new item.
+pub(crate) fn new_meta_info() -> MetaInfo {
+    MetaInfo::new(Some(Clock::now()), None, None)
+}
+
+
+/// Returns a new checking account.
+///
+/// * `name` - name of account
+/// * `initial_balance` - initial balance of account
+pub(crate) fn account(name: &str, initial_balance: isize) -> Account {
+    Account {
+        id: None,
+        name: name.to_owned(),
+        balance: initial_balance,
+        initial_balance,
+        currency: String::new(),
+        archived: false,
+        account_type: AccountType::Checking,
+        meta_info: new_meta_info()
+    }
+}
+
+
+/// Returns a new category.
+///
+/// * `name` - name of category
+/// * `category_type` - type of category
+pub(crate) fn category(name: &str, category_type: CategoryType) -> Category {
+    Category {
+        id: None,
+        name: name.to_owned(),
+        category_type,
+        meta_info: new_meta_info()
+    }
+}
+
+
+/// Returns a new transaction.
+///
+/// * `account` - identifier of account
+/// * `category` - identifier of category
+/// * `amount` - amount of money
+/// * `timestamp` - time of transaction
+pub(crate) fn transaction(account: Id, category: Id, amount: isize, timestamp: Timestamp) -> Transaction {
+    Transaction {
+        id: None,
+        timestamp,
+        description: format!("{} at {}", amount, timestamp),
+        account_id: account,
+        category_id: category,
+        amount,
+        transfer_id: None,
+        tags: Vec::new(),
+        pending: false,
+        meta_info: new_meta_info()
+    }
+}
+
+
+/// Returns a new monthly plan.
+///
+/// * `category` - identifier of category
+/// * `amount_limit` - limit of spendings
+pub(crate) fn plan(category: Id, amount_limit: isize) -> Plan {
+    Plan {
+        id: None,
+        category_id: category,
+        name: format!("Limit of {}", amount_limit),
+        amount_limit,
+        period: PlanPeriod::Monthly,
+        rollover: false,
+        meta_info: new_meta_info()
+    }
+}
+
+
+/// Returns a new monthly recurring transaction.
+///
+/// * `account` - identifier of account
+/// * `category` - identifier of category
+/// * `amount` - amount of money
+/// * `start_timestamp` - time of the first occurrence
+pub(crate) fn recurring(account: Id, category: Id, amount: isize, start_timestamp: Timestamp) -> RecurringTransaction {
+    RecurringTransaction {
+        id: None,
+        description: format!("{} monthly", amount),
+        account_id: account,
+        category_id: category,
+        amount,
+        period: RecurrencePeriod::Monthly,
+        start_timestamp,
+        end_timestamp: None,
+        materialized_timestamp: None,
+        meta_info: new_meta_info()
+    }
+}
@@ -0,0 +1,60 @@
+//
+// Diagnostics are emitted only if `tracing` feature is enabled.
+// Macros have fixed shapes and accept only counters, sizes,
+// identifiers and static strings, hence sensitive data cannot
+// be emitted by construction. Without the feature arguments are
+// only borrowed to keep them used, hence nothing is computed
+//
+
+
+/// Emits number of rows read or written by a storage query.
+/// Event belongs to the span of current storage operation.
+///
+/// * `rows` - number of rows
+macro_rules! trace_rows {
+    ($rows:expr) => {{
+        #[cfg(feature = "tracing")]
+        tracing::trace!(rows = $rows as u64, "rows processed");
+
+        #[cfg(not(feature = "tracing"))]
+        let _ = &$rows;
+    }};
+}
+
+
+/// Evaluates an expression inside of a span named after
+/// synchronization phase and emits its duration.
+///
+/// * `phase` - static name of phase
+/// * `body` - expression, that performs the phase
+macro_rules! trace_phase {
+    ($phase:literal, $body:expr) => {{
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("sync_phase", phase = $phase).entered();
+
+        #[cfg(feature = "tracing")]
+        let started = std::time::Instant::now();
+
+        let result = $body;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(elapsed_ms = started.elapsed().as_millis() as u64, "phase finished");
+
+        result
+    }};
+}
+
+
+/// Emits a decision to skip a remote change during merge.
+///
+/// * `id` - identifier of skipped item
+/// * `reason` - static description of decision
+macro_rules! trace_skipped {
+    ($id:expr, $reason:expr) => {{
+        #[cfg(feature = "tracing")]
+        tracing::debug!(id = %$id, reason = $reason, "remote change is skipped");
+
+        #[cfg(not(feature = "tracing"))]
+        let _ = (&$id, &$reason);
+    }};
+}